@@ -0,0 +1,468 @@
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+ *--------------------------------------------------------------------------------------------*/
+
+// Generates the Rust bindings of the comms in `crates/amalthea/src/comm/`
+// from their OpenRPC contracts.
+//
+// Each comm `name` is described by `name.json`, along with the methods the
+// backend implements in `name-backend-openrpc.json` and the methods and events
+// the frontend implements in `name-frontend-openrpc.json`.
+//
+// Usage: node comms/generate-comms.js [name...]
+//
+// Without arguments, all the comms in this directory are generated.
+
+const fs = require('fs');
+const path = require('path');
+
+const commsDir = __dirname;
+const outputDir = path.join(commsDir, '..', 'crates', 'amalthea', 'src', 'comm');
+
+const year = 2024;
+
+// Maximum length of a doc comment line, leader included
+const commentWidth = 75;
+
+// Names of the variants of enums whose values aren't words
+const symbolNames = {
+	'=': 'Eq',
+	'!=': 'NotEq',
+	'<': 'Lt',
+	'<=': 'LtEq',
+	'>': 'Gt',
+	'>=': 'GtEq',
+};
+
+const derives = 'Clone, Debug, Serialize, Deserialize, PartialEq';
+
+// Enums whose values are passed on to the interpreter as strings, and thus
+// also implement `Display`
+const displayEnums = new Set(['RenderFormat']);
+
+function pascalCase(name) {
+	return name
+		.split(/[^A-Za-z0-9]+/)
+		.filter(word => word.length > 0)
+		.map(word => word[0].toUpperCase() + word.slice(1))
+		.join('');
+}
+
+function variantName(value) {
+	return symbolNames[value] ?? pascalCase(value);
+}
+
+function formatComment(leader, comment) {
+	let result = '';
+	for (const paragraph of comment.trim().split(/\n\s*\n/)) {
+		if (result.length > 0) {
+			result += leader.trimEnd() + '\n';
+		}
+		let line = leader;
+		for (const word of paragraph.split(/\s+/)) {
+			if (line !== leader && line.length + word.length > commentWidth) {
+				result += line.trimEnd() + '\n';
+				line = leader;
+			}
+			line += word + ' ';
+		}
+		result += line.trimEnd() + '\n';
+	}
+	return result;
+}
+
+function refName(ref) {
+	return pascalCase(ref.split('/').pop());
+}
+
+function readContract(file) {
+	const full = path.join(commsDir, file);
+	if (!fs.existsSync(full)) {
+		return undefined;
+	}
+	return JSON.parse(fs.readFileSync(full, 'utf8'));
+}
+
+// Walks the types defined by a contract in declaration order: the parameters
+// and results of its methods, then its shared components. Calls `visit` with
+// each schema, its Rust name, and the context it is defined in.
+function walkSchemas(contract, visit) {
+	const walk = (schema, name, context) => {
+		visit(schema, name, context);
+
+		if (schema.type === 'object' && schema.properties) {
+			for (const [key, property] of Object.entries(schema.properties)) {
+				walk(property, pascalCase(property.name ?? key), {
+					kind: 'property',
+					key,
+					parent: name,
+				});
+			}
+		} else if (schema.type === 'array' && schema.items) {
+			walk(schema.items, pascalCase(schema.items.name ?? 'items'), {
+				kind: 'items',
+				parent: context.key,
+			});
+		} else if (schema.oneOf) {
+			for (const item of schema.oneOf) {
+				if (!item.$ref) {
+					walk(item, pascalCase(item.name), { kind: 'union', parent: name });
+				}
+			}
+		}
+	};
+
+	for (const method of contract.methods ?? []) {
+		for (const param of method.params ?? []) {
+			walk(param.schema, pascalCase(param.schema.name ?? param.name), {
+				kind: 'param',
+				key: param.name,
+				method: method.name,
+			});
+		}
+		if (method.result?.schema) {
+			const schema = method.result.schema;
+			walk(schema, pascalCase(schema.name ?? `${method.name}_result`), {
+				kind: 'result',
+				key: 'result',
+				method: method.name,
+			});
+		}
+	}
+
+	for (const [key, schema] of Object.entries(contract.components?.schemas ?? {})) {
+		walk(schema, pascalCase(key), { kind: 'component', key });
+	}
+}
+
+// The Rust type of a schema, named `name` if the schema defines a type of its
+// own
+function rustType(schema, name) {
+	if (schema.$ref) {
+		return refName(schema.$ref);
+	}
+	if (schema.oneOf || schema.enum || (schema.type === 'object' && schema.properties)) {
+		return name;
+	}
+	switch (schema.type) {
+		case 'string':
+			return 'String';
+		case 'integer':
+			return 'i64';
+		case 'number':
+			return 'f64';
+		case 'boolean':
+			return 'bool';
+		case 'array':
+			return `Vec<${rustType(schema.items, pascalCase(schema.items.name ?? 'items'))}>`;
+		default:
+			return name;
+	}
+}
+
+function isAlias(schema) {
+	return !schema.$ref && !schema.type && !schema.oneOf && !schema.enum && schema.name;
+}
+
+function enumName(name, context) {
+	switch (context.kind) {
+		case 'param':
+			return pascalCase(context.method) + pascalCase(context.key);
+		case 'property':
+			return context.parent + pascalCase(context.key);
+		default:
+			return name;
+	}
+}
+
+function* createStruct(schema, name, context) {
+	if (schema.description) {
+		yield formatComment('/// ', schema.description);
+	} else if (context.kind === 'result') {
+		yield '/// Result in Methods\n';
+	} else {
+		yield `/// ${name} in Schemas\n`;
+	}
+	yield `#[derive(${derives})]\n`;
+	yield `pub struct ${name} {\n`;
+
+	const required = schema.required ?? [];
+	const fields = Object.entries(schema.properties).map(([key, property]) => {
+		let type = rustType(property, propertyTypeName(property, key, name));
+		if (!required.includes(key)) {
+			type = `Option<${type}>`;
+		}
+		return formatComment('\t/// ', property.description) + `\tpub ${key}: ${type}`;
+	});
+	yield fields.join(',\n\n');
+	yield '\n}\n\n';
+}
+
+function propertyTypeName(property, key, parent) {
+	if (property.enum) {
+		return parent + pascalCase(key);
+	}
+	return pascalCase(property.name ?? key);
+}
+
+function* createEnum(schema, name, context, display) {
+	const typeName = enumName(name, context);
+	if (context.kind === 'component') {
+		yield `/// Possible values for ${typeName}\n`;
+	} else {
+		const owner = context.kind === 'param' ? pascalCase(context.method) : context.parent;
+		yield `/// Possible values for ${pascalCase(context.key)} in ${owner}\n`;
+	}
+	yield display
+		? `#[derive(${derives}, strum_macros::Display)]\n`
+		: `#[derive(${derives})]\n`;
+	yield `pub enum ${typeName} {\n`;
+	yield schema.enum
+		.map(value => `\t#[serde(rename = "${value}")]\n\t${variantName(value)}`)
+		.join(',\n\n');
+	yield '\n}\n\n';
+}
+
+function* createUnion(schema, name, context) {
+	if (context.kind === 'property') {
+		yield `/// Union type ${name} in Properties\n`;
+	} else {
+		yield `/// Union type ${name}\n`;
+	}
+	yield `#[derive(${derives})]\n`;
+	yield '#[serde(untagged)]\n';
+	yield `pub enum ${name} {\n`;
+	yield schema.oneOf
+		.map(item => `\t${pascalCase(item.name)}(${rustType(item, pascalCase(item.name))})`)
+		.join(',\n\n');
+	yield '\n}\n\n';
+}
+
+function* createParams(method) {
+	const name = pascalCase(method.name);
+	yield `/// Parameters for the ${name} method.\n`;
+	yield `#[derive(${derives})]\n`;
+	yield `pub struct ${name}Params {\n`;
+	const fields = method.params.map(param => {
+		let type = rustType(param.schema, pascalCase(param.schema.name ?? param.name));
+		if (param.schema.enum) {
+			type = name + pascalCase(param.name);
+		}
+		if (param.required === false) {
+			type = `Option<${type}>`;
+		}
+		return formatComment('\t/// ', param.description) + `\tpub ${param.name}: ${type},\n`;
+	});
+	yield fields.join('\n');
+	yield '}\n\n';
+}
+
+function hasResult(method) {
+	const schema = method.result?.schema;
+	return schema !== undefined && schema.type !== 'null';
+}
+
+function resultType(method) {
+	const schema = method.result.schema;
+	const type = rustType(schema, pascalCase(schema.name ?? `${method.name}_result`));
+	return method.result.required === false ? `Option<${type}>` : type;
+}
+
+function* createRequests(prefix, comm, kind, methods, events) {
+	yield '/**\n';
+	yield ` * ${kind} for the ${comm} comm\n`;
+	yield ' */\n';
+	yield `#[derive(${derives})]\n`;
+	yield '#[serde(tag = "method", content = "params")]\n';
+	yield `pub enum ${prefix} {\n`;
+	for (const method of methods) {
+		if (events) {
+			if (method.description) {
+				yield formatComment('\t/// ', method.description);
+			}
+		} else {
+			yield formatComment('\t/// ', `${method.summary}\n\n${method.description}`);
+		}
+		yield `\t#[serde(rename = "${method.name}")]\n`;
+		const name = pascalCase(method.name);
+		yield method.params?.length ? `\t${name}(${name}Params),\n\n` : `\t${name},\n\n`;
+	}
+	yield '}\n\n';
+}
+
+function* createReplies(prefix, comm, kind, methods) {
+	yield '/**\n';
+	yield ` * ${kind} for the ${comm} comm\n`;
+	yield ' */\n';
+	yield `#[derive(${derives})]\n`;
+	yield '#[serde(tag = "method", content = "result")]\n';
+	yield `pub enum ${prefix} {\n`;
+	for (const method of methods) {
+		const name = pascalCase(method.name);
+		if (hasResult(method)) {
+			if (method.result.schema.description) {
+				yield formatComment('\t/// ', method.result.schema.description);
+			}
+			yield `\t${name}Reply(${resultType(method)}),\n\n`;
+		} else {
+			yield `\t/// Reply for the ${method.name} method (no result)\n`;
+			yield `\t${name}Reply(),\n\n`;
+		}
+	}
+	yield '}\n\n';
+}
+
+function* createReplyConversion(comm, prefix, methods) {
+	yield '/**\n';
+	yield '* Conversion of JSON values to frontend RPC Reply types\n';
+	yield '*/\n';
+	yield `pub fn ${comm}_frontend_reply_from_value(\n`;
+	yield '\treply: serde_json::Value,\n';
+	yield `\trequest: &${prefix}FrontendRequest,\n`;
+	yield `) -> anyhow::Result<${prefix}FrontendReply> {\n`;
+	yield '\tmatch request {\n';
+	for (const method of methods) {
+		const name = pascalCase(method.name);
+		const request = method.params?.length
+			? `${prefix}FrontendRequest::${name}(_)`
+			: `${prefix}FrontendRequest::${name}`;
+		const reply = hasResult(method)
+			? `${prefix}FrontendReply::${name}Reply(serde_json::from_value(reply)?)`
+			: `${prefix}FrontendReply::${name}Reply()`;
+		yield `\t\t${request} => Ok(${reply}),\n`;
+	}
+	yield '\t}\n';
+	yield '}\n\n';
+}
+
+function* createRustComm(name, frontend, backend) {
+	yield '// @generated\n\n';
+	yield '/*---------------------------------------------------------------------------------------------\n';
+	yield ` *  Copyright (C) ${year} Posit Software, PBC. All rights reserved.\n`;
+	yield ' *--------------------------------------------------------------------------------------------*/\n\n';
+	yield '//\n';
+	yield `// AUTO-GENERATED from ${name}.json; do not edit.\n`;
+	yield '//\n\n';
+	yield 'use serde::Deserialize;\n';
+	yield 'use serde::Serialize;\n\n';
+
+	const contracts = [backend, frontend].filter(contract => contract !== undefined);
+
+	// Type aliases and structs
+	for (const contract of contracts) {
+		const output = [];
+		walkSchemas(contract, (schema, typeName, context) => {
+			if (isAlias(schema)) {
+				const doc = schema.description ??
+					`${pascalCase(context.kind)} in ${pascalCase(context.parent ?? context.key)}`;
+				output.push(formatComment('/// ', doc));
+				output.push(`pub type ${typeName} = serde_json::Value;\n\n`);
+			} else if (schema.type === 'object' && schema.properties && context.kind !== 'param') {
+				output.push(...createStruct(schema, typeName, context));
+			}
+		});
+		yield* output;
+	}
+
+	// Enums
+	for (const contract of contracts) {
+		const output = [];
+		walkSchemas(contract, (schema, typeName, context) => {
+			if (schema.enum) {
+				const display = displayEnums.has(enumName(typeName, context));
+				output.push(...createEnum(schema, typeName, context, display));
+			}
+		});
+		yield* output;
+	}
+
+	// Unions
+	for (const contract of contracts) {
+		const output = [];
+		walkSchemas(contract, (schema, typeName, context) => {
+			if (schema.oneOf) {
+				output.push(...createUnion(schema, typeName, context));
+			}
+		});
+		yield* output;
+	}
+
+	// Method parameters
+	for (const contract of contracts) {
+		for (const method of contract.methods ?? []) {
+			if (method.params?.length) {
+				yield* createParams(method);
+			}
+		}
+	}
+
+	const prefix = pascalCase(name);
+	const frontendMethods = frontend?.methods ?? [];
+	const frontendRequests = frontendMethods.filter(method => method.result !== undefined);
+	const frontendEvents = frontendMethods.filter(method => method.result === undefined);
+
+	yield* createRequests(
+		`${prefix}BackendRequest`,
+		name,
+		'Backend RPC request types',
+		backend?.methods ?? [],
+		false,
+	);
+	yield* createReplies(
+		`${prefix}BackendReply`,
+		name,
+		'Backend RPC Reply types',
+		backend?.methods ?? [],
+	);
+	yield* createRequests(
+		`${prefix}FrontendRequest`,
+		name,
+		'Frontend RPC request types',
+		frontendRequests,
+		false,
+	);
+	yield* createReplies(
+		`${prefix}FrontendReply`,
+		name,
+		'Frontend RPC Reply types',
+		frontendRequests,
+	);
+	yield* createRequests(
+		`${prefix}FrontendEvent`,
+		name,
+		'Frontend events',
+		frontendEvents,
+		true,
+	);
+
+	if (frontendRequests.length) {
+		yield* createReplyConversion(name, prefix, frontendRequests);
+	}
+}
+
+function generate(name) {
+	const frontend = readContract(`${name}-frontend-openrpc.json`);
+	const backend = readContract(`${name}-backend-openrpc.json`);
+	if (!frontend && !backend) {
+		throw new Error(`No contract for the ${name} comm`);
+	}
+
+	const output = path.join(outputDir, `${name}_comm.rs`);
+	fs.writeFileSync(output, [...createRustComm(name, frontend, backend)].join(''));
+	console.log(`Generated ${path.relative(process.cwd(), output)}`);
+}
+
+function main() {
+	let names = process.argv.slice(2);
+	if (!names.length) {
+		names = fs
+			.readdirSync(commsDir)
+			.filter(file => /^[a-z_]+\.json$/.test(file))
+			.map(file => file.replace(/\.json$/, ''));
+	}
+	for (const name of names) {
+		generate(name);
+	}
+}
+
+main();
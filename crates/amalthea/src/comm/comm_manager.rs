@@ -6,12 +6,15 @@
  */
 
 use std::collections::HashMap;
+use std::time::Duration;
+use std::time::Instant;
 
 use crossbeam::channel::Receiver;
 use crossbeam::channel::Select;
 use crossbeam::channel::Sender;
 use log::info;
 use log::warn;
+use serde_json::json;
 use stdext::result::ResultOrLog;
 use stdext::spawn;
 
@@ -25,12 +28,37 @@ use crate::wire::comm_msg::CommWireMsg;
 use crate::wire::comm_open::CommOpen;
 use crate::wire::header::JupyterHeader;
 
+/// Configuration for the comm keepalive. When enabled, the comm manager
+/// periodically pings every open comm, and closes comms that have not
+/// exchanged any message with the frontend, in either direction, for longer
+/// than `timeout`.
+#[derive(Debug, Clone)]
+pub struct CommKeepalive {
+    /// How often keepalive pings are sent to the frontend.
+    pub interval: Duration,
+
+    /// How long a comm may go without any traffic before it is closed.
+    pub timeout: Duration,
+}
+
+impl Default for CommKeepalive {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5 * 60),
+            timeout: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
 pub struct CommManager {
     open_comms: Vec<CommSocket>,
     iopub_tx: Sender<IOPubMessage>,
     comm_event_rx: Receiver<CommManagerEvent>,
     comm_shell_tx: Sender<CommShellEvent>,
     pending_rpcs: HashMap<String, JupyterHeader>,
+    keepalive: Option<CommKeepalive>,
+    next_keepalive: Instant,
+    last_activity: HashMap<String, Instant>,
 }
 
 impl CommManager {
@@ -42,14 +70,18 @@ impl CommManager {
      * - `iopub_tx`: The channel to send messages to the frontend.
      * - `comm_event_rx`: The channel to receive messages about changes to the set
      *   (or state) of open comms.
+     * - `keepalive`: The keepalive configuration, or `None` to keep comms open
+     *   until they are explicitly closed.
      */
     pub fn start(
         iopub_tx: Sender<IOPubMessage>,
         comm_event_rx: Receiver<CommManagerEvent>,
+        keepalive: Option<CommKeepalive>,
     ) -> Receiver<CommShellEvent> {
         let (comm_changed_tx, comm_changed_rx) = crossbeam::channel::unbounded();
         spawn!("comm-manager", move || {
-            let mut comm_manager = CommManager::new(iopub_tx, comm_event_rx, comm_changed_tx);
+            let mut comm_manager =
+                CommManager::new(iopub_tx, comm_event_rx, comm_changed_tx, keepalive);
             loop {
                 comm_manager.execution_thread();
             }
@@ -64,13 +96,21 @@ impl CommManager {
         iopub_tx: Sender<IOPubMessage>,
         comm_event_rx: Receiver<CommManagerEvent>,
         comm_shell_tx: Sender<CommShellEvent>,
+        keepalive: Option<CommKeepalive>,
     ) -> Self {
+        let next_keepalive = match &keepalive {
+            Some(keepalive) => Instant::now() + keepalive.interval,
+            None => Instant::now(),
+        };
         Self {
             iopub_tx,
             comm_event_rx,
             comm_shell_tx,
             open_comms: Vec::<CommSocket>::new(),
             pending_rpcs: HashMap::<String, JupyterHeader>::new(),
+            keepalive,
+            next_keepalive,
+            last_activity: HashMap::<String, Instant>::new(),
         }
     }
 
@@ -80,6 +120,10 @@ impl CommManager {
      * intended to be called in a loop.
      */
     pub fn execution_thread(&mut self) {
+        if self.keepalive.is_some() && Instant::now() >= self.next_keepalive {
+            self.run_keepalive();
+        }
+
         let mut sel = Select::new();
 
         // Listen for messages from each of the open comms that are destined for
@@ -93,8 +137,16 @@ impl CommManager {
         // start a new `Select` with the updated set of open comms.
         sel.recv(&self.comm_event_rx);
 
-        // Wait until a message is received (blocking call)
-        let oper = sel.select();
+        // Wait until a message is received (blocking call). When the
+        // keepalive is enabled, wake up in time for the next ping.
+        let oper = match self.keepalive {
+            Some(_) => match sel.select_deadline(self.next_keepalive) {
+                Ok(oper) => oper,
+                // Time for the next ping; handled on the next iteration
+                Err(_) => return,
+            },
+            None => sel.select(),
+        };
 
         // Look up the index in the set of open comms
         let index = oper.index();
@@ -130,6 +182,8 @@ impl CommManager {
                     }
 
                     // Add to our own list of open comms
+                    self.last_activity
+                        .insert(comm_socket.comm_id.clone(), Instant::now());
                     self.open_comms.push(comm_socket);
 
                     info!(
@@ -156,6 +210,8 @@ impl CommManager {
                         let comm = self.open_comms.get(index).unwrap();
                         log::trace!("Comm manager: Sending message to comm '{}'", comm.comm_name);
 
+                        self.last_activity.insert(comm_id, Instant::now());
+
                         comm.incoming_tx.send(msg).unwrap();
                     } else {
                         log::warn!(
@@ -176,20 +232,7 @@ impl CommManager {
 
                     // If we found it, remove it.
                     if let Some(index) = index {
-                        // Notify the comm that it's been closed
-                        let comm = self.open_comms.get(index).unwrap();
-                        comm.incoming_tx
-                            .send(CommMsg::Close)
-                            .or_log_error("Failed to send comm_close to comm.");
-
-                        self.open_comms.remove(index);
-                        self.comm_shell_tx
-                            .send(CommShellEvent::Removed(comm_id))
-                            .unwrap();
-                        info!(
-                            "Comm channel closed; there are now {} open comms",
-                            self.open_comms.len()
-                        );
+                        self.remove_comm(index);
                     } else {
                         warn!(
                             "Received close message for unknown comm channel {}",
@@ -209,6 +252,11 @@ impl CommManager {
                 },
             };
 
+            // A comm that is still sending messages to the frontend is in use,
+            // even if the frontend doesn't talk back
+            self.last_activity
+                .insert(comm_socket.comm_id.clone(), Instant::now());
+
             // Amend the message with the comm's ID, convert it to an
             // IOPub message, and send it to the frontend
             let msg = match comm_msg {
@@ -254,4 +302,84 @@ impl CommManager {
             self.iopub_tx.send(msg).unwrap();
         }
     }
+
+    /**
+     * Removes the comm at `index` from the set of open comms, notifying the
+     * comm itself (so it can release its resources) and the shell.
+     */
+    fn remove_comm(&mut self, index: usize) {
+        let comm = self.open_comms.remove(index);
+
+        // Notify the comm that it's been closed
+        comm.incoming_tx
+            .send(CommMsg::Close)
+            .or_log_error("Failed to send comm_close to comm.");
+
+        self.last_activity.remove(&comm.comm_id);
+        self.comm_shell_tx
+            .send(CommShellEvent::Removed(comm.comm_id))
+            .unwrap();
+        info!(
+            "Comm channel closed; there are now {} open comms",
+            self.open_comms.len()
+        );
+    }
+
+    /**
+     * Closes the comms that have had no traffic for longer than the keepalive
+     * timeout, then pings the remaining ones. The pings themselves don't count
+     * as traffic.
+     */
+    fn run_keepalive(&mut self) {
+        let Some(keepalive) = self.keepalive.clone() else {
+            return;
+        };
+        let now = Instant::now();
+        self.next_keepalive = now + keepalive.interval;
+
+        // Reap stale comms. They are closed on both sides: the backend
+        // releases the resources held by the comm, and the frontend is told
+        // the comm is gone.
+        let stale: Vec<String> = self
+            .open_comms
+            .iter()
+            .filter(|comm| match self.last_activity.get(&comm.comm_id) {
+                Some(last) => now.duration_since(*last) >= keepalive.timeout,
+                None => false,
+            })
+            .map(|comm| comm.comm_id.clone())
+            .collect();
+
+        for comm_id in stale {
+            let Some(index) = self
+                .open_comms
+                .iter()
+                .position(|comm| comm.comm_id == comm_id)
+            else {
+                continue;
+            };
+            info!(
+                "Closing comm {comm_id} after {:?} of inactivity",
+                keepalive.timeout
+            );
+            self.remove_comm(index);
+            self.iopub_tx
+                .send(IOPubMessage::CommClose(comm_id))
+                .or_log_error("Failed to send comm_close to frontend.");
+        }
+
+        // Ping the comms that are still open
+        for comm in &self.open_comms {
+            self.iopub_tx
+                .send(IOPubMessage::CommMsgEvent(CommWireMsg {
+                    comm_id: comm.comm_id.clone(),
+                    data: json!({
+                        "jsonrpc": "2.0",
+                        "method": "keepalive",
+                        "params": {},
+                    }),
+                }))
+                .or_log_error("Failed to send keepalive to frontend.");
+        }
+    }
 }
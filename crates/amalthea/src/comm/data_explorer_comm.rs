@@ -21,6 +21,14 @@ pub struct SearchSchemaResult {
 	pub total_num_matches: i64
 }
 
+/// Result of searching the data for a matching row
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct SearchDataResult {
+	/// Index (starting from zero) of the first matching row in the current
+	/// sort order, if any
+	pub row_index: Option<i64>
+}
+
 /// Exported result
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct ExportedData {
@@ -130,7 +138,25 @@ pub struct FormatOptions {
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct TableSchema {
 	/// Schema for each column in the table
-	pub columns: Vec<ColumnSchema>
+	pub columns: Vec<ColumnSchema>,
+
+	/// The current column layout, if columns were reordered or pinned
+	pub column_layout: Option<ColumnLayout>
+}
+
+/// The display order of the columns of a table. Individual columns are
+/// always referred to by their index in the table, e.g. in sort keys,
+/// filters or `column_index` selections. Only column ranges, i.e. the
+/// columns of schema requests and the `column_range` and `cell_range`
+/// selections, are over display positions.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ColumnLayout {
+	/// Indices of all the columns in display order, starting with the pinned
+	/// columns
+	pub column_order: Vec<i64>,
+
+	/// Indices of the columns pinned to the left, in display order
+	pub pinned_columns: Vec<i64>
 }
 
 /// Provides number of rows and columns in a table
@@ -238,7 +264,17 @@ pub struct ColumnProfileRequest {
 	pub column_index: i64,
 
 	/// The type of analytical column profile
-	pub profile_type: ColumnProfileType
+	pub profile_type: ColumnProfileType,
+
+	/// Parameters for a histogram profile request
+	pub histogram_params: Option<ColumnHistogramParams>
+}
+
+/// Parameters for a histogram profile request
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ColumnHistogramParams {
+	/// Number of bins in the computed histogram
+	pub num_bins: i64
 }
 
 /// Support status for a given column profile type
@@ -377,7 +413,16 @@ pub struct ColumnHistogram {
 	pub bin_sizes: Vec<i64>,
 
 	/// Absolute floating-point width of a histogram bin
-	pub bin_width: f64
+	pub bin_width: f64,
+
+	/// Edges of the histogram bins. There is one more edge than bins
+	pub bin_edges: Option<Vec<f64>>,
+
+	/// Absolute count of values between consecutive bin edges
+	pub bin_counts: Option<Vec<i64>>,
+
+	/// Whether the histogram was computed over a sample of the values
+	pub sampled: Option<bool>
 }
 
 /// Result from a frequency_table profile request
@@ -430,6 +475,9 @@ pub struct SupportedFeatures {
 	/// Support for 'search_schema' RPC and its features
 	pub search_schema: SearchSchemaFeatures,
 
+	/// Support for 'search_data' RPC and its features
+	pub search_data: SearchDataFeatures,
+
 	/// Support for 'set_row_filters' RPC and its features
 	pub set_row_filters: SetRowFiltersFeatures,
 
@@ -450,6 +498,13 @@ pub struct SearchSchemaFeatures {
 	pub support_status: SupportStatus
 }
 
+/// Feature flags for 'search_data' RPC
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct SearchDataFeatures {
+	/// The support status for this RPC method
+	pub support_status: SupportStatus
+}
+
 /// Feature flags for 'set_row_filters' RPC
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct SetRowFiltersFeatures {
@@ -771,6 +826,27 @@ pub struct SearchSchemaParams {
 	pub max_results: i64,
 }
 
+/// Parameters for the SearchData method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct SearchDataParams {
+	/// Substring to match cell values against
+	pub search_term: String,
+
+	/// Column to search in. All columns are searched if missing
+	pub column_index: Option<i64>,
+
+	/// Index (starting from zero) of the first row to search from, in the
+	/// current sort order
+	pub start_index: i64,
+
+	/// If true, do a case-sensitive search, otherwise case-insensitive
+	pub case_sensitive: bool,
+
+	/// If true, continue searching from the first row after reaching the last
+	/// one
+	pub wrap_around: bool,
+}
+
 /// Parameters for the GetDataValues method.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct GetDataValuesParams {
@@ -813,6 +889,17 @@ pub struct SetSortColumnsParams {
 	pub sort_keys: Vec<ColumnSortKey>,
 }
 
+/// Parameters for the SetColumnLayout method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct SetColumnLayoutParams {
+	/// Indices of all the columns in display order. Pass an empty array to
+	/// restore the natural order
+	pub column_order: Vec<i64>,
+
+	/// Indices of the columns to pin to the left, in display order
+	pub pinned_columns: Vec<i64>,
+}
+
 /// Parameters for the GetColumnProfiles method.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct GetColumnProfilesParams {
@@ -841,6 +928,13 @@ pub enum DataExplorerBackendRequest {
 	#[serde(rename = "search_schema")]
 	SearchSchema(SearchSchemaParams),
 
+	/// Search data for a matching row
+	///
+	/// Find the next row, in the current sort order, with a cell value
+	/// containing a passed substring
+	#[serde(rename = "search_data")]
+	SearchData(SearchDataParams),
+
 	/// Get a rectangle of data values
 	///
 	/// Request a rectangular subset of data with values formatted as strings
@@ -867,6 +961,15 @@ pub enum DataExplorerBackendRequest {
 	#[serde(rename = "set_sort_columns")]
 	SetSortColumns(SetSortColumnsParams),
 
+	/// Reorder and pin columns
+	///
+	/// Set the display order of the columns and the columns pinned to the
+	/// left, replacing any previous layout. The layout is kept across data
+	/// updates as long as the set of columns doesn't change. Emits a
+	/// `schema_update` event.
+	#[serde(rename = "set_column_layout")]
+	SetColumnLayout(SetColumnLayoutParams),
+
 	/// Request a batch of column profiles
 	///
 	/// Requests a statistical summary or data profile for batch of columns
@@ -892,6 +995,9 @@ pub enum DataExplorerBackendReply {
 
 	SearchSchemaReply(SearchSchemaResult),
 
+	/// Result of searching the data for a matching row
+	SearchDataReply(SearchDataResult),
+
 	/// Table values formatted as strings
 	GetDataValuesReply(TableData),
 
@@ -904,6 +1010,9 @@ pub enum DataExplorerBackendReply {
 	/// Reply for the set_sort_columns method (no result)
 	SetSortColumnsReply(),
 
+	/// Reply for the set_column_layout method (no result)
+	SetColumnLayoutReply(),
+
 	GetColumnProfilesReply(Vec<ColumnProfileResult>),
 
 	/// The current backend state for the data explorer
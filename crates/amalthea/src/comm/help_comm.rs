@@ -31,6 +31,16 @@ pub struct ShowHelpTopicParams {
 	pub topic: String,
 }
 
+/// Parameters for the RenderTopicMarkdown method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct RenderTopicMarkdownParams {
+	/// The package documenting the topic
+	pub package: String,
+
+	/// The help topic to render
+	pub topic: String,
+}
+
 /// Parameters for the ShowHelp method.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct ShowHelpParams {
@@ -59,6 +69,13 @@ pub enum HelpBackendRequest {
 	#[serde(rename = "show_help_topic")]
 	ShowHelpTopic(ShowHelpTopicParams),
 
+	/// Render a help topic as Markdown
+	///
+	/// Requests the help page documenting a topic, rendered as Markdown. If
+	/// the topic is an alias, the page documenting it is returned.
+	#[serde(rename = "render_topic_markdown")]
+	RenderTopicMarkdown(RenderTopicMarkdownParams),
+
 }
 
 /**
@@ -71,6 +88,9 @@ pub enum HelpBackendReply {
 	/// Help notification.
 	ShowHelpTopicReply(bool),
 
+	/// The help page rendered as Markdown, or null if the topic wasn't found
+	RenderTopicMarkdownReply(Option<String>),
+
 }
 
 /**
@@ -21,7 +21,17 @@ pub struct PlotResult {
 	pub mime_type: String
 }
 
-/// Possible values for Format in Render
+/// The size of a plot
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PlotSize {
+	/// The plot height, in pixels
+	pub height: i64,
+
+	/// The plot width, in pixels
+	pub width: i64
+}
+
+/// Possible values for RenderFormat
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, strum_macros::Display)]
 pub enum RenderFormat {
 	#[serde(rename = "png")]
@@ -53,6 +63,23 @@ pub struct RenderParams {
 	pub format: RenderFormat,
 }
 
+/// Parameters for the RenderImage method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct RenderImageParams {
+	/// The requested image format
+	pub format: RenderFormat,
+
+	/// The requested image size
+	pub size: PlotSize,
+
+	/// The pixel ratio of the display device
+	pub pixel_ratio: f64,
+
+	/// The resolution of the image, in dots per inch. Defaults to the
+	/// resolution used for the Plots pane.
+	pub dpi: Option<f64>,
+}
+
 /**
  * Backend RPC request types for the plot comm
  */
@@ -66,6 +93,14 @@ pub enum PlotBackendRequest {
 	#[serde(rename = "render")]
 	Render(RenderParams),
 
+	/// Render a plot to an image
+	///
+	/// Renders a plot to an image of a given format and size, e.g. to copy or
+	/// save it. Unlike `render`, the plot displayed by the frontend is not
+	/// affected. The image data is returned in a base64-encoded string.
+	#[serde(rename = "render_image")]
+	RenderImage(RenderImageParams),
+
 }
 
 /**
@@ -77,6 +112,9 @@ pub enum PlotBackendReply {
 	/// A rendered plot
 	RenderReply(PlotResult),
 
+	/// A rendered image
+	RenderImageReply(PlotResult),
+
 }
 
 /**
@@ -108,3 +146,4 @@ pub enum PlotFrontendEvent {
 	Show,
 
 }
+
@@ -5,6 +5,8 @@
  *
  */
 
+use std::net::Ipv6Addr;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::sync::Mutex;
 
@@ -19,7 +21,64 @@ use crate::language::server_handler::ServerHandler;
 #[derive(Debug, Serialize, Deserialize)]
 pub struct StartServer {
     /// The address on which the client is listening for server requests.
+    /// When `port` is supplied, this is only the host part of the address
+    /// and may be left empty to use the loopback interface.
+    #[serde(default)]
     pub client_address: String,
+
+    /// The port on which the client is listening, if not already part of
+    /// `client_address`.
+    #[serde(default)]
+    pub port: Option<u16>,
+
+    /// Whether `client_address` is an IPv6 address. Only used to form the
+    /// address when `port` is supplied.
+    #[serde(default)]
+    pub ipv6: bool,
+}
+
+impl StartServer {
+    /// Returns the TCP address the server should bind to.
+    ///
+    /// Without a `port`, `client_address` is used as is, which is the
+    /// historical behavior. Otherwise the address is formed from the host in
+    /// `client_address` and `port`. It is an error to supply a port in both
+    /// fields.
+    pub fn tcp_address(&self) -> Result<String, String> {
+        let Some(port) = self.port else {
+            if self.client_address.is_empty() {
+                return Err(String::from("Expected a client address or a port"));
+            }
+            return Ok(self.client_address.clone());
+        };
+
+        let host = self.client_address.as_str();
+
+        if host.parse::<SocketAddr>().is_ok() {
+            return Err(format!(
+                "Client address '{host}' already includes a port, but port {port} was also supplied"
+            ));
+        }
+
+        if self.ipv6 {
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+            let host = if host.is_empty() { "::1" } else { host };
+
+            if host.parse::<Ipv6Addr>().is_err() {
+                return Err(format!("Client address '{host}' is not an IPv6 address"));
+            }
+            return Ok(format!("[{host}]:{port}"));
+        }
+
+        if host.contains(':') {
+            return Err(format!(
+                "Client address '{host}' already includes a port, but port {port} was also supplied"
+            ));
+        }
+        let host = if host.is_empty() { "127.0.0.1" } else { host };
+
+        Ok(format!("{host}:{port}"))
+    }
 }
 
 pub struct ServerComm {
@@ -42,13 +101,11 @@ impl ServerComm {
     /// This should return immediately after starting the server in a
     /// separate thread. Signal that the server is ready to accept
     /// connection by sending `true` via `conn_init_tx`.
-    pub fn start(&self, data: StartServer, conn_init_tx: Sender<bool>) -> Result<(), Error> {
+    ///
+    /// - `tcp_address` is the resolved address, see `StartServer::tcp_address()`.
+    pub fn start(&self, tcp_address: String, conn_init_tx: Sender<bool>) -> Result<(), Error> {
         let mut handler = self.handler.lock().unwrap();
-        handler.start(
-            data.client_address.clone(),
-            conn_init_tx,
-            self.msg_tx.clone(),
-        )?;
+        handler.start(tcp_address, conn_init_tx, self.msg_tx.clone())?;
         Ok(())
     }
 
@@ -97,6 +97,16 @@ pub struct Range {
 	pub end: Position
 }
 
+/// Possible values for Theme in DidChangeTheme
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum DidChangeThemeTheme {
+	#[serde(rename = "light")]
+	Light,
+
+	#[serde(rename = "dark")]
+	Dark
+}
+
 /// Parameters for the CallMethod method.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct CallMethodParams {
@@ -107,6 +117,20 @@ pub struct CallMethodParams {
 	pub params: Vec<Param>,
 }
 
+/// Parameters for the SetWorkingDirectory method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct SetWorkingDirectoryParams {
+	/// The directory to change to
+	pub directory: String,
+}
+
+/// Parameters for the DidChangeTheme method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct DidChangeThemeParams {
+	/// Whether the frontend now uses a light or dark color theme
+	pub theme: DidChangeThemeTheme,
+}
+
 /// Parameters for the Busy method.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct BusyParams {
@@ -251,6 +275,23 @@ pub struct ShowUrlParams {
 	pub url: String,
 }
 
+/// Parameters for the Progress method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ProgressParams {
+	/// The identifier of the progress bar
+	pub id: String,
+
+	/// The amount of work done so far
+	pub current: f64,
+
+	/// The total amount of work
+	pub total: f64,
+
+	/// Whether the work is over, in which case the progress bar should be
+	/// closed
+	pub done: bool,
+}
+
 /**
  * Backend RPC request types for the ui comm
  */
@@ -265,6 +306,20 @@ pub enum UiBackendRequest {
 	#[serde(rename = "call_method")]
 	CallMethod(CallMethodParams),
 
+	/// Change the working directory of the interpreter
+	///
+	/// Changes the working directory as `setwd()` would and returns the
+	/// normalized new working directory.
+	#[serde(rename = "set_working_directory")]
+	SetWorkingDirectory(SetWorkingDirectoryParams),
+
+	/// Notify the interpreter of a color theme change
+	///
+	/// Sent when the user switches the frontend between a light and a dark
+	/// color theme, so that the interpreter can adapt the output it renders.
+	#[serde(rename = "did_change_theme")]
+	DidChangeTheme(DidChangeThemeParams),
+
 }
 
 /**
@@ -276,6 +331,12 @@ pub enum UiBackendReply {
 	/// The method result
 	CallMethodReply(CallMethodResult),
 
+	/// The normalized new working directory
+	SetWorkingDirectoryReply(String),
+
+	/// Reply for the did_change_theme method (no result)
+	DidChangeThemeReply(),
+
 }
 
 /**
@@ -422,6 +483,12 @@ pub enum UiFrontendEvent {
 	#[serde(rename = "show_url")]
 	ShowUrl(ShowUrlParams),
 
+	/// Reports the progress of a long computation, e.g. from a text progress
+	/// bar, so that it can be displayed natively. Only sent to frontends that
+	/// opt in by setting the `positron.progress_bars` R option.
+	#[serde(rename = "progress")]
+	Progress(ProgressParams),
+
 }
 
 /**
@@ -26,6 +26,19 @@ pub struct VariableList {
 	pub version: Option<i64>
 }
 
+/// Criteria to filter the top-level variables of the session.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct VariableSearch {
+	/// Only include variables whose name contains this substring
+	pub name: Option<String>,
+
+	/// Only include variables inheriting from this class, e.g. `data.frame`
+	pub class: Option<String>,
+
+	/// Whether name matching is case sensitive. Defaults to false.
+	pub case_sensitive: Option<bool>
+}
+
 /// An inspected variable.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct InspectedVariable {
@@ -41,7 +54,32 @@ pub struct InspectedVariable {
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct FormattedVariable {
 	/// The formatted content of the variable.
-	pub content: String
+	pub content: String,
+
+	/// Whether the content was truncated because the variable is too large.
+	/// Only set in replies to `format_variable`.
+	pub is_truncated: Option<bool>
+}
+
+/// The value of a watched expression.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct WatchedExpression {
+	/// The identifier of the watch
+	pub id: String,
+
+	/// The watched expression
+	pub expression: String,
+
+	/// A string representation of the value of the expression. Empty if the
+	/// evaluation failed.
+	pub display_value: String,
+
+	/// The type of the value of the expression. Empty if the evaluation
+	/// failed.
+	pub display_type: String,
+
+	/// The error message, if the evaluation failed
+	pub error: Option<String>
 }
 
 /// A single variable in the runtime.
@@ -100,6 +138,19 @@ pub enum ClipboardFormatFormat {
 	TextPlain
 }
 
+/// Possible values for Format in FormatVariable
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum FormatVariableFormat {
+	#[serde(rename = "json")]
+	Json,
+
+	#[serde(rename = "csv")]
+	Csv,
+
+	#[serde(rename = "r")]
+	R
+}
+
 /// Possible values for Kind in Variable
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub enum VariableKind {
@@ -174,6 +225,16 @@ pub struct ClipboardFormatParams {
 	pub format: ClipboardFormatFormat,
 }
 
+/// Parameters for the FormatVariable method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct FormatVariableParams {
+	/// The path to the variable to format, as an array of access keys.
+	pub path: Vec<String>,
+
+	/// The requested format for the variable
+	pub format: FormatVariableFormat,
+}
+
 /// Parameters for the View method.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct ViewParams {
@@ -181,6 +242,28 @@ pub struct ViewParams {
 	pub path: Vec<String>,
 }
 
+/// Parameters for the WatchExpression method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct WatchExpressionParams {
+	/// The expression to watch, evaluated in the session's environment
+	pub expression: String,
+}
+
+/// Parameters for the UnwatchExpression method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct UnwatchExpressionParams {
+	/// The identifier of the watch to remove
+	pub id: String,
+}
+
+/// Parameters for the Search method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct SearchParams {
+	/// Criteria the listed variables must match. Also applies to subsequent
+	/// updates until another list or search request is made.
+	pub filter: VariableSearch,
+}
+
 /// Parameters for the Update method.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct UpdateParams {
@@ -212,6 +295,13 @@ pub struct RefreshParams {
 	pub version: i64,
 }
 
+/// Parameters for the WatchUpdate method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct WatchUpdateParams {
+	/// The new value of the watched expression
+	pub watch: WatchedExpression,
+}
+
 /**
  * Backend RPC request types for the variables comm
  */
@@ -249,6 +339,14 @@ pub enum VariablesBackendRequest {
 	#[serde(rename = "clipboard_format")]
 	ClipboardFormat(ClipboardFormatParams),
 
+	/// Format a variable for export
+	///
+	/// Requests a representation of a variable as JSON, CSV, or R code, e.g.
+	/// for copying to the clipboard. Large variables are truncated, which is
+	/// reported with `is_truncated` rather than in the content.
+	#[serde(rename = "format_variable")]
+	FormatVariable(FormatVariableParams),
+
 	/// Request a viewer for a variable
 	///
 	/// Request that the runtime open a data viewer to display the data in a
@@ -256,6 +354,27 @@ pub enum VariablesBackendRequest {
 	#[serde(rename = "view")]
 	View(ViewParams),
 
+	/// Watch an expression
+	///
+	/// Evaluates an expression whenever the runtime becomes idle, and sends a
+	/// `watch_update` event when its value changes. Returns the current value
+	/// of the expression.
+	#[serde(rename = "watch_expression")]
+	WatchExpression(WatchExpressionParams),
+
+	/// Stop watching an expression
+	///
+	/// Removes a watch added with `watch_expression`.
+	#[serde(rename = "unwatch_expression")]
+	UnwatchExpression(UnwatchExpressionParams),
+
+	/// Search variables
+	///
+	/// Returns a list of the variables in the current session that match the
+	/// given name and class criteria.
+	#[serde(rename = "search")]
+	Search(SearchParams),
+
 }
 
 /**
@@ -279,9 +398,21 @@ pub enum VariablesBackendReply {
 	/// An object formatted for copying to the clipboard.
 	ClipboardFormatReply(FormattedVariable),
 
+	/// A variable formatted as JSON, CSV, or R code.
+	FormatVariableReply(FormattedVariable),
+
 	/// The ID of the viewer that was opened.
 	ViewReply(String),
 
+	/// The value of a watched expression.
+	WatchExpressionReply(WatchedExpression),
+
+	/// Reply for the unwatch_expression method (no result)
+	UnwatchExpressionReply(),
+
+	/// A view containing a list of the matching variables in the session.
+	SearchReply(VariableList),
+
 }
 
 /**
@@ -315,5 +446,9 @@ pub enum VariablesFrontendEvent {
 	#[serde(rename = "refresh")]
 	Refresh(RefreshParams),
 
+	/// The value of a watched expression changed.
+	#[serde(rename = "watch_update")]
+	WatchUpdate(WatchUpdateParams),
+
 }
 
@@ -13,7 +13,7 @@ use std::path::Path;
 use serde::Deserialize;
 
 /// The contents of the Connection File as listed in the Jupyter specfication;
-/// directly parsed from JSON.
+/// directly parsed from JSON. Unknown keys are ignored.
 #[derive(Deserialize, Debug)]
 pub struct ConnectionFile {
     /// ZeroMQ port: Control channel (kernel interrupts)
@@ -43,6 +43,15 @@ pub struct ConnectionFile {
     /// The HMAC-256 signing key, or an empty string for an unauthenticated
     /// connection
     pub key: String,
+
+    /// The name of the kernel spec the kernel was started from, included by
+    /// `jupyter_client` and some other frontends
+    #[serde(default)]
+    pub kernel_name: Option<String>,
+
+    /// The identifier of the frontend's session, included by some frontends
+    #[serde(default)]
+    pub jupyter_session: Option<String>,
 }
 
 impl ConnectionFile {
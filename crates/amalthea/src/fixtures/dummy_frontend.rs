@@ -1,18 +1,21 @@
 /*
- * mod.rs
+ * dummy_frontend.rs
  *
- * Copyright (C) 2022 Posit Software, PBC. All rights reserved.
+ * Copyright (C) 2022-2024 Posit Software, PBC. All rights reserved.
  *
  */
 
-use amalthea::connection_file::ConnectionFile;
-use amalthea::session::Session;
-use amalthea::socket::socket::Socket;
-use amalthea::wire::jupyter_message::JupyterMessage;
-use amalthea::wire::jupyter_message::Message;
-use amalthea::wire::jupyter_message::ProtocolMessage;
-
-pub struct Frontend {
+use crate::connection_file::ConnectionFile;
+use crate::session::Session;
+use crate::socket::socket::Socket;
+use crate::wire::jupyter_message::JupyterMessage;
+use crate::wire::jupyter_message::Message;
+use crate::wire::jupyter_message::ProtocolMessage;
+
+/// A synthetic Jupyter frontend for tests. It binds the client side of the
+/// five Jupyter sockets on free local ports; pass the result of
+/// `get_connection_file()` to `Kernel::new()` to connect a kernel to it.
+pub struct DummyFrontend {
     pub control_socket: Socket,
     pub shell_socket: Socket,
     pub iopub_socket: Socket,
@@ -27,7 +30,7 @@ pub struct Frontend {
     heartbeat_port: u16,
 }
 
-impl Frontend {
+impl DummyFrontend {
     pub fn new() -> Self {
         use rand::Rng;
 
@@ -130,6 +133,15 @@ impl Frontend {
         id
     }
 
+    /// Sends a Jupyter message on the Control socket; returns the ID of the
+    /// newly created message
+    pub fn send_control<T: ProtocolMessage>(&self, msg: T) -> String {
+        let message = JupyterMessage::create(msg, None, &self.session);
+        let id = message.header.msg_id.clone();
+        message.send(&self.control_socket).unwrap();
+        id
+    }
+
     /// Sends a Jupyter message on the Stdin socket
     pub fn send_stdin<T: ProtocolMessage>(&self, msg: T) {
         let message = JupyterMessage::create(msg, None, &self.session);
@@ -141,6 +153,11 @@ impl Frontend {
         Message::read_from_socket(&self.shell_socket).unwrap()
     }
 
+    /// Receives a Jupyter message from the Control socket
+    pub fn receive_control(&self) -> Message {
+        Message::read_from_socket(&self.control_socket).unwrap()
+    }
+
     /// Receives a Jupyter message from the IOPub socket
     pub fn receive_iopub(&self) -> Message {
         Message::read_from_socket(&self.iopub_socket).unwrap()
@@ -176,6 +193,14 @@ impl Frontend {
             signature_scheme: String::from("hmac-sha256"),
             ip: String::from("127.0.0.1"),
             key: self.key.clone(),
+            kernel_name: None,
+            jupyter_session: None,
         }
     }
 }
+
+impl Default for DummyFrontend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,8 @@
+/*
+ * mod.rs
+ *
+ * Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+ *
+ */
+
+pub mod dummy_frontend;
@@ -0,0 +1,250 @@
+/*
+ * history.rs
+ *
+ * Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+ *
+ */
+
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::wire::history_request::HistAccessType;
+use crate::wire::history_request::HistoryRequest;
+
+/// The maximum number of entries kept in a history file. Older entries are
+/// dropped once the file grows past this (plus some slack, so that the file
+/// isn't rewritten on every execution).
+pub const MAX_HISTORY_ENTRIES: usize = 10_000;
+
+/// A single executed input.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// The kernel session the input was executed in. Each kernel start opens
+    /// a new session.
+    pub session: u32,
+
+    /// The execution count of the input within its session.
+    pub line: u32,
+
+    /// The executed code.
+    pub input: String,
+}
+
+/// Execution history persisted to a file, so that inputs from previous
+/// kernel sessions can be served to `history_request`s.
+///
+/// The file holds one JSON-encoded `HistoryEntry` per line.
+pub struct History {
+    path: PathBuf,
+    entries: Vec<HistoryEntry>,
+    session: u32,
+    max_entries: usize,
+}
+
+impl History {
+    /// Opens the history file at `path`, creating it if needed, and starts a
+    /// new session. Lines that can't be parsed are skipped.
+    pub fn open(path: PathBuf) -> std::io::Result<Self> {
+        Self::open_with_max_entries(path, MAX_HISTORY_ENTRIES)
+    }
+
+    /// Like `open()`, but keeps at most `max_entries` entries.
+    pub fn open_with_max_entries(path: PathBuf, max_entries: usize) -> std::io::Result<Self> {
+        let mut entries = Vec::new();
+
+        match File::open(&path) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines() {
+                    let line = line?;
+                    match serde_json::from_str::<HistoryEntry>(&line) {
+                        Ok(entry) => entries.push(entry),
+                        Err(err) => log::warn!("Skipping invalid history entry: {err}"),
+                    }
+                }
+            },
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+            },
+            Err(err) => return Err(err),
+        }
+
+        let session = entries.iter().map(|entry| entry.session).max().unwrap_or(0) + 1;
+
+        let mut history = Self {
+            path,
+            entries,
+            session,
+            max_entries,
+        };
+
+        if history.entries.len() > max_entries {
+            history.compact()?;
+        }
+
+        log::info!(
+            "Opened history file {:?} with {} entries (session {})",
+            history.path,
+            history.entries.len(),
+            history.session
+        );
+
+        Ok(history)
+    }
+
+    /// The current session number.
+    pub fn session(&self) -> u32 {
+        self.session
+    }
+
+    /// All entries, oldest first.
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+
+    /// Records an executed input in the current session and appends it to
+    /// the history file.
+    pub fn record(&mut self, line: u32, input: &str) -> std::io::Result<()> {
+        let entry = HistoryEntry {
+            session: self.session,
+            line,
+            input: input.to_string(),
+        };
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+
+        self.entries.push(entry);
+
+        if self.entries.len() > self.max_entries + self.max_entries / 10 {
+            self.compact()?;
+        }
+
+        Ok(())
+    }
+
+    /// Drops the oldest entries beyond `max_entries` and rewrites the file.
+    fn compact(&mut self) -> std::io::Result<()> {
+        let excess = self.entries.len().saturating_sub(self.max_entries);
+        self.entries.drain(..excess);
+
+        // Write to a temporary file first so that the history is never lost
+        // to a partial write
+        let tmp = self.path.with_extension("tmp");
+        let mut contents = String::new();
+        for entry in &self.entries {
+            contents.push_str(&serde_json::to_string(entry)?);
+            contents.push('\n');
+        }
+        fs::write(&tmp, contents)?;
+        fs::rename(&tmp, &self.path)
+    }
+
+    /// Returns the entries matching a `history_request`, formatted for the
+    /// `history_reply`.
+    pub fn query(&self, req: &HistoryRequest) -> Vec<(u32, u32, Value)> {
+        let entries: Vec<&HistoryEntry> = match req.hist_access_type {
+            HistAccessType::Tail => last_n(self.entries.iter().collect(), req.n),
+            HistAccessType::Range => {
+                let session = if req.session <= 0 {
+                    self.session as i64 + req.session as i64
+                } else {
+                    req.session as i64
+                };
+                self.entries
+                    .iter()
+                    .filter(|entry| entry.session as i64 == session)
+                    .filter(|entry| entry.line as i64 >= req.start as i64)
+                    .filter(|entry| req.stop <= 0 || (entry.line as i64) < req.stop as i64)
+                    .collect()
+            },
+            HistAccessType::Search => {
+                let pattern = if req.pattern.is_empty() {
+                    "*"
+                } else {
+                    req.pattern.as_str()
+                };
+                let mut matches: Vec<&HistoryEntry> = self
+                    .entries
+                    .iter()
+                    .filter(|entry| glob_match(pattern, &entry.input))
+                    .collect();
+
+                if req.unique {
+                    // Keep the most recent occurrence of each input
+                    let mut seen = std::collections::HashSet::new();
+                    matches.reverse();
+                    matches.retain(|entry| seen.insert(entry.input.as_str()));
+                    matches.reverse();
+                }
+
+                last_n(matches, req.n)
+            },
+        };
+
+        entries
+            .into_iter()
+            .map(|entry| {
+                let input = Value::String(entry.input.clone());
+                // Outputs aren't recorded
+                let input = if req.output {
+                    Value::Array(vec![input, Value::Null])
+                } else {
+                    input
+                };
+                (entry.session, entry.line, input)
+            })
+            .collect()
+    }
+}
+
+/// Keeps the last `n` entries; all of them if `n` isn't positive.
+fn last_n(mut entries: Vec<&HistoryEntry>, n: i32) -> Vec<&HistoryEntry> {
+    if n > 0 {
+        let excess = entries.len().saturating_sub(n as usize);
+        entries.drain(..excess);
+    }
+    entries
+}
+
+/// Matches `text` against a glob `pattern`, where `*` matches any sequence
+/// of characters and `?` matches a single character.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            // Let the last `*` absorb one more character
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
@@ -5,6 +5,7 @@
  *
  */
 
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::Mutex;
 
@@ -17,11 +18,13 @@ use log::error;
 use stdext::spawn;
 use stdext::unwrap;
 
+use crate::comm::comm_manager::CommKeepalive;
 use crate::comm::comm_manager::CommManager;
 use crate::comm::event::CommManagerEvent;
 use crate::comm::event::CommShellEvent;
 use crate::connection_file::ConnectionFile;
 use crate::error::Error;
+use crate::history::History;
 use crate::language::control_handler::ControlHandler;
 use crate::language::server_handler::ServerHandler;
 use crate::language::shell_handler::ShellHandler;
@@ -65,6 +68,14 @@ pub struct Kernel {
 
     /// Receives notifications about comm changes and events
     comm_manager_rx: Receiver<CommManagerEvent>,
+
+    /// Keepalive configuration for open comms; `None` (the default) disables
+    /// the keepalive. Use `set_comm_keepalive` to change it.
+    comm_keepalive: Option<CommKeepalive>,
+
+    /// File the execution history is persisted to; `None` (the default)
+    /// disables persistence. Use `set_history_file` to change it.
+    history_file: Option<PathBuf>,
 }
 
 /// Possible behaviors for the stream capture thread. When set to `Capture`,
@@ -95,9 +106,23 @@ impl Kernel {
             iopub_rx: Some(iopub_rx),
             comm_manager_tx,
             comm_manager_rx,
+            comm_keepalive: None,
+            history_file: None,
         })
     }
 
+    /// Persists the execution history to `path` so that `history_request`s
+    /// can be served across restarts. Must be called before `connect`.
+    pub fn set_history_file(&mut self, path: Option<PathBuf>) {
+        self.history_file = path;
+    }
+
+    /// Enables (or, with `None`, disables) the comm keepalive. Must be called
+    /// before `connect`.
+    pub fn set_comm_keepalive(&mut self, keepalive: Option<CommKeepalive>) {
+        self.comm_keepalive = keepalive;
+    }
+
     /// Connects the Kernel to the frontend
     pub fn connect(
         &mut self,
@@ -126,7 +151,8 @@ impl Kernel {
         // Create the comm manager thread
         let iopub_tx = self.create_iopub_tx();
         let comm_manager_rx = self.comm_manager_rx.clone();
-        let comm_changed_rx = CommManager::start(iopub_tx, comm_manager_rx);
+        let comm_changed_rx =
+            CommManager::start(iopub_tx, comm_manager_rx, self.comm_keepalive.clone());
 
         // Create the Shell ROUTER/DEALER socket and start a thread to listen
         // for client messages.
@@ -139,6 +165,33 @@ impl Kernel {
             self.connection.endpoint(self.connection.shell_port),
         )?;
 
+        // Open the history file, if persistence is enabled. A history file
+        // that can't be opened disables persistence rather than failing.
+        let history = self.history_file.clone().and_then(|path| {
+            History::open(path.clone())
+                .map_err(|err| error!("Can't open history file {path:?}: {err}"))
+                .ok()
+        });
+
+        // Internal sockets for notifying the shell thread that an execute
+        // reply is ready to be sent
+        let shell_reply_notif_socket_tx = Socket::new_pair(
+            self.session.clone(),
+            ctx.clone(),
+            String::from("ShellReplyNotifierTx"),
+            None,
+            String::from("inproc://shell_reply_notif"),
+            true,
+        )?;
+        let shell_reply_notif_socket_rx = Socket::new_pair(
+            self.session.clone(),
+            ctx.clone(),
+            String::from("ShellReplyNotifierRx"),
+            None,
+            String::from("inproc://shell_reply_notif"),
+            false,
+        )?;
+
         let shell_clone = shell_handler.clone();
         let iopub_tx_clone = self.create_iopub_tx();
         let comm_manager_tx_clone = self.comm_manager_tx.clone();
@@ -147,12 +200,15 @@ impl Kernel {
         spawn!(format!("{}-shell", self.name), move || {
             Self::shell_thread(
                 shell_socket,
+                shell_reply_notif_socket_rx,
+                shell_reply_notif_socket_tx,
                 iopub_tx_clone,
                 comm_manager_tx_clone,
                 comm_changed_rx,
                 shell_clone,
                 lsp_handler_clone,
                 dap_handler_clone,
+                history,
             )
         });
 
@@ -309,21 +365,27 @@ impl Kernel {
     /// Starts the shell thread.
     fn shell_thread(
         socket: Socket,
+        reply_notif_rx: Socket,
+        reply_notif_tx: Socket,
         iopub_tx: Sender<IOPubMessage>,
         comm_manager_tx: Sender<CommManagerEvent>,
         comm_changed_rx: Receiver<CommShellEvent>,
         shell_handler: Arc<Mutex<dyn ShellHandler>>,
         lsp_handler: Option<Arc<Mutex<dyn ServerHandler>>>,
         dap_handler: Option<Arc<Mutex<dyn ServerHandler>>>,
+        history: Option<History>,
     ) -> Result<(), Error> {
         let mut shell = Shell::new(
             socket,
+            reply_notif_rx,
+            reply_notif_tx,
             iopub_tx.clone(),
             comm_manager_tx,
             comm_changed_rx,
             shell_handler,
             lsp_handler,
             dap_handler,
+            history,
         );
         shell.listen();
         Ok(())
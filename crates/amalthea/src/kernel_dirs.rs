@@ -8,35 +8,129 @@
 use std::env;
 use std::path::PathBuf;
 
+/// Where a kernel spec should be installed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InstallLocation {
+    /// The first directory of `JUPYTER_PATH` if set, the user's Jupyter
+    /// data directory otherwise.
+    User,
+
+    /// The Jupyter data directory of the active Python environment
+    /// (`<sys.prefix>/share/jupyter`), like `jupyter kernelspec install
+    /// --sys-prefix`.
+    SysPrefix,
+}
+
 /// Returns the path where Jupyter kernels should be/are installed.
 pub fn jupyter_kernel_path() -> Option<PathBuf> {
-    if let Some(path) = jupyter_dir() {
-        return Some(path.join("kernels"));
+    jupyter_kernel_install_path(InstallLocation::User)
+}
+
+/// Returns the path where Jupyter kernels should be installed for the given
+/// location, i.e. the `kernels` folder of the first install root.
+pub fn jupyter_kernel_install_path(location: InstallLocation) -> Option<PathBuf> {
+    jupyter_install_roots(location)
+        .into_iter()
+        .next()
+        .map(|path| path.join("kernels"))
+}
+
+/// Returns the candidate roots for installing a kernel spec, in order of
+/// preference. Jupyter searches `JUPYTER_PATH` before the user's data
+/// directory, so a kernel spec installed to the first root is the one that
+/// Jupyter finds.
+pub fn jupyter_install_roots(location: InstallLocation) -> Vec<PathBuf> {
+    match location {
+        InstallLocation::User => {
+            let mut roots = jupyter_path();
+            roots.extend(jupyter_data_dir());
+            roots
+        },
+        InstallLocation::SysPrefix => sys_prefix_data_dir().into_iter().collect(),
     }
-    None
 }
 
-/// Returns the root Jupyter directory; uses the `JUPYTER_PATH` environment
-/// variable if set, XDG values if not.
+/// Returns the directories Jupyter searches for data files (including kernel
+/// specs), in Jupyter's documented order of precedence:
+///
+/// 1. The directories listed in `JUPYTER_PATH`.
+/// 2. The user's data directory (see `jupyter_data_dir()`).
+/// 3. The data directory of the active Python environment, if any.
+/// 4. The system-wide data directories.
+///
+/// See <https://docs.jupyter.org/en/latest/use/jupyter-directories.html>.
+pub fn jupyter_data_dirs() -> Vec<PathBuf> {
+    let mut dirs = jupyter_path();
+    dirs.extend(jupyter_data_dir());
+    dirs.extend(sys_prefix_data_dir());
+    dirs.extend(jupyter_system_dirs());
+
+    // A directory may be listed several times, e.g. in `JUPYTER_PATH` and as
+    // the user directory; only the first occurrence matters
+    let mut unique = Vec::with_capacity(dirs.len());
+    for dir in dirs {
+        if !unique.contains(&dir) {
+            unique.push(dir);
+        }
+    }
+    unique
+}
+
+/// Returns the root Jupyter directory; uses the first directory of the
+/// `JUPYTER_PATH` environment variable if set, XDG values if not.
 pub fn jupyter_dir() -> Option<PathBuf> {
-    if let Ok(envpath) = env::var("JUPYTER_PATH") {
-        Some(PathBuf::from(envpath))
-    } else if let Some(userpath) = jupyter_xdg_dir() {
-        Some(userpath)
-    } else {
-        None
+    jupyter_install_roots(InstallLocation::User)
+        .into_iter()
+        .next()
+}
+
+/// Returns the directories listed in `JUPYTER_PATH`, which is separated like
+/// `PATH` (`:` on Unix, `;` on Windows).
+pub fn jupyter_path() -> Vec<PathBuf> {
+    match env::var_os("JUPYTER_PATH") {
+        Some(path) => env::split_paths(&path)
+            .filter(|path| !path.as_os_str().is_empty())
+            .collect(),
+        None => Vec::new(),
     }
 }
 
+/// Returns the user's Jupyter data directory; uses the `JUPYTER_DATA_DIR`
+/// environment variable if set, XDG values if not.
+pub fn jupyter_data_dir() -> Option<PathBuf> {
+    match env::var_os("JUPYTER_DATA_DIR") {
+        Some(path) if !path.is_empty() => Some(PathBuf::from(path)),
+        _ => jupyter_xdg_dir(),
+    }
+}
+
+/// Returns the prefix of the active Python environment, taken from
+/// `VIRTUAL_ENV` or `CONDA_PREFIX`. This is the `sys.prefix` that Jupyter
+/// would use if it were launched from the same environment.
+pub fn sys_prefix() -> Option<PathBuf> {
+    ["VIRTUAL_ENV", "CONDA_PREFIX"]
+        .iter()
+        .filter_map(env::var_os)
+        .find(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+fn sys_prefix_data_dir() -> Option<PathBuf> {
+    sys_prefix().map(|prefix| prefix.join("share").join("jupyter"))
+}
+
 /// Returns the XDG root directory for Jupyter
 #[cfg(not(target_os = "macos"))]
 fn jupyter_xdg_dir() -> Option<PathBuf> {
-    // On Windows/Linux, the path is XDG_DATA_DIR/jupyter
-    if let Some(path) = dirs::data_dir() {
-        Some(path.join("jupyter"))
-    } else {
-        None
+    // On Linux, the path is $XDG_DATA_HOME/jupyter, falling back to
+    // ~/.local/share/jupyter. On Windows, it is %APPDATA%\jupyter.
+    if let Some(path) = env::var_os("XDG_DATA_HOME") {
+        let path = PathBuf::from(path);
+        if cfg!(unix) && path.is_absolute() {
+            return Some(path.join("jupyter"));
+        }
     }
+    dirs::data_dir().map(|path| path.join("jupyter"))
 }
 
 #[cfg(target_os = "macos")]
@@ -52,3 +146,20 @@ fn jupyter_xdg_dir() -> Option<PathBuf> {
     }
     None
 }
+
+/// Returns the system-wide Jupyter data directories
+#[cfg(unix)]
+fn jupyter_system_dirs() -> Vec<PathBuf> {
+    vec![
+        PathBuf::from("/usr/local/share/jupyter"),
+        PathBuf::from("/usr/share/jupyter"),
+    ]
+}
+
+#[cfg(windows)]
+fn jupyter_system_dirs() -> Vec<PathBuf> {
+    match env::var_os("PROGRAMDATA") {
+        Some(path) => vec![PathBuf::from(path).join("jupyter")],
+        None => Vec::new(),
+    }
+}
@@ -16,6 +16,7 @@ use serde_json::Value;
 
 use crate::error::Error;
 use crate::kernel_dirs;
+use crate::kernel_dirs::InstallLocation;
 
 /// From the Jupyter documentation for [Kernel Specs](https://jupyter-client.readthedocs.io/en/stable/kernels.html#kernel-specs).
 #[derive(Serialize)]
@@ -36,10 +37,22 @@ pub struct KernelSpec {
 impl KernelSpec {
     /// Install a kernel spec to disk.
     pub fn install(&self, folder: String) -> Result<PathBuf, Error> {
-        if let Some(kernel_dir) = kernel_dirs::jupyter_kernel_path() {
-            return self.install_to(kernel_dir.join(folder));
+        self.install_at(InstallLocation::User, folder)
+    }
+
+    /// Install a kernel spec to disk, at the given location.
+    pub fn install_at(&self, location: InstallLocation, folder: String) -> Result<PathBuf, Error> {
+        let path = Self::install_dir(location, folder)?;
+        self.install_to(path)
+    }
+
+    /// Returns the directory a kernel spec would be installed to by
+    /// `install_at()`, without writing anything.
+    pub fn install_dir(location: InstallLocation, folder: String) -> Result<PathBuf, Error> {
+        match kernel_dirs::jupyter_kernel_install_path(location) {
+            Some(kernel_dir) => Ok(kernel_dir.join(folder)),
+            None => Err(Error::NoInstallDir),
         }
-        return Err(Error::NoInstallDir);
     }
 
     fn install_to(&self, path: PathBuf) -> Result<PathBuf, Error> {
@@ -47,6 +47,10 @@ pub trait ShellHandler: Send {
     /// The `originator` is an opaque byte array identifying the peer that sent
     /// the request; it is needed to perform an input request during execution.
     ///
+    /// Called from a dedicated thread, one request at a time and in submission
+    /// order. The shell keeps reading other requests in the meantime, but
+    /// those that need the handler wait for the execution to complete.
+    ///
     /// Docs: https://jupyter-client.readthedocs.io/en/stable/messaging.html#execute
     async fn handle_execute_request(
         &mut self,
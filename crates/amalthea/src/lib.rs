@@ -8,6 +8,9 @@
 pub mod comm;
 pub mod connection_file;
 pub mod error;
+#[cfg(feature = "testing")]
+pub mod fixtures;
+pub mod history;
 pub mod kernel;
 pub mod kernel_dirs;
 pub mod kernel_spec;
@@ -5,12 +5,18 @@
  *
  */
 
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::sync::Mutex;
+
 use crossbeam::channel::Receiver;
 use crossbeam::channel::Sender;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use serde_json::Value;
 
 use crate::comm::base_comm::json_rpc_error;
+use crate::comm::base_comm::JsonRpcError;
 use crate::comm::base_comm::JsonRpcErrorCode;
 use crate::comm::comm_channel::CommMsg;
 
@@ -53,6 +59,11 @@ pub struct CommSocket {
 
     /// The other side of the channel receiving messages from the frontend
     pub incoming_rx: Receiver<CommMsg>,
+
+    /// Correlation ids of the requests sent with `notify()` that haven't been
+    /// handled yet. Shared between clones of the socket so that the comm's
+    /// handler can tell their replies apart from replies to the frontend.
+    backend_requests: Arc<Mutex<HashSet<String>>>,
 }
 
 /**
@@ -96,7 +107,37 @@ impl CommSocket {
             outgoing_rx,
             incoming_tx,
             incoming_rx,
+            backend_requests: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /**
+     * Send a request to the comm's handler from another backend thread
+     * without waiting for its reply.
+     *
+     * The request is delivered like an RPC from the frontend, tagged with a
+     * fresh correlation id. This never blocks, so it can be called from
+     * threads that the comm's handler might itself be waiting on, such as the
+     * R thread. The handler's reply is dropped instead of being sent to the
+     * frontend, and logged if it's an error.
+     *
+     * - `request`: The request, serialized like the comm's backend requests.
+     */
+    pub fn notify<Req>(&self, request: Req) -> anyhow::Result<()>
+    where
+        Req: Serialize,
+    {
+        let data = serde_json::to_value(request)?;
+        let id = uuid::Uuid::new_v4().to_string();
+
+        self.backend_requests.lock().unwrap().insert(id.clone());
+
+        if let Err(err) = self.incoming_tx.send(CommMsg::Rpc(id.clone(), data)) {
+            self.backend_requests.lock().unwrap().remove(&id);
+            anyhow::bail!("Can't send request to {} comm: {err}", self.comm_name);
         }
+
+        Ok(())
     }
 
     /**
@@ -156,9 +197,27 @@ impl CommSocket {
             ),
         };
 
-        let response = CommMsg::Rpc(id, json);
-
-        self.outgoing_tx.send(response).unwrap();
+        self.send_reply(id, json);
         true
     }
+
+    /**
+     * Send the reply to an RPC to the frontend, unless the request was sent by
+     * a backend thread with `notify()`.
+     */
+    fn send_reply(&self, id: String, reply: Value) {
+        if !self.backend_requests.lock().unwrap().remove(&id) {
+            self.outgoing_tx.send(CommMsg::Rpc(id, reply)).unwrap();
+            return;
+        }
+
+        // The frontend doesn't know about this request so drop the reply
+        if let Ok(err) = serde_json::from_value::<JsonRpcError>(reply) {
+            log::error!(
+                "{} comm request {id} failed: {}",
+                self.comm_name,
+                err.error.message
+            );
+        }
+    }
 }
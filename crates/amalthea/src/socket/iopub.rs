@@ -79,7 +79,8 @@ pub enum IOPubMessage {
 }
 
 /// A special IOPub message used to block the sender until the IOPub queue has
-/// forwarded all messages before this one on to the frontend.
+/// forwarded all messages before this one on to the frontend, including
+/// buffered stream output.
 pub struct Wait {
     pub wait_tx: Sender<()>,
 }
@@ -295,7 +296,13 @@ impl IOPub {
     /// different socket that is sent after waiting to still get processed by
     /// the frontend before the messages we cleared from the IOPub queue.
     fn process_wait_request(&mut self, message: Wait) -> Result<(), Error> {
-        message.wait_tx.send(()).unwrap();
+        // Stream output may still be sitting in the buffer, forward it too
+        self.flush_stream();
+
+        // The sender may have given up waiting
+        if message.wait_tx.send(()).is_err() {
+            trace!("Wait request no longer has a receiver");
+        }
         Ok(())
     }
 
@@ -338,7 +345,7 @@ impl StreamBuffer {
     }
 
     fn drain(&mut self) -> StreamOutput {
-        let text = self.buffer.join("");
+        let text = collapse_carriage_returns(&self.buffer.join(""));
         self.buffer.clear();
 
         StreamOutput {
@@ -347,8 +354,72 @@ impl StreamBuffer {
         }
     }
 
+    /// The maximum latency of stream output. Partial lines, like progress
+    /// bars, are flushed at this interval too.
     fn interval() -> &'static Duration {
-        static STREAM_BUFFER_INTERVAL: Duration = Duration::from_millis(80);
+        static STREAM_BUFFER_INTERVAL: Duration = Duration::from_millis(50);
         &STREAM_BUFFER_INTERVAL
     }
 }
+
+/// Drops the parts of lines that a later carriage return overwrites, e.g. the
+/// intermediate states of a progress bar, so they aren't sent to the frontend
+/// just to be erased. The last `\r` of a line is kept so the frontend still
+/// overwrites the part of the line it received in an earlier message. `\r\n`
+/// line endings and trailing `\r` are left alone.
+fn collapse_carriage_returns(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+
+    for line in text.split_inclusive('\n') {
+        let (body, ending) = match line.strip_suffix("\r\n") {
+            Some(body) => (body, "\r\n"),
+            None => match line.strip_suffix('\n') {
+                Some(body) => (body, "\n"),
+                None => (line, ""),
+            },
+        };
+
+        match body.trim_end_matches('\r').rfind('\r') {
+            Some(i) => out.push_str(&body[i..]),
+            None => out.push_str(body),
+        }
+        out.push_str(ending);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::socket::iopub::collapse_carriage_returns;
+    use crate::socket::iopub::StreamBuffer;
+    use crate::wire::stream::Stream;
+
+    #[test]
+    fn test_collapse_carriage_returns() {
+        assert_eq!(collapse_carriage_returns("10%\r50%\r100%\n"), "\r100%\n");
+        assert_eq!(collapse_carriage_returns("a\r\nb\n"), "a\r\nb\n");
+        assert_eq!(collapse_carriage_returns("50%\r"), "50%\r");
+        assert_eq!(collapse_carriage_returns("no returns"), "no returns");
+    }
+
+    #[test]
+    fn test_collapse_carriage_returns_across_chunks() {
+        let mut buffer = StreamBuffer::new(Stream::Stdout);
+
+        // Chunks are joined before collapsing, so a progress bar split across
+        // writes only keeps its last state
+        buffer.push(String::from("10%\r"));
+        buffer.push(String::from("50%"));
+        buffer.push(String::from("\r100%\ndone\n"));
+        assert_eq!(buffer.drain().text, "\r100%\ndone\n");
+        assert!(buffer.is_empty());
+
+        // The last `\r` of a partial line is kept so the frontend overwrites
+        // the part of the line sent in the previous message
+        buffer.push(String::from("20%"));
+        assert_eq!(buffer.drain().text, "20%");
+        buffer.push(String::from("\r30%\r40%"));
+        assert_eq!(buffer.drain().text, "\r40%");
+    }
+}
@@ -9,6 +9,7 @@ use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::Mutex;
 
+use crossbeam::channel::unbounded;
 use crossbeam::channel::Receiver;
 use crossbeam::channel::SendError;
 use crossbeam::channel::Sender;
@@ -18,13 +19,16 @@ use log::trace;
 use log::warn;
 use serde_json::json;
 use stdext::result::ResultOrLog;
+use stdext::spawn;
 
 use crate::comm::comm_channel::Comm;
 use crate::comm::comm_channel::CommMsg;
 use crate::comm::event::CommManagerEvent;
 use crate::comm::event::CommShellEvent;
 use crate::comm::server_comm::ServerComm;
+use crate::comm::server_comm::StartServer;
 use crate::error::Error;
+use crate::history::History;
 use crate::language::server_handler::ServerHandler;
 use crate::language::shell_handler::ShellHandler;
 use crate::socket::comm::CommInitiator;
@@ -40,7 +44,11 @@ use crate::wire::comm_msg::CommWireMsg;
 use crate::wire::comm_open::CommOpen;
 use crate::wire::complete_reply::CompleteReply;
 use crate::wire::complete_request::CompleteRequest;
+use crate::wire::execute_reply::ExecuteReply;
+use crate::wire::execute_reply_exception::ExecuteReplyException;
 use crate::wire::execute_request::ExecuteRequest;
+use crate::wire::history_reply::HistoryReply;
+use crate::wire::history_request::HistoryRequest;
 use crate::wire::inspect_reply::InspectReply;
 use crate::wire::inspect_request::InspectRequest;
 use crate::wire::is_complete_reply::IsCompleteReply;
@@ -55,12 +63,28 @@ use crate::wire::originator::Originator;
 use crate::wire::status::ExecutionState;
 use crate::wire::status::KernelStatus;
 
+/// An execute request along with the outcome of its execution
+type ExecuteOutcome = (
+    JupyterMessage<ExecuteRequest>,
+    Result<ExecuteReply, ExecuteReplyException>,
+);
+
 /// Wrapper for the Shell socket; receives requests for execution, etc. from the
 /// frontend and handles them or dispatches them to the execution thread.
 pub struct Shell {
     /// The ZeroMQ Shell socket
     socket: Socket,
 
+    /// Notifications from the executor thread that an execute reply is ready
+    /// in `reply_rx`
+    reply_notif_socket: Socket,
+
+    /// Queue of execute requests, consumed by the executor thread
+    execute_tx: Sender<JupyterMessage<ExecuteRequest>>,
+
+    /// Execute requests that have run, to be replied to on the Shell socket
+    reply_rx: Receiver<ExecuteOutcome>,
+
     /// Sends messages to the IOPub socket (owned by another thread)
     iopub_tx: Sender<IOPubMessage>,
 
@@ -81,28 +105,82 @@ pub struct Shell {
 
     /// Channel used to receive comm events from the comm manager
     comm_shell_rx: Receiver<CommShellEvent>,
+
+    /// Persisted execution history, if enabled
+    history: Option<Arc<Mutex<History>>>,
+}
+
+/// Runs the execute requests queued by the shell, one at a time and in
+/// submission order. Lives on its own thread so that the shell can keep
+/// reading requests from its socket while code is executing.
+struct Executor {
+    /// Sends messages to the IOPub socket
+    iopub_tx: Sender<IOPubMessage>,
+
+    /// Language-provided shell handler object
+    shell_handler: Arc<Mutex<dyn ShellHandler>>,
+
+    /// Queue of execute requests, filled by the shell
+    execute_rx: Receiver<JupyterMessage<ExecuteRequest>>,
+
+    /// Delivers the outcome of each execution to the shell
+    reply_tx: Sender<ExecuteOutcome>,
+
+    /// Wakes up the shell once an outcome is available in `reply_tx`. The
+    /// shell owns the Shell socket and waits on it, so it can't wait on a
+    /// channel as well.
+    reply_notif_socket: Socket,
+
+    /// Persisted execution history, if enabled
+    history: Option<Arc<Mutex<History>>>,
 }
 
 impl Shell {
-    /// Create a new Shell socket.
+    /// Create a new Shell socket, along with the thread executing its execute
+    /// requests.
     ///
     /// * `socket` - The underlying ZeroMQ Shell socket
+    /// * `reply_notif_rx` - A socket notifying the shell of execute replies
+    /// * `reply_notif_tx` - The other end of `reply_notif_rx`
     /// * `iopub_tx` - A channel that delivers messages to the IOPub socket
     /// * `comm_manager_tx` - A channel that delivers messages to the comm manager thread
     /// * `comm_changed_rx` - A channel that receives messages from the comm manager thread
     /// * `shell_handler` - The language's shell channel handler
     /// * `lsp_handler` - The language's LSP handler, if it supports LSP
+    /// * `dap_handler` - The language's DAP handler, if it supports DAP
+    /// * `history` - The execution history store, if history is persisted
     pub fn new(
         socket: Socket,
+        reply_notif_rx: Socket,
+        reply_notif_tx: Socket,
         iopub_tx: Sender<IOPubMessage>,
         comm_manager_tx: Sender<CommManagerEvent>,
         comm_shell_rx: Receiver<CommShellEvent>,
         shell_handler: Arc<Mutex<dyn ShellHandler>>,
         lsp_handler: Option<Arc<Mutex<dyn ServerHandler>>>,
         dap_handler: Option<Arc<Mutex<dyn ServerHandler>>>,
+        history: Option<History>,
     ) -> Self {
+        let history = history.map(|history| Arc::new(Mutex::new(history)));
+
+        let (execute_tx, execute_rx) = unbounded();
+        let (reply_tx, reply_rx) = unbounded();
+
+        let executor = Executor {
+            iopub_tx: iopub_tx.clone(),
+            shell_handler: shell_handler.clone(),
+            execute_rx,
+            reply_tx,
+            reply_notif_socket: reply_notif_tx,
+            history: history.clone(),
+        };
+        spawn!("shell-executor", move || executor.listen());
+
         Self {
             socket,
+            reply_notif_socket: reply_notif_rx,
+            execute_tx,
+            reply_rx,
             iopub_tx,
             shell_handler,
             lsp_handler,
@@ -110,6 +188,7 @@ impl Shell {
             open_comms: Vec::new(),
             comm_manager_tx,
             comm_shell_rx,
+            history,
         }
     }
 
@@ -118,6 +197,27 @@ impl Shell {
         // Begin listening for shell messages
         loop {
             trace!("Waiting for shell messages");
+
+            // Wait for either a message from the frontend or an execute reply
+            let (has_message, has_reply) = {
+                let mut poll_items = [
+                    self.socket.socket.as_poll_item(zmq::POLLIN),
+                    self.reply_notif_socket.socket.as_poll_item(zmq::POLLIN),
+                ];
+                if let Err(err) = zmq::poll(&mut poll_items, -1) {
+                    warn!("Could not poll shell socket: {}", err);
+                    continue;
+                }
+                (poll_items[0].is_readable(), poll_items[1].is_readable())
+            };
+
+            if has_reply {
+                self.send_execute_reply();
+            }
+            if !has_message {
+                continue;
+            }
+
             // Attempt to read the next message from the ZeroMQ socket
             let message = match Message::read_from_socket(&self.socket) {
                 Ok(m) => m,
@@ -149,14 +249,12 @@ impl Shell {
             Message::IsCompleteRequest(req) => {
                 self.handle_request(req, |h, r| self.handle_is_complete_request(h, r))
             },
-            Message::ExecuteRequest(req) => {
-                self.handle_request(req, |h, r| self.handle_execute_request(h, r))
-            },
+            Message::ExecuteRequest(req) => self.handle_execute_request(req),
             Message::CompleteRequest(req) => {
                 self.handle_request(req, |h, r| self.handle_complete_request(h, r))
             },
             Message::CommInfoRequest(req) => {
-                self.handle_request(req, |h, r| self.handle_comm_info_request(h, r))
+                self.handle_shell_request(req, |r| self.handle_comm_info_request(r))
             },
             Message::CommOpen(req) => self.handle_comm_open(req),
             Message::CommMsg(req) => self.handle_request(req, |h, r| self.handle_comm_msg(h, r)),
@@ -164,6 +262,9 @@ impl Shell {
             Message::InspectRequest(req) => {
                 self.handle_request(req, |h, r| self.handle_inspect_request(h, r))
             },
+            Message::HistoryRequest(req) => {
+                self.handle_shell_request(req, |r| self.handle_history_request(r))
+            },
             _ => Err(Error::UnsupportedMessage(msg, String::from("shell"))),
         }
     }
@@ -207,38 +308,90 @@ impl Shell {
         result
     }
 
+    /// Like `handle_request()`, for requests that the shell answers on its
+    /// own. These don't wait for the shell handler, which is locked for as
+    /// long as code is executing.
+    fn handle_shell_request<T: ProtocolMessage, H: Fn(JupyterMessage<T>) -> Result<(), Error>>(
+        &self,
+        req: JupyterMessage<T>,
+        handler: H,
+    ) -> Result<(), Error> {
+        if let Err(err) = self.send_state(req.clone(), ExecutionState::Busy) {
+            warn!("Failed to change kernel status to busy: {}", err)
+        }
+
+        let result = handler(req.clone());
+
+        if let Err(err) = self.send_state(req, ExecutionState::Idle) {
+            warn!("Failed to restore kernel status to idle: {}", err)
+        }
+        result
+    }
+
     /// Sets the kernel state by sending a message on the IOPub channel.
     fn send_state<T: ProtocolMessage>(
         &self,
         parent: JupyterMessage<T>,
         state: ExecutionState,
     ) -> Result<(), SendError<IOPubMessage>> {
-        let reply = KernelStatus {
-            execution_state: state,
-        };
-        let message = IOPubMessage::Status(parent.header, IOPubContextChannel::Shell, reply);
-        self.iopub_tx.send(message)
+        send_state(&self.iopub_tx, parent, state)
     }
 
-    /// Handles an ExecuteRequest; dispatches the request to the execution
-    /// thread and forwards the response
-    fn handle_execute_request(
-        &self,
-        handler: &mut dyn ShellHandler,
-        req: JupyterMessage<ExecuteRequest>,
-    ) -> Result<(), Error> {
+    /// Handles an ExecuteRequest by queueing it for the executor thread. The
+    /// shell doesn't wait for the execution: the busy and idle statuses are
+    /// sent by the executor around the execution, and the reply is sent from
+    /// `send_execute_reply()` once it's ready.
+    fn handle_execute_request(&self, req: JupyterMessage<ExecuteRequest>) -> Result<(), Error> {
         debug!("Received execution request {:?}", req);
-        let originator = Originator::from(&req);
-        match block_on(handler.handle_execute_request(Some(originator), &req.content)) {
+        self.execute_tx
+            .send(req)
+            .map_err(|err| Error::SendError(format!("{err:?}")))
+    }
+
+    /// Sends the reply of an execute request that has run. Called once per
+    /// notification from the executor thread.
+    fn send_execute_reply(&self) {
+        // Consume the notification
+        let mut notification = zmq::Message::new();
+        if let Err(err) = self.reply_notif_socket.recv(&mut notification) {
+            warn!("Could not receive execute reply notification: {}", err);
+            return;
+        }
+
+        let Ok((req, result)) = self.reply_rx.try_recv() else {
+            warn!("Notified of an execute reply, but none is ready");
+            return;
+        };
+
+        let result = match result {
             Ok(reply) => {
                 trace!("Got execution reply, delivering to frontend: {:?}", reply);
-                let r = req.send_reply(reply, &self.socket);
-                r
+                req.send_reply(reply, &self.socket)
             },
             Err(err) => req.send_reply(err, &self.socket),
+        };
+        if let Err(err) = result {
+            warn!("Could not send execute reply: {}", err);
         }
     }
 
+    /// Handle a request for past inputs. Without a history store, the reply
+    /// is empty.
+    fn handle_history_request(&self, req: JupyterMessage<HistoryRequest>) -> Result<(), Error> {
+        debug!("Received history request: {:?}", req);
+
+        let history = match &self.history {
+            Some(history) => history.lock().unwrap().query(&req.content),
+            None => Vec::new(),
+        };
+
+        let reply = HistoryReply {
+            status: Status::Ok,
+            history,
+        };
+        req.send_reply(reply, &self.socket)
+    }
+
     /// Handle a request to test code for completion.
     fn handle_is_complete_request(
         &self,
@@ -279,11 +432,7 @@ impl Shell {
     }
 
     /// Handle a request for open comms
-    fn handle_comm_info_request(
-        &self,
-        _handler: &dyn ShellHandler,
-        req: JupyterMessage<CommInfoRequest>,
-    ) -> Result<(), Error> {
+    fn handle_comm_info_request(&self, req: JupyterMessage<CommInfoRequest>) -> Result<(), Error> {
         debug!("Received request for open comms: {:?}", req);
 
         // Convert our internal map of open comms to a JSON object
@@ -518,12 +667,16 @@ impl Shell {
             let (init_tx, init_rx) = crossbeam::channel::bounded::<bool>(1);
 
             // Parse the message as server address
-            let address = serde_json::from_value(req.content.data.clone()).map_err(|err| {
-                Error::InvalidCommMessage(
-                    req.content.target_name.clone(),
-                    data_str,
-                    err.to_string(),
-                )
+            let data: StartServer =
+                serde_json::from_value(req.content.data.clone()).map_err(|err| {
+                    Error::InvalidCommMessage(
+                        req.content.target_name.clone(),
+                        data_str.clone(),
+                        err.to_string(),
+                    )
+                })?;
+            let address = data.tcp_address().map_err(|err| {
+                Error::InvalidCommMessage(req.content.target_name.clone(), data_str, err)
             })?;
 
             // Create the new comm wrapper for the server and start it in a
@@ -595,3 +748,73 @@ impl Shell {
         // messages to receive
     }
 }
+
+impl Executor {
+    /// Main loop for the executor thread. Exits once the shell is gone.
+    fn listen(&self) {
+        for req in self.execute_rx.iter() {
+            self.execute(req);
+        }
+    }
+
+    /// Runs an ExecuteRequest, wrapped in its busy and idle statuses, and
+    /// hands its outcome to the shell
+    fn execute(&self, req: JupyterMessage<ExecuteRequest>) {
+        if let Err(err) = send_state(&self.iopub_tx, req.clone(), ExecutionState::Busy) {
+            warn!("Failed to change kernel status to busy: {}", err)
+        }
+
+        let result = {
+            let mut handler = self.shell_handler.lock().unwrap();
+            let originator = Originator::from(&req);
+            block_on(handler.handle_execute_request(Some(originator), &req.content))
+        };
+
+        let execution_count = match &result {
+            Ok(reply) => reply.execution_count,
+            Err(err) => err.execution_count,
+        };
+        self.record_history(&req.content, execution_count);
+
+        // Send the notification after the outcome so that the shell finds it
+        // when it wakes up
+        if let Err(err) = self.reply_tx.send((req.clone(), result)) {
+            warn!("Could not deliver execute reply to the shell: {}", err);
+        } else if let Err(err) = self.reply_notif_socket.send(zmq::Message::new()) {
+            warn!("Could not notify the shell of an execute reply: {}", err);
+        }
+
+        // Return to idle, even if the execution generated an error
+        if let Err(err) = send_state(&self.iopub_tx, req, ExecutionState::Idle) {
+            warn!("Failed to restore kernel status to idle: {}", err)
+        }
+    }
+
+    /// Records executed code in the history, if history is persisted and the
+    /// request asked for it
+    fn record_history(&self, req: &ExecuteRequest, execution_count: u32) {
+        let Some(history) = &self.history else {
+            return;
+        };
+        if req.silent || !req.store_history {
+            return;
+        }
+
+        if let Err(err) = history.lock().unwrap().record(execution_count, &req.code) {
+            warn!("Failed to record execution in history: {err}");
+        }
+    }
+}
+
+/// Sets the kernel state by sending a message on the IOPub channel.
+fn send_state<T: ProtocolMessage>(
+    iopub_tx: &Sender<IOPubMessage>,
+    parent: JupyterMessage<T>,
+    state: ExecutionState,
+) -> Result<(), SendError<IOPubMessage>> {
+    let reply = KernelStatus {
+        execution_state: state,
+    };
+    let message = IOPubMessage::Status(parent.header, IOPubContextChannel::Shell, reply);
+    iopub_tx.send(message)
+}
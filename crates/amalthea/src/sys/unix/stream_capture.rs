@@ -47,6 +47,11 @@ impl StreamCapture {
         let stderr_poll = nix::poll::PollFd::new(stderr_fd, nix::poll::PollFlags::POLLIN);
         let mut poll_fds = [stdout_poll, stderr_poll];
 
+        // Bytes of an incomplete UTF-8 sequence at the end of the last read,
+        // for each stream
+        let mut stdout_pending: Vec<u8> = Vec::new();
+        let mut stderr_pending: Vec<u8> = Vec::new();
+
         loop {
             // Wait for data to be available on either stdout or stderr.  This
             // blocks until data is available, the streams are interrupted, or
@@ -81,17 +86,17 @@ impl StreamCapture {
                 if revents.contains(nix::poll::PollFlags::POLLIN) {
                     let fd = poll_fd.as_raw_fd();
                     // Look up the stream name from its file descriptor.
-                    let stream = if fd == stdout_fd {
-                        Stream::Stdout
+                    let (stream, pending) = if fd == stdout_fd {
+                        (Stream::Stdout, &mut stdout_pending)
                     } else if fd == stderr_fd {
-                        Stream::Stderr
+                        (Stream::Stderr, &mut stderr_pending)
                     } else {
                         warn!("Unknown stream fd: {}", fd);
                         continue;
                     };
 
                     // Read the data from the stream and send it to iopub.
-                    Self::fd_to_iopub(fd, stream, iopub_tx.clone());
+                    Self::fd_to_iopub(fd, stream, pending, iopub_tx.clone());
                 }
             }
         }
@@ -100,7 +105,17 @@ impl StreamCapture {
     }
 
     /// Reads data from a file descriptor and sends it to the IOPub socket.
-    fn fd_to_iopub(fd: RawFd, stream: Stream, iopub_tx: Sender<IOPubMessage>) {
+    ///
+    /// Output is forwarded as soon as it's read, without waiting for a
+    /// newline, so partial lines like progress bars show up. IOPub takes
+    /// care of batching. Only a multi-byte UTF-8 character split across
+    /// reads is held back in `pending` until the rest of it arrives.
+    fn fd_to_iopub(
+        fd: RawFd,
+        stream: Stream,
+        pending: &mut Vec<u8>,
+        iopub_tx: Sender<IOPubMessage>,
+    ) {
         // Read up to 1024 bytes from the stream into `buf`
         let mut buf = [0u8; 1024];
         let count = match nix::unistd::read(fd, &mut buf) {
@@ -116,8 +131,12 @@ impl StreamCapture {
             return;
         }
 
-        // Convert the UTF-8 bytes to a string.
-        let data = String::from_utf8_lossy(&buf[..count]).to_string();
+        pending.extend_from_slice(&buf[..count]);
+
+        let Some(data) = take_utf8(pending) else {
+            return;
+        };
+
         let output = StreamOutput {
             name: stream,
             text: data,
@@ -167,3 +186,55 @@ impl StreamCapture {
         return Ok(read);
     }
 }
+
+/// Converts the UTF-8 bytes of `pending` to a string and removes them.
+/// Invalid bytes are replaced, but an incomplete sequence at the end is kept
+/// for the next read. Returns `None` if there is nothing to send yet.
+fn take_utf8(pending: &mut Vec<u8>) -> Option<String> {
+    let valid = match std::str::from_utf8(pending) {
+        Ok(_) => pending.len(),
+        Err(err) if err.error_len().is_none() => err.valid_up_to(),
+        Err(_) => pending.len(),
+    };
+    if valid == 0 {
+        return None;
+    }
+
+    let data = String::from_utf8_lossy(&pending[..valid]).to_string();
+    pending.drain(..valid);
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sys::unix::stream_capture::take_utf8;
+
+    #[test]
+    fn test_take_utf8_split_character() {
+        // `é` is encoded as two bytes, split across two reads
+        let bytes = "café".as_bytes();
+        let (first, second) = bytes.split_at(bytes.len() - 1);
+
+        let mut pending = first.to_vec();
+        assert_eq!(take_utf8(&mut pending), Some(String::from("caf")));
+        assert_eq!(pending, vec![0xC3]);
+
+        // Nothing to send until the rest of the character arrives
+        assert_eq!(take_utf8(&mut pending), None);
+
+        pending.extend_from_slice(second);
+        assert_eq!(take_utf8(&mut pending), Some(String::from("é")));
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn test_take_utf8_invalid_bytes() {
+        // Invalid bytes are replaced rather than held back
+        let mut pending = vec![b'a', 0xFF, b'b'];
+        assert_eq!(take_utf8(&mut pending), Some(String::from("a\u{FFFD}b")));
+        assert!(pending.is_empty());
+
+        let mut pending = Vec::new();
+        assert_eq!(take_utf8(&mut pending), None);
+    }
+}
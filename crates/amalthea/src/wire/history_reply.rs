@@ -0,0 +1,30 @@
+/*
+ * history_reply.rs
+ *
+ * Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+ *
+ */
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::wire::jupyter_message::MessageType;
+use crate::wire::jupyter_message::Status;
+
+/// Represents a reply to a `history_request`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HistoryReply {
+    /// The status of the request
+    pub status: Status,
+
+    /// The matching entries, as `(session, line_number, input)` tuples. When
+    /// output was requested, `input` is an `(input, output)` pair instead.
+    pub history: Vec<(u32, u32, Value)>,
+}
+
+impl MessageType for HistoryReply {
+    fn message_type() -> String {
+        String::from("history_reply")
+    }
+}
@@ -0,0 +1,68 @@
+/*
+ * history_request.rs
+ *
+ * Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+ *
+ */
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::wire::jupyter_message::MessageType;
+
+/// Represents a request from the frontend for past inputs
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HistoryRequest {
+    /// Whether to return the output of each input along with it
+    #[serde(default)]
+    pub output: bool,
+
+    /// Whether to return the raw input rather than the transformed input
+    #[serde(default)]
+    pub raw: bool,
+
+    /// Which entries to return
+    pub hist_access_type: HistAccessType,
+
+    /// For `range` requests, the session to look in. Zero or negative values
+    /// are relative to the current session.
+    #[serde(default)]
+    pub session: i32,
+
+    /// For `range` requests, the first line number to return
+    #[serde(default)]
+    pub start: i32,
+
+    /// For `range` requests, the line number to stop before. Zero means up
+    /// to the last line.
+    #[serde(default)]
+    pub stop: i32,
+
+    /// For `tail` and `search` requests, the maximum number of entries to
+    /// return, starting from the most recent ones
+    #[serde(default)]
+    pub n: i32,
+
+    /// For `search` requests, a glob pattern (`*` and `?`) matched against
+    /// the inputs
+    #[serde(default)]
+    pub pattern: String,
+
+    /// For `search` requests, whether to skip duplicate inputs
+    #[serde(default)]
+    pub unique: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HistAccessType {
+    Range,
+    Tail,
+    Search,
+}
+
+impl MessageType for HistoryRequest {
+    fn message_type() -> String {
+        String::from("history_request")
+    }
+}
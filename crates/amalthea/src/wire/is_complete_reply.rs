@@ -20,7 +20,7 @@ pub struct IsCompleteReply {
     pub indent: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum IsComplete {
     /// The submitted code is complete as written.
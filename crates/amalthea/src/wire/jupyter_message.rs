@@ -21,6 +21,7 @@ use crate::wire::comm_msg::CommWireMsg;
 use crate::wire::comm_open::CommOpen;
 use crate::wire::complete_reply::CompleteReply;
 use crate::wire::complete_request::CompleteRequest;
+use crate::wire::display_data::DisplayData;
 use crate::wire::error_reply::ErrorReply;
 use crate::wire::exception::Exception;
 use crate::wire::execute_error::ExecuteError;
@@ -30,6 +31,8 @@ use crate::wire::execute_reply_exception::ExecuteReplyException;
 use crate::wire::execute_request::ExecuteRequest;
 use crate::wire::execute_result::ExecuteResult;
 use crate::wire::header::JupyterHeader;
+use crate::wire::history_reply::HistoryReply;
+use crate::wire::history_request::HistoryRequest;
 use crate::wire::input_reply::InputReply;
 use crate::wire::input_request::InputRequest;
 use crate::wire::inspect_reply::InspectReply;
@@ -41,8 +44,10 @@ use crate::wire::is_complete_request::IsCompleteRequest;
 use crate::wire::kernel_info_reply::KernelInfoReply;
 use crate::wire::kernel_info_request::KernelInfoRequest;
 use crate::wire::originator::Originator;
+use crate::wire::shutdown_reply::ShutdownReply;
 use crate::wire::shutdown_request::ShutdownRequest;
 use crate::wire::status::KernelStatus;
+use crate::wire::update_display_data::UpdateDisplayData;
 use crate::wire::wire_message::WireMessage;
 
 /// Represents a Jupyter message
@@ -58,6 +63,10 @@ pub struct JupyterMessage<T> {
     /// not all messages have an originator.
     pub parent_header: Option<JupyterHeader>,
 
+    /// Additional metadata, e.g. the `cellId` of the notebook cell an
+    /// execute request was sent from
+    pub metadata: serde_json::Value,
+
     /// The body (payload) of the message
     pub content: T,
 }
@@ -83,6 +92,8 @@ pub enum Message {
     ExecuteResult(JupyterMessage<ExecuteResult>),
     ExecuteError(JupyterMessage<ExecuteError>),
     ExecuteInput(JupyterMessage<ExecuteInput>),
+    HistoryReply(JupyterMessage<HistoryReply>),
+    HistoryRequest(JupyterMessage<HistoryRequest>),
     InputReply(JupyterMessage<InputReply>),
     InputRequest(JupyterMessage<InputRequest>),
     InspectReply(JupyterMessage<InspectReply>),
@@ -93,6 +104,7 @@ pub enum Message {
     IsCompleteRequest(JupyterMessage<IsCompleteRequest>),
     KernelInfoReply(JupyterMessage<KernelInfoReply>),
     KernelInfoRequest(JupyterMessage<KernelInfoRequest>),
+    ShutdownReply(JupyterMessage<ShutdownReply>),
     ShutdownRequest(JupyterMessage<ShutdownRequest>),
     Status(JupyterMessage<KernelStatus>),
     CommInfoReply(JupyterMessage<CommInfoReply>),
@@ -103,6 +115,8 @@ pub enum Message {
     CommReply(JupyterMessage<JsonRpcReply>),
     CommClose(JupyterMessage<CommClose>),
     StreamOutput(JupyterMessage<StreamOutput>),
+    DisplayData(JupyterMessage<DisplayData>),
+    UpdateDisplayData(JupyterMessage<UpdateDisplayData>),
 }
 
 /// Associates a `Message` to a 0MQ socket
@@ -133,6 +147,8 @@ impl TryFrom<&Message> for WireMessage {
             Message::ExecuteResult(msg) => WireMessage::try_from(msg),
             Message::ExecuteError(msg) => WireMessage::try_from(msg),
             Message::ExecuteInput(msg) => WireMessage::try_from(msg),
+            Message::HistoryReply(msg) => WireMessage::try_from(msg),
+            Message::HistoryRequest(msg) => WireMessage::try_from(msg),
             Message::InputReply(msg) => WireMessage::try_from(msg),
             Message::InputRequest(msg) => WireMessage::try_from(msg),
             Message::InspectReply(msg) => WireMessage::try_from(msg),
@@ -143,6 +159,7 @@ impl TryFrom<&Message> for WireMessage {
             Message::IsCompleteRequest(msg) => WireMessage::try_from(msg),
             Message::KernelInfoReply(msg) => WireMessage::try_from(msg),
             Message::KernelInfoRequest(msg) => WireMessage::try_from(msg),
+            Message::ShutdownReply(msg) => WireMessage::try_from(msg),
             Message::ShutdownRequest(msg) => WireMessage::try_from(msg),
             Message::Status(msg) => WireMessage::try_from(msg),
             Message::CommInfoReply(msg) => WireMessage::try_from(msg),
@@ -153,6 +170,8 @@ impl TryFrom<&Message> for WireMessage {
             Message::CommRequest(msg) => WireMessage::try_from(msg),
             Message::CommReply(msg) => WireMessage::try_from(msg),
             Message::StreamOutput(msg) => WireMessage::try_from(msg),
+            Message::DisplayData(msg) => WireMessage::try_from(msg),
+            Message::UpdateDisplayData(msg) => WireMessage::try_from(msg),
         }
     }
 }
@@ -183,17 +202,31 @@ impl TryFrom<&WireMessage> for Message {
         } else if kind == ExecuteRequest::message_type() {
             return Ok(Message::ExecuteRequest(JupyterMessage::try_from(msg)?));
         } else if kind == ExecuteReply::message_type() {
+            // Failed executions carry the exception in the reply
+            if msg.content["status"] == "error" {
+                return Ok(Message::ExecuteReplyException(JupyterMessage::try_from(
+                    msg,
+                )?));
+            }
             return Ok(Message::ExecuteReply(JupyterMessage::try_from(msg)?));
         } else if kind == ExecuteResult::message_type() {
             return Ok(Message::ExecuteResult(JupyterMessage::try_from(msg)?));
+        } else if kind == ExecuteError::message_type() {
+            return Ok(Message::ExecuteError(JupyterMessage::try_from(msg)?));
         } else if kind == ExecuteInput::message_type() {
             return Ok(Message::ExecuteInput(JupyterMessage::try_from(msg)?));
         } else if kind == CompleteRequest::message_type() {
             return Ok(Message::CompleteRequest(JupyterMessage::try_from(msg)?));
         } else if kind == CompleteReply::message_type() {
             return Ok(Message::CompleteReply(JupyterMessage::try_from(msg)?));
+        } else if kind == HistoryRequest::message_type() {
+            return Ok(Message::HistoryRequest(JupyterMessage::try_from(msg)?));
+        } else if kind == HistoryReply::message_type() {
+            return Ok(Message::HistoryReply(JupyterMessage::try_from(msg)?));
         } else if kind == ShutdownRequest::message_type() {
             return Ok(Message::ShutdownRequest(JupyterMessage::try_from(msg)?));
+        } else if kind == ShutdownReply::message_type() {
+            return Ok(Message::ShutdownReply(JupyterMessage::try_from(msg)?));
         } else if kind == KernelStatus::message_type() {
             return Ok(Message::Status(JupyterMessage::try_from(msg)?));
         } else if kind == CommInfoRequest::message_type() {
@@ -216,6 +249,10 @@ impl TryFrom<&WireMessage> for Message {
             return Ok(Message::InputRequest(JupyterMessage::try_from(msg)?));
         } else if kind == StreamOutput::message_type() {
             return Ok(Message::StreamOutput(JupyterMessage::try_from(msg)?));
+        } else if kind == DisplayData::message_type() {
+            return Ok(Message::DisplayData(JupyterMessage::try_from(msg)?));
+        } else if kind == UpdateDisplayData::message_type() {
+            return Ok(Message::UpdateDisplayData(JupyterMessage::try_from(msg)?));
         } else if kind == UiFrontendRequest::message_type() {
             return Ok(Message::CommRequest(JupyterMessage::try_from(msg)?));
         } else if kind == JsonRpcReply::message_type() {
@@ -264,6 +301,7 @@ where
                 session.username.clone(),
             ),
             parent_header: parent,
+            metadata: serde_json::json!({}),
             content,
         }
     }
@@ -287,6 +325,7 @@ where
                 session.username.clone(),
             ),
             parent_header,
+            metadata: serde_json::json!({}),
             content,
         }
     }
@@ -335,6 +374,7 @@ where
                 session.username.clone(),
             ),
             parent_header: Some(self.header.clone()),
+            metadata: serde_json::json!({}),
             content,
         }
     }
@@ -357,6 +397,7 @@ where
                 session.username.clone(),
             ),
             parent_header: Some(self.header.clone()),
+            metadata: serde_json::json!({}),
             content: ErrorReply {
                 status: Status::Error,
                 exception,
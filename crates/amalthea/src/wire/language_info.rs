@@ -45,4 +45,7 @@ pub struct LanguageInfoPositron {
 
     /// Initial continuation prompt
     pub continuation_prompt: Option<String>,
+
+    /// Mode in which the session runs, e.g. `console` or `notebook`
+    pub session_mode: Option<String>,
 }
@@ -24,6 +24,8 @@ pub mod execute_response;
 pub mod execute_result;
 pub mod header;
 pub mod help_link;
+pub mod history_reply;
+pub mod history_request;
 pub mod input_reply;
 pub mod input_request;
 pub mod inspect_reply;
@@ -12,6 +12,8 @@ use crate::wire::jupyter_message::JupyterMessage;
 pub struct Originator {
     pub zmq_id: Vec<u8>,
     pub header: JupyterHeader,
+    /// The notebook cell the request was sent from, if any
+    pub cell_id: Option<String>,
 }
 
 impl<T> From<&JupyterMessage<T>> for Originator {
@@ -19,6 +21,11 @@ impl<T> From<&JupyterMessage<T>> for Originator {
         Originator {
             zmq_id: msg.zmq_identities[0].clone(),
             header: msg.header.clone(),
+            cell_id: msg
+                .metadata
+                .get("cellId")
+                .and_then(|id| id.as_str())
+                .map(String::from),
         }
     }
 }
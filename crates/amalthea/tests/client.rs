@@ -10,6 +10,7 @@ use std::sync::Mutex;
 
 use amalthea::comm::comm_channel::CommMsg;
 use amalthea::comm::event::CommManagerEvent;
+use amalthea::fixtures::dummy_frontend::DummyFrontend;
 use amalthea::kernel::Kernel;
 use amalthea::kernel::StreamBehavior;
 use amalthea::socket::comm::CommInitiator;
@@ -38,12 +39,11 @@ use log::info;
 use serde_json;
 
 mod control;
-mod frontend;
 mod shell;
 
 #[test]
 fn test_kernel() {
-    let frontend = frontend::Frontend::new();
+    let frontend = DummyFrontend::new();
     let connection_file = frontend.get_connection_file();
     let mut kernel = Kernel::new("amalthea", connection_file).unwrap();
 
@@ -312,6 +312,88 @@ fn test_kernel() {
         },
     }
 
+    // The shell keeps reading requests while code is executing. Start an
+    // execution that waits for input, then check that other requests are
+    // handled in the meantime and that execute requests are queued.
+    info!("Sending request to generate an input prompt");
+    frontend.send_shell(ExecuteRequest {
+        code: "prompt".to_string(),
+        silent: false,
+        store_history: true,
+        user_expressions: serde_json::Value::Null,
+        allow_stdin: true,
+        stop_on_error: false,
+    });
+
+    info!("Waiting for kernel to send an input request");
+    let request = frontend.receive_stdin();
+    assert!(matches!(request, Message::InputRequest(_)));
+
+    info!("Sending an execute request while the prompt is waiting for input");
+    let queued_id = frontend.send_shell(ExecuteRequest {
+        code: "42".to_string(),
+        silent: false,
+        store_history: true,
+        user_expressions: serde_json::Value::Null,
+        allow_stdin: false,
+        stop_on_error: false,
+    });
+
+    info!("Requesting comm info while the prompt is waiting for input");
+    frontend.send_shell(CommInfoRequest {
+        target_name: "".to_string(),
+    });
+    let reply = frontend.receive_shell();
+    match reply {
+        Message::CommInfoReply(reply) => {
+            info!("Got comm info: {:?}", reply);
+        },
+        _ => {
+            panic!(
+                "Unexpected message received (expected comm info): {:?}",
+                reply
+            );
+        },
+    }
+
+    info!("Sending input to the kernel");
+    frontend.send_stdin(InputReply {
+        value: "42".to_string(),
+    });
+
+    // Executions complete in submission order
+    for execution_count in [3, 4] {
+        let reply = frontend.receive_shell();
+        match reply {
+            Message::ExecuteReply(reply) => {
+                info!("Received execute reply: {:?}", reply);
+                assert_eq!(reply.content.status, Status::Ok);
+                assert_eq!(reply.content.execution_count, execution_count);
+            },
+            _ => {
+                panic!("Unexpected execute reply received: {:?}", reply);
+            },
+        }
+    }
+
+    // Absorb the IOPub messages of the above requests, up to the idle status
+    // of the queued execution
+    loop {
+        let msg = frontend.receive_iopub();
+        match msg {
+            Message::Status(status)
+                if status.content.execution_state == ExecutionState::Idle &&
+                    status.parent_header.as_ref().unwrap().msg_id == queued_id =>
+            {
+                break;
+            },
+            _ => {
+                info!("Ignoring message: {:?}", msg);
+                continue;
+            },
+        }
+    }
+
     // Test the heartbeat
     info!("Sending heartbeat to the kernel");
     let msg = zmq::Message::from("Heartbeat");
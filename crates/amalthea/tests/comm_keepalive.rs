@@ -0,0 +1,125 @@
+/*
+ * comm_keepalive.rs
+ *
+ * Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+ *
+ */
+
+use std::time::Duration;
+use std::time::Instant;
+
+use amalthea::comm::comm_channel::CommMsg;
+use amalthea::comm::comm_manager::CommKeepalive;
+use amalthea::comm::comm_manager::CommManager;
+use amalthea::comm::event::CommManagerEvent;
+use amalthea::comm::event::CommShellEvent;
+use amalthea::socket::comm::CommInitiator;
+use amalthea::socket::comm::CommSocket;
+use amalthea::socket::iopub::IOPubMessage;
+use crossbeam::channel::Receiver;
+use crossbeam::channel::Sender;
+use serde_json::json;
+use serde_json::Value;
+
+const TIMEOUT: Duration = Duration::from_millis(200);
+
+struct Fixture {
+    manager: CommManager,
+    comm_event_tx: Sender<CommManagerEvent>,
+    iopub_rx: Receiver<IOPubMessage>,
+    _comm_shell_rx: Receiver<CommShellEvent>,
+    comm: CommSocket,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        let (iopub_tx, iopub_rx) = crossbeam::channel::unbounded();
+        let (comm_event_tx, comm_event_rx) = crossbeam::channel::unbounded();
+        let (comm_shell_tx, comm_shell_rx) = crossbeam::channel::unbounded();
+
+        let keepalive = CommKeepalive {
+            interval: Duration::from_millis(10),
+            timeout: TIMEOUT,
+        };
+        let mut manager = CommManager::new(iopub_tx, comm_event_rx, comm_shell_tx, Some(keepalive));
+
+        let comm = CommSocket::new(
+            CommInitiator::FrontEnd,
+            String::from("test-comm-id"),
+            String::from("test"),
+        );
+        comm_event_tx
+            .send(CommManagerEvent::Opened(comm.clone(), Value::Null))
+            .unwrap();
+        manager.execution_thread();
+
+        Self {
+            manager,
+            comm_event_tx,
+            iopub_rx,
+            _comm_shell_rx: comm_shell_rx,
+            comm,
+        }
+    }
+
+    /// Runs the comm manager for `duration`, calling `tick` between
+    /// iterations. Returns whether the comm was closed.
+    fn run(&mut self, duration: Duration, mut tick: impl FnMut(&Self)) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < duration {
+            tick(self);
+            self.manager.execution_thread();
+
+            while let Ok(msg) = self.iopub_rx.try_recv() {
+                if let IOPubMessage::CommClose(comm_id) = msg {
+                    assert_eq!(comm_id, self.comm.comm_id);
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+#[test]
+fn test_comm_keepalive_closes_quiet_comms() {
+    let mut fixture = Fixture::new();
+    let start = Instant::now();
+
+    assert!(fixture.run(TIMEOUT * 10, |_| {}));
+    assert!(start.elapsed() >= TIMEOUT);
+
+    // The backend side of the comm is notified so it can free its resources
+    let msg = fixture.comm.incoming_rx.try_recv().unwrap();
+    assert!(matches!(msg, CommMsg::Close));
+}
+
+#[test]
+fn test_comm_keepalive_counts_messages_from_frontend() {
+    let mut fixture = Fixture::new();
+
+    let closed = fixture.run(TIMEOUT * 3, |fixture| {
+        fixture
+            .comm_event_tx
+            .send(CommManagerEvent::Message(
+                fixture.comm.comm_id.clone(),
+                CommMsg::Data(json!({})),
+            ))
+            .unwrap();
+    });
+    assert!(!closed);
+}
+
+#[test]
+fn test_comm_keepalive_counts_messages_to_frontend() {
+    let mut fixture = Fixture::new();
+
+    let closed = fixture.run(TIMEOUT * 3, |fixture| {
+        fixture
+            .comm
+            .outgoing_tx
+            .send(CommMsg::Data(json!({})))
+            .unwrap();
+    });
+    assert!(!closed);
+}
@@ -0,0 +1,87 @@
+/*
+ * comm_notify.rs
+ *
+ * Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+ *
+ */
+
+use amalthea::comm::comm_channel::CommMsg;
+use amalthea::socket::comm::CommInitiator;
+use amalthea::socket::comm::CommSocket;
+use serde_json::json;
+use serde_json::Value;
+
+fn new_comm() -> CommSocket {
+    CommSocket::new(
+        CommInitiator::BackEnd,
+        String::from("test-comm-id"),
+        String::from("test"),
+    )
+}
+
+/// Handles the next request received by `comm`, replying with its params
+fn handle_next(comm: &CommSocket) {
+    let msg = comm.incoming_rx.recv().unwrap();
+    let handled = comm.handle_request(msg, |req: Value| -> anyhow::Result<Value> {
+        Ok(req["params"].clone())
+    });
+    assert!(handled);
+}
+
+fn send_frontend_request(comm: &CommSocket, id: &str, params: Value) {
+    comm.incoming_tx
+        .send(CommMsg::Rpc(
+            String::from(id),
+            json!({ "method": "echo", "params": params }),
+        ))
+        .unwrap();
+}
+
+fn assert_frontend_reply(comm: &CommSocket, id: &str, params: Value) {
+    match comm.outgoing_rx.try_recv().unwrap() {
+        CommMsg::Rpc(reply_id, reply) => {
+            assert_eq!(reply_id, id);
+            assert_eq!(reply, params);
+        },
+        msg => panic!("Unexpected message: {msg:?}"),
+    }
+}
+
+#[test]
+fn test_comm_notify() {
+    let comm = new_comm();
+
+    // Doesn't wait for the handler
+    comm.notify(json!({ "method": "echo", "params": 42 }))
+        .unwrap();
+
+    // The reply isn't sent to the frontend
+    handle_next(&comm);
+    assert!(comm.outgoing_rx.try_recv().is_err());
+
+    // Replies to frontend requests are still sent to the frontend
+    send_frontend_request(&comm, "frontend-id", json!(1));
+    handle_next(&comm);
+    assert_frontend_reply(&comm, "frontend-id", json!(1));
+}
+
+#[test]
+fn test_comm_notify_interleaved_with_frontend_requests() {
+    let comm = new_comm();
+
+    // Frontend ids are arbitrary strings and may look like anything
+    comm.notify(json!({ "method": "echo", "params": 1 }))
+        .unwrap();
+    send_frontend_request(&comm, "backend-id", json!(2));
+    comm.notify(json!({ "method": "echo", "params": 3 }))
+        .unwrap();
+
+    handle_next(&comm);
+    assert!(comm.outgoing_rx.try_recv().is_err());
+
+    handle_next(&comm);
+    assert_frontend_reply(&comm, "backend-id", json!(2));
+
+    handle_next(&comm);
+    assert!(comm.outgoing_rx.try_recv().is_err());
+}
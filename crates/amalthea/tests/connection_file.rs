@@ -0,0 +1,72 @@
+/*
+ * connection_file.rs
+ *
+ * Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+ *
+ */
+
+use amalthea::connection_file::ConnectionFile;
+
+fn write_connection_file(name: &str, contents: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("amalthea-connection-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn test_connection_file_extra_keys() {
+    let path = write_connection_file(
+        "extra.json",
+        r#"{
+            "control_port": 50160,
+            "shell_port": 57503,
+            "transport": "tcp",
+            "signature_scheme": "hmac-sha256",
+            "stdin_port": 52597,
+            "hb_port": 42540,
+            "ip": "127.0.0.1",
+            "iopub_port": 40885,
+            "key": "a0436f6c-1916-498b-8eb9-e81ab9368e84",
+            "kernel_name": "ark",
+            "jupyter_session": "/home/user/analysis.ipynb",
+            "some_future_key": {"nested": [1, 2, 3]}
+        }"#,
+    );
+
+    let connection = ConnectionFile::from_file(&path).unwrap();
+    assert_eq!(connection.shell_port, 57503);
+    assert_eq!(connection.kernel_name, Some(String::from("ark")));
+    assert_eq!(
+        connection.jupyter_session,
+        Some(String::from("/home/user/analysis.ipynb"))
+    );
+    assert_eq!(connection.endpoint(50160), "tcp://127.0.0.1:50160");
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_connection_file_optional_keys() {
+    let path = write_connection_file(
+        "minimal.json",
+        r#"{
+            "control_port": 1,
+            "shell_port": 2,
+            "transport": "tcp",
+            "signature_scheme": "hmac-sha256",
+            "stdin_port": 3,
+            "hb_port": 4,
+            "ip": "127.0.0.1",
+            "iopub_port": 5,
+            "key": ""
+        }"#,
+    );
+
+    let connection = ConnectionFile::from_file(&path).unwrap();
+    assert_eq!(connection.kernel_name, None);
+    assert_eq!(connection.jupyter_session, None);
+
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,125 @@
+/*
+ * history.rs
+ *
+ * Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+ *
+ */
+
+use amalthea::history::History;
+use amalthea::wire::history_request::HistAccessType;
+use amalthea::wire::history_request::HistoryRequest;
+use serde_json::json;
+
+fn request(hist_access_type: HistAccessType) -> HistoryRequest {
+    HistoryRequest {
+        output: false,
+        raw: true,
+        hist_access_type,
+        session: 0,
+        start: 0,
+        stop: 0,
+        n: 0,
+        pattern: String::new(),
+        unique: false,
+    }
+}
+
+fn inputs(history: &History, req: &HistoryRequest) -> Vec<String> {
+    history
+        .query(req)
+        .into_iter()
+        .map(|(_, _, input)| input.as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn test_history_persists_across_sessions() {
+    let dir = std::env::temp_dir().join(format!("amalthea-history-{}", std::process::id()));
+    let path = dir.join("history.jsonl");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    // First session
+    let mut history = History::open(path.clone()).unwrap();
+    assert_eq!(history.session(), 1);
+    history.record(1, "x <- 1").unwrap();
+    history.record(2, "print(x)").unwrap();
+    drop(history);
+
+    // Second session; the previous inputs are still there
+    let mut history = History::open(path.clone()).unwrap();
+    assert_eq!(history.session(), 2);
+    history.record(1, "y <- 2").unwrap();
+    history.record(2, "print(y)").unwrap();
+    history.record(3, "x + y").unwrap();
+
+    // Tail returns the last `n` entries across sessions
+    let mut req = request(HistAccessType::Tail);
+    req.n = 3;
+    assert_eq!(
+        history.query(&req),
+        vec![
+            (2, 1, json!("y <- 2")),
+            (2, 2, json!("print(y)")),
+            (2, 3, json!("x + y")),
+        ]
+    );
+
+    // Ranges are relative to the current session
+    let mut req = request(HistAccessType::Range);
+    req.session = -1;
+    req.start = 1;
+    assert_eq!(inputs(&history, &req), vec!["x <- 1", "print(x)"]);
+
+    let mut req = request(HistAccessType::Range);
+    req.start = 2;
+    req.stop = 3;
+    assert_eq!(inputs(&history, &req), vec!["print(y)"]);
+
+    // Search uses glob patterns
+    let mut req = request(HistAccessType::Search);
+    req.pattern = String::from("print(?)");
+    assert_eq!(inputs(&history, &req), vec!["print(x)", "print(y)"]);
+
+    req.n = 1;
+    assert_eq!(inputs(&history, &req), vec!["print(y)"]);
+
+    // Outputs aren't recorded, but are returned as pairs when requested
+    let mut req = request(HistAccessType::Tail);
+    req.n = 1;
+    req.output = true;
+    assert_eq!(history.query(&req), vec![(2, 3, json!(["x + y", null]))]);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_history_is_bounded() {
+    let dir = std::env::temp_dir().join(format!("amalthea-history-bounded-{}", std::process::id()));
+    let path = dir.join("history.jsonl");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let mut history = History::open_with_max_entries(path.clone(), 10).unwrap();
+    for i in 1..=20 {
+        history.record(i, &format!("x <- {i}")).unwrap();
+    }
+    assert!(history.entries().len() <= 11);
+    drop(history);
+
+    // Reopening trims the file down to the maximum
+    let history = History::open_with_max_entries(path.clone(), 10).unwrap();
+    assert_eq!(history.entries().len(), 10);
+    assert_eq!(history.entries().last().unwrap().input, "x <- 20");
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(contents.lines().count(), 10);
+
+    // Duplicate inputs can be skipped when searching
+    let mut history = history;
+    history.record(1, "x <- 20").unwrap();
+    let mut req = request(HistAccessType::Search);
+    req.pattern = String::from("x <- 2*");
+    req.unique = true;
+    assert_eq!(inputs(&history, &req), vec!["x <- 20"]);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,108 @@
+/*
+ * kernel_dirs.rs
+ *
+ * Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+ *
+ */
+
+use std::env;
+use std::path::PathBuf;
+
+use amalthea::kernel_dirs::jupyter_data_dirs;
+use amalthea::kernel_dirs::jupyter_install_roots;
+use amalthea::kernel_dirs::jupyter_kernel_install_path;
+use amalthea::kernel_dirs::InstallLocation;
+use amalthea::kernel_spec::KernelSpec;
+
+// The environment is process-wide, so all the checks live in a single test
+// to keep them from racing with each other.
+#[test]
+fn test_kernel_install_dirs() {
+    for var in [
+        "JUPYTER_PATH",
+        "JUPYTER_DATA_DIR",
+        "XDG_DATA_HOME",
+        "VIRTUAL_ENV",
+        "CONDA_PREFIX",
+    ] {
+        env::remove_var(var);
+    }
+
+    let root = env::temp_dir().join("amalthea-kernel-dirs");
+    let first = root.join("first");
+    let second = root.join("second");
+    let user = root.join("user");
+    let prefix = root.join("prefix");
+
+    // `JUPYTER_DATA_DIR` sets the user directory
+    env::set_var("JUPYTER_DATA_DIR", &user);
+    assert_eq!(
+        jupyter_kernel_install_path(InstallLocation::User),
+        Some(user.join("kernels"))
+    );
+
+    // `JUPYTER_PATH` takes precedence over the user directory, in order
+    let jupyter_path = env::join_paths([&first, &second]).unwrap();
+    env::set_var("JUPYTER_PATH", &jupyter_path);
+    assert_eq!(
+        jupyter_install_roots(InstallLocation::User),
+        vec![first.clone(), second.clone(), user.clone()]
+    );
+    assert_eq!(
+        KernelSpec::install_dir(InstallLocation::User, String::from("ark")).unwrap(),
+        first.join("kernels").join("ark")
+    );
+
+    // The search path lists the environment and system directories last
+    env::set_var("VIRTUAL_ENV", &prefix);
+    let dirs = jupyter_data_dirs();
+    assert_eq!(
+        dirs[..4],
+        [
+            first.clone(),
+            second.clone(),
+            user.clone(),
+            prefix.join("share").join("jupyter")
+        ]
+    );
+
+    // `--sys-prefix` installs into the active environment only
+    assert_eq!(
+        jupyter_kernel_install_path(InstallLocation::SysPrefix),
+        Some(prefix.join("share").join("jupyter").join("kernels"))
+    );
+    env::remove_var("VIRTUAL_ENV");
+    env::set_var("CONDA_PREFIX", &prefix);
+    assert_eq!(
+        jupyter_kernel_install_path(InstallLocation::SysPrefix),
+        Some(prefix.join("share").join("jupyter").join("kernels"))
+    );
+    env::remove_var("CONDA_PREFIX");
+    assert_eq!(
+        jupyter_kernel_install_path(InstallLocation::SysPrefix),
+        None
+    );
+    assert!(KernelSpec::install_dir(InstallLocation::SysPrefix, String::from("ark")).is_err());
+
+    // Without `JUPYTER_DATA_DIR`, the XDG data directory is used on Linux
+    env::remove_var("JUPYTER_PATH");
+    env::remove_var("JUPYTER_DATA_DIR");
+    if cfg!(target_os = "linux") {
+        let xdg = root.join("xdg");
+        env::set_var("XDG_DATA_HOME", &xdg);
+        assert_eq!(
+            jupyter_kernel_install_path(InstallLocation::User),
+            Some(xdg.join("jupyter").join("kernels"))
+        );
+        env::remove_var("XDG_DATA_HOME");
+    }
+
+    // Relative paths in `JUPYTER_PATH` are kept, empty entries are dropped
+    let jupyter_path = env::join_paths([PathBuf::from(""), PathBuf::from("relative")]).unwrap();
+    env::set_var("JUPYTER_PATH", &jupyter_path);
+    assert_eq!(
+        jupyter_install_roots(InstallLocation::User).first(),
+        Some(&PathBuf::from("relative"))
+    );
+    env::remove_var("JUPYTER_PATH");
+}
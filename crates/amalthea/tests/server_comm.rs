@@ -0,0 +1,70 @@
+/*
+ * server_comm.rs
+ *
+ * Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+ *
+ */
+
+use amalthea::comm::server_comm::StartServer;
+use serde_json::json;
+
+fn tcp_address(data: serde_json::Value) -> Result<String, String> {
+    let data: StartServer = serde_json::from_value(data).unwrap();
+    data.tcp_address()
+}
+
+#[test]
+fn test_start_server_client_address() {
+    // The historical form, with the port included in the address
+    assert_eq!(
+        tcp_address(json!({ "client_address": "127.0.0.1:8080" })),
+        Ok(String::from("127.0.0.1:8080"))
+    );
+    assert!(tcp_address(json!({})).is_err());
+}
+
+#[test]
+fn test_start_server_port() {
+    assert_eq!(
+        tcp_address(json!({ "client_address": "localhost", "port": 8080 })),
+        Ok(String::from("localhost:8080"))
+    );
+
+    // Defaults to the loopback interface
+    assert_eq!(
+        tcp_address(json!({ "port": 8080 })),
+        Ok(String::from("127.0.0.1:8080"))
+    );
+    assert_eq!(
+        tcp_address(json!({ "port": 8080, "ipv6": true })),
+        Ok(String::from("[::1]:8080"))
+    );
+
+    // IPv6 hosts are bracketed, whether or not they were already
+    assert_eq!(
+        tcp_address(json!({ "client_address": "::1", "port": 8080, "ipv6": true })),
+        Ok(String::from("[::1]:8080"))
+    );
+    assert_eq!(
+        tcp_address(json!({ "client_address": "[fe80::1]", "port": 8080, "ipv6": true })),
+        Ok(String::from("[fe80::1]:8080"))
+    );
+}
+
+#[test]
+fn test_start_server_port_errors() {
+    // The port can't be supplied twice
+    assert!(tcp_address(json!({ "client_address": "127.0.0.1:8080", "port": 8080 })).is_err());
+    assert!(tcp_address(json!({ "client_address": "localhost:8080", "port": 8080 })).is_err());
+    assert!(tcp_address(json!({
+        "client_address": "[::1]:8080",
+        "port": 8080,
+        "ipv6": true
+    }))
+    .is_err());
+
+    // Not an IPv6 address
+    assert!(
+        tcp_address(json!({ "client_address": "localhost", "port": 8080, "ipv6": true })).is_err()
+    );
+}
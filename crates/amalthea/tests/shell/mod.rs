@@ -5,8 +5,6 @@
  *
  */
 
-use std::thread;
-
 use amalthea::comm::comm_channel::Comm;
 use amalthea::comm::comm_channel::CommMsg;
 use amalthea::language::shell_handler::ShellHandler;
@@ -252,7 +250,7 @@ impl ShellHandler for Shell {
         // Open a test comm channel; this test comm channel is used for every
         // comm open request (regardless of the target name). It just echoes back any
         // messages it receives.
-        thread::spawn(move || loop {
+        stdext::spawn!("shell-test-comm", move || loop {
             match comm.incoming_rx.recv().unwrap() {
                 CommMsg::Data(val) => {
                     // Echo back the data we received on the comm channel to the
@@ -5,15 +5,26 @@
 //
 //
 
+use std::path::Path;
+use std::sync::atomic::AtomicU16;
+
 use amalthea::comm::ui_comm::ShowUrlParams;
 use amalthea::comm::ui_comm::UiFrontendEvent;
 use harp::object::RObject;
+use harp::utils::r_null_or_try_into;
 use libr::Rf_ScalarLogical;
 use libr::SEXP;
+use url::Url;
 
 use crate::help::message::HelpEvent;
 use crate::help::message::ShowHelpUrlParams;
 use crate::interface::RMain;
+use crate::sys::platform::Platform;
+use crate::sys::PlatformOps;
+
+/// The port the help proxy is currently bound to, or 0 if it hasn't been
+/// started yet. A restarted help proxy tries to bind to this port again.
+pub static PORT: AtomicU16 = AtomicU16::new(0);
 
 #[harp::register]
 pub unsafe extern "C" fn ps_browse_url(url: SEXP) -> anyhow::Result<SEXP> {
@@ -48,6 +59,16 @@ unsafe fn ps_browse_url_impl(url: SEXP) -> anyhow::Result<SEXP> {
         log::trace!("Help is not handling URL");
     }
 
+    // Without a frontend UI to show the URL, e.g. in Jupyter, users can opt
+    // in to opening it outside of the frontend instead
+    if should_open_externally() &&
+        !RMain::with(|main| main.get_kernel().lock().unwrap().ui_connected())
+    {
+        log::trace!("Opening URL externally");
+        open_externally(&url, &Platform)?;
+        return Ok(Rf_ScalarLogical(1));
+    }
+
     // For all other URLs, create a ShowUrl event and send it to the main
     // thread; Positron will handle it.
     let params = ShowUrlParams { url };
@@ -57,3 +78,80 @@ unsafe fn ps_browse_url_impl(url: SEXP) -> anyhow::Result<SEXP> {
 
     Ok(Rf_ScalarLogical(1))
 }
+
+/// Whether to open URLs with the system's browser when no frontend UI is
+/// connected. Disabled by default since the kernel may run on a remote
+/// machine.
+fn should_open_externally() -> bool {
+    let opt: Option<bool> = r_null_or_try_into(harp::get_option("ark.browser.open_externally"))
+        .ok()
+        .flatten();
+
+    opt.unwrap_or(false)
+}
+
+/// Opens `url` with the system's browser. Local files, as URLs or paths, are
+/// opened with their default application.
+fn open_externally(url: &str, platform: &impl PlatformOps) -> anyhow::Result<()> {
+    match Url::parse(url) {
+        Ok(parsed) if parsed.scheme() == "file" => match parsed.to_file_path() {
+            Ok(path) => platform.open_path(&path),
+            Err(_) => platform.open_url(url),
+        },
+        Ok(_) => platform.open_url(url),
+        Err(_) => platform.open_path(Path::new(url)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::path::Path;
+
+    use url::Url;
+
+    use crate::browser::open_externally;
+    use crate::sys::PlatformOps;
+
+    /// Records the operations instead of launching processes
+    #[derive(Default)]
+    struct MockPlatform {
+        calls: RefCell<Vec<String>>,
+    }
+
+    impl PlatformOps for MockPlatform {
+        fn open_path(&self, path: &Path) -> anyhow::Result<()> {
+            let call = format!("open_path {}", path.display());
+            self.calls.borrow_mut().push(call);
+            Ok(())
+        }
+
+        fn open_url(&self, url: &str) -> anyhow::Result<()> {
+            self.calls.borrow_mut().push(format!("open_url {url}"));
+            Ok(())
+        }
+
+        fn reveal_in_file_manager(&self, path: &Path) -> anyhow::Result<()> {
+            let call = format!("reveal {}", path.display());
+            self.calls.borrow_mut().push(call);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_open_externally() {
+        let platform = MockPlatform::default();
+        let file = std::env::temp_dir().join("report.html");
+        let file_url = Url::from_file_path(&file).unwrap();
+
+        open_externally("https://www.r-project.org/", &platform).unwrap();
+        open_externally(file_url.as_str(), &platform).unwrap();
+        open_externally("report.html", &platform).unwrap();
+
+        assert_eq!(platform.calls.into_inner(), vec![
+            String::from("open_url https://www.r-project.org/"),
+            format!("open_path {}", file.display()),
+            String::from("open_path report.html"),
+        ]);
+    }
+}
@@ -0,0 +1,219 @@
+//
+// check.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::env::consts::DLL_PREFIX;
+use std::env::consts::DLL_SUFFIX;
+use std::path::Path;
+use std::path::PathBuf;
+
+use amalthea::connection_file::ConnectionFile;
+use amalthea::kernel_dirs::InstallLocation;
+use amalthea::kernel_spec::KernelSpec;
+use anyhow::anyhow;
+
+use crate::version::detect_r;
+use crate::version::RVersion;
+
+/// The outcome of one of the `--check` diagnostics
+pub struct CheckResult {
+    pub name: &'static str,
+    pub result: anyhow::Result<String>,
+}
+
+impl std::fmt::Display for CheckResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.result {
+            Ok(details) => write!(f, "[ok]   {}: {details}", self.name),
+            Err(err) => write!(f, "[fail] {}: {err:#}", self.name),
+        }
+    }
+}
+
+/// Validates the environment Ark would run in, without starting R:
+///
+/// - R can be found, either at `r_version` (from `--r-home`) or on the `PATH`.
+/// - The R shared library can be loaded.
+/// - The connection file, if any, can be parsed.
+/// - The kernel spec install directory is writable.
+pub fn run_checks(
+    r_version: Option<RVersion>,
+    connection_file: Option<&str>,
+    location: InstallLocation,
+) -> Vec<CheckResult> {
+    let mut checks = Vec::new();
+
+    let r_version = match r_version {
+        Some(r_version) => Ok(r_version),
+        None => detect_r(),
+    };
+
+    match r_version {
+        Ok(r_version) => {
+            checks.push(CheckResult {
+                name: "R",
+                result: Ok(format!("{r_version} at {}", r_version.r_home.display())),
+            });
+            checks.push(CheckResult {
+                name: "R shared library",
+                result: check_r_shared_library(&r_version),
+            });
+        },
+        Err(err) => {
+            checks.push(CheckResult {
+                name: "R",
+                result: Err(err),
+            });
+        },
+    }
+
+    if let Some(connection_file) = connection_file {
+        checks.push(CheckResult {
+            name: "Connection file",
+            result: check_connection_file(connection_file),
+        });
+    }
+
+    checks.push(CheckResult {
+        name: "Kernel spec directory",
+        result: check_install_dir(location),
+    });
+
+    checks
+}
+
+fn check_r_shared_library(r_version: &RVersion) -> anyhow::Result<String> {
+    let path = load_r_shared_library(&r_version.r_home)?;
+    Ok(format!("{}", path.display()))
+}
+
+/// Checks that R can be loaded from `R_HOME` before the kernel starts, so
+/// that a broken installation is reported with a clear error rather than a
+/// panic once the kernel threads are running.
+pub fn check_r_startup() -> anyhow::Result<()> {
+    let r_home = std::env::var("R_HOME")
+        .map_err(|_| anyhow!("`R_HOME` is not set. Set it or use `--r-home` to select R."))?;
+
+    load_r_shared_library(Path::new(&r_home))
+        .map_err(|err| anyhow!("Can't load R from R_HOME '{r_home}': {err:#}"))?;
+
+    Ok(())
+}
+
+/// Opens the R shared library of the R installation at `r_home` and resolves
+/// one of its symbols. Libraries built for another architecture or with
+/// missing dependencies fail to open, and resolving a symbol catches
+/// libraries that aren't R's. Returns the path of the library.
+fn load_r_shared_library(r_home: &Path) -> anyhow::Result<PathBuf> {
+    let lib_dir = harp::sys::library::find_r_shared_library_folder(&r_home.to_path_buf());
+    let path = lib_dir.join(format!("{DLL_PREFIX}R{DLL_SUFFIX}"));
+
+    if !path.exists() {
+        return Err(anyhow!("Can't find '{}'", path.display()));
+    }
+
+    let library = harp::sys::library::open_r_shared_library(&path)
+        .map_err(|err| anyhow!("Can't load '{}': {err}", path.display()))?;
+
+    unsafe { library.get::<*const ()>(b"setup_Rmainloop\0") }
+        .map_err(|err| anyhow!("Can't find R's API in '{}': {err}", path.display()))?;
+
+    Ok(path)
+}
+
+fn check_connection_file(path: &str) -> anyhow::Result<String> {
+    let connection =
+        ConnectionFile::from_file(path).map_err(|err| anyhow!("Can't parse '{path}': {err}"))?;
+
+    Ok(format!(
+        "{path} ({} on {})",
+        connection.transport, connection.ip
+    ))
+}
+
+fn check_install_dir(location: InstallLocation) -> anyhow::Result<String> {
+    let dir =
+        KernelSpec::install_dir(location, String::from("ark")).map_err(|err| anyhow!("{err}"))?;
+    probe_writable(&dir)?;
+    Ok(format!("{}", dir.display()))
+}
+
+/// Checks that `dir` can be written to, or created if it doesn't exist yet,
+/// by writing a file to its closest existing ancestor.
+pub fn probe_writable(dir: &Path) -> anyhow::Result<()> {
+    let Some(existing) = dir.ancestors().find(|path| path.exists()) else {
+        return Err(anyhow!("No parent of '{}' exists", dir.display()));
+    };
+
+    let probe = existing.join(format!(".ark-check-{}", std::process::id()));
+    std::fs::write(&probe, "")
+        .map_err(|err| anyhow!("Can't write to '{}': {err}", existing.display()))?;
+    let _ = std::fs::remove_file(&probe);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::consts::DLL_PREFIX;
+    use std::env::consts::DLL_SUFFIX;
+
+    use crate::check::check_connection_file;
+    use crate::check::load_r_shared_library;
+    use crate::check::probe_writable;
+
+    #[test]
+    fn test_probe_writable() {
+        let dir = std::env::temp_dir().join(format!("ark-check-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        // Missing directories are probed through their existing ancestors
+        assert!(probe_writable(&dir.join("kernels").join("ark")).is_ok());
+        assert!(!dir.exists());
+
+        std::fs::create_dir_all(&dir).unwrap();
+        assert!(probe_writable(&dir).is_ok());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_load_r_shared_library() {
+        let r_home = std::env::temp_dir().join(format!("ark-check-r-{}", std::process::id()));
+        let lib_dir = harp::sys::library::find_r_shared_library_folder(&r_home);
+        std::fs::create_dir_all(&lib_dir).unwrap();
+
+        // Missing library
+        let err = load_r_shared_library(&r_home).unwrap_err().to_string();
+        assert!(err.starts_with("Can't find"));
+
+        // Files that aren't shared libraries can't be loaded, and the error
+        // points to the attempted path
+        let path = lib_dir.join(format!("{DLL_PREFIX}R{DLL_SUFFIX}"));
+        std::fs::write(&path, "not a library").unwrap();
+        let err = load_r_shared_library(&r_home).unwrap_err().to_string();
+        assert!(err.starts_with("Can't load"));
+        assert!(err.contains(&path.display().to_string()));
+
+        let _ = std::fs::remove_dir_all(&r_home);
+    }
+
+    #[test]
+    fn test_check_connection_file() {
+        let dir = std::env::temp_dir().join(format!("ark-check-cf-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("connection.json");
+        std::fs::write(&path, "{").unwrap();
+        assert!(check_connection_file(path.to_str().unwrap()).is_err());
+
+        let missing = dir.join("missing.json");
+        assert!(check_connection_file(missing.to_str().unwrap()).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -19,10 +19,13 @@ use amalthea::comm::event::CommManagerEvent;
 use amalthea::socket::comm::CommInitiator;
 use amalthea::socket::comm::CommSocket;
 use crossbeam::channel::Sender;
+use harp::environment::Environment;
+use harp::environment_iter::BindingValue;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
 use harp::object::RObject;
 use harp::utils::r_is_null;
+use harp::utils::r_is_s4;
 use libr::R_NilValue;
 use libr::SEXP;
 use serde::Deserialize;
@@ -328,3 +331,23 @@ pub unsafe extern "C" fn ps_connection_updated(id: SEXP) -> Result<SEXP, anyhow:
 
     Ok(R_NilValue)
 }
+
+/// Lists the bindings of `env` that may hold a DBI connection, without
+/// forcing promises or calling active bindings. DBI connections are S4
+/// objects, so R still needs to check their class with `methods::is()`.
+#[harp::register]
+pub unsafe extern "C" fn ps_connection_candidates(env: SEXP) -> Result<SEXP, anyhow::Error> {
+    let env = Environment::view(env);
+
+    let names: Vec<String> = env
+        .iter()
+        .filter_map(|binding| binding.ok())
+        .filter(|binding| match &binding.value {
+            BindingValue::Standard { object, .. } => r_is_s4(object.sexp),
+            _ => false,
+        })
+        .map(|binding| String::from(binding.name))
+        .collect();
+
+    Ok(RObject::from(names).into())
+}
@@ -57,7 +57,20 @@ impl ControlHandler for Control {
 
     async fn handle_interrupt_request(&self) -> Result<InterruptReply, Exception> {
         debug!("Received interrupt request");
+
+        // Flag the interrupt. R picks it up at the next safe point, i.e. the
+        // next `R_CheckUserInterrupt()`, if it is busy evaluating code.
         crate::sys::control::handle_interrupt_request();
+
+        // If R is instead waiting for input in `ReadConsole()`, e.g. in
+        // `readline()`, wake it up so it can propagate the interrupt. R is
+        // busy if the channel is full, the flag is enough then. At top level
+        // `ReadConsole()` simply discards the request.
+        if let Err(err) = self.r_request_tx.try_send(RRequest::Interrupt) {
+            trace!("Interrupt request not delivered to the R thread: {err:?}");
+        }
+
+        // Reply whether or not there was anything to interrupt
         Ok(InterruptReply { status: Status::Ok })
     }
 }
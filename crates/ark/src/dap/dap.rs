@@ -12,18 +12,20 @@ use std::sync::Mutex;
 use amalthea::comm::comm_channel::CommMsg;
 use amalthea::language::server_handler::ServerHandler;
 use crossbeam::channel::Sender;
+use harp::environment::R_ENVS;
 use harp::object::RObject;
 use serde_json::json;
 use stdext::log_error;
 use stdext::spawn;
 
+use crate::dap::dap_breakpoints::BreakpointInfo;
 use crate::dap::dap_r_main::FrameInfo;
 use crate::dap::dap_r_main::FrameSource;
 use crate::dap::dap_server;
 use crate::request::RRequest;
 use crate::thread::RThreadSafe;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub enum DapBackendEvent {
     /// Event sent when a normal (non-browser) prompt marks the end of a
     /// debugging session.
@@ -34,7 +36,32 @@ pub enum DapBackendEvent {
 
     /// Event sent when a browser prompt is emitted during an existing
     /// debugging session
-    Stopped,
+    Stopped(DapStoppedReason),
+}
+
+/// Why the REPL stopped with a browser prompt
+#[derive(Debug, Clone)]
+pub enum DapStoppedReason {
+    /// Stepping, or any other reason we don't know about
+    Step,
+
+    /// A breakpoint with this `id` was hit
+    Breakpoint(i64),
+
+    /// A condition matching an exception filter was signalled. Contains the
+    /// filter (`stop`, `warning`, or `message`) and the condition message.
+    Exception(String, String),
+}
+
+/// The scopes shown for each frame in the variables pane. Both are views of
+/// the frame environment.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameScope {
+    /// The formals of the function, as bound in the frame environment
+    Arguments,
+
+    /// All other bindings of the frame environment
+    Locals,
 }
 
 pub struct Dap {
@@ -61,9 +88,23 @@ pub struct Dap {
     /// Maps a frame `id` from within the `stack` to a unique
     /// `variables_reference` id, which then allows you to use
     /// `variables_reference_to_r_object` to look up the R object to collect
-    /// variables from. Reset after each debug step.
+    /// variables from. This is the reference of the frame's Locals scope.
+    /// Reset after each debug step.
     pub frame_id_to_variables_reference: HashMap<i64, i64>,
 
+    /// Maps a frame `id` to the `variables_reference` of its Arguments scope.
+    /// Reset after each debug step.
+    pub frame_id_to_arguments_reference: HashMap<i64, i64>,
+
+    /// Maps the `variables_reference` of a frame scope to the kind of scope.
+    /// These references map to the frame environment, whose bindings are
+    /// filtered according to the scope. Reset after each debug step.
+    pub variables_reference_to_scope: HashMap<i64, FrameScope>,
+
+    /// The `variables_reference` of the Globals scope, shared by all frames.
+    /// Reset after each debug step.
+    pub globals_variables_reference: Option<i64>,
+
     /// Maps a `variables_reference` to the corresponding R object used to
     /// collect variables from. The R object may be a frame environment from
     /// a `FrameInfo`, or an arbitrarily nested child of one of those
@@ -79,6 +120,25 @@ pub struct Dap {
     /// information.
     current_variables_reference: i64,
 
+    /// Breakpoints of each source file, keyed by path. Unlike the other
+    /// fields, these persist across debug sessions.
+    pub breakpoints: HashMap<String, Vec<BreakpointInfo>>,
+
+    /// Breakpoints on variables, in the order of the last
+    /// `SetDataBreakpoints` request.
+    pub data_breakpoints: Vec<BreakpointInfo>,
+
+    /// Breakpoints on entry of functions, in the order of the last
+    /// `SetFunctionBreakpoints` request.
+    pub function_breakpoints: Vec<BreakpointInfo>,
+
+    /// Why we are about to stop, set by R before entering the browser.
+    /// Consumed by the next `Stopped` event.
+    pub stop_reason: Option<DapStoppedReason>,
+
+    /// The last `id` assigned to a breakpoint. Unique within the R session.
+    current_breakpoint_id: i64,
+
     /// Channel for sending events to the comm frontend.
     comm_tx: Option<Sender<CommMsg>>,
 
@@ -100,8 +160,16 @@ impl Dap {
             fallback_sources: HashMap::new(),
             current_source_reference: 1,
             frame_id_to_variables_reference: HashMap::new(),
+            frame_id_to_arguments_reference: HashMap::new(),
+            variables_reference_to_scope: HashMap::new(),
+            globals_variables_reference: None,
             variables_reference_to_r_object: HashMap::new(),
             current_variables_reference: 1,
+            breakpoints: HashMap::new(),
+            data_breakpoints: Vec::new(),
+            function_breakpoints: Vec::new(),
+            stop_reason: None,
+            current_breakpoint_id: 0,
             comm_tx: None,
             r_request_tx,
             shared_self: None,
@@ -124,8 +192,9 @@ impl Dap {
         self.stack = Some(stack);
 
         if self.is_debugging {
+            let reason = self.stop_reason.take().unwrap_or(DapStoppedReason::Step);
             if let Some(tx) = &self.backend_events_tx {
-                log_error!(tx.send(DapBackendEvent::Stopped));
+                log_error!(tx.send(DapBackendEvent::Stopped(reason)));
             }
         } else {
             if let Some(tx) = &self.comm_tx {
@@ -145,6 +214,7 @@ impl Dap {
     pub fn stop_debug(&mut self) {
         // Reset state
         self.stack = None;
+        self.stop_reason = None;
         self.clear_fallback_sources();
         self.clear_variables_reference_maps();
         self.reset_variables_reference_count();
@@ -206,21 +276,33 @@ impl Dap {
                 continue;
             };
 
-            // Map this frame's `id` to a unique `variables_reference`, and
-            // then map that `variables_reference` to the R object we will
-            // eventually get the variables from
+            // Map this frame's `id` to a unique `variables_reference` for
+            // each of its scopes, and then map these to the frame environment
+            // we will eventually get the variables from
+            let arguments = RThreadSafe::new(environment.get().clone());
+            let arguments = self.insert_variables_reference_object(arguments);
+            self.variables_reference_to_scope
+                .insert(arguments, FrameScope::Arguments);
+            self.frame_id_to_arguments_reference
+                .insert(frame.id, arguments);
+
+            let locals = self.insert_variables_reference_object(environment);
+            self.variables_reference_to_scope
+                .insert(locals, FrameScope::Locals);
             self.frame_id_to_variables_reference
-                .insert(frame.id, self.current_variables_reference);
-            self.variables_reference_to_r_object
-                .insert(self.current_variables_reference, environment);
-
-            self.current_variables_reference += 1;
+                .insert(frame.id, locals);
         }
+
+        let globals = RThreadSafe::new(RObject::view(R_ENVS.global));
+        self.globals_variables_reference = Some(self.insert_variables_reference_object(globals));
     }
 
     // Called between steps
     fn clear_variables_reference_maps(&mut self) {
         self.frame_id_to_variables_reference.clear();
+        self.frame_id_to_arguments_reference.clear();
+        self.variables_reference_to_scope.clear();
+        self.globals_variables_reference = None;
         self.variables_reference_to_r_object.clear();
     }
 
@@ -246,6 +328,29 @@ impl Dap {
 
         variables_reference
     }
+
+    pub fn next_breakpoint_id(&mut self) -> i64 {
+        self.current_breakpoint_id += 1;
+        self.current_breakpoint_id
+    }
+
+    pub fn find_breakpoint(&self, id: i64) -> Option<&BreakpointInfo> {
+        self.breakpoints
+            .values()
+            .flat_map(|breakpoints| breakpoints.iter())
+            .chain(self.data_breakpoints.iter())
+            .chain(self.function_breakpoints.iter())
+            .find(|breakpoint| breakpoint.id == id)
+    }
+
+    pub fn find_breakpoint_mut(&mut self, id: i64) -> Option<&mut BreakpointInfo> {
+        self.breakpoints
+            .values_mut()
+            .flat_map(|breakpoints| breakpoints.iter_mut())
+            .chain(self.data_breakpoints.iter_mut())
+            .chain(self.function_breakpoints.iter_mut())
+            .find(|breakpoint| breakpoint.id == id)
+    }
 }
 
 // Handler for Amalthea socket threads
@@ -0,0 +1,421 @@
+//
+// dap_breakpoints.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use anyhow::anyhow;
+use harp::eval::r_parse_eval0;
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use harp::object::RObject;
+use harp::utils::r_typeof;
+use libr::R_NilValue;
+use libr::ENVSXP;
+use libr::SEXP;
+
+use crate::dap::dap::DapStoppedReason;
+use crate::interface::RMain;
+use crate::modules::ARK_ENVS;
+
+const NOT_SOURCED_MESSAGE: &str =
+    "This file wasn't sourced with `keep.source = TRUE`, or only a temporary copy of it was.";
+
+/// A breakpoint set by the frontend, either in a source file or on a
+/// variable (data breakpoint)
+#[derive(Debug, Clone)]
+pub struct BreakpointInfo {
+    /// Unique identifier, shared with the R side so that injected
+    /// breakpoints can find their `BreakpointInfo` when hit.
+    pub id: i64,
+    /// Line of source breakpoints. `None` for data breakpoints.
+    pub line: Option<i64>,
+    /// R expression evaluated in the frame of the breakpoint. We only stop
+    /// if it evaluates to `TRUE`.
+    pub condition: Option<String>,
+    /// Predicate over the number of hits. We only stop if it matches.
+    pub hit_condition: Option<HitCondition>,
+    /// Number of times the breakpoint was reached with its condition
+    /// satisfied. Starts over when the breakpoints of the file are set again.
+    pub hits: u64,
+    /// Whether the breakpoint could be installed in a function defined in
+    /// the file.
+    pub verified: bool,
+    /// Why the breakpoint can't be installed, if known in advance
+    pub message: Option<String>,
+}
+
+impl BreakpointInfo {
+    /// Create a breakpoint from the conditions sent by the frontend. Empty
+    /// conditions are ignored. An invalid hit condition is reported in
+    /// `message`.
+    pub fn new(
+        id: i64,
+        line: Option<i64>,
+        condition: Option<&str>,
+        hit_condition: Option<&str>,
+    ) -> Self {
+        let condition = condition
+            .filter(|condition| !condition.trim().is_empty())
+            .map(String::from);
+
+        let hit_condition = hit_condition
+            .filter(|hit_condition| !hit_condition.trim().is_empty())
+            .map(|hit_condition| hit_condition.parse::<HitCondition>())
+            .transpose();
+
+        let (hit_condition, message) = match hit_condition {
+            Ok(hit_condition) => (hit_condition, None),
+            Err(err) => (None, Some(err.to_string())),
+        };
+
+        Self {
+            id,
+            line,
+            condition,
+            hit_condition,
+            hits: 0,
+            verified: false,
+            message,
+        }
+    }
+
+    /// Record a hit and return whether we should stop
+    pub fn hit(&mut self) -> bool {
+        self.hits += 1;
+
+        match &self.hit_condition {
+            Some(hit_condition) => hit_condition.matches(self.hits),
+            None => true,
+        }
+    }
+}
+
+/// Hit condition of a breakpoint, e.g. `>= 5` or `% 3`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HitCondition {
+    Greater(u64),
+    GreaterOrEqual(u64),
+    Equal(u64),
+    Multiple(u64),
+}
+
+impl HitCondition {
+    pub fn matches(&self, hits: u64) -> bool {
+        match *self {
+            HitCondition::Greater(n) => hits > n,
+            HitCondition::GreaterOrEqual(n) => hits >= n,
+            HitCondition::Equal(n) => hits == n,
+            HitCondition::Multiple(n) => hits % n == 0,
+        }
+    }
+}
+
+impl std::str::FromStr for HitCondition {
+    type Err = anyhow::Error;
+
+    fn from_str(x: &str) -> Result<Self, Self::Err> {
+        let x = x.trim();
+
+        // Longest operators first so `>=` isn't parsed as `>`
+        let (op, n) = [">=", "==", ">", "%"]
+            .into_iter()
+            .find_map(|op| x.strip_prefix(op).map(|n| (op, n)))
+            .unwrap_or(("==", x));
+
+        let Ok(n) = n.trim().parse::<u64>() else {
+            return Err(anyhow!(
+                "Invalid hit condition `{x}`. Expected e.g. `5`, `>= 5`, or `% 3`."
+            ));
+        };
+
+        match op {
+            ">=" => Ok(HitCondition::GreaterOrEqual(n)),
+            ">" => Ok(HitCondition::Greater(n)),
+            "%" if n == 0 => Err(anyhow!(
+                "Invalid hit condition `{x}`. Can't use multiples of 0."
+            )),
+            "%" => Ok(HitCondition::Multiple(n)),
+            _ => Ok(HitCondition::Equal(n)),
+        }
+    }
+}
+
+/// Install breakpoints in the functions defined in `path`
+///
+/// Previous breakpoints for `path` are removed first. Breakpoints are
+/// injected in functions of the global environment whose source references
+/// point to `path`, so the file must have been sourced with
+/// `keep.source = TRUE`, or from a temporary copy mapped to `path` with
+/// `.ps.dap.map_source()`. Sets `verified` on each breakpoint that could be
+/// installed, and explains in `message` why if the file wasn't sourced.
+pub fn set_r_breakpoints(path: &str, breakpoints: &mut [BreakpointInfo]) -> anyhow::Result<()> {
+    // Skip breakpoints we already know can't be installed
+    let valid = || breakpoints.iter().filter(|bp| bp.message.is_none());
+    let lines: Vec<i64> = valid().filter_map(|bp| bp.line).collect();
+    let ids: Vec<i64> = valid().map(|bp| bp.id).collect();
+
+    let out = RFunction::new("", "dap_set_breakpoints")
+        .add(path)
+        .add(&lines)
+        .add(&ids)
+        .call_in(ARK_ENVS.positron_ns)?;
+
+    let verified: Vec<i32> = out.vector_elt(0)?.try_into()?;
+    let sourced: bool = out.vector_elt(1)?.try_into()?;
+
+    for breakpoint in breakpoints.iter_mut() {
+        breakpoint.verified = verified.contains(&(breakpoint.id as i32));
+
+        if !breakpoint.verified && !sourced {
+            breakpoint
+                .message
+                .get_or_insert_with(|| String::from(NOT_SOURCED_MESSAGE));
+        }
+    }
+
+    Ok(())
+}
+
+/// Get an identifier for a data breakpoint on the binding `name` of `env`
+///
+/// Returns `None` if `env` is not an environment or if the binding can't be
+/// watched. Only bindings of environments are supported, e.g. the variables
+/// of a frame. Modifications deep inside an object, such as an element of a
+/// list modified in place by reference semantics, can't be detected.
+pub fn data_breakpoint_id(env: RObject, name: &str) -> anyhow::Result<Option<String>> {
+    if r_typeof(env.sexp) != ENVSXP {
+        return Ok(None);
+    }
+
+    let id = RFunction::new("", "dap_data_breakpoint_info")
+        .add(env)
+        .add(name)
+        .call_in(ARK_ENVS.positron_ns)?;
+
+    Ok(id.try_into()?)
+}
+
+/// Install data breakpoints on the bindings identified by `data_ids`
+///
+/// Previous data breakpoints are removed first. The bindings are replaced
+/// by active bindings that stop when a new value is assigned. Sets
+/// `verified` on each breakpoint that could be installed.
+pub fn set_r_data_breakpoints(
+    data_ids: &[String],
+    breakpoints: &mut [BreakpointInfo],
+) -> anyhow::Result<()> {
+    let ids: Vec<i64> = breakpoints.iter().map(|bp| bp.id).collect();
+
+    let verified: Vec<i32> = RFunction::new("", "dap_set_data_breakpoints")
+        .add(data_ids.to_vec())
+        .add(&ids)
+        .call_in(ARK_ENVS.positron_ns)?
+        .try_into()?;
+
+    for breakpoint in breakpoints.iter_mut() {
+        breakpoint.verified = verified.contains(&(breakpoint.id as i32));
+    }
+
+    Ok(())
+}
+
+/// Install breakpoints on entry of the functions called `names`
+///
+/// Previous function breakpoints are removed first. Names may be qualified
+/// with a namespace, e.g. `pkg::fn`, otherwise they are looked up from the
+/// global environment. The functions are traced with `trace()` and traced
+/// again when they are redefined at top level. Sets `verified` on each
+/// breakpoint that could be installed.
+pub fn set_r_function_breakpoints(
+    names: &[String],
+    breakpoints: &mut [BreakpointInfo],
+) -> anyhow::Result<()> {
+    let ids: Vec<i64> = breakpoints.iter().map(|bp| bp.id).collect();
+
+    let verified: Vec<i32> = RFunction::new("", "dap_set_function_breakpoints")
+        .add(names.to_vec())
+        .add(&ids)
+        .call_in(ARK_ENVS.positron_ns)?
+        .try_into()?;
+
+    for breakpoint in breakpoints.iter_mut() {
+        breakpoint.verified = verified.contains(&(breakpoint.id as i32));
+    }
+
+    Ok(())
+}
+
+/// Evaluates the condition of the breakpoint, if any, in `env`
+///
+/// A condition that fails to evaluate stops anyway so that typos don't go
+/// unnoticed. In that case the error message is returned so it can be shown
+/// to the user.
+fn eval_condition(breakpoint: &BreakpointInfo, env: SEXP) -> Result<bool, String> {
+    let Some(condition) = &breakpoint.condition else {
+        return Ok(true);
+    };
+
+    let value = match r_parse_eval0(condition, env) {
+        Ok(value) => value,
+        Err(err) => {
+            return Err(format!(
+                "Breakpoint condition `{condition}` failed to evaluate: {err}"
+            ))
+        },
+    };
+
+    // Only stop on a single `TRUE`, like `isTRUE()`
+    Ok(matches!(Option::<bool>::try_from(value), Ok(Some(true))))
+}
+
+#[harp::register]
+pub unsafe extern "C" fn ps_dap_should_break(id: SEXP, env: SEXP) -> anyhow::Result<SEXP> {
+    let id: i32 = RObject::view(id).try_into()?;
+
+    if !RMain::initialized() {
+        return Ok(RObject::from(false).sexp);
+    }
+
+    // Clone the breakpoint so we don't hold the DAP lock while evaluating
+    // the condition
+    let breakpoint = {
+        let dap = RMain::get().get_dap();
+        let dap = dap.lock().unwrap();
+        dap.find_breakpoint(id as i64).cloned()
+    };
+
+    // The breakpoint was removed but the function was not restored, e.g.
+    // because it was redefined in the meantime
+    let Some(breakpoint) = breakpoint else {
+        return Ok(RObject::from(false).sexp);
+    };
+
+    let out = match eval_condition(&breakpoint, env) {
+        Ok(false) => RObject::from(false),
+        Ok(true) => {
+            let dap = RMain::get().get_dap();
+            let mut dap = dap.lock().unwrap();
+            let stop = match dap.find_breakpoint_mut(id as i64) {
+                Some(breakpoint) => breakpoint.hit(),
+                None => false,
+            };
+            if stop {
+                dap.stop_reason = Some(DapStoppedReason::Breakpoint(id as i64));
+            }
+            RObject::from(stop)
+        },
+        Err(message) => {
+            log::warn!("DAP: {message}");
+            let dap = RMain::get().get_dap();
+            dap.lock().unwrap().stop_reason = Some(DapStoppedReason::Breakpoint(id as i64));
+            RObject::from(message)
+        },
+    };
+
+    Ok(out.sexp)
+}
+
+#[harp::register]
+pub unsafe extern "C" fn ps_dap_exception_stop(
+    filter: SEXP,
+    message: SEXP,
+) -> anyhow::Result<SEXP> {
+    let filter: String = RObject::view(filter).try_into()?;
+    let message: String = RObject::view(message).try_into()?;
+
+    if RMain::initialized() {
+        let dap = RMain::get().get_dap();
+        dap.lock().unwrap().stop_reason = Some(DapStoppedReason::Exception(filter, message));
+    }
+
+    Ok(R_NilValue)
+}
+
+#[cfg(test)]
+mod tests {
+    use harp::environment::R_ENVS;
+    use harp::eval::r_parse_eval0;
+
+    use crate::dap::dap_breakpoints::eval_condition;
+    use crate::dap::dap_breakpoints::BreakpointInfo;
+    use crate::dap::dap_breakpoints::HitCondition;
+    use crate::test::r_test;
+
+    fn breakpoint(condition: Option<&str>) -> BreakpointInfo {
+        BreakpointInfo {
+            id: 1,
+            line: Some(1),
+            condition: condition.map(String::from),
+            hit_condition: None,
+            hits: 0,
+            verified: true,
+            message: None,
+        }
+    }
+
+    #[test]
+    fn test_hit_condition_parse() {
+        assert_eq!("5".parse::<HitCondition>().unwrap(), HitCondition::Equal(5));
+        assert_eq!(
+            "== 5".parse::<HitCondition>().unwrap(),
+            HitCondition::Equal(5)
+        );
+        assert_eq!(
+            ">5".parse::<HitCondition>().unwrap(),
+            HitCondition::Greater(5)
+        );
+        assert_eq!(
+            " >= 5 ".parse::<HitCondition>().unwrap(),
+            HitCondition::GreaterOrEqual(5)
+        );
+        assert_eq!(
+            "% 3".parse::<HitCondition>().unwrap(),
+            HitCondition::Multiple(3)
+        );
+
+        assert!("".parse::<HitCondition>().is_err());
+        assert!("< 5".parse::<HitCondition>().is_err());
+        assert!(">= five".parse::<HitCondition>().is_err());
+        assert!("% 0".parse::<HitCondition>().is_err());
+    }
+
+    #[test]
+    fn test_breakpoint_hits() {
+        let mut bp = breakpoint(None);
+        bp.hit_condition = Some(HitCondition::Multiple(3));
+
+        let stops: Vec<bool> = (0..6).map(|_| bp.hit()).collect();
+        assert_eq!(stops, vec![false, false, true, false, false, true]);
+
+        let mut bp = breakpoint(None);
+        bp.hit_condition = Some(HitCondition::GreaterOrEqual(2));
+        assert!(!bp.hit());
+        assert!(bp.hit());
+        assert!(bp.hit());
+    }
+
+    #[test]
+    fn test_breakpoint_condition() {
+        r_test(|| {
+            let env = r_parse_eval0("local({ i <- 3; environment() })", R_ENVS.global).unwrap();
+
+            assert_eq!(eval_condition(&breakpoint(None), env.sexp), Ok(true));
+            assert_eq!(
+                eval_condition(&breakpoint(Some("i == 3")), env.sexp),
+                Ok(true)
+            );
+            assert_eq!(
+                eval_condition(&breakpoint(Some("i > 3")), env.sexp),
+                Ok(false)
+            );
+            assert_eq!(eval_condition(&breakpoint(Some("NA")), env.sexp), Ok(false));
+            assert_eq!(
+                eval_condition(&breakpoint(Some("c(TRUE, TRUE)")), env.sexp),
+                Ok(false)
+            );
+            assert!(eval_condition(&breakpoint(Some("j == 3")), env.sexp).is_err());
+        })
+    }
+}
@@ -97,6 +97,10 @@ impl RMainDap {
         self.debugging
     }
 
+    pub fn dap(&self) -> Arc<Mutex<Dap>> {
+        self.dap.clone()
+    }
+
     pub fn start_debug(&mut self, stack: Vec<FrameInfo>) {
         self.debugging = true;
         let mut dap = self.dap.lock().unwrap();
@@ -327,3 +331,46 @@ impl RMainDap {
         self.current_frame_info_id = 0;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use harp::environment::R_ENVS;
+    use harp::eval::r_parse_eval0;
+    use harp::exec::RFunction;
+    use harp::exec::RFunctionExt;
+
+    use crate::modules::ARK_ENVS;
+    use crate::test::r_test;
+
+    fn frame_name(call: &str, fun: &str) -> String {
+        let call = r_parse_eval0(call, R_ENVS.global).unwrap();
+        let fun = r_parse_eval0(fun, R_ENVS.global).unwrap();
+        RFunction::new("", "debugger_frame_name")
+            .add(call)
+            .add(fun)
+            .call_in(ARK_ENVS.positron_ns)
+            .unwrap()
+            .try_into()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_debugger_frame_name() {
+        r_test(|| {
+            assert_eq!(frame_name("quote(f(x, y = 1))", "identity"), "f(x, y = 1)");
+            assert_eq!(frame_name("quote(pkg::f(x))", "identity"), "pkg::f(x)");
+
+            // Long calls are truncated
+            let name = frame_name("as.call(list(quote(f), strrep('x', 200)))", "identity");
+            assert_eq!(name.len(), 80);
+            assert!(name.starts_with("f(\"xxx"));
+            assert!(name.ends_with("..."));
+
+            // Anonymous functions show their definition location when known
+            let call = "quote((function(x) x)(1))";
+            let fun = "eval(parse(text = 'function(x) x', srcfile = srcfilecopy('foo.R', '')))";
+            assert_eq!(frame_name(call, fun), "<anonymous> (foo.R:1)");
+            assert_eq!(frame_name(call, "function(x) x"), "<anonymous>");
+        })
+    }
+}
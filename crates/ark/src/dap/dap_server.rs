@@ -15,6 +15,7 @@ use std::sync::Arc;
 use std::sync::Mutex;
 
 use amalthea::comm::comm_channel::CommMsg;
+use anyhow::anyhow;
 use crossbeam::channel::bounded;
 use crossbeam::channel::unbounded;
 use crossbeam::channel::Receiver;
@@ -26,20 +27,37 @@ use dap::requests::*;
 use dap::responses::*;
 use dap::server::ServerOutput;
 use dap::types::*;
+use harp::environment::R_ENVS;
+use harp::eval::r_parse_eval0;
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use harp::object::RObject;
+use harp::utils::r_is_null;
 use serde_json::json;
 use stdext::result::ResultOrLog;
 use stdext::spawn;
 
 use super::dap::Dap;
 use super::dap::DapBackendEvent;
+use super::dap::DapStoppedReason;
+use crate::dap::dap_breakpoints::data_breakpoint_id;
+use crate::dap::dap_breakpoints::set_r_breakpoints;
+use crate::dap::dap_breakpoints::set_r_data_breakpoints;
+use crate::dap::dap_breakpoints::set_r_function_breakpoints;
+use crate::dap::dap_breakpoints::BreakpointInfo;
 use crate::dap::dap_r_main::FrameInfo;
 use crate::dap::dap_r_main::FrameSource;
+use crate::dap::dap_variables::assign_variable;
+use crate::dap::dap_variables::object_variable;
 use crate::dap::dap_variables::object_variables;
+use crate::dap::dap_variables::scope_variables;
 use crate::dap::dap_variables::RVariable;
+use crate::modules::ARK_ENVS;
 use crate::r_task;
 use crate::request::debug_request_command;
 use crate::request::DebugRequest;
 use crate::request::RRequest;
+use crate::thread::RThreadSafe;
 
 const THREAD_ID: i64 = -1;
 
@@ -140,16 +158,8 @@ fn listen_dap_events<W: Write>(
                         })
                     },
 
-                    DapBackendEvent::Stopped => {
-                        Event::Stopped(StoppedEventBody {
-                            reason: StoppedEventReason::Step,
-                            description: None,
-                            thread_id: Some(THREAD_ID),
-                            preserve_focus_hint: Some(false),
-                            text: None,
-                            all_threads_stopped: Some(true),
-                            hit_breakpoint_ids: None,
-                        })
+                    DapBackendEvent::Stopped(reason) => {
+                        Event::Stopped(into_stopped_event(reason, None))
                     },
 
                     DapBackendEvent::Terminated => {
@@ -220,6 +230,18 @@ impl<R: Read, W: Write> DapServer<R, W> {
             Command::Threads => {
                 self.handle_threads(req);
             },
+            Command::SetBreakpoints(args) => {
+                self.handle_set_breakpoints(req, args);
+            },
+            Command::SetFunctionBreakpoints(args) => {
+                self.handle_set_function_breakpoints(req, args);
+            },
+            Command::DataBreakpointInfo(args) => {
+                self.handle_data_breakpoint_info(req, args);
+            },
+            Command::SetDataBreakpoints(args) => {
+                self.handle_set_data_breakpoints(req, args);
+            },
             Command::SetExceptionBreakpoints(args) => {
                 self.handle_set_exception_breakpoints(req, args);
             },
@@ -235,6 +257,15 @@ impl<R: Read, W: Write> DapServer<R, W> {
             Command::Variables(args) => {
                 self.handle_variables(req, args);
             },
+            Command::Evaluate(args) => {
+                self.handle_evaluate(req, args);
+            },
+            Command::SetVariable(args) => {
+                self.handle_set_variable(req, args);
+            },
+            Command::SetExpression(args) => {
+                self.handle_set_expression(req, args);
+            },
             Command::Continue(args) => {
                 let resp = ResponseBody::Continue(ContinueResponse {
                     all_threads_continued: Some(true),
@@ -263,6 +294,14 @@ impl<R: Read, W: Write> DapServer<R, W> {
     fn handle_initialize(&mut self, req: Request, _args: InitializeArguments) {
         let rsp = req.success(ResponseBody::Initialize(types::Capabilities {
             supports_restart_request: Some(true),
+            supports_conditional_breakpoints: Some(true),
+            supports_hit_conditional_breakpoints: Some(true),
+            supports_evaluate_for_hovers: Some(true),
+            supports_data_breakpoints: Some(true),
+            supports_function_breakpoints: Some(true),
+            supports_set_variable: Some(true),
+            supports_set_expression: Some(true),
+            exception_breakpoint_filters: Some(exception_breakpoint_filters()),
             ..Default::default()
         }));
         self.server.respond(rsp).unwrap();
@@ -274,20 +313,28 @@ impl<R: Read, W: Write> DapServer<R, W> {
         let rsp = req.success(ResponseBody::Attach);
         self.server.respond(rsp).unwrap();
 
-        self.server
-            .send_event(Event::Stopped(StoppedEventBody {
-                reason: StoppedEventReason::Step,
-                description: Some(String::from("Execution paused")),
-                thread_id: Some(THREAD_ID),
-                preserve_focus_hint: Some(false),
-                text: None,
-                all_threads_stopped: None,
-                hit_breakpoint_ids: None,
-            }))
-            .unwrap();
+        // The frontend attaches when we first stop, see `Dap::start_debug()`
+        let reason = {
+            let mut state = self.state.lock().unwrap();
+            state.stop_reason.take().unwrap_or(DapStoppedReason::Step)
+        };
+        let description = String::from("Execution paused");
+        let event = into_stopped_event(reason, Some(description));
+
+        self.server.send_event(Event::Stopped(event)).unwrap();
     }
 
     fn handle_disconnect(&mut self, req: Request, _args: DisconnectArguments) {
+        // Stop pausing on conditions once the session is over
+        let cleared = r_task(|| {
+            RFunction::new("", "dap_set_exception_filters")
+                .add(Vec::<String>::new())
+                .call_in(ARK_ENVS.positron_ns)
+        });
+        if let Err(err) = cleared {
+            log::error!("DAP: Can't clear exception filters: {err:?}");
+        }
+
         // Only send `Q` if currently in a debugging session.
         let is_debugging = { self.state.lock().unwrap().is_debugging };
         if is_debugging {
@@ -322,14 +369,249 @@ impl<R: Read, W: Write> DapServer<R, W> {
         self.server.respond(rsp).unwrap();
     }
 
+    fn handle_set_breakpoints(&mut self, req: Request, args: SetBreakpointsArguments) {
+        let source_breakpoints = args.breakpoints.unwrap_or_default();
+
+        // Breakpoints can only be installed in functions sourced from files
+        let Some(path) = args.source.path.clone() else {
+            let breakpoints = source_breakpoints
+                .iter()
+                .map(|bp| {
+                    into_dap_breakpoint(
+                        None,
+                        Some(bp.line),
+                        false,
+                        Some(String::from("Breakpoints are only supported in files.")),
+                    )
+                })
+                .collect();
+            let rsp = req.success(ResponseBody::SetBreakpoints(SetBreakpointsResponse {
+                breakpoints,
+            }));
+            self.server.respond(rsp).unwrap();
+            return;
+        };
+
+        let mut breakpoints: Vec<BreakpointInfo> = {
+            let mut state = self.state.lock().unwrap();
+            source_breakpoints
+                .iter()
+                .map(|bp| {
+                    BreakpointInfo::new(
+                        state.next_breakpoint_id(),
+                        Some(bp.line),
+                        bp.condition.as_deref(),
+                        bp.hit_condition.as_deref(),
+                    )
+                })
+                .collect()
+        };
+
+        // Don't hold the lock while R installs the breakpoints
+        if let Err(err) = r_task(|| set_r_breakpoints(&path, &mut breakpoints)) {
+            log::error!("DAP: Can't set breakpoints in '{path}': {err:?}");
+        }
+
+        let response = breakpoints
+            .iter()
+            .map(|bp| {
+                let message = bp.message.clone().or_else(|| {
+                    (!bp.verified).then(|| {
+                        String::from("No function sourced from this file contains this line.")
+                    })
+                });
+                into_dap_breakpoint(Some(bp.id), bp.line, bp.verified, message)
+            })
+            .collect();
+
+        {
+            let mut state = self.state.lock().unwrap();
+            if breakpoints.is_empty() {
+                state.breakpoints.remove(&path);
+            } else {
+                state.breakpoints.insert(path, breakpoints);
+            }
+        }
+
+        let rsp = req.success(ResponseBody::SetBreakpoints(SetBreakpointsResponse {
+            breakpoints: response,
+        }));
+        self.server.respond(rsp).unwrap();
+    }
+
+    fn handle_set_function_breakpoints(
+        &mut self,
+        req: Request,
+        args: SetFunctionBreakpointsArguments,
+    ) {
+        let names: Vec<String> = args.breakpoints.iter().map(|bp| bp.name.clone()).collect();
+
+        let mut breakpoints: Vec<BreakpointInfo> = {
+            let mut state = self.state.lock().unwrap();
+            args.breakpoints
+                .iter()
+                .map(|bp| {
+                    BreakpointInfo::new(
+                        state.next_breakpoint_id(),
+                        None,
+                        bp.condition.as_deref(),
+                        bp.hit_condition.as_deref(),
+                    )
+                })
+                .collect()
+        };
+
+        // Don't hold the lock while R installs the breakpoints
+        if let Err(err) = r_task(|| set_r_function_breakpoints(&names, &mut breakpoints)) {
+            log::error!("DAP: Can't set function breakpoints: {err:?}");
+        }
+
+        let response = breakpoints
+            .iter()
+            .zip(names.iter())
+            .map(|(bp, name)| {
+                let message = bp.message.clone().or_else(|| {
+                    (!bp.verified).then(|| format!("Can't find a function called `{name}`."))
+                });
+                into_dap_breakpoint(Some(bp.id), None, bp.verified, message)
+            })
+            .collect();
+
+        {
+            let mut state = self.state.lock().unwrap();
+            state.function_breakpoints = breakpoints;
+        }
+
+        let rsp = req.success(ResponseBody::SetFunctionBreakpoints(
+            SetFunctionBreakpointsResponse {
+                breakpoints: response,
+            },
+        ));
+        self.server.respond(rsp).unwrap();
+    }
+
+    fn handle_data_breakpoint_info(&mut self, req: Request, args: DataBreakpointInfoArguments) {
+        let name = args.name.clone();
+        let state = &self.state;
+
+        let data_id = match args.variables_reference {
+            Some(variables_reference) => r_task(|| -> anyhow::Result<Option<String>> {
+                let object = {
+                    let state = state.lock().unwrap();
+                    state
+                        .variables_reference_to_r_object
+                        .get(&variables_reference)
+                        .map(|object| object.get().clone())
+                };
+                match object {
+                    Some(object) => data_breakpoint_id(object, &name),
+                    None => Ok(None),
+                }
+            }),
+            None => Ok(None),
+        };
+
+        let data_id = data_id.unwrap_or_else(|err| {
+            log::error!("DAP: Can't get data breakpoint info for `{name}`: {err:?}");
+            None
+        });
+
+        let description = match data_id {
+            Some(_) => format!("Break when `{name}` changes"),
+            None => {
+                String::from("Data breakpoints are only supported on variables of environments.")
+            },
+        };
+
+        let rsp = req.success(ResponseBody::DataBreakpointInfo(
+            DataBreakpointInfoResponse {
+                data_id,
+                description,
+                access_types: None,
+                can_persist: Some(false),
+            },
+        ));
+        self.server.respond(rsp).unwrap();
+    }
+
+    fn handle_set_data_breakpoints(&mut self, req: Request, args: SetDataBreakpointsArguments) {
+        let data_ids: Vec<String> = args
+            .breakpoints
+            .iter()
+            .map(|bp| bp.data_id.clone())
+            .collect();
+
+        let mut breakpoints: Vec<BreakpointInfo> = {
+            let mut state = self.state.lock().unwrap();
+            args.breakpoints
+                .iter()
+                .map(|bp| {
+                    BreakpointInfo::new(
+                        state.next_breakpoint_id(),
+                        None,
+                        bp.condition.as_deref(),
+                        bp.hit_condition.as_deref(),
+                    )
+                })
+                .collect()
+        };
+
+        // Don't hold the lock while R installs the breakpoints
+        if let Err(err) = r_task(|| set_r_data_breakpoints(&data_ids, &mut breakpoints)) {
+            log::error!("DAP: Can't set data breakpoints: {err:?}");
+        }
+
+        let response = breakpoints
+            .iter()
+            .map(|bp| {
+                let message = bp.message.clone().or_else(|| {
+                    (!bp.verified).then(|| String::from("The variable no longer exists."))
+                });
+                into_dap_breakpoint(Some(bp.id), None, bp.verified, message)
+            })
+            .collect();
+
+        {
+            let mut state = self.state.lock().unwrap();
+            state.data_breakpoints = breakpoints;
+        }
+
+        let rsp = req.success(ResponseBody::SetDataBreakpoints(
+            SetDataBreakpointsResponse {
+                breakpoints: response,
+            },
+        ));
+        self.server.respond(rsp).unwrap();
+    }
+
     fn handle_set_exception_breakpoints(
         &mut self,
         req: Request,
-        _args: SetExceptionBreakpointsArguments,
+        args: SetExceptionBreakpointsArguments,
     ) {
+        let filters = args.filters.clone();
+
+        let installed = r_task(|| -> anyhow::Result<bool> {
+            let installed = RFunction::new("", "dap_set_exception_filters")
+                .add(filters.clone())
+                .call_in(ARK_ENVS.positron_ns)?;
+            Ok(installed.try_into()?)
+        });
+
+        let verified = installed.unwrap_or_else(|err| {
+            log::error!("DAP: Can't set exception filters: {err:?}");
+            false
+        });
+        let message = (!verified).then(|| String::from("Requires R >= 4.0.0."));
+
+        let breakpoints = filters
+            .iter()
+            .map(|_| into_dap_breakpoint(None, None, verified, message.clone()))
+            .collect();
+
         let rsp = req.success(ResponseBody::SetExceptionBreakpoints(
             SetExceptionBreakpointsResponse {
-                breakpoints: None, // TODO
+                breakpoints: Some(breakpoints),
             },
         ));
         self.server.respond(rsp).unwrap();
@@ -426,32 +708,25 @@ impl<R: Read, W: Write> DapServer<R, W> {
 
     fn handle_scopes(&mut self, req: Request, args: ScopesArguments) {
         let state = self.state.lock().unwrap();
-        let frame_id_to_variables_reference = &state.frame_id_to_variables_reference;
+        let mut scopes = Vec::new();
 
         // Entirely possible that the requested `frame_id` doesn't have any
-        // variables (like the top most frame where the call was made). We send
-        // back `0` in those cases, which is an indication of "no variables".
-        let variables_reference = frame_id_to_variables_reference
-            .get(&args.frame_id)
-            .copied()
-            .unwrap_or(0);
-
-        let mut scopes = Vec::new();
+        // variables (like the top most frame where the call was made). We only
+        // send back the Globals scope in those cases.
+        if let Some(reference) = state.frame_id_to_arguments_reference.get(&args.frame_id) {
+            let hint = Some(ScopePresentationhint::Arguments);
+            scopes.push(new_scope("Arguments", hint, *reference, false));
+        }
+        if let Some(reference) = state.frame_id_to_variables_reference.get(&args.frame_id) {
+            let hint = Some(ScopePresentationhint::Locals);
+            scopes.push(new_scope("Locals", hint, *reference, false));
+        }
 
-        // Only 1 overarching scope for now
-        scopes.push(Scope {
-            name: String::from("Locals"),
-            presentation_hint: Some(ScopePresentationhint::Locals),
-            variables_reference,
-            named_variables: None,
-            indexed_variables: None,
-            expensive: false,
-            source: None,
-            line: None,
-            column: None,
-            end_line: None,
-            end_column: None,
-        });
+        // The global environment may be large, let the frontend collect it
+        // on demand
+        if let Some(reference) = state.globals_variables_reference {
+            scopes.push(new_scope("Globals", None, reference, true));
+        }
 
         let rsp = req.success(ResponseBody::Scopes(ScopesResponse { scopes }));
 
@@ -477,11 +752,20 @@ impl<R: Read, W: Write> DapServer<R, W> {
             return Vec::new();
         };
 
+        // Frame environments are split in scopes
+        let scope = state
+            .variables_reference_to_scope
+            .get(&variables_reference)
+            .copied();
+
         // Should be safe to run an r-task while paused in the debugger, tasks
         // are still run while polling within the read console hook
         let variables = r_task(|| {
             let object = object.get();
-            object_variables(object.sexp)
+            match scope {
+                Some(scope) => scope_variables(object.sexp, scope),
+                None => object_variables(object.sexp),
+            }
         });
 
         variables
@@ -524,6 +808,168 @@ impl<R: Read, W: Write> DapServer<R, W> {
         out
     }
 
+    fn handle_evaluate(&mut self, req: Request, args: EvaluateArguments) {
+        let expression = args.expression.clone();
+        let state = &self.state;
+
+        // Hovers are requested as the mouse moves over the editor, so they
+        // must not have side effects. Only symbols and `$`/`@` accessors are
+        // looked up, without calling any function.
+        let hover = matches!(args.context, Some(EvaluateArgumentsContext::Hover));
+
+        // Evaluate in the environment of the selected frame, or in the global
+        // environment if there is none. We don't hold the lock while
+        // evaluating as the expression might hit a breakpoint.
+        let result = r_task(|| -> anyhow::Result<RVariable> {
+            let env = {
+                let state = state.lock().unwrap();
+                args.frame_id
+                    .and_then(|id| state.frame_id_to_variables_reference.get(&id))
+                    .and_then(|reference| state.variables_reference_to_r_object.get(reference))
+                    .map(|object| object.get().clone())
+            };
+            let env = env.unwrap_or_else(|| RObject::view(R_ENVS.global));
+
+            let value = if hover {
+                hover_value(&expression, env)?
+            } else {
+                r_parse_eval0(&expression, env)?
+            };
+            Ok(object_variable(expression.clone(), value.sexp))
+        });
+
+        let variable = match result {
+            Ok(variable) => variable,
+            Err(err) => {
+                // Report errors to the frontend, e.g. in the Watch pane,
+                // without interrupting the debug session
+                log::trace!("DAP: Can't evaluate `{expression}`: {err}");
+                let rsp = req.error(&format!("{err}"));
+                self.server.respond(rsp).unwrap();
+                return;
+            },
+        };
+
+        let variables_reference =
+            self.insert_variable_reference(variable.variables_reference_object);
+
+        let rsp = req.success(ResponseBody::Evaluate(EvaluateResponse {
+            result: variable.value,
+            type_field: variable.type_field,
+            presentation_hint: None,
+            variables_reference,
+            named_variables: None,
+            indexed_variables: None,
+            memory_reference: None,
+        }));
+        self.server.respond(rsp).unwrap();
+    }
+
+    fn handle_set_variable(&mut self, req: Request, args: SetVariableArguments) {
+        let state = &self.state;
+
+        let result = r_task(|| -> anyhow::Result<RVariable> {
+            let env = {
+                let state = state.lock().unwrap();
+                let reference = args.variables_reference;
+                match state.variables_reference_to_r_object.get(&reference) {
+                    Some(object) => object.get().clone(),
+                    None => return Err(anyhow!("Unknown variables reference {reference}")),
+                }
+            };
+
+            // Quote the name so that non-syntactic names can be assigned to
+            let target = format!("`{}`", args.name.replace('`', "\\`"));
+            let mut variable = assign_variable(env.sexp, &target, &args.value)?;
+            variable.name = args.name.clone();
+            Ok(variable)
+        });
+
+        let variable = match result {
+            Ok(variable) => variable,
+            Err(err) => {
+                log::trace!("DAP: Can't set `{}`: {err}", args.name);
+                let rsp = req.error(&format!("{err}"));
+                self.server.respond(rsp).unwrap();
+                return;
+            },
+        };
+
+        let variables_reference =
+            self.insert_variable_reference(variable.variables_reference_object);
+
+        let rsp = req.success(ResponseBody::SetVariable(SetVariableResponse {
+            value: variable.value,
+            type_field: variable.type_field,
+            variables_reference: Some(variables_reference),
+            named_variables: None,
+            indexed_variables: None,
+        }));
+        self.server.respond(rsp).unwrap();
+    }
+
+    fn handle_set_expression(&mut self, req: Request, args: SetExpressionArguments) {
+        let state = &self.state;
+
+        // Assign in the environment of the selected frame, or in the global
+        // environment if there is none. Unlike evaluations, assignments to
+        // frames that no longer exist are rejected rather than redirected
+        // to the global environment.
+        let result = r_task(|| -> anyhow::Result<RVariable> {
+            let env = match args.frame_id {
+                Some(id) => {
+                    let state = state.lock().unwrap();
+                    let env = state
+                        .frame_id_to_variables_reference
+                        .get(&id)
+                        .and_then(|reference| state.variables_reference_to_r_object.get(reference));
+                    match env {
+                        Some(env) => env.get().clone(),
+                        None => return Err(anyhow!("Frame {id} doesn't exist")),
+                    }
+                },
+                None => RObject::view(R_ENVS.global),
+            };
+
+            assign_variable(env.sexp, &args.expression, &args.value)
+        });
+
+        let variable = match result {
+            Ok(variable) => variable,
+            Err(err) => {
+                log::trace!("DAP: Can't set `{}`: {err}", args.expression);
+                let rsp = req.error(&format!("{err}"));
+                self.server.respond(rsp).unwrap();
+                return;
+            },
+        };
+
+        let variables_reference =
+            self.insert_variable_reference(variable.variables_reference_object);
+
+        let rsp = req.success(ResponseBody::SetExpression(SetExpressionResponse {
+            value: variable.value,
+            type_field: variable.type_field,
+            presentation_hint: None,
+            variables_reference: Some(variables_reference),
+            named_variables: None,
+            indexed_variables: None,
+        }));
+        self.server.respond(rsp).unwrap();
+    }
+
+    /// Returns a new `variables_reference` for the children of a variable,
+    /// or 0 if it has none
+    fn insert_variable_reference(&self, object: Option<RThreadSafe<RObject>>) -> i64 {
+        match object {
+            Some(x) => {
+                let mut state = self.state.lock().unwrap();
+                state.insert_variables_reference_object(x)
+            },
+            None => 0,
+        }
+    }
+
     fn handle_step<A>(&mut self, req: Request, _args: A, cmd: DebugRequest, resp: ResponseBody) {
         self.send_command(cmd);
         let rsp = req.success(resp);
@@ -550,6 +996,113 @@ impl<R: Read, W: Write> DapServer<R, W> {
     }
 }
 
+fn exception_breakpoint_filters() -> Vec<ExceptionBreakpointsFilter> {
+    let filter = |filter: &str, label: &str, description: &str| ExceptionBreakpointsFilter {
+        filter: String::from(filter),
+        label: String::from(label),
+        description: Some(String::from(description)),
+        default: Some(false),
+        supports_condition: None,
+        condition_description: None,
+    };
+
+    vec![
+        filter("stop", "Errors", "Pause on errors signalled with `stop()`."),
+        filter("warning", "Warnings", "Pause on warnings."),
+        filter("message", "Messages", "Pause on messages."),
+    ]
+}
+
+/// Looks up the value of `expression` for a hover, without calling any
+/// function. Fails if the expression isn't a symbol or a chain of `$` and `@`
+/// accessors, or if its value isn't available without side effects.
+fn hover_value(expression: &str, env: RObject) -> anyhow::Result<RObject> {
+    let value = RFunction::new("", "dap_hover_value")
+        .add(expression)
+        .add(env)
+        .call_in(ARK_ENVS.positron_ns)?;
+
+    if r_is_null(value.sexp) {
+        return Err(anyhow!("Value not available"));
+    }
+    Ok(value.vector_elt(0)?)
+}
+
+fn into_stopped_event(reason: DapStoppedReason, description: Option<String>) -> StoppedEventBody {
+    let (reason, description, text, hit_breakpoint_ids) = match reason {
+        DapStoppedReason::Step => (StoppedEventReason::Step, description, None, None),
+        DapStoppedReason::Breakpoint(id) => (
+            StoppedEventReason::Breakpoint,
+            Some(String::from("Paused on breakpoint")),
+            None,
+            Some(vec![id]),
+        ),
+        DapStoppedReason::Exception(filter, message) => {
+            let kind = match filter.as_str() {
+                "stop" => "error",
+                kind => kind,
+            };
+            (
+                StoppedEventReason::Exception,
+                Some(format!("Paused on {kind}")),
+                Some(message),
+                None,
+            )
+        },
+    };
+
+    StoppedEventBody {
+        reason,
+        description,
+        thread_id: Some(THREAD_ID),
+        preserve_focus_hint: Some(false),
+        text,
+        all_threads_stopped: Some(true),
+        hit_breakpoint_ids,
+    }
+}
+
+fn into_dap_breakpoint(
+    id: Option<i64>,
+    line: Option<i64>,
+    verified: bool,
+    message: Option<String>,
+) -> Breakpoint {
+    Breakpoint {
+        id,
+        verified,
+        message,
+        source: None,
+        line,
+        column: None,
+        end_line: None,
+        end_column: None,
+        instruction_reference: None,
+        offset: None,
+    }
+}
+
+fn new_scope(
+    name: &str,
+    presentation_hint: Option<ScopePresentationhint>,
+    variables_reference: i64,
+    expensive: bool,
+) -> Scope {
+    Scope {
+        name: String::from(name),
+        presentation_hint,
+        variables_reference,
+        named_variables: None,
+        indexed_variables: None,
+        expensive,
+        source: None,
+        line: None,
+        column: None,
+        end_line: None,
+        end_column: None,
+    }
+}
+
 fn into_dap_frame(frame: &FrameInfo, fallback_sources: &HashMap<String, i32>) -> StackFrame {
     let id = frame.id;
     let source_name = frame.source_name.clone();
@@ -599,3 +1152,60 @@ fn into_dap_frame(frame: &FrameInfo, fallback_sources: &HashMap<String, i32>) ->
         presentation_hint: None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use harp::environment::R_ENVS;
+    use harp::eval::r_parse_eval0;
+
+    use crate::dap::dap_server::hover_value;
+    use crate::test::r_test;
+
+    #[test]
+    fn test_hover_value() {
+        r_test(|| {
+            let env = r_parse_eval0(
+                r#"local({
+                    x <- list(a = 1, b = list(c = 2))
+                    y <- structure(list(a = 1), class = "ark_hover_test")
+                    f <- function() stop("called")
+                    delayedAssign("lazy", stop("forced"))
+                    makeActiveBinding("active", function() stop("called"), environment())
+                    environment()
+                })"#,
+                R_ENVS.global,
+            )
+            .unwrap();
+            r_parse_eval0(
+                r#"`$.ark_hover_test` <- function(x, name) stop("called")"#,
+                R_ENVS.global,
+            )
+            .unwrap();
+
+            let value = |expression: &str| -> Option<f64> {
+                let value = hover_value(expression, env.clone()).ok()?;
+                Some(f64::try_from(value).unwrap())
+            };
+
+            // Symbols and accessors are looked up, through enclosures too
+            assert_eq!(value("x$a"), Some(1.0));
+            assert_eq!(value("x$b$c"), Some(2.0));
+            assert_eq!(value("x$'a'"), Some(1.0));
+            assert_eq!(value("pi"), Some(std::f64::consts::PI));
+            assert!(hover_value("f", env.clone()).is_ok());
+
+            // Nothing that would call a function
+            assert!(hover_value("f()", env.clone()).is_err());
+            assert!(hover_value("x[[1]]", env.clone()).is_err());
+            assert!(hover_value("y$a", env.clone()).is_err());
+            assert!(hover_value("lazy", env.clone()).is_err());
+            assert!(hover_value("active", env.clone()).is_err());
+
+            // Not an expression, or an unknown symbol
+            assert!(hover_value("x$", env.clone()).is_err());
+            assert!(hover_value("unknown", env.clone()).is_err());
+
+            r_parse_eval0("rm(`$.ark_hover_test`)", R_ENVS.global).unwrap();
+        })
+    }
+}
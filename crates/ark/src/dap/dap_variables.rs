@@ -5,7 +5,12 @@
 //
 //
 
+use anyhow::anyhow;
 use harp::call::r_expr_quote;
+use harp::call::RCall;
+use harp::environment::Environment;
+use harp::exec::r_parse;
+use harp::exec::try_eval;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
 use harp::object::*;
@@ -17,6 +22,8 @@ use harp::vector::Vector;
 use libr::*;
 use stdext::unwrap;
 
+use crate::dap::dap::FrameScope;
+use crate::modules::ARK_ENVS;
 use crate::thread::RThreadSafe;
 
 pub struct RVariable {
@@ -107,6 +114,97 @@ fn env_variables(x: SEXP) -> Vec<RVariable> {
         .collect()
 }
 
+/// Collects the variables of the frame environment `x` that belong to `scope`.
+/// Arguments are listed in the order of the function's formals.
+pub(super) fn scope_variables(x: SEXP, scope: FrameScope) -> Vec<RVariable> {
+    let arguments = frame_arguments(x).unwrap_or_else(|err| {
+        log::error!("Can't determine frame arguments: {err:?}");
+        Vec::new()
+    });
+
+    let names = match scope {
+        FrameScope::Arguments => arguments,
+        FrameScope::Locals => {
+            let names = RObject::from(r_env_names(x));
+            let names = Vec::<String>::try_from(names).unwrap_or(Vec::new());
+            names
+                .into_iter()
+                .filter(|name| !arguments.contains(name))
+                .collect()
+        },
+    };
+
+    // Unbound arguments, e.g. `...` when no dots were passed, are dropped
+    names
+        .into_iter()
+        .filter_map(|name| env_binding_variable(name, x))
+        .collect()
+}
+
+/// Evaluates `value` and assigns it to `target` in the environment `env`, as
+/// if `target <- value` had been typed at the browser prompt. `target` is a
+/// symbol or a complex assignment target such as `x$a` or `names(x)[1]`.
+/// Returns the new value of `target`.
+///
+/// Assignments to locked bindings and new bindings in locked environments
+/// are rejected.
+pub(super) fn assign_variable(env: SEXP, target: &str, value: &str) -> anyhow::Result<RVariable> {
+    if r_typeof(env) != ENVSXP {
+        return Err(anyhow!("Can only assign to variables of environments"));
+    }
+
+    let target_expr = r_parse(target)?;
+    let value_expr = r_parse(value)?;
+
+    let Some(root) = assignment_root(target_expr.sexp) else {
+        return Err(anyhow!("Can't assign to `{target}`"));
+    };
+
+    let frame = Environment::view(env);
+    if frame.exists(root) {
+        if frame.is_locked_binding(root) {
+            let root = String::from(root);
+            return Err(anyhow!("Can't assign to `{target}`: `{root}` is locked"));
+        }
+    } else if frame.is_locked() {
+        let root = String::from(root);
+        return Err(anyhow!(
+            "Can't assign to `{target}`: can't create `{root}` in a locked environment"
+        ));
+    }
+
+    let call = RCall::new(unsafe { r_symbol!("<-") })
+        .add(target_expr.clone())
+        .add(value_expr)
+        .build();
+    try_eval(call.sexp, env)?;
+
+    let value = try_eval(target_expr.sexp, env)?;
+    Ok(object_variable(String::from(target), value.sexp))
+}
+
+/// The variable a complex assignment ultimately modifies, e.g. `x` in
+/// `names(x$a)[1]`
+fn assignment_root(mut x: SEXP) -> Option<RSymbol> {
+    loop {
+        match r_typeof(x) {
+            SYMSXP => return Some(RSymbol::new_unchecked(x)),
+            LANGSXP => x = unsafe { CADR(x) },
+            _ => return None,
+        }
+    }
+}
+
+/// Names of the formals of the function evaluated in the frame `x`. The
+/// function is looked up on the call stack, where the frame is still alive
+/// while we are stopped in the debugger.
+fn frame_arguments(x: SEXP) -> anyhow::Result<Vec<String>> {
+    let names = RFunction::new("", "dap_frame_arguments")
+        .add(x)
+        .call_in(ARK_ENVS.positron_ns)?;
+    Ok(Vec::<String>::try_from(names)?)
+}
+
 fn env_binding_variable(name: String, x: SEXP) -> Option<RVariable> {
     if is_ignored_name(&name) {
         // Drop ignored names entirely
@@ -152,7 +250,9 @@ fn list_variables(x: SEXP) -> Vec<RVariable> {
     out
 }
 
-fn object_variable(name: String, x: SEXP) -> RVariable {
+/// Collect an `RVariable` for a single object, e.g. the result of an
+/// `Evaluate` DAP request
+pub(super) fn object_variable(name: String, x: SEXP) -> RVariable {
     if r_is_object(x) {
         object_variable_classed(name, x)
     } else {
@@ -447,7 +547,9 @@ mod tests {
     use harp::utils::r_envir_set;
     use libr::*;
 
+    use crate::dap::dap_variables::assign_variable;
     use crate::dap::dap_variables::env_binding_variable;
+    use crate::modules::ARK_ENVS;
     use crate::test::r_test;
 
     #[test]
@@ -470,6 +572,27 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_dap_frame_arguments() {
+        r_test(|| {
+            let code = "local({
+                f <- function(a, b = 2, ...) {
+                    x <- 1
+                    dap_frame_arguments(environment())
+                }
+                f(1)
+            })";
+            let arguments = r_parse_eval0(code, ARK_ENVS.positron_ns).unwrap();
+            let arguments = Vec::<String>::try_from(arguments).unwrap();
+            assert_eq!(arguments, vec!["a", "b", "..."]);
+
+            // Not a frame on the call stack
+            let code = "dap_frame_arguments(new.env())";
+            let arguments = r_parse_eval0(code, ARK_ENVS.positron_ns).unwrap();
+            assert!(Vec::<String>::try_from(arguments).unwrap().is_empty());
+        })
+    }
+
     #[test]
     fn test_env_binding_variable_classed() {
         r_test(|| unsafe {
@@ -515,4 +638,41 @@ mod tests {
             assert_eq!(variable.type_field, Some(String::from("<active binding>")));
         })
     }
+
+    #[test]
+    fn test_assign_variable() {
+        r_test(|| {
+            let env = r_parse_eval0("new.env(parent = baseenv())", R_ENVS.global).unwrap();
+            r_parse_eval0("x <- list(a = 1)", env.clone()).unwrap();
+
+            // Complex assignments modify the root variable
+            let variable = assign_variable(env.sexp, "x$a", "1 + 1").unwrap();
+            assert_eq!(variable.name, String::from("x$a"));
+            assert_eq!(variable.value, String::from("2"));
+            let value = r_parse_eval0("x$a", env.clone()).unwrap();
+            assert_eq!(f64::try_from(value).unwrap(), 2.0);
+
+            // New bindings are created and values are evaluated in `env`
+            let variable = assign_variable(env.sexp, "y", "x$a * 2").unwrap();
+            assert_eq!(variable.value, String::from("4"));
+
+            // Invalid targets and values
+            assert!(assign_variable(env.sexp, "f()", "1").is_err());
+            assert!(assign_variable(env.sexp, "y", "1 +").is_err());
+            assert!(assign_variable(env.sexp, "y", "stop('oh no')").is_err());
+
+            // Locked bindings and environments
+            r_parse_eval0("lockBinding('x', environment())", env.clone()).unwrap();
+            let err = assign_variable(env.sexp, "x$a", "3").unwrap_err();
+            assert!(format!("{err}").contains("`x` is locked"));
+
+            r_parse_eval0("lockEnvironment(environment())", env.clone()).unwrap();
+            assert!(assign_variable(env.sexp, "y", "5").is_ok());
+            assert!(assign_variable(env.sexp, "z", "5").is_err());
+
+            // Only environments can be assigned to
+            let list = r_parse_eval0("list(a = 1)", R_ENVS.base).unwrap();
+            assert!(assign_variable(list.sexp, "a", "2").is_err());
+        })
+    }
 }
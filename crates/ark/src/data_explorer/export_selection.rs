@@ -26,15 +26,21 @@ use crate::modules::ARK_ENVS;
 // - data: The data frame full data frame to export
 // - view_indices: The order of rows, and maybe filtered rows from the data frame to be selected.
 //   Must be applied before the selection rules if selection affects rows.
+// - column_order: The display order of the columns, if they were reordered or pinned. Column
+//   ranges (`CellRange` and `ColumnRange`) are over display positions, since a range of the
+//   columns the user sees is not a range of table indices once columns are reordered. Column
+//   indices (`SingleCell` and `ColumnIndices`) are table indices, like in all other requests.
+//   Row selections include all columns in display order.
 // - selection: The selected region of the data frame
 // - format: The format to export the data frame to (csv, tsv and html are currently supported).
 pub fn export_selection(
     data: SEXP,
     view_indices: Option<Vec<i32>>,
+    column_order: Option<Vec<i32>>,
     selection: DataSelection,
     format: ExportFormat,
 ) -> anyhow::Result<String> {
-    let region = get_selection(data, view_indices, selection.clone())?;
+    let region = get_selection(data, view_indices, column_order, selection.clone())?;
     let format_string = match format {
         ExportFormat::Csv => "csv",
         ExportFormat::Tsv => "tsv",
@@ -59,8 +65,14 @@ pub fn export_selection(
 fn get_selection(
     data: SEXP,
     view_indices: Option<Vec<i32>>,
+    column_order: Option<Vec<i32>>,
     selection: DataSelection,
 ) -> anyhow::Result<RObject> {
+    let column_range = matches!(
+        selection.kind,
+        DataSelectionKind::CellRange | DataSelectionKind::ColumnRange
+    );
+
     let (i, j) = match selection.kind {
         DataSelectionKind::SingleCell => match selection.selection {
             Selection::SingleCell(DataSelectionSingleCell {
@@ -105,6 +117,22 @@ fn get_selection(
         },
     };
 
+    // Map the display positions of column ranges to column indices
+    let j = match (column_order, j) {
+        (Some(column_order), Some(j)) if column_range => Some(
+            j.iter()
+                .map(|j| match column_order.get(*j as usize) {
+                    Some(index) => Ok(*index as i64),
+                    None => Err(anyhow::anyhow!("Column position {j} is out of bounds")),
+                })
+                .collect::<anyhow::Result<Vec<i64>>>()?,
+        ),
+        (Some(column_order), None) => {
+            Some(column_order.iter().map(|index| *index as i64).collect())
+        },
+        (_, j) => j,
+    };
+
     subset_with_view_indices(data, view_indices, i, j)
 }
 
@@ -169,7 +197,7 @@ mod tests {
         selection: DataSelection,
         format: ExportFormat,
     ) -> String {
-        export_selection(data.sexp, None, selection, format).unwrap()
+        export_selection(data.sexp, None, None, selection, format).unwrap()
     }
 
     fn export_selection_helper_with_view_indices(
@@ -177,7 +205,14 @@ mod tests {
         view_indices: Vec<i32>,
         selection: DataSelection,
     ) -> String {
-        export_selection(data.sexp, Some(view_indices), selection, ExportFormat::Csv).unwrap()
+        export_selection(
+            data.sexp,
+            Some(view_indices),
+            None,
+            selection,
+            ExportFormat::Csv,
+        )
+        .unwrap()
     }
 
     fn small_test_data() -> RObject {
@@ -456,4 +491,58 @@ mod tests {
             );
         })
     }
+
+    #[test]
+    fn test_column_order() {
+        r_test(|| {
+            let data = small_test_data();
+            let column_order = Some(vec![2, 0, 1]);
+
+            let export = |kind, selection| {
+                let selection = DataSelection { kind, selection };
+                export_selection(
+                    data.sexp,
+                    None,
+                    column_order.clone(),
+                    selection,
+                    ExportFormat::Csv,
+                )
+                .unwrap()
+            };
+
+            let range = |first_index, last_index| {
+                Selection::IndexRange(DataSelectionRange {
+                    first_index,
+                    last_index,
+                })
+            };
+
+            // Column ranges are over display positions
+            assert_eq!(
+                export(DataSelectionKind::ColumnRange, range(0, 1)),
+                "c,a\na,1\nb,2\nc,3".to_string()
+            );
+
+            // Rows include all columns in display order
+            assert_eq!(
+                export(DataSelectionKind::RowRange, range(0, 0)),
+                "c,a,b\na,1,4".to_string()
+            );
+
+            // Column indices are table indices
+            let indices = Selection::Indices(DataSelectionIndices {
+                indices: vec![0, 2],
+            });
+            assert_eq!(
+                export(DataSelectionKind::ColumnIndices, indices),
+                "a,c\n1,a\n2,b\n3,c".to_string()
+            );
+
+            let cell = Selection::SingleCell(DataSelectionSingleCell {
+                row_index: 0,
+                column_index: 0,
+            });
+            assert_eq!(export(DataSelectionKind::SingleCell, cell), "1".to_string());
+        })
+    }
 }
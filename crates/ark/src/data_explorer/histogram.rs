@@ -0,0 +1,112 @@
+//
+// histogram.rs
+//
+// Copyright (C) 2024 by Posit Software, PBC
+//
+//
+
+use std::collections::HashMap;
+
+use amalthea::comm::data_explorer_comm::ColumnHistogram;
+use anyhow::anyhow;
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use harp::object::RObject;
+use libr::SEXP;
+
+use crate::modules::ARK_ENVS;
+
+/// Number of bins used when the frontend doesn't specify one
+pub const DEFAULT_NUM_BINS: i64 = 20;
+
+/// Columns with more values than this are binned over a sample
+pub const MAX_SAMPLE_SIZE: i32 = 100_000;
+
+/// Computes a histogram of a numeric column. The column should already be
+/// filtered, so that the histogram only reflects the rows that are shown.
+pub fn histogram(column: SEXP, num_bins: i64) -> anyhow::Result<ColumnHistogram> {
+    let num_bins = num_bins.clamp(1, i32::MAX as i64) as i32;
+
+    let out: HashMap<String, RObject> = RFunction::from("profile_histogram")
+        .add(column)
+        .add(num_bins)
+        .add(MAX_SAMPLE_SIZE)
+        .call_in(ARK_ENVS.positron_ns)?
+        .try_into()?;
+
+    let get = |name: &str| {
+        out.get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("Missing histogram field {name}"))
+    };
+
+    let bin_edges: Vec<f64> = get("bin_edges")?.try_into()?;
+    let bin_counts: Vec<i32> = get("bin_counts")?.try_into()?;
+    let sampled: bool = get("sampled")?.try_into()?;
+
+    let bin_counts: Vec<i64> = bin_counts.into_iter().map(|x| x as i64).collect();
+    let bin_width = match bin_edges.as_slice() {
+        [first, second, ..] => second - first,
+        _ => 0.0,
+    };
+
+    Ok(ColumnHistogram {
+        bin_sizes: bin_counts.clone(),
+        bin_width,
+        bin_edges: Some(bin_edges),
+        bin_counts: Some(bin_counts),
+        sampled: Some(sampled),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use harp::environment::R_ENVS;
+    use harp::eval::r_parse_eval0;
+
+    use super::*;
+    use crate::test::r_test;
+
+    #[test]
+    fn test_histogram() {
+        r_test(|| {
+            let column = r_parse_eval0("c(0, 1, 2, 3, 4, NA, Inf)", R_ENVS.global).unwrap();
+            let hist = histogram(column.sexp, 4).unwrap();
+            assert_eq!(hist.bin_edges, Some(vec![0.0, 1.0, 2.0, 3.0, 4.0]));
+            assert_eq!(hist.bin_counts, Some(vec![1, 1, 1, 2]));
+            assert_eq!(hist.bin_sizes, vec![1, 1, 1, 2]);
+            assert_eq!(hist.bin_width, 1.0);
+            assert_eq!(hist.sampled, Some(false));
+
+            let column = r_parse_eval0("c(5L, 5L, NA)", R_ENVS.global).unwrap();
+            let hist = histogram(column.sexp, 10).unwrap();
+            assert_eq!(hist.bin_edges, Some(vec![5.0, 5.0]));
+            assert_eq!(hist.bin_sizes, vec![2]);
+            assert_eq!(hist.bin_width, 0.0);
+
+            let column = r_parse_eval0("c(NA_real_)", R_ENVS.global).unwrap();
+            let hist = histogram(column.sexp, 10).unwrap();
+            assert_eq!(hist.bin_edges, Some(vec![]));
+            assert!(hist.bin_sizes.is_empty());
+            assert_eq!(hist.bin_width, 0.0);
+
+            let column = r_parse_eval0("c('a', 'b')", R_ENVS.global).unwrap();
+            assert!(histogram(column.sexp, 10).is_err());
+        })
+    }
+
+    #[test]
+    fn test_histogram_sampled() {
+        r_test(|| {
+            let code = format!("as.double(seq_len({}))", MAX_SAMPLE_SIZE * 2);
+            let column = r_parse_eval0(&code, R_ENVS.global).unwrap();
+            let hist = histogram(column.sexp, 2).unwrap();
+            assert_eq!(hist.sampled, Some(true));
+            assert_eq!(hist.bin_sizes.iter().sum::<i64>(), MAX_SAMPLE_SIZE as i64);
+
+            let bin_edges = hist.bin_edges.unwrap();
+            assert_eq!(bin_edges.first(), Some(&1.0));
+            assert_eq!(bin_edges.last(), Some(&((MAX_SAMPLE_SIZE * 2) as f64)));
+        })
+    }
+}
@@ -7,5 +7,6 @@
 
 pub mod export_selection;
 pub mod format;
+pub mod histogram;
 pub mod r_data_explorer;
 pub mod summary_stats;
@@ -11,6 +11,8 @@ use std::collections::HashMap;
 use amalthea::comm::comm_channel::CommMsg;
 use amalthea::comm::data_explorer_comm::BackendState;
 use amalthea::comm::data_explorer_comm::ColumnDisplayType;
+use amalthea::comm::data_explorer_comm::ColumnHistogram;
+use amalthea::comm::data_explorer_comm::ColumnLayout;
 use amalthea::comm::data_explorer_comm::ColumnProfileResult;
 use amalthea::comm::data_explorer_comm::ColumnProfileType;
 use amalthea::comm::data_explorer_comm::ColumnProfileTypeSupportStatus;
@@ -36,7 +38,11 @@ use amalthea::comm::data_explorer_comm::GetSchemaParams;
 use amalthea::comm::data_explorer_comm::RowFilter;
 use amalthea::comm::data_explorer_comm::RowFilterType;
 use amalthea::comm::data_explorer_comm::RowFilterTypeSupportStatus;
+use amalthea::comm::data_explorer_comm::SearchDataFeatures;
+use amalthea::comm::data_explorer_comm::SearchDataParams;
+use amalthea::comm::data_explorer_comm::SearchDataResult;
 use amalthea::comm::data_explorer_comm::SearchSchemaFeatures;
+use amalthea::comm::data_explorer_comm::SetColumnLayoutParams;
 use amalthea::comm::data_explorer_comm::SetRowFiltersFeatures;
 use amalthea::comm::data_explorer_comm::SetRowFiltersParams;
 use amalthea::comm::data_explorer_comm::SetSortColumnsFeatures;
@@ -56,6 +62,7 @@ use crossbeam::channel::Sender;
 use crossbeam::select;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
+use harp::object::r_null_or_try_into;
 use harp::object::RObject;
 use harp::r_symbol;
 use harp::tbl_get_column;
@@ -76,6 +83,8 @@ use uuid::Uuid;
 
 use crate::data_explorer::export_selection;
 use crate::data_explorer::format;
+use crate::data_explorer::histogram::histogram;
+use crate::data_explorer::histogram::DEFAULT_NUM_BINS;
 use crate::data_explorer::summary_stats::summary_stats;
 use crate::interface::RMain;
 use crate::lsp::events::EVENTS;
@@ -94,6 +103,9 @@ pub struct DataObjectEnvInfo {
     pub env: RThreadSafe<RObject>,
 }
 
+/// Number of rows scanned by each R task when searching data
+const SEARCH_DATA_CHUNK_SIZE: i32 = 10_000;
+
 struct DataObjectShape {
     pub columns: Vec<ColumnSchema>,
     pub num_rows: i32,
@@ -123,6 +135,23 @@ pub struct RDataExplorer {
     /// A cache containing the current set of row filters.
     row_filters: Vec<RowFilter>,
 
+    /// The display order of the columns, as indices into `shape.columns`,
+    /// with the pinned columns first. `None` when the columns are displayed
+    /// in their natural order.
+    ///
+    /// All requests refer to individual columns by their index in the table,
+    /// which doesn't depend on the layout. Only column ranges are over display
+    /// positions: the `start_index` and `num_columns` of schema requests, and
+    /// the column ranges of exported selections.
+    column_order: Option<Vec<i32>>,
+
+    /// The columns pinned to the left, in display order.
+    pinned_columns: Vec<i32>,
+
+    /// An event to send to the frontend after the reply to the request being
+    /// handled.
+    pending_event: Option<DataExplorerFrontendEvent>,
+
     /// The set of sorted row indices, if any sorts are applied. This always
     /// includes all row indices.
     sorted_indices: Option<Vec<i32>>,
@@ -185,6 +214,9 @@ impl RDataExplorer {
                         view_indices: None,
                         sort_keys: vec![],
                         row_filters: vec![],
+                        column_order: None,
+                        pinned_columns: vec![],
+                        pending_event: None,
                         comm,
                         comm_manager_tx,
                     };
@@ -282,6 +314,12 @@ impl RDataExplorer {
 
                     let comm = self.comm.clone();
                     comm.handle_request(msg, |req| self.handle_rpc(req));
+
+                    if let Some(event) = self.pending_event.take() {
+                        if let Err(err) = self.send_event(event) {
+                            log::error!("Data Viewer: Can't send event to frontend: {err:?}");
+                        }
+                    }
                 }
             }
         }
@@ -351,6 +389,20 @@ impl RDataExplorer {
         // Generate the appropriate event based on whether the schema has
         // changed
         let event = if self.shape.columns != new_shape.columns {
+            // The column layout only makes sense for the same set of columns.
+            // It survives changes of column types, but not added, removed,
+            // or renamed columns.
+            let same_columns = self.shape.columns.len() == new_shape.columns.len() &&
+                self.shape
+                    .columns
+                    .iter()
+                    .zip(new_shape.columns.iter())
+                    .all(|(old, new)| old.column_name == new.column_name);
+            if !same_columns {
+                self.column_order = None;
+                self.pinned_columns.clear();
+            }
+
             // Columns changed, so update our cache, and we need to send a
             // schema update event
             self.shape = new_shape;
@@ -383,10 +435,15 @@ impl RDataExplorer {
             DataExplorerFrontendEvent::DataUpdate
         };
 
+        self.send_event(event)?;
+        Ok(true)
+    }
+
+    fn send_event(&self, event: DataExplorerFrontendEvent) -> anyhow::Result<()> {
         self.comm
             .outgoing_tx
             .send(CommMsg::Data(serde_json::to_value(event)?))?;
-        Ok(true)
+        Ok(())
     }
 
     // Marks row_filters as invalid if the column no longer exists
@@ -477,6 +534,21 @@ impl RDataExplorer {
 
                 Ok(DataExplorerBackendReply::SetSortColumnsReply())
             },
+            DataExplorerBackendRequest::SetColumnLayout(SetColumnLayoutParams {
+                column_order,
+                pinned_columns,
+            }) => {
+                let num_columns = self.shape.columns.len() as i32;
+                let pinned_columns = column_indices_checked(num_columns, &pinned_columns)?;
+                self.column_order =
+                    column_display_order(num_columns, &column_order, &pinned_columns)?;
+                self.pinned_columns = pinned_columns;
+
+                // The frontend needs to fetch the schema in the new order
+                self.pending_event = Some(DataExplorerFrontendEvent::SchemaUpdate);
+
+                Ok(DataExplorerBackendReply::SetColumnLayoutReply())
+            },
             DataExplorerBackendRequest::SetRowFilters(SetRowFiltersParams { filters }) => {
                 // Save the new row filters
                 self.row_filters = filters;
@@ -546,6 +618,30 @@ impl RDataExplorer {
                                 frequency_table: None,
                             }
                         },
+                        ColumnProfileType::Histogram => {
+                            let num_bins = match request.histogram_params {
+                                Some(params) => params.num_bins,
+                                None => DEFAULT_NUM_BINS,
+                            };
+                            let histogram =
+                                r_task(|| self.r_histogram(request.column_index as i32, num_bins));
+                            ColumnProfileResult {
+                                null_count: None,
+                                summary_stats: None,
+                                histogram: match histogram {
+                                    Err(err) => {
+                                        log::error!(
+                                            "Error getting histogram for column {}: {}",
+                                            request.column_index,
+                                            err
+                                        );
+                                        None
+                                    },
+                                    Ok(histogram) => Some(histogram),
+                                },
+                                frequency_table: None,
+                            }
+                        },
                         _ => {
                             // Other kinds of column profiles are not yet
                             // implemented in R
@@ -564,6 +660,11 @@ impl RDataExplorer {
             DataExplorerBackendRequest::SearchSchema(_) => {
                 bail!("Data Viewer: Not yet implemented")
             },
+            DataExplorerBackendRequest::SearchData(params) => {
+                let row_index = self.search_data(&params)?;
+                let result = SearchDataResult { row_index };
+                Ok(DataExplorerBackendReply::SearchDataReply(result))
+            },
             DataExplorerBackendRequest::ExportDataSelection(ExportDataSelectionParams {
                 selection,
                 format,
@@ -575,6 +676,42 @@ impl RDataExplorer {
             )),
         }
     }
+
+    /// Find the first row at or after `start_index`, in the current sort
+    /// order, with a cell value matching the search term. Returns the row
+    /// index in view coordinates.
+    ///
+    /// Rows are scanned in chunks, each in its own R task, so that searching
+    /// a very large table doesn't block the R thread for the whole duration.
+    /// Console input and other tasks get a chance to run between chunks.
+    fn search_data(&self, params: &SearchDataParams) -> anyhow::Result<Option<i64>> {
+        let num_rows = match self.view_indices {
+            Some(ref indices) => indices.len() as i32,
+            None => self.shape.num_rows,
+        };
+        let start = (params.start_index.max(0) as i32).min(num_rows);
+
+        let mut ranges = vec![(start, num_rows)];
+        if params.wrap_around {
+            ranges.push((0, start));
+        }
+
+        for (from, to) in ranges {
+            let mut chunk_start = from;
+            while chunk_start < to {
+                let chunk_end = cmp::min(chunk_start + SEARCH_DATA_CHUNK_SIZE, to);
+
+                let found = r_task(|| self.r_search_rows(chunk_start, chunk_end, params))?;
+                if let Some(offset) = found {
+                    return Ok(Some((chunk_start + offset) as i64));
+                }
+
+                chunk_start = chunk_end;
+            }
+        }
+
+        Ok(None)
+    }
 }
 
 // Methods that must be run on the main R thread
@@ -613,6 +750,13 @@ impl RDataExplorer {
                 let type_name = WorkspaceVariableDisplayType::from(col, false).display_type;
                 let type_display = display_type(col);
 
+                // Datetimes are formatted in their own timezone, or in the
+                // session's one if they don't have any
+                let timezone = match type_display {
+                    ColumnDisplayType::Datetime => column_timezone(col)?,
+                    _ => None,
+                };
+
                 column_schemas.push(ColumnSchema {
                     column_name,
                     column_index: i as i64,
@@ -622,7 +766,7 @@ impl RDataExplorer {
                     children: None,
                     precision: None,
                     scale: None,
-                    timezone: None,
+                    timezone,
                     type_size: None,
                 });
             }
@@ -674,6 +818,16 @@ impl RDataExplorer {
         Ok(summary_stats(filtered_column.sexp, dtype, format_options))
     }
 
+    /// Compute a histogram of a numeric column, e.g. for a sparkline.
+    ///
+    /// Like summary stats, the histogram only covers the filtered rows.
+    fn r_histogram(&self, column_index: i32, num_bins: i64) -> anyhow::Result<ColumnHistogram> {
+        let column = tbl_get_column(self.table.get().sexp, column_index, self.shape.kind)?;
+        let filtered_column = r_filter_indices(column, &self.filtered_indices)?;
+
+        histogram(filtered_column.sexp, num_bins)
+    }
+
     /// Sort the rows of the data object according to the sort keys in
     /// self.sort_keys.
     ///
@@ -891,9 +1045,28 @@ impl RDataExplorer {
         let lower_bound = cmp::min(start_index, total_num_columns);
         let upper_bound = cmp::min(total_num_columns, start_index + num_columns);
 
-        // Return the schema for the requested columns
+        // Return the schema for the requested columns, in display order
+        let range = lower_bound as usize..upper_bound as usize;
+        let columns = match self.column_order {
+            Some(ref order) => order[range]
+                .iter()
+                .map(|index| self.shape.columns[*index as usize].clone())
+                .collect(),
+            None => self.shape.columns[range].to_vec(),
+        };
+
+        let column_layout = self.column_order.as_ref().map(|order| ColumnLayout {
+            column_order: order.iter().map(|index| *index as i64).collect(),
+            pinned_columns: self
+                .pinned_columns
+                .iter()
+                .map(|index| *index as i64)
+                .collect(),
+        });
+
         let response = TableSchema {
-            columns: self.shape.columns[lower_bound as usize..upper_bound as usize].to_vec(),
+            columns,
+            column_layout,
         };
 
         Ok(DataExplorerBackendReply::GetSchemaReply(response))
@@ -930,11 +1103,18 @@ impl RDataExplorer {
                             profile_type: ColumnProfileType::SummaryStats,
                             support_status: SupportStatus::Experimental,
                         },
+                        ColumnProfileTypeSupportStatus {
+                            profile_type: ColumnProfileType::Histogram,
+                            support_status: SupportStatus::Experimental,
+                        },
                     ],
                 },
                 search_schema: SearchSchemaFeatures {
                     support_status: SupportStatus::Unsupported,
                 },
+                search_data: SearchDataFeatures {
+                    support_status: SupportStatus::Supported,
+                },
                 set_row_filters: SetRowFiltersFeatures {
                     support_status: SupportStatus::Supported,
                     supported_types: vec![
@@ -948,6 +1128,7 @@ impl RDataExplorer {
                         RowFilterType::NotEmpty,
                         RowFilterType::NotNull,
                         RowFilterType::Search,
+                        RowFilterType::SetMembership,
                     ]
                     .iter()
                     .map(|row_filter_type| RowFilterTypeSupportStatus {
@@ -1050,6 +1231,42 @@ impl RDataExplorer {
         Ok(DataExplorerBackendReply::GetDataValuesReply(response))
     }
 
+    /// Search the view rows from `lower_bound` (inclusive) to `upper_bound`
+    /// (exclusive) for a cell value matching the search term. Returns the
+    /// offset of the first matching row from `lower_bound`.
+    fn r_search_rows(
+        &self,
+        lower_bound: i32,
+        upper_bound: i32,
+        params: &SearchDataParams,
+    ) -> anyhow::Result<Option<i32>> {
+        let row_indices: Vec<i32> = match &self.view_indices {
+            Some(indices) => indices[lower_bound as usize..upper_bound as usize].to_vec(),
+            None => ((lower_bound + 1)..(upper_bound + 1)).collect(),
+        };
+
+        let total_num_cols = self.shape.columns.len() as i32;
+        let col_indices: Vec<i32> = match params.column_index {
+            Some(index) if index < 0 || index >= total_num_cols as i64 => {
+                bail!("Column index {index} is out of bounds")
+            },
+            Some(index) => vec![index as i32 + 1],
+            None => (1..(total_num_cols + 1)).collect(),
+        };
+
+        let position: i32 = RFunction::new("", ".ps.search_rows")
+            .add(self.table.get().sexp)
+            .add(RObject::try_from(&row_indices)?)
+            .add(RObject::try_from(&col_indices)?)
+            .add(params.search_term.as_str())
+            .add(params.case_sensitive)
+            .call_in(ARK_ENVS.positron_ns)?
+            .try_into()?;
+
+        // The R helper returns a 1-based position, or 0 if nothing matched
+        Ok((position > 0).then_some(position - 1))
+    }
+
     fn r_export_data_selection(
         &self,
         selection: DataSelection,
@@ -1059,6 +1276,7 @@ impl RDataExplorer {
             export_selection::export_selection(
                 self.table.get().sexp,
                 self.view_indices.clone(),
+                self.column_order.clone(),
                 selection,
                 format,
             )
@@ -1108,6 +1326,9 @@ fn display_type(x: SEXP) -> ColumnDisplayType {
         if r_inherits(x, "POSIXlt") {
             return ColumnDisplayType::Datetime;
         }
+        if r_inherits(x, "difftime") {
+            return ColumnDisplayType::Number;
+        }
 
         // TODO: vctrs's list_of
         if r_inherits(x, "list") {
@@ -1127,6 +1348,66 @@ fn display_type(x: SEXP) -> ColumnDisplayType {
     }
 }
 
+fn column_timezone(x: SEXP) -> anyhow::Result<Option<String>> {
+    let timezone = RFunction::from("summary_stats_get_timezone")
+        .add(x)
+        .call_in(ARK_ENVS.positron_ns)?;
+    Ok(r_null_or_try_into(timezone)?)
+}
+
+/// Computes the display order of the columns from the order requested by the
+/// frontend and the pinned columns. Pinned columns come first, followed by
+/// the other columns in the requested order, or in their natural order if
+/// `column_order` is empty.
+///
+/// Returns `None` if the columns are displayed in their natural order.
+fn column_display_order(
+    num_columns: i32,
+    column_order: &[i64],
+    pinned_columns: &[i32],
+) -> anyhow::Result<Option<Vec<i32>>> {
+    let column_order = if column_order.is_empty() {
+        (0..num_columns).collect()
+    } else {
+        let column_order = column_indices_checked(num_columns, column_order)?;
+        if column_order.len() != num_columns as usize {
+            bail!("Column order must include all {num_columns} columns");
+        }
+        column_order
+    };
+
+    let mut display_order = pinned_columns.to_vec();
+    display_order.extend(
+        column_order
+            .into_iter()
+            .filter(|index| !pinned_columns.contains(index)),
+    );
+
+    if pinned_columns.is_empty() && display_order.iter().copied().eq(0..num_columns) {
+        return Ok(None);
+    }
+    Ok(Some(display_order))
+}
+
+/// Checks that column indices sent by the frontend are in bounds and unique.
+fn column_indices_checked(num_columns: i32, indices: &[i64]) -> anyhow::Result<Vec<i32>> {
+    let mut seen = vec![false; num_columns as usize];
+    let mut out = Vec::with_capacity(indices.len());
+
+    for &index in indices {
+        if index < 0 || index >= num_columns as i64 {
+            bail!("Column index {index} is out of bounds");
+        }
+        if seen[index as usize] {
+            bail!("Column index {index} is listed more than once");
+        }
+        seen[index as usize] = true;
+        out.push(index as i32);
+    }
+
+    Ok(out)
+}
+
 fn table_info_or_bail(x: SEXP) -> anyhow::Result<TableInfo> {
     harp::table_info(x).ok_or(anyhow!("Unsupported type for data viewer"))
 }
@@ -5,6 +5,7 @@
 //
 //
 
+use amalthea::wire::exception::Exception;
 use harp::object::RObject;
 use harp::r_symbol;
 use harp::session::r_format_traceback;
@@ -20,13 +21,23 @@ use stdext::unwrap;
 use crate::interface::RMain;
 
 #[harp::register]
-unsafe extern "C" fn ps_record_error(evalue: SEXP, traceback: SEXP) -> anyhow::Result<SEXP> {
+unsafe extern "C" fn ps_record_error(
+    ename: SEXP,
+    evalue: SEXP,
+    traceback: SEXP,
+) -> anyhow::Result<SEXP> {
     let main = RMain::get_mut();
 
     // Convert to `RObject` for access to `try_from()` / `try_into()` methods.
+    let ename = RObject::new(ename);
     let evalue = RObject::new(evalue);
     let traceback = RObject::new(traceback);
 
+    let ename: String = unwrap!(ename.try_into(), Err(error) => {
+        warn!("Can't convert `ename` to a Rust string: {}.", error);
+        "".to_string()
+    });
+
     let evalue: String = unwrap!(evalue.try_into(), Err(error) => {
         warn!("Can't convert `evalue` to a Rust string: {}.", error);
         "".to_string()
@@ -38,6 +49,7 @@ unsafe extern "C" fn ps_record_error(evalue: SEXP, traceback: SEXP) -> anyhow::R
     });
 
     main.error_occurred = true;
+    main.error_name = ename;
     main.error_message = evalue;
     main.error_traceback = traceback;
 
@@ -67,3 +79,208 @@ unsafe extern "C" fn ps_rust_backtrace() -> anyhow::Result<SEXP> {
     let trace = format!("{trace}");
     Ok(*RObject::from(trace))
 }
+
+/// Returns a copy of `exception` without ANSI escape sequences, for the
+/// structured `execute_reply`. Frontends style these fields themselves,
+/// whereas the `error` message on IOPub keeps the colours.
+pub fn strip_ansi_exception(exception: &Exception) -> Exception {
+    Exception {
+        ename: strip_ansi(&exception.ename),
+        evalue: strip_ansi(&exception.evalue),
+        traceback: exception.traceback.iter().map(|x| strip_ansi(x)).collect(),
+    }
+}
+
+/// Removes ANSI escape sequences from `text`, as emitted by cli or crayon:
+/// CSI sequences (e.g. colours, `ESC [ 31 m`), OSC sequences (e.g.
+/// hyperlinks, `ESC ] 8 ; ; url ESC \`), and other two-character escapes.
+///
+/// Works on chars rather than bytes, so multibyte UTF-8 text is never split.
+pub fn strip_ansi(text: &str) -> String {
+    const ESC: char = '\u{1b}';
+    const BEL: char = '\u{07}';
+    const CSI: char = '\u{9b}';
+
+    // Fast path for the common case
+    if !text.contains([ESC, CSI]) {
+        return text.to_string();
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            CSI => skip_csi(&mut chars, &mut out),
+            ESC => match chars.next() {
+                Some('[') => skip_csi(&mut chars, &mut out),
+                Some(']') => {
+                    // OSC, terminated by BEL or ST (`ESC \`)
+                    while let Some(c) = chars.next() {
+                        if c == BEL {
+                            break;
+                        }
+                        if c == ESC && chars.peek() == Some(&'\\') {
+                            chars.next();
+                            break;
+                        }
+                    }
+                },
+                // Two-character escape, e.g. `ESC =`
+                Some(_) | None => {},
+            },
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// Skips the parameter and intermediate characters of a CSI sequence, and
+/// its final character in `@` to `~`.
+fn skip_csi(chars: &mut impl Iterator<Item = char>, out: &mut String) {
+    for c in chars {
+        if ('@'..='~').contains(&c) {
+            return;
+        }
+        if !(' '..='?').contains(&c) {
+            // Malformed sequence; keep the text that follows it
+            out.push(c);
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amalthea::wire::exception::Exception;
+    use harp::eval::r_parse_eval0;
+
+    use crate::errors::strip_ansi;
+    use crate::errors::strip_ansi_exception;
+
+    use crate::modules::ARK_ENVS;
+    use crate::test::r_test;
+
+    // Returns the error info of an error thrown inside nested functions
+    const NESTED_ERROR_INFO: &str = r#"local({
+        f <- function() g()
+        g <- function() h()
+        h <- function() stop("boom")
+
+        info <- NULL
+        try(silent = TRUE, withCallingHandlers(
+            f(),
+            error = function(cnd) info <<- error_info_base(cnd, sys.calls())
+        ))
+        info
+    })"#;
+
+    #[test]
+    fn test_error_info_nested_traceback() {
+        r_test(|| {
+            let field = |name: &str| {
+                r_parse_eval0(&format!("{NESTED_ERROR_INFO}${name}"), ARK_ENVS.positron_ns).unwrap()
+            };
+
+            let ename: String = field("ename").try_into().unwrap();
+            assert_eq!(ename, "RuntimeError");
+
+            // The raw message is preserved
+            let evalue: String = field("evalue").try_into().unwrap();
+            assert_eq!(evalue, "Error:\nboom");
+
+            let traceback: Vec<String> = field("traceback").try_into().unwrap();
+            assert!(!traceback.is_empty());
+            assert!(traceback.iter().any(|call| call.contains("h()")));
+        })
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("\x1b[31mboom\x1b[39m"), "boom");
+        assert_eq!(strip_ansi("plain"), "plain");
+
+        // Multibyte characters are preserved
+        assert_eq!(strip_ansi("\x1b[1;38;5;196mé€😀\x1b[0m ok"), "é€😀 ok");
+
+        // Hyperlinks
+        assert_eq!(
+            strip_ansi("\x1b]8;;https://r-lib.org\x1b\\link\x1b]8;;\x1b\\"),
+            "link"
+        );
+        assert_eq!(strip_ansi("a\x1b]8;;url\x07b"), "ab");
+
+        // Truncated sequences
+        assert_eq!(strip_ansi("end\x1b"), "end");
+        assert_eq!(strip_ansi("end\x1b[1"), "end");
+    }
+
+    #[test]
+    fn test_strip_ansi_rlang_error() {
+        r_test(|| {
+            let installed: bool = r_parse_eval0(
+                "requireNamespace('rlang', quietly = TRUE) && requireNamespace('cli', quietly = TRUE)",
+                ARK_ENVS.positron_ns,
+            )
+            .unwrap()
+            .try_into()
+            .unwrap();
+            if !installed {
+                return;
+            }
+
+            let message: String = r_parse_eval0(
+                r#"local({
+                    local_options <- options(cli.num_colors = 256)
+                    on.exit(options(local_options))
+                    cnd <- tryCatch(
+                        rlang::abort(c(cli::col_red("boom"), i = cli::style_bold("hint"))),
+                        error = identity
+                    )
+                    rlang::cnd_message(cnd, prefix = TRUE)
+                })"#,
+                ARK_ENVS.positron_ns,
+            )
+            .unwrap()
+            .try_into()
+            .unwrap();
+            assert!(message.contains('\x1b'));
+
+            let exception = Exception {
+                ename: String::from("rlang_error"),
+                evalue: message.clone(),
+                traceback: vec![message],
+            };
+            let stripped = strip_ansi_exception(&exception);
+            assert!(!stripped.evalue.contains('\x1b'));
+            assert!(stripped.evalue.contains("boom"));
+            assert!(stripped.evalue.contains("hint"));
+            assert_eq!(stripped.traceback, vec![stripped.evalue.clone()]);
+        })
+    }
+
+    #[test]
+    fn test_error_name() {
+        r_test(|| {
+            let name = |code: &str| -> String {
+                let code = format!("error_name(tryCatch({code}, error = identity))");
+                r_parse_eval0(&code, ARK_ENVS.positron_ns)
+                    .unwrap()
+                    .try_into()
+                    .unwrap()
+            };
+
+            assert_eq!(name("stop('foo')"), "RuntimeError");
+            assert_eq!(name("loadNamespace('notapackage')"), "ModuleNotFoundError");
+            assert_eq!(
+                name("stop(errorCondition('foo', class = c('my_error', 'parent_error')))"),
+                "my_error/parent_error"
+            );
+            assert_eq!(
+                name("stop(structure(class = c('error', 'condition'), list(message = 'foo')))"),
+                "Error"
+            );
+        })
+    }
+}
@@ -0,0 +1,216 @@
+//
+// markdown.rs
+//
+// Copyright (C) 2024 by Posit Software, PBC
+//
+//
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use harp::object::r_null_or_try_into;
+use once_cell::sync::Lazy;
+
+/// Names of the help pages documenting a topic, keyed by package and topic.
+/// Aliases of a page all resolve to the same name.
+static TOPIC_PAGES: Lazy<Mutex<HashMap<(String, String), String>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Help pages rendered as Markdown, keyed by package, page name, and whether
+/// only the summary was rendered
+static PAGE_CACHE: Lazy<Mutex<HashMap<(String, String, bool), String>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Modification times of the help index of the packages in the caches, which
+/// change when a package is reinstalled
+static PACKAGE_STAMPS: Lazy<Mutex<HashMap<String, f64>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Renders the help page documenting `topic` in `package` as Markdown, from
+/// the package's Rd database. Returns `None` if there is no such topic.
+///
+/// Used by the Help pane to show documentation without going through the
+/// HTML help server. Aliases are resolved to the page that documents them,
+/// which is only rendered once for all of its aliases.
+///
+/// Must be called on the R thread.
+pub fn render_topic_markdown(package: &str, topic: &str) -> anyhow::Result<Option<String>> {
+    render_topic(package, topic, false)
+}
+
+/// Like `render_topic_markdown()` but only renders the Description, Usage,
+/// and Arguments sections. Used for hovers, where the full page would be too
+/// long.
+///
+/// Must be called on the R thread.
+pub fn render_topic_summary_markdown(package: &str, topic: &str) -> anyhow::Result<Option<String>> {
+    render_topic(package, topic, true)
+}
+
+fn render_topic(package: &str, topic: &str, summary: bool) -> anyhow::Result<Option<String>> {
+    check_package_stamp(package)?;
+
+    let Some(page) = resolve_topic(package, topic)? else {
+        return Ok(None);
+    };
+
+    let key = (package.to_string(), page, summary);
+    if let Some(markdown) = PAGE_CACHE.lock().unwrap().get(&key) {
+        return Ok(Some(markdown.clone()));
+    }
+
+    let markdown: String = RFunction::from(".ps.help.rdMarkdown")
+        .param("package", package)
+        .param("name", key.1.as_str())
+        .param("summary", summary)
+        .call()?
+        .try_into()?;
+
+    PAGE_CACHE.lock().unwrap().insert(key, markdown.clone());
+    Ok(Some(markdown))
+}
+
+/// Finds the attached package documenting `topic`, looking through the
+/// packages in the same order as `help()`. Returns `None` if no attached
+/// package documents `topic`.
+///
+/// Must be called on the R thread.
+pub fn topic_package(topic: &str) -> anyhow::Result<Option<String>> {
+    r_null_or_try_into(
+        RFunction::from(".ps.help.topicPackage")
+            .param("topic", topic)
+            .call()?,
+    )
+}
+
+/// Forgets the cached help of `package`. Called when a package is loaded,
+/// since it may have been reinstalled or be a development version.
+pub fn invalidate_package(package: &str) {
+    TOPIC_PAGES
+        .lock()
+        .unwrap()
+        .retain(|(cached, _), _| cached != package);
+    PAGE_CACHE
+        .lock()
+        .unwrap()
+        .retain(|(cached, _, _), _| cached != package);
+    PACKAGE_STAMPS.lock().unwrap().remove(package);
+}
+
+/// Invalidates the cached help of `package` if it was reinstalled since it
+/// was cached, which doesn't require reloading the package
+fn check_package_stamp(package: &str) -> anyhow::Result<()> {
+    let stamp: Option<f64> = r_null_or_try_into(
+        RFunction::from(".ps.help.rdStamp")
+            .param("package", package)
+            .call()?,
+    )?;
+
+    let cached = PACKAGE_STAMPS.lock().unwrap().get(package).copied();
+    if cached != stamp {
+        invalidate_package(package);
+        if let Some(stamp) = stamp {
+            PACKAGE_STAMPS
+                .lock()
+                .unwrap()
+                .insert(package.to_string(), stamp);
+        }
+    }
+
+    Ok(())
+}
+
+fn resolve_topic(package: &str, topic: &str) -> anyhow::Result<Option<String>> {
+    let key = (package.to_string(), topic.to_string());
+    if let Some(page) = TOPIC_PAGES.lock().unwrap().get(&key) {
+        return Ok(Some(page.clone()));
+    }
+
+    let page: Option<String> = r_null_or_try_into(
+        RFunction::from(".ps.help.resolveRdTopic")
+            .param("package", package)
+            .param("topic", topic)
+            .call()?,
+    )?;
+
+    if let Some(page) = &page {
+        TOPIC_PAGES.lock().unwrap().insert(key, page.clone());
+    }
+
+    Ok(page)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::help::markdown::invalidate_package;
+    use crate::help::markdown::render_topic_markdown;
+    use crate::help::markdown::render_topic_summary_markdown;
+    use crate::help::markdown::resolve_topic;
+    use crate::help::markdown::topic_package;
+    use crate::help::markdown::PAGE_CACHE;
+    use crate::test::r_test;
+
+    #[test]
+    fn test_render_topic_markdown() {
+        r_test(|| {
+            let markdown = render_topic_markdown("base", "paste").unwrap().unwrap();
+            assert!(markdown.starts_with("## Concatenate Strings"));
+            assert!(markdown.contains("### Usage\n\n```r\npaste"));
+            assert!(markdown.contains("`sep`\n:   "));
+            assert!(markdown.contains("### Examples"));
+
+            // Aliases resolve to the page documenting them
+            assert_eq!(resolve_topic("base", "paste0").unwrap().unwrap(), "paste");
+            let alias = render_topic_markdown("base", "paste0").unwrap().unwrap();
+            assert_eq!(alias, markdown);
+
+            assert!(render_topic_markdown("base", "not_a_topic")
+                .unwrap()
+                .is_none());
+            assert!(render_topic_markdown("notapackage", "paste")
+                .unwrap()
+                .is_none());
+        })
+    }
+
+    #[test]
+    fn test_render_topic_summary_markdown() {
+        r_test(|| {
+            let markdown = render_topic_summary_markdown("base", "paste")
+                .unwrap()
+                .unwrap();
+            assert!(markdown.starts_with("## Concatenate Strings"));
+            assert!(markdown.contains("### Description"));
+            assert!(markdown.contains("### Usage"));
+            assert!(markdown.contains("### Arguments"));
+            assert!(!markdown.contains("### Details"));
+            assert!(!markdown.contains("### Examples"));
+
+            // Cached separately from the full page
+            let full = render_topic_markdown("base", "paste").unwrap().unwrap();
+            assert!(full.contains("### Examples"));
+        })
+    }
+
+    #[test]
+    fn test_topic_package() {
+        r_test(|| {
+            assert_eq!(topic_package("paste").unwrap(), Some(String::from("base")));
+            assert_eq!(topic_package("not_a_topic").unwrap(), None);
+        })
+    }
+
+    #[test]
+    fn test_invalidate_package() {
+        r_test(|| {
+            let key = (String::from("utils"), String::from("head"), false);
+
+            render_topic_markdown("utils", "head").unwrap().unwrap();
+            assert!(PAGE_CACHE.lock().unwrap().contains_key(&key));
+
+            invalidate_package("utils");
+            assert!(!PAGE_CACHE.lock().unwrap().contains_key(&key));
+        })
+    }
+}
@@ -5,17 +5,23 @@
 //
 //
 
+use serde::Deserialize;
+use serde::Serialize;
+
 /**
- * Enum representing events for the Help thread from other threads.
+ * Enum representing requests to the Help thread from other threads. These
+ * are sent over the help comm with `CommSocket::notify()`.
  */
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "method", content = "params")]
 pub enum HelpEvent {
     /// Event to show the given URL to the user in the Help pane. Accomplished by
     /// forwarding the URL on to the frontend using `HelpFrontendEvent::ShowHelp`.
+    #[serde(rename = "show_help_url")]
     ShowHelpUrl(ShowHelpUrlParams),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ShowHelpUrlParams {
     /// Url to attempt to show.
     pub url: String,
@@ -5,5 +5,6 @@
 //
 //
 
+pub mod markdown;
 pub mod message;
 pub mod r_help;
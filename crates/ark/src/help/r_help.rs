@@ -13,9 +13,6 @@ use amalthea::comm::help_comm::ShowHelpKind;
 use amalthea::comm::help_comm::ShowHelpParams;
 use amalthea::socket::comm::CommSocket;
 use anyhow::anyhow;
-use crossbeam::channel::Receiver;
-use crossbeam::channel::Sender;
-use crossbeam::select;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
 use log::info;
@@ -23,6 +20,7 @@ use log::trace;
 use log::warn;
 use stdext::spawn;
 
+use crate::help::markdown::render_topic_markdown;
 use crate::help::message::HelpEvent;
 use crate::help::message::ShowHelpUrlParams;
 use crate::r_task;
@@ -35,41 +33,31 @@ pub struct RHelp {
     comm: CommSocket,
     r_port: u16,
     proxy_port: u16,
-    help_event_rx: Receiver<HelpEvent>,
 }
 
 impl RHelp {
     /**
-     * Start the help handler. Returns a channel for sending help events to
-     * the help thread.
+     * Start the help handler. Other threads send help events to the help
+     * thread with `comm.notify()`.
      *
      * - `comm`: The socket for communicating with the frontend.
      * - `r_port`: The R help server port.
      * - `proxy_port`: Our proxy help server port.
      */
-    pub fn start(
-        comm: CommSocket,
-        r_port: u16,
-        proxy_port: u16,
-    ) -> anyhow::Result<Sender<HelpEvent>> {
-        // Create the channel that will be used to send help events from other threads.
-        let (help_event_tx, help_event_rx) = crossbeam::channel::unbounded();
-
-        // Start the help thread and wait for requests from the frontend or events
-        // from another thread.
+    pub fn start(comm: CommSocket, r_port: u16, proxy_port: u16) -> anyhow::Result<()> {
+        // Start the help thread and wait for requests from the frontend or
+        // events from another thread.
         spawn!("ark-help", move || {
             let help = Self {
                 comm,
                 r_port,
                 proxy_port,
-                help_event_rx,
             };
 
             help.execution_thread();
         });
 
-        // Return the channel for sending help events to the help thread
-        Ok(help_event_tx)
+        Ok(())
     }
 
     /// Public associated function so that callers of `start()` can cheaply check if
@@ -90,44 +78,25 @@ impl RHelp {
      */
     fn execution_thread(&self) {
         loop {
-            // Wait for either a message from the frontend or a help event
-            // from another thread.
-            select! {
-                // A message from the frontend; typically a request to show
-                // help for a specific topic.
-                recv(&self.comm.incoming_rx) -> msg => {
-                    match msg {
-                        Ok(msg) => {
-                            if !self.handle_comm_message(msg) {
-                                info!("Help comm {} closing by request from frontend.", self.comm.comm_id);
-                                break;
-                            }
-                        },
-                        Err(err) => {
-                            // The connection with the frontend has been closed; let
-                            // the thread exit.
-                            warn!("Error receiving message from frontend: {:?}", err);
-                            break;
-                        },
+            // Wait for a message, either from the frontend (typically a
+            // request to show help for a specific topic) or from another
+            // thread (typically notifying us that a help URL is ready for
+            // viewing).
+            match self.comm.incoming_rx.recv() {
+                Ok(msg) => {
+                    if !self.handle_comm_message(msg) {
+                        info!(
+                            "Help comm {} closing by request from frontend.",
+                            self.comm.comm_id
+                        );
+                        break;
                     }
                 },
-
-                // A message from another thread, typically notifying us that a
-                // help URL is ready for viewing.
-                recv(&self.help_event_rx) -> msg => {
-                    match msg {
-                        Ok(msg) => {
-                            if let Err(err) = self.handle_event(msg) {
-                                log::error!("Error handling Help event: {:?}", err);
-                            }
-                        },
-                        Err(err) => {
-                            // The connection with the frontend has been closed; let
-                            // the thread exit.
-                            log::error!("Error receiving internal Help message: {:?}", err);
-                            break;
-                        },
-                    }
+                Err(err) => {
+                    // The connection with the frontend has been closed; let
+                    // the thread exit.
+                    warn!("Error receiving message from frontend: {:?}", err);
+                    break;
                 },
             }
         }
@@ -146,6 +115,15 @@ impl RHelp {
             return false;
         }
 
+        // Requests from other threads are tagged with their own methods
+        if let CommMsg::Rpc(_, ref data) = message {
+            if serde_json::from_value::<HelpEvent>(data.clone()).is_ok() {
+                self.comm
+                    .handle_request(message, |req| self.handle_event(req));
+                return true;
+            }
+        }
+
         if self
             .comm
             .handle_request(message, |req| self.handle_rpc(req))
@@ -167,6 +145,10 @@ impl RHelp {
                     Err(err) => Err(err),
                 }
             },
+            HelpBackendRequest::RenderTopicMarkdown(params) => {
+                let markdown = r_task(|| render_topic_markdown(&params.package, &params.topic))?;
+                Ok(HelpBackendReply::RenderTopicMarkdownReply(markdown))
+            },
         }
     }
 
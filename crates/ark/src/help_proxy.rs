@@ -5,7 +5,11 @@
 //
 //
 
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::SocketAddr;
 use std::net::TcpListener;
+use std::sync::atomic::Ordering;
 
 use actix_web::get;
 use actix_web::http::header::ContentType;
@@ -17,14 +21,27 @@ use actix_web::HttpServer;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
 use mime_guess::from_path;
+use once_cell::sync::Lazy;
+use regex::Captures;
+use regex::Regex;
 use rust_embed::RustEmbed;
 use serde::Deserialize;
 use stdext::spawn;
 use stdext::unwrap;
 use url::Url;
 
+use crate::browser;
+use crate::html_widget;
 use crate::r_task;
 
+// The address the help proxy binds to when `ARK_HELP_PROXY_ADDRESS` is unset.
+// We only ever want to serve help locally by default.
+const DEFAULT_BIND_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+// The number of OS-assigned ports we try before giving up, after the
+// preferred port (if any) was found to be taken.
+const MAX_BIND_ATTEMPTS: usize = 5;
+
 // Embed `resources/help/` which is where replacement resources can be found.
 #[derive(RustEmbed)]
 #[folder = "resources/help/"]
@@ -37,10 +54,14 @@ struct PreviewRdParams {
 
 // Starts the help proxy.
 pub fn start(target_port: u16) -> anyhow::Result<u16> {
-    let source_port = HelpProxy::get_os_assigned_port()?;
+    let listener = HelpProxy::bind(bind_address(), preferred_port())?;
+    let source_port = listener.local_addr()?.port();
+
+    // Remember the port so that a restarted proxy can try to reuse it.
+    browser::PORT.store(source_port, Ordering::Relaxed);
 
     spawn!("ark-help-proxy", move || {
-        match task(source_port, target_port) {
+        match task(listener, target_port) {
             Ok(value) => log::info!("Help proxy server exited with value: {:?}", value),
             Err(error) => log::error!("Help proxy server exited unexpectedly: {}", error),
         }
@@ -49,42 +70,84 @@ pub fn start(target_port: u16) -> anyhow::Result<u16> {
     Ok(source_port)
 }
 
+// Returns the address to bind the help proxy to. Defaults to loopback, and can
+// be overridden with the `ARK_HELP_PROXY_ADDRESS` environment variable.
+fn bind_address() -> IpAddr {
+    let Ok(address) = std::env::var("ARK_HELP_PROXY_ADDRESS") else {
+        return DEFAULT_BIND_ADDRESS;
+    };
+
+    match address.parse::<IpAddr>() {
+        Ok(address) => address,
+        Err(err) => {
+            log::warn!("Ignoring invalid `ARK_HELP_PROXY_ADDRESS` '{address}': {err}");
+            DEFAULT_BIND_ADDRESS
+        },
+    }
+}
+
+// Returns the port we'd like to bind to, if any. This is the port of a
+// previous help proxy in this session, or the `ARK_HELP_PROXY_PORT`
+// environment variable, which lets a frontend carry the port across restarts.
+fn preferred_port() -> Option<u16> {
+    match browser::PORT.load(Ordering::Relaxed) {
+        0 => std::env::var("ARK_HELP_PROXY_PORT")
+            .ok()
+            .and_then(|port| port.parse::<u16>().ok())
+            .filter(|port| *port != 0),
+        port => Some(port),
+    }
+}
+
 // The help proxy main entry point.
 #[tokio::main]
-async fn task(source_port: u16, target_port: u16) -> anyhow::Result<()> {
+async fn task(listener: TcpListener, target_port: u16) -> anyhow::Result<()> {
     // Create the help proxy.
-    let help_proxy = HelpProxy::new(source_port, target_port)?;
+    let help_proxy = HelpProxy::new(listener, target_port)?;
 
     // Run the help proxy.
     Ok(help_proxy.run().await?)
 }
 
+// Matches `<script>` elements, which we leave untouched when rewriting links.
+static RE_SCRIPT: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<script\b.*?</script\s*>").unwrap());
+
+// Matches `href` and `src` attributes pointing to an absolute R help server URL.
+static RE_HELP_LINK: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?i)(?P<attr>\b(?:href|src)\s*=\s*["']?)http://(?P<host>127\.0\.0\.1|localhost):(?P<port>\d+)/(?P<path>(?:library|doc|session)/)"#,
+    )
+    .unwrap()
+});
+
 // AppState struct.
 #[derive(Clone)]
 struct AppState {
+    source_port: u16,
     target_port: u16,
 }
 
 // HelpProxy struct.
 struct HelpProxy {
-    source_port: u16,
+    listener: TcpListener,
     target_port: u16,
 }
 
 // HelpProxy implementation.
 impl HelpProxy {
     // Creates a new HelpProxy.
-    fn new(source_port: u16, target_port: u16) -> anyhow::Result<Self> {
+    fn new(listener: TcpListener, target_port: u16) -> anyhow::Result<Self> {
         Ok(HelpProxy {
-            source_port,
+            listener,
             target_port,
         })
     }
 
     // Runs the HelpProxy.
-    async fn run(&self) -> anyhow::Result<()> {
+    async fn run(self) -> anyhow::Result<()> {
         // Create the app state.
         let app_state = web::Data::new(AppState {
+            source_port: self.listener.local_addr()?.port(),
             target_port: self.target_port,
         });
 
@@ -94,16 +157,47 @@ impl HelpProxy {
                 .app_data(app_state.clone())
                 .service(preview_rd)
                 .service(preview_img)
+                .service(widget_asset)
                 .default_service(web::to(proxy_request))
         })
-        .bind(("127.0.0.1", self.source_port))?;
+        .listen(self.listener)?;
 
         // Run the server.
         Ok(server.run().await?)
     }
 
-    fn get_os_assigned_port() -> std::io::Result<u16> {
-        Ok(TcpListener::bind("127.0.0.1:0")?.local_addr()?.port())
+    // Binds the listener up front so the port can't be taken between choosing
+    // it and starting the server. Tries the preferred port first, then falls
+    // back to OS-assigned ports.
+    fn bind(address: IpAddr, preferred_port: Option<u16>) -> anyhow::Result<TcpListener> {
+        if let Some(port) = preferred_port {
+            match TcpListener::bind(SocketAddr::new(address, port)) {
+                Ok(listener) => {
+                    log::info!("Help proxy bound to preferred address {address}:{port}");
+                    return Ok(listener);
+                },
+                Err(err) => {
+                    log::info!("Help proxy can't reuse port {port}, picking a new one: {err}");
+                },
+            }
+        }
+
+        let mut last_error = None;
+
+        for _ in 0..MAX_BIND_ATTEMPTS {
+            match TcpListener::bind(SocketAddr::new(address, 0)) {
+                Ok(listener) => {
+                    log::info!("Help proxy bound to address {}", listener.local_addr()?);
+                    return Ok(listener);
+                },
+                Err(err) => last_error = Some(err),
+            }
+        }
+
+        let err = last_error.map(|err| err.to_string()).unwrap_or_default();
+        Err(anyhow::anyhow!(
+            "Can't bind help proxy to {address} after {MAX_BIND_ATTEMPTS} attempts: {err}"
+        ))
     }
 }
 
@@ -158,18 +252,38 @@ async fn proxy_request(req: HttpRequest, app_state: web::Data<AppState>) -> Http
                 _ => None,
             };
 
-            // Return the replacement resource or the real resource.
-            match replacement_embedded_file {
-                Some(replacement_embedded_file) => {
-                    http_response_builder.body(replacement_embedded_file.data)
+            // Return the replacement resource.
+            if let Some(replacement_embedded_file) = replacement_embedded_file {
+                return http_response_builder.body(replacement_embedded_file.data);
+            }
+
+            // Get the real resource.
+            let body = match response.bytes().await {
+                Ok(body) => body,
+                Err(error) => {
+                    log::error!("Error proxying {}: {}", target_url_string, error);
+                    return HttpResponse::BadGateway().finish();
                 },
-                None => http_response_builder.body(match response.bytes().await {
-                    Ok(body) => body,
-                    Err(error) => {
-                        log::error!("Error proxying {}: {}", target_url_string, error);
-                        return HttpResponse::BadGateway().finish();
-                    },
-                }),
+            };
+
+            // Absolute links to the R help server in HTML pages are rewritten
+            // so they go through the proxy too.
+            let is_html = content_type
+                .and_then(|content_type| content_type.to_str().ok())
+                .map(|content_type| content_type.starts_with("text/html"))
+                .unwrap_or(false);
+
+            if !is_html {
+                return http_response_builder.body(body);
+            }
+
+            match std::str::from_utf8(&body) {
+                Ok(html) => http_response_builder.body(rewrite_help_links(
+                    html,
+                    app_state.target_port,
+                    app_state.source_port,
+                )),
+                Err(_) => http_response_builder.body(body),
             }
         },
         // Error.
@@ -180,6 +294,40 @@ async fn proxy_request(req: HttpRequest, app_state: web::Data<AppState>) -> Http
     }
 }
 
+// Rewrites `href` and `src` attributes pointing to the R help server at
+// `target_port` so they point to the proxy at `source_port` instead. Only
+// `library/`, `doc/`, and `session/` paths are rewritten, and the contents of
+// `<script>` elements are left as is.
+fn rewrite_help_links(html: &str, target_port: u16, source_port: u16) -> String {
+    let target_port = target_port.to_string();
+
+    let rewrite = |html: &str| -> String {
+        RE_HELP_LINK
+            .replace_all(html, |captures: &Captures| {
+                if captures["port"] != target_port {
+                    return captures[0].to_string();
+                }
+                format!(
+                    "{}http://{}:{}/{}",
+                    &captures["attr"], &captures["host"], source_port, &captures["path"]
+                )
+            })
+            .into_owned()
+    };
+
+    let mut out = String::with_capacity(html.len());
+    let mut last = 0;
+
+    for script in RE_SCRIPT.find_iter(html) {
+        out.push_str(&rewrite(&html[last..script.start()]));
+        out.push_str(script.as_str());
+        last = script.end();
+    }
+    out.push_str(&rewrite(&html[last..]));
+
+    out
+}
+
 #[get("/preview")]
 async fn preview_rd(params: web::Query<PreviewRdParams>) -> HttpResponse {
     let file = params.file.as_str();
@@ -238,3 +386,73 @@ async fn preview_img(params: web::Query<PreviewRdParams>) -> HttpResponse {
 
     HttpResponse::Ok().content_type(mime_str).body(content)
 }
+
+// Serves the dependencies (scripts, stylesheets, etc.) of HTML widgets, see
+// `html_widget::ps_html_widget_serve_assets()`.
+#[get("/widget-assets/{id}/{path:.*}")]
+async fn widget_asset(path: web::Path<(String, String)>) -> HttpResponse {
+    let (id, path) = path.into_inner();
+
+    let Some(file) = html_widget::widget_asset_path(&id, &path) else {
+        log::error!("Unknown widget asset '{path}' for widget '{id}'.");
+        return HttpResponse::NotFound().finish();
+    };
+
+    let content = match tokio::fs::read(&file).await {
+        Ok(content) => content,
+        Err(err) => {
+            log::error!("Error reading widget asset '{}': {err:?}", file.display());
+            return HttpResponse::NotFound().finish();
+        },
+    };
+
+    let mime_type = from_path(&file).first_or_octet_stream();
+    HttpResponse::Ok()
+        .content_type(mime_type.to_string())
+        .body(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::help_proxy::rewrite_help_links;
+
+    #[test]
+    fn test_rewrite_help_links() {
+        let html = r#"<a href="http://127.0.0.1:1234/library/base/html/mean.html">mean</a>"#;
+        assert_eq!(
+            rewrite_help_links(html, 1234, 5678),
+            r#"<a href="http://127.0.0.1:5678/library/base/html/mean.html">mean</a>"#
+        );
+
+        let html = r#"<img src='http://localhost:1234/doc/html/logo.jpg'>"#;
+        assert_eq!(
+            rewrite_help_links(html, 1234, 5678),
+            r#"<img src='http://localhost:5678/doc/html/logo.jpg'>"#
+        );
+
+        let html = r#"<a href="http://127.0.0.1:1234/session/Rtmp/index.html">x</a>"#;
+        assert_eq!(
+            rewrite_help_links(html, 1234, 5678),
+            r#"<a href="http://127.0.0.1:5678/session/Rtmp/index.html">x</a>"#
+        );
+    }
+
+    #[test]
+    fn test_rewrite_help_links_leaves_other_links_alone() {
+        // External links
+        let html = r#"<a href="https://www.r-project.org/library/">R</a>"#;
+        assert_eq!(rewrite_help_links(html, 1234, 5678), html);
+
+        // Other ports
+        let html = r#"<a href="http://127.0.0.1:4321/library/base/html/mean.html">mean</a>"#;
+        assert_eq!(rewrite_help_links(html, 1234, 5678), html);
+
+        // Other paths
+        let html = r#"<a href="http://127.0.0.1:1234/other/index.html">x</a>"#;
+        assert_eq!(rewrite_help_links(html, 1234, 5678), html);
+
+        // Inline scripts
+        let html = r#"<script>var x = 'href="http://127.0.0.1:1234/library/";'</script>"#;
+        assert_eq!(rewrite_help_links(html, 1234, 5678), html);
+    }
+}
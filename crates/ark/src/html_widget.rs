@@ -5,17 +5,36 @@
 //
 //
 
+use std::collections::VecDeque;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
 use std::result::Result::Ok;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
 
 use amalthea::socket::iopub::IOPubMessage;
 use amalthea::wire::display_data::DisplayData;
 use harp::object::RObject;
 use libr::R_NilValue;
 use libr::SEXP;
+use once_cell::sync::Lazy;
 use serde_json::Value;
+use uuid::Uuid;
 
+use crate::browser;
 use crate::interface::RMain;
 
+/// The number of widgets whose dependencies we keep serving. Widgets don't
+/// have a comm that would tell us when the frontend is done with them, so the
+/// oldest widgets are released once we go past this.
+const MAX_SERVED_WIDGETS: usize = 64;
+
+/// Directories holding copies of widget dependencies, served by the help proxy
+/// under `/widget-assets/{id}/`. Oldest first.
+static WIDGET_ASSETS: Lazy<Mutex<VecDeque<(String, PathBuf)>>> =
+    Lazy::new(|| Mutex::new(VecDeque::new()));
+
 #[harp::register]
 pub unsafe extern "C" fn ps_html_widget(kind: SEXP, tags: SEXP) -> Result<SEXP, anyhow::Error> {
     // For friendly display: the class/kind of the widget
@@ -45,3 +64,132 @@ pub unsafe extern "C" fn ps_html_widget(kind: SEXP, tags: SEXP) -> Result<SEXP,
 
     Ok(R_NilValue)
 }
+
+/// Starts serving the widget dependencies in `dir` and returns the base URL
+/// they are served at, or `NULL` if the help proxy isn't running. The
+/// directory is owned by the asset server from now on and is deleted when the
+/// widget is released.
+#[harp::register]
+pub unsafe extern "C" fn ps_html_widget_serve_assets(dir: SEXP) -> anyhow::Result<SEXP> {
+    let dir = RObject::view(dir).to::<String>()?;
+
+    match serve_widget_assets(PathBuf::from(&dir)) {
+        Some(url) => Ok(RObject::from(url).sexp),
+        None => {
+            log::warn!("Can't serve widget assets from '{dir}': the help proxy isn't running");
+            Ok(R_NilValue)
+        },
+    }
+}
+
+#[harp::register]
+pub unsafe extern "C" fn ps_html_widget_release_assets(url: SEXP) -> anyhow::Result<SEXP> {
+    let url = RObject::view(url).to::<String>()?;
+
+    // Accept either the base URL returned by `ps_html_widget_serve_assets()`
+    // or the bare widget id
+    let id = url
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default();
+    release_widget_assets(id);
+
+    Ok(R_NilValue)
+}
+
+/// Registers `dir` with the asset server and returns the base URL its files
+/// are served at, or `None` if the help proxy isn't running. `dir` is deleted
+/// once we stop serving it.
+pub fn serve_widget_assets(dir: PathBuf) -> Option<String> {
+    let port = browser::PORT.load(Ordering::Relaxed);
+    if port == 0 {
+        return None;
+    }
+
+    let id = register_widget_assets(dir);
+    Some(format!("http://127.0.0.1:{port}/widget-assets/{id}/"))
+}
+
+fn register_widget_assets(dir: PathBuf) -> String {
+    let id = Uuid::new_v4().to_string();
+
+    let released = {
+        let mut assets = WIDGET_ASSETS.lock().unwrap();
+        assets.push_back((id.clone(), dir));
+
+        let n_released = assets.len().saturating_sub(MAX_SERVED_WIDGETS);
+        assets.drain(..n_released).collect::<Vec<_>>()
+    };
+
+    for (_, dir) in released {
+        remove_widget_dir(&dir);
+    }
+
+    id
+}
+
+/// Stops serving the assets of widget `id` and deletes its directory.
+pub fn release_widget_assets(id: &str) {
+    let released = {
+        let mut assets = WIDGET_ASSETS.lock().unwrap();
+        let index = assets.iter().position(|(widget_id, _)| widget_id == id);
+        index.and_then(|index| assets.remove(index))
+    };
+
+    match released {
+        Some((_, dir)) => remove_widget_dir(&dir),
+        None => log::trace!("No widget assets to release for '{id}'"),
+    }
+}
+
+/// Resolves `path` within the asset directory of widget `id`. Returns `None`
+/// for unknown widgets and for paths that would escape the directory.
+pub fn widget_asset_path(id: &str, path: &str) -> Option<PathBuf> {
+    let path = Path::new(path);
+    if !path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+
+    let assets = WIDGET_ASSETS.lock().unwrap();
+    let (_, dir) = assets.iter().find(|(widget_id, _)| widget_id == id)?;
+
+    Some(dir.join(path))
+}
+
+fn remove_widget_dir(dir: &Path) {
+    if let Err(err) = std::fs::remove_dir_all(dir) {
+        log::warn!("Can't remove widget assets in '{}': {err}", dir.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::html_widget::register_widget_assets;
+    use crate::html_widget::release_widget_assets;
+    use crate::html_widget::widget_asset_path;
+
+    #[test]
+    fn test_widget_asset_path() {
+        let dir = std::env::temp_dir().join(format!("ark-widget-assets-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let id = register_widget_assets(dir.clone());
+
+        assert_eq!(
+            widget_asset_path(&id, "leaflet-1.3.1/leaflet.js"),
+            Some(dir.join("leaflet-1.3.1/leaflet.js"))
+        );
+
+        // Paths can't escape the widget directory
+        assert_eq!(widget_asset_path(&id, "../secret"), None);
+        assert_eq!(widget_asset_path(&id, "/etc/passwd"), None);
+        assert_eq!(widget_asset_path("unknown", "leaflet.js"), None);
+
+        release_widget_assets(&id);
+        assert_eq!(widget_asset_path(&id, "leaflet.js"), None);
+        assert!(!dir.exists());
+    }
+}
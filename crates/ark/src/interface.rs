@@ -10,14 +10,18 @@
 // The frontend methods called by R are forwarded to the corresponding
 // `RMain` methods via `R_MAIN`.
 
+use std::cell::Cell;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::ffi::*;
 use std::os::raw::c_uchar;
+use std::path::Path;
 use std::path::PathBuf;
 use std::result::Result::Ok;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::Once;
+use std::sync::OnceLock;
 use std::task::Poll;
 use std::time::Duration;
 
@@ -29,6 +33,7 @@ use amalthea::comm::ui_comm::PromptStateParams;
 use amalthea::comm::ui_comm::ShowMessageParams;
 use amalthea::comm::ui_comm::UiFrontendEvent;
 use amalthea::comm::ui_comm::UiFrontendRequest;
+use amalthea::socket::comm::CommSocket;
 use amalthea::socket::iopub::IOPubMessage;
 use amalthea::socket::iopub::Wait;
 use amalthea::socket::stdin::StdInRequest;
@@ -53,6 +58,7 @@ use amalthea::Error;
 use anyhow::*;
 use bus::Bus;
 use crossbeam::channel::bounded;
+use crossbeam::channel::never;
 use crossbeam::channel::unbounded;
 use crossbeam::channel::Receiver;
 use crossbeam::channel::Sender;
@@ -61,9 +67,10 @@ use harp::environment::r_ns_env;
 use harp::environment::Environment;
 use harp::environment::R_ENVS;
 use harp::exec::r_check_stack;
+use harp::exec::r_parse_vector;
 use harp::exec::r_peek_error_buffer;
 use harp::exec::r_sandbox;
-use harp::exec::r_source;
+use harp::exec::ParseResult;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
 use harp::library::RLibraries;
@@ -90,7 +97,6 @@ use log::*;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use serde_json::json;
-use stdext::result::ResultOrLog;
 use stdext::*;
 use uuid::Uuid;
 
@@ -98,9 +104,12 @@ use crate::dap::dap::DapBackendEvent;
 use crate::dap::dap_r_main::RMainDap;
 use crate::dap::Dap;
 use crate::errors;
+use crate::errors::strip_ansi_exception;
+use crate::help::markdown::invalidate_package;
 use crate::help::message::HelpEvent;
 use crate::help::r_help::RHelp;
 use crate::kernel::Kernel;
+use crate::logger_r;
 use crate::lsp::events::EVENTS;
 use crate::lsp::main_loop::Event;
 use crate::lsp::main_loop::KernelNotification;
@@ -118,12 +127,20 @@ use crate::request::RRequest;
 use crate::signals::initialize_signal_handlers;
 use crate::signals::interrupts_pending;
 use crate::signals::set_interrupts_pending;
+use crate::srcref::dev_package_populate_srcref;
+use crate::srcref::is_valid_cell_id;
 use crate::srcref::ns_populate_srcref;
+use crate::srcref::register_cell_source;
 use crate::srcref::resource_loaded_namespaces;
 use crate::startup;
+use crate::startup::StartupOption;
 use crate::sys::console::console_to_utf8;
+use crate::traps;
+use crate::ui;
+use crate::viewer;
 
 /// An enum representing the different modes in which the R session can run.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SessionMode {
     /// A session with an interactive console (REPL), such as in Positron.
     Console,
@@ -135,6 +152,17 @@ pub enum SessionMode {
     Background,
 }
 
+impl SessionMode {
+    /// The name of the mode, as accepted by `--session-mode`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SessionMode::Console => "console",
+            SessionMode::Notebook => "notebook",
+            SessionMode::Background => "background",
+        }
+    }
+}
+
 // --- Globals ---
 // These values must be global in order for them to be accessible from R
 // callbacks, which do not have a facility for passing or returning context.
@@ -142,6 +170,9 @@ pub enum SessionMode {
 /// Ensures that the kernel is only ever initialized once
 static INIT: Once = Once::new();
 
+/// Default for `iopub_drain_timeout()`
+const IOPUB_DRAIN_TIMEOUT: Duration = Duration::from_millis(500);
+
 // The global state used by R callbacks.
 //
 // Doesn't need a mutex because it's only accessed by the R thread. Should
@@ -150,13 +181,67 @@ static INIT: Once = Once::new();
 // `RMain::get_mut()`).
 static mut R_MAIN: Option<RMain> = None;
 
+/// Sending side of the idle callbacks registry, see `on_idle()`
+static IDLE_CALLBACKS_TX: OnceLock<Sender<IdleCallback>> = OnceLock::new();
+
+pub type IdleCallback = Box<dyn FnOnce() + Send + 'static>;
+
+thread_local! {
+    /// Whether the R thread is currently running an idle callback
+    static IDLE_CALLBACK_RUNNING: Cell<bool> = const { Cell::new(false) };
+
+    /// Callbacks registered from other callbacks, which run at the next prompt
+    static IDLE_CALLBACKS_DEFERRED: RefCell<Vec<IdleCallback>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Registers a callback to run the next time R is idle at the top-level prompt.
+///
+/// Useful for maintenance tasks, such as cleaning up stale comms or flushing
+/// caches, that shouldn't run in the middle of an execution. Can be called
+/// from any thread.
+///
+/// Contract:
+/// - Callbacks run once, in registration order, on the R thread, right
+///   before `ReadConsole()` blocks waiting for the next top-level input.
+///   Callbacks registered while R is already waiting at the top-level prompt
+///   run right away. They never run at browser, continuation, or
+///   `readline()` prompts.
+/// - Callbacks delay the processing of incoming requests, so they must be
+///   cheap.
+/// - Callbacks are not reentrant. They must not bring R back to a prompt,
+///   e.g. with `browser()` or `readline()`. A callback registered from
+///   another callback runs at the next prompt.
+/// - R errors thrown by callbacks are caught and logged.
+pub fn on_idle<F>(callback: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    // Defer to the next prompt so that callbacks rescheduling themselves
+    // don't run in a loop while R is idle
+    if IDLE_CALLBACK_RUNNING.get() {
+        IDLE_CALLBACKS_DEFERRED.with_borrow_mut(|deferred| deferred.push(Box::new(callback)));
+        return;
+    }
+
+    let Some(tx) = IDLE_CALLBACKS_TX.get() else {
+        log::error!("Can't register idle callback before R is started");
+        return;
+    };
+    if let Err(err) = tx.send(Box::new(callback)) {
+        log::error!("Can't register idle callback: {err:?}");
+    }
+}
+
 /// Starts the main R thread. Doesn't return.
 pub fn start_r(
     r_args: Vec<String>,
-    startup_file: Option<String>,
+    startup_files: Vec<String>,
+    startup_options: Vec<StartupOption>,
+    modules_dir: Option<String>,
     kernel_mutex: Arc<Mutex<Kernel>>,
     comm_manager_tx: Sender<CommManagerEvent>,
     r_request_rx: Receiver<RRequest>,
+    execute_request_rx: Receiver<RRequest>,
     stdin_request_tx: Sender<StdInRequest>,
     stdin_reply_rx: Receiver<amalthea::Result<InputReply>>,
     iopub_tx: Sender<IOPubMessage>,
@@ -174,12 +259,17 @@ pub fn start_r(
 
         r_task::initialize(tasks_interrupt_tx.clone(), tasks_idle_tx.clone());
 
+        let (idle_callbacks_tx, idle_callbacks_rx) = unbounded::<IdleCallback>();
+        IDLE_CALLBACKS_TX.set(idle_callbacks_tx).unwrap();
+
         R_MAIN = Some(RMain::new(
             kernel_mutex,
             tasks_interrupt_rx,
             tasks_idle_rx,
+            idle_callbacks_rx,
             comm_manager_tx,
             r_request_rx,
+            execute_request_rx,
             stdin_request_tx,
             stdin_reply_rx,
             iopub_tx,
@@ -233,16 +323,28 @@ pub fn start_r(
         // Initialize harp (after routine registration)
         harp::initialize();
 
-        // Optionally run a frontend specified R startup script (after harp init)
-        if let Some(file) = &startup_file {
-            r_source(file).or_log_error(&format!("Failed to source startup file '{file}' due to"));
-        }
+        // Forward R's own diagnostics to the log as early as possible
+        logger_r::install();
+
+        // Apply frontend specified R options before any user code runs
+        startup::apply_startup_options(&startup_options);
+
+        // Optionally run frontend specified R startup scripts (after harp init)
+        startup::source_startup_files(&startup_files);
 
         // Initialize support functions (after routine registration)
         if let Err(err) = modules::initialize(false) {
             log::error!("Can't load R modules: {err:?}");
         }
 
+        // Load site-specific modules on top of the built-in ones, before
+        // hooks are registered so they can define their own
+        if let Some(dir) = &modules_dir {
+            if let Err(err) = modules::load_extra(Path::new(dir)) {
+                log::error!("Can't load R modules from '{dir}': {err:?}");
+            }
+        }
+
         // Register all hooks once all modules have been imported
         let hook_result = RFunction::from(".ps.register_all_hooks").call();
         if let Err(err) = hook_result {
@@ -280,6 +382,9 @@ pub fn start_r(
         startup::source_user_r_profile();
     }
 
+    // R's messages are shown in the console from now on
+    logger_r::disable();
+
     // Does not return!
     crate::sys::interface::run_r();
 }
@@ -294,10 +399,14 @@ pub struct RMain {
     /// Channel used to send along messages relayed on the open comms.
     comm_manager_tx: Sender<CommManagerEvent>,
 
-    /// Execution requests from the frontend. Processed from `ReadConsole()`.
-    /// Requests for code execution provide input to that method.
+    /// Requests from the control and debugger threads, e.g. shutdown or
+    /// debugger commands. Processed from `ReadConsole()`.
     r_request_rx: Receiver<RRequest>,
 
+    /// Execution requests from the frontend, sent by the shell. Processed
+    /// from `ReadConsole()`, to which they provide input.
+    execute_request_rx: Receiver<RRequest>,
+
     /// Input requests to the frontend. Processed from `ReadConsole()`
     /// calls triggered by e.g. `readline()`.
     stdin_request_tx: Sender<StdInRequest>,
@@ -328,17 +437,21 @@ pub struct RMain {
     tasks_idle_rx: Receiver<RTask>,
     pending_futures: HashMap<Uuid, (BoxFuture<'static, ()>, RTaskStartInfo)>,
 
+    /// Callbacks registered with `on_idle()`, drained at top-level prompts
+    idle_callbacks_rx: Receiver<IdleCallback>,
+
     /// Shared reference to kernel. Currently used by the ark-execution
     /// thread, the R frontend callbacks, and LSP routines called from R
     kernel: Arc<Mutex<Kernel>>,
 
     /// Represents whether an error occurred during R code execution.
     pub error_occurred: bool,
+    pub error_name: String,    // `ename` in the Jupyter protocol
     pub error_message: String, // `evalue` in the Jupyter protocol
     pub error_traceback: Vec<String>,
 
-    /// Channel to communicate with the Help thread
-    help_event_tx: Option<Sender<HelpEvent>>,
+    /// Socket of the help comm, used to send requests to the Help thread
+    help_comm: Option<CommSocket>,
     /// R help port
     help_port: Option<u16>,
 
@@ -368,6 +481,10 @@ pub struct KernelInfo {
     pub banner: String,
     pub input_prompt: Option<String>,
     pub continuation_prompt: Option<String>,
+    pub session_mode: SessionMode,
+    /// Port of the R help server, linked from the help menu of Jupyter
+    /// frontends
+    pub help_port: Option<u16>,
 }
 
 /// This struct represents the data that we wish R would pass to
@@ -416,8 +533,10 @@ impl RMain {
         kernel: Arc<Mutex<Kernel>>,
         tasks_interrupt_rx: Receiver<RTask>,
         tasks_idle_rx: Receiver<RTask>,
+        idle_callbacks_rx: Receiver<IdleCallback>,
         comm_manager_tx: Sender<CommManagerEvent>,
         r_request_rx: Receiver<RRequest>,
+        execute_request_rx: Receiver<RRequest>,
         stdin_request_tx: Sender<StdInRequest>,
         stdin_reply_rx: Receiver<amalthea::Result<InputReply>>,
         iopub_tx: Sender<IOPubMessage>,
@@ -428,6 +547,7 @@ impl RMain {
         Self {
             initializing: true,
             r_request_rx,
+            execute_request_rx,
             comm_manager_tx,
             stdin_request_tx,
             stdin_reply_rx,
@@ -439,9 +559,10 @@ impl RMain {
             banner_output: String::new(),
             kernel,
             error_occurred: false,
+            error_name: String::new(),
             error_message: String::new(),
             error_traceback: Vec::new(),
-            help_event_tx: None,
+            help_comm: None,
             help_port: None,
             lsp_events_tx: None,
             dap: RMainDap::new(dap),
@@ -449,6 +570,7 @@ impl RMain {
             tasks_interrupt_rx,
             tasks_idle_rx,
             pending_futures: HashMap::new(),
+            idle_callbacks_rx,
             session_mode,
         }
     }
@@ -527,11 +649,23 @@ impl RMain {
             let input_prompt: String = harp::get_option("prompt").try_into().unwrap();
             let continuation_prompt: String = harp::get_option("continue").try_into().unwrap();
 
+            // Start the help server now so that kernel info requests can be
+            // answered without the R thread
+            let help_port = match RHelp::r_start_or_reconnect_to_help_server() {
+                Ok(port) => Some(port),
+                Err(err) => {
+                    log::error!("Could not start R help server: {err:?}");
+                    None
+                },
+            };
+
             let kernel_info = KernelInfo {
                 version: version.clone(),
                 banner: self.banner_output.clone(),
                 input_prompt: Some(input_prompt),
                 continuation_prompt: Some(continuation_prompt),
+                session_mode: self.session_mode,
+                help_port,
             };
 
             debug!("Sending kernel info: {}", version);
@@ -551,14 +685,16 @@ impl RMain {
         // Reset the autoprint buffer
         self.autoprint_output = String::new();
 
-        // Increment counter if we are storing this execution in history
-        if req.store_history {
+        // Silent executions are never stored in history, whatever the value of
+        // `store_history`
+        let store_history = req.store_history && !req.silent;
+
+        // Increment counter and re-broadcast the execution to all frontends
+        // if we are storing this execution in history. Otherwise the execution
+        // reuses the prior count for its result and reply.
+        if store_history {
             self.execution_count = self.execution_count + 1;
-        }
 
-        // If the code is not to be executed silently, re-broadcast the
-        // execution to all frontends
-        if !req.silent {
             if let Err(err) = self.iopub_tx.send(IOPubMessage::ExecuteInput(ExecuteInput {
                 code: req.code.clone(),
                 execution_count: self.execution_count,
@@ -591,9 +727,19 @@ impl RMain {
         buflen: c_int,
         _hist: c_int,
     ) -> ConsoleResult {
-        let info = Self::prompt_info(prompt);
+        let pending_input = self
+            .active_request
+            .as_ref()
+            .map(|req| req.request.code.as_str());
+        let info = Self::prompt_info(prompt, pending_input);
         debug!("R prompt: {}", info.input_prompt);
 
+        // R is done evaluating the last input, unless it's requesting input
+        // from the user while evaluating it
+        if !info.input_request {
+            traps::clear_current_input();
+        }
+
         // Upon entering read-console, finalize any debug call text that we were capturing.
         // At this point, the user can either advance the debugger, causing us to capture
         // a new expression, or execute arbitrary code, where we will reuse a finalized
@@ -612,6 +758,13 @@ impl RMain {
 
         if info.input_request {
             if let Some(req) = &self.active_request {
+                if !req.request.allow_stdin {
+                    // The frontend can't answer input requests for this
+                    // execution, propagate error to R instead of waiting
+                    // for an `input_reply` that never comes
+                    return self.handle_disallowed_input_request();
+                }
+
                 // Send request to frontend.  We'll wait for an `input_reply`
                 // from the frontend in the event loop below. The active request
                 // remains active.
@@ -675,6 +828,27 @@ impl RMain {
             }
         }
 
+        // R is idle at top level, run maintenance callbacks before blocking
+        let idle = !info.browser && !info.incomplete && !info.input_request;
+        if idle {
+            // Close the progress bars left open by the last request, e.g.
+            // because it failed
+            for params in ui::events::progress_close_all() {
+                self.send_frontend_event(UiFrontendEvent::Progress(params));
+            }
+
+            self.run_idle_callbacks();
+        }
+
+        // Callbacks registered from other threads while we wait also run
+        // right away when idle. Callbacks registered by callbacks are
+        // deferred to the next prompt instead, see `on_idle()`.
+        let idle_callbacks_rx = if idle {
+            self.idle_callbacks_rx.clone()
+        } else {
+            never()
+        };
+
         loop {
             // If an interrupt was signaled and we are in a user
             // request prompt, e.g. `readline()`, we need to propagate
@@ -699,8 +873,14 @@ impl RMain {
             // to be handled in a blocking way to ensure subscribers are
             // notified before the next incoming message is processed.
 
-            // First handle execute requests outside of `select!` to ensure they
-            // have priority. `select!` chooses at random.
+            // First handle requests outside of `select!` to ensure they
+            // have priority. `select!` chooses at random. Execute requests
+            // come first.
+            if let Ok(req) = self.execute_request_rx.try_recv() {
+                if let Some(input) = self.handle_execute_request(req, &info, buf, buflen) {
+                    return input;
+                }
+            }
             if let Ok(req) = self.r_request_rx.try_recv() {
                 if let Some(input) = self.handle_execute_request(req, &info, buf, buflen) {
                     return input;
@@ -709,6 +889,18 @@ impl RMain {
 
             select! {
                 // Wait for an execution request from the frontend.
+                recv(self.execute_request_rx) -> req => {
+                    let Ok(req) = req else {
+                        // The shell is gone
+                        return ConsoleResult::Disconnected;
+                    };
+
+                    if let Some(input) = self.handle_execute_request(req, &info, buf, buflen) {
+                        return input;
+                    }
+                }
+
+                // Wait for a request from the control or debugger threads.
                 recv(self.r_request_rx) -> req => {
                     let Ok(req) = req else {
                         // The channel is disconnected and empty
@@ -732,6 +924,9 @@ impl RMain {
                 recv(self.tasks_idle_rx) -> task => {
                     self.handle_task(task.unwrap());
                 }
+                recv(idle_callbacks_rx) -> callback => {
+                    self.run_idle_callback(callback.unwrap());
+                }
 
                 // Wait with a timeout. Necessary because we need to
                 // pump the event loop while waiting for console input.
@@ -749,7 +944,9 @@ impl RMain {
     // We prefer to panic if there is an error while trying to determine the
     // prompt type because any confusion here is prone to put the frontend in a
     // bad state (e.g. causing freezes)
-    fn prompt_info(prompt_c: *const c_char) -> PromptInfo {
+    //
+    // `pending_input` is the code of the active execute request, if any.
+    fn prompt_info(prompt_c: *const c_char, pending_input: Option<&str>) -> PromptInfo {
         let n_frame = harp::session::r_n_frame().unwrap();
         trace!("prompt_info(): n_frame = '{}'", n_frame);
 
@@ -775,9 +972,15 @@ impl RMain {
         let user_request = !browser && n_frame > 0;
 
         // The request is incomplete if we see the continue prompt, except if
-        // we're in a user request, e.g. `readline("+ ")`
+        // we're in a user request, e.g. `readline("+ ")`. Both prompts are
+        // read from the options since users may customise them. When they are
+        // identical, the prompt is ambiguous and we check whether the pending
+        // input parses instead.
+        let input_prompt: String = harp::get_option("prompt").try_into().unwrap();
         let continuation_prompt: String = harp::get_option("continue").try_into().unwrap();
-        let incomplete = !user_request && prompt == continuation_prompt;
+        let incomplete = !user_request &&
+            prompt == continuation_prompt &&
+            (prompt != input_prompt || pending_input.map_or(false, is_incomplete_input));
 
         if incomplete {
             trace!("Got R prompt '{}', marking request incomplete", prompt);
@@ -801,15 +1004,36 @@ impl RMain {
         buf: *mut c_uchar,
         buflen: c_int,
     ) -> Option<ConsoleResult> {
+        if let RRequest::Interrupt = req {
+            // At top level, there is nothing to interrupt
+            if info.input_request && interrupts_pending() {
+                return Some(ConsoleResult::Interrupt);
+            }
+            set_interrupts_pending(false);
+            return None;
+        }
+
         if info.input_request {
             panic!("Unexpected `execute_request` while waiting for `input_reply`.");
         }
 
+        // Identifier of the cell being executed, if any
+        let mut cell = None;
+
         let input = match req {
             RRequest::ExecuteCode(exec_req, orig, response_tx) => {
                 // Extract input from request
                 let (input, exec_count) = { self.init_execute_request(&exec_req) };
 
+                // Prefer the notebook's own cell ID, which is stable across
+                // re-executions, over the execution count
+                cell = Some(
+                    orig.as_ref()
+                        .and_then(|orig| orig.cell_id.clone())
+                        .filter(|id| is_valid_cell_id(id))
+                        .unwrap_or_else(|| exec_count.to_string()),
+                );
+
                 // Save `ExecuteCode` request so we can respond to it at next prompt
                 self.active_request = Some(ActiveReadConsoleRequest {
                     exec_count,
@@ -821,7 +1045,14 @@ impl RMain {
                 input
             },
 
-            RRequest::Shutdown(_) => ConsoleInput::EOF,
+            RRequest::Shutdown(_) => {
+                // R exits the process right after reading EOF, make sure
+                // outputs of earlier requests have reached the frontend
+                self.drain_iopub(iopub_drain_timeout());
+                ConsoleInput::EOF
+            },
+
+            RRequest::Interrupt => unreachable!("Interrupts are handled above"),
 
             RRequest::DebugCommand(cmd) => {
                 // Just ignore command in case we left the debugging state already
@@ -851,9 +1082,21 @@ impl RMain {
                 // In notebooks, wrap in braces so that only the last complete
                 // expression is auto-printed
                 if let SessionMode::Notebook = self.session_mode {
-                    code = format!("{{ {code} }}");
+                    code = match cell {
+                        // Parse the cell with a synthetic srcfile named after
+                        // the cell, so that source references in tracebacks
+                        // and in the debugger can be mapped back to it. The
+                        // `#line` directive makes lines relative to the cell.
+                        Some(cell_id) => {
+                            let filename = register_cell_source(&cell_id, &code);
+                            format!("{{\n#line 1 \"{filename}\"\n{code}\n}}")
+                        },
+                        None => format!("{{ {code} }}"),
+                    };
                 }
 
+                traps::set_current_input(&code);
+
                 Self::on_console_input(buf, buflen, code);
                 Some(ConsoleResult::NewInput)
             },
@@ -893,6 +1136,22 @@ impl RMain {
         return ConsoleResult::Error(Error::InvalidInputRequest(message));
     }
 
+    /// Handle an `input_request` for an `execute_request` sent with
+    /// `allow_stdin` set to `false`, e.g. by a frontend that doesn't
+    /// support the stdin channel. `readline()` and friends fail with an R
+    /// error.
+    fn handle_disallowed_input_request(&self) -> ConsoleResult {
+        log::info!(
+            "Detected `input_request` while stdin is not allowed. Preparing to throw an R error."
+        );
+
+        let message = String::from(
+            "Can't request input from the user: the frontend doesn't allow input requests for this execution.",
+        );
+
+        return ConsoleResult::Error(Error::InvalidInputRequest(message));
+    }
+
     fn in_renv_autoloader() -> bool {
         harp::get_option("renv.autoloader.running")
             .try_into()
@@ -954,6 +1213,33 @@ impl RMain {
         }
     }
 
+    /// Runs a snapshot of the callbacks registered with `on_idle()` so far,
+    /// including the ones deferred by callbacks at the last prompt.
+    /// Callbacks registered while draining are deferred to the next prompt.
+    fn run_idle_callbacks(&mut self) {
+        let mut callbacks = IDLE_CALLBACKS_DEFERRED.take();
+
+        let n = self.idle_callbacks_rx.len();
+        callbacks.extend(self.idle_callbacks_rx.try_iter().take(n));
+
+        for callback in callbacks {
+            self.run_idle_callback(callback);
+        }
+    }
+
+    fn run_idle_callback(&mut self, callback: IdleCallback) {
+        // Like tasks, callbacks can't take any user input
+        let _interactive = harp::raii::RLocalInteractive::new(false);
+
+        IDLE_CALLBACK_RUNNING.set(true);
+        let result = r_sandbox(callback);
+        IDLE_CALLBACK_RUNNING.set(false);
+
+        if let Err(err) = result {
+            log::error!("Idle callback failed: {err:?}");
+        }
+    }
+
     /// Returns start information when the task has been completed
     fn handle_task(&mut self, task: RTask) -> Option<RTaskStartInfo> {
         // Background tasks can't take any user input, so we set R_Interactive
@@ -965,14 +1251,22 @@ impl RMain {
                 // Immediately let caller know we have started so it can set up the
                 // timeout
                 if let Some(ref status_tx) = task.status_tx {
-                    status_tx.send(RTaskStatus::Started).unwrap();
+                    if status_tx.send(RTaskStatus::Started).is_err() {
+                        // The caller of `r_task_timeout()` gave up waiting
+                        // before the task started, don't run it
+                        log::trace!("Dropping timed out task");
+                        return None;
+                    }
                 }
 
                 let result = task.start_info.span.in_scope(|| r_sandbox(task.fun));
 
-                // Unblock caller via the notification channel
+                // Unblock caller via the notification channel. This fails if
+                // the caller timed out while the task was running.
                 if let Some(ref status_tx) = task.status_tx {
-                    status_tx.send(RTaskStatus::Finished(result)).unwrap()
+                    if status_tx.send(RTaskStatus::Finished(result)).is_err() {
+                        log::trace!("Finished task after caller timed out");
+                    }
                 }
 
                 Some(task.start_info)
@@ -1094,11 +1388,17 @@ This is a Positron limitation we plan to fix. In the meantime, you can:
             log::trace!("Got R prompt '{}', completing execution", prompt);
 
             self.make_execute_response_error(req.exec_count)
-                .unwrap_or_else(|| self.make_execute_response_result(req.exec_count))
+                .unwrap_or_else(|| {
+                    self.make_execute_response_result(req.exec_count, &req.request.user_expressions)
+                })
         };
 
+        // Silent executions don't publish their result, consistently with
+        // their output being discarded
         if let Some(result) = result {
-            self.iopub_tx.send(result).unwrap();
+            if !req.request.silent || matches!(result, IOPubMessage::ExecuteError(_)) {
+                self.iopub_tx.send(result).unwrap();
+            }
         }
 
         log::trace!("Sending `execute_response`: {response:?}");
@@ -1130,12 +1430,11 @@ This is a Positron limitation we plan to fix. In the meantime, you can:
             return None;
         }
 
-        // We don't fill out `ename` with anything meaningful because typically
-        // R errors don't have names. We could consider using the condition class
-        // here, which r-lib/tidyverse packages have been using more heavily.
+        // R errors don't have names so `ename` is derived from the condition
+        // class by the global error handler, see `error_name()`
         let mut exception = if error_occurred {
             Exception {
-                ename: String::from(""),
+                ename: self.error_name.clone(),
                 evalue: self.error_message.clone(),
                 traceback: self.error_traceback.clone(),
             }
@@ -1146,7 +1445,7 @@ This is a Positron limitation we plan to fix. In the meantime, you can:
             // push a tree too far to the right.
             let traceback = r_traceback();
             Exception {
-                ename: String::from(""),
+                ename: String::from("RecursionError"),
                 evalue: err_buf.clone(),
                 traceback,
             }
@@ -1162,7 +1461,9 @@ This is a Positron limitation we plan to fix. In the meantime, you can:
             exception.traceback.insert(0, exception.evalue.clone())
         }
 
-        let response = new_execute_response_error(exception.clone(), exec_count);
+        // The structured reply is plain text, while the `error` message keeps
+        // the ANSI styling of e.g. cli errors
+        let response = new_execute_response_error(strip_ansi_exception(&exception), exec_count);
         let result = IOPubMessage::ExecuteError(ExecuteError { exception });
 
         Some((response, Some(result)))
@@ -1171,6 +1472,7 @@ This is a Positron limitation we plan to fix. In the meantime, you can:
     fn make_execute_response_result(
         &mut self,
         exec_count: u32,
+        user_expressions: &serde_json::Value,
     ) -> (ExecuteResponse, Option<IOPubMessage>) {
         // TODO: Implement rich printing of certain outputs.
         // Will we need something similar to the RStudio model,
@@ -1207,7 +1509,9 @@ This is a Positron limitation we plan to fix. In the meantime, you can:
             }
         }
 
-        let response = new_execute_response(exec_count);
+        // User expressions are only evaluated when the code succeeded
+        let user_expressions = eval_user_expressions(user_expressions);
+        let response = new_execute_response(exec_count, user_expressions);
 
         let result = (data.len() > 0).then(|| {
             IOPubMessage::ExecuteResult(ExecuteResult {
@@ -1225,18 +1529,39 @@ This is a Positron limitation we plan to fix. In the meantime, you can:
     /// in front of this one have been forwarded on to the frontend.
     /// TODO: Remove this when we can, see `request_input()` for rationale.
     fn wait_for_empty_iopub(&self) {
+        let Some(wait_rx) = self.send_iopub_wait() else {
+            return;
+        };
+
+        if let Err(error) = wait_rx.recv() {
+            log::error!("Failed to receive wait response from iopub: {error:?}");
+        }
+    }
+
+    /// Like `wait_for_empty_iopub()` but gives up after `timeout`, since
+    /// this is used on shutdown where a stuck IOPub thread must not prevent
+    /// the session from exiting.
+    fn drain_iopub(&self, timeout: Duration) {
+        let Some(wait_rx) = self.send_iopub_wait() else {
+            return;
+        };
+
+        if let Err(error) = wait_rx.recv_timeout(timeout) {
+            log::warn!("IOPub not drained after {timeout:?}, some outputs may be lost: {error:?}");
+        }
+    }
+
+    fn send_iopub_wait(&self) -> Option<Receiver<()>> {
         let (wait_tx, wait_rx) = bounded::<()>(1);
 
         let message = IOPubMessage::Wait(Wait { wait_tx });
 
         if let Err(error) = self.iopub_tx.send(message) {
             log::error!("Failed to send wait request to iopub: {error:?}");
-            return;
+            return None;
         }
 
-        if let Err(error) = wait_rx.recv() {
-            log::error!("Failed to receive wait response from iopub: {error:?}");
-        }
+        Some(wait_rx)
     }
 
     /// Request input from frontend in case code like `readline()` is
@@ -1382,6 +1707,14 @@ This is a Positron limitation we plan to fix. In the meantime, you can:
                 break;
             }
         }
+
+        // A Shiny app hosted in the viewer keeps R busy until it stops. Stop
+        // it once the next top-level command comes in so that it can run.
+        if viewer::is_shiny_app_running() && !self.execute_request_rx.is_empty() {
+            if let Err(err) = viewer::stop_shiny_app() {
+                log::error!("Can't stop Shiny app: {err:?}");
+            }
+        }
     }
 
     unsafe fn process_events() {
@@ -1404,6 +1737,10 @@ This is a Positron limitation we plan to fix. In the meantime, you can:
         graphics_device::on_process_events();
     }
 
+    pub fn get_dap(&self) -> Arc<Mutex<Dap>> {
+        self.dap.dap()
+    }
+
     pub fn get_comm_manager_tx(&self) -> &Sender<CommManagerEvent> {
         // Read only access to `comm_manager_tx`
         &self.comm_manager_tx
@@ -1413,21 +1750,23 @@ This is a Positron limitation we plan to fix. In the meantime, you can:
         &self.kernel
     }
 
-    pub(crate) fn set_help_fields(&mut self, help_event_tx: Sender<HelpEvent>, help_port: u16) {
-        self.help_event_tx = Some(help_event_tx);
+    pub fn session_mode(&self) -> SessionMode {
+        self.session_mode
+    }
+
+    pub(crate) fn set_help_fields(&mut self, help_comm: CommSocket, help_port: u16) {
+        self.help_comm = Some(help_comm);
         self.help_port = Some(help_port);
     }
 
     pub(crate) fn send_help_event(&self, event: HelpEvent) -> anyhow::Result<()> {
-        let Some(ref tx) = self.help_event_tx else {
+        let Some(ref comm) = self.help_comm else {
             return Err(anyhow!("No help channel available to handle help event. Is the help comm open? Event {event:?}."));
         };
 
-        if let Err(err) = tx.send(event) {
-            return Err(anyhow!("Failed to send help message: {err:?}"));
-        }
-
-        Ok(())
+        // Don't wait for the Help thread: it might be blocked on an R task
+        // waiting for us, e.g. when the event comes from `show_help_topic()`
+        comm.notify(event)
     }
 
     pub(crate) fn is_help_url(&self, url: &str) -> bool {
@@ -1527,6 +1866,11 @@ This is a Positron limitation we plan to fix. In the meantime, you can:
     }
 }
 
+/// Does `code` need more input to parse? Syntax errors are not incomplete.
+fn is_incomplete_input(code: &str) -> bool {
+    matches!(unsafe { r_parse_vector(code) }, Ok(ParseResult::Incomplete))
+}
+
 /// Report an incomplete request to the frontend
 fn new_incomplete_response(req: &ExecuteRequest, exec_count: u32) -> ExecuteResponse {
     ExecuteResponse::ReplyException(ExecuteReplyException {
@@ -1543,13 +1887,63 @@ fn new_incomplete_response(req: &ExecuteRequest, exec_count: u32) -> ExecuteResp
 static RE_STACK_OVERFLOW: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"C stack usage [ 0-9]+ is too close to the limit\n").unwrap());
 
-fn new_execute_response(exec_count: u32) -> ExecuteResponse {
+fn new_execute_response(exec_count: u32, user_expressions: serde_json::Value) -> ExecuteResponse {
     ExecuteResponse::Reply(ExecuteReply {
         status: Status::Ok,
         execution_count: exec_count,
-        user_expressions: json!({}),
+        user_expressions,
     })
 }
+
+/// Evaluates the `user_expressions` of an `execute_request` in the global
+/// environment. Each expression maps to a result shaped like a
+/// `display_data` on success or like an error reply on failure, as
+/// expected in `execute_reply`.
+fn eval_user_expressions(user_expressions: &serde_json::Value) -> serde_json::Value {
+    let mut results = serde_json::Map::new();
+
+    let Some(user_expressions) = user_expressions.as_object() else {
+        return serde_json::Value::Object(results);
+    };
+
+    for (name, code) in user_expressions.iter() {
+        let code = code.as_str().unwrap_or_default();
+
+        let result = RFunction::from(".ps.console.userExpression")
+            .add(code)
+            .call()
+            .and_then(String::try_from);
+
+        let result = match result {
+            Ok(text) => json!({
+                "status": "ok",
+                "data": { "text/plain": text },
+                "metadata": {},
+            }),
+            Err(err) => {
+                let (ename, evalue) = match err {
+                    harp::Error::TryCatchError { message, class, .. } => {
+                        let ename = class
+                            .and_then(|class| class.into_iter().next())
+                            .unwrap_or_else(|| String::from("error"));
+                        (ename, message)
+                    },
+                    err => (String::from("error"), format!("{err}")),
+                };
+                json!({
+                    "status": "error",
+                    "ename": ename,
+                    "evalue": evalue,
+                    "traceback": [],
+                })
+            },
+        };
+
+        results.insert(name.clone(), result);
+    }
+
+    serde_json::Value::Object(results)
+}
 fn new_execute_response_error(exception: Exception, exec_count: u32) -> ExecuteResponse {
     ExecuteResponse::ReplyException(ExecuteReplyException {
         status: Status::Error,
@@ -1569,6 +1963,16 @@ fn to_html(frame: SEXP) -> Result<String> {
     }
 }
 
+/// Installed packages, along with the state of the library paths they were
+/// collected from. Listing installed packages is slow on networked drives so
+/// we only do it again when the library paths change, e.g. because renv
+/// activated a project library, or when a library directory was modified by
+/// an installation or removal.
+static INSTALLED_PACKAGES: Lazy<Mutex<Option<(Vec<LibraryPathState>, Vec<String>)>>> =
+    Lazy::new(|| Mutex::new(None));
+
+type LibraryPathState = (String, Option<std::time::SystemTime>);
+
 // Inputs generated by `ReadConsole` for the LSP
 pub(crate) fn console_inputs() -> anyhow::Result<ConsoleInputs> {
     // TODO: Should send the debug environment if debugging:
@@ -1576,11 +1980,10 @@ pub(crate) fn console_inputs() -> anyhow::Result<ConsoleInputs> {
     let env = Environment::new(R_ENVS.global.into());
     let scopes = env.ancestors().map(|e| e.names()).collect();
 
-    // Get the set of installed packages
-    let installed_packages: Vec<String> = RFunction::new("base", ".packages")
-        .param("all.available", true)
-        .call()?
-        .try_into()?;
+    // Honor the library paths of the session, which renv and project
+    // `.Rprofile` files may have changed
+    let library_paths: Vec<String> = RFunction::new("base", ".libPaths").call()?.try_into()?;
+    let installed_packages = installed_packages(&library_paths)?;
 
     Ok(ConsoleInputs {
         console_scopes: scopes,
@@ -1588,6 +1991,35 @@ pub(crate) fn console_inputs() -> anyhow::Result<ConsoleInputs> {
     })
 }
 
+fn installed_packages(library_paths: &[String]) -> anyhow::Result<Vec<String>> {
+    let key: Vec<LibraryPathState> = library_paths
+        .iter()
+        .map(|path| {
+            let modified = std::fs::metadata(path).and_then(|x| x.modified()).ok();
+            (path.clone(), modified)
+        })
+        .collect();
+
+    let mut cache = INSTALLED_PACKAGES.lock().unwrap();
+
+    if let Some((cached_key, packages)) = cache.as_ref() {
+        if *cached_key == key {
+            return Ok(packages.clone());
+        }
+    }
+
+    log::trace!("Library paths changed, listing installed packages");
+
+    let packages: Vec<String> = RFunction::new("base", ".packages")
+        .param("all.available", true)
+        .param("lib.loc", library_paths.to_vec())
+        .call()?
+        .try_into()?;
+
+    *cache = Some((key, packages.clone()));
+    Ok(packages)
+}
+
 // --- Frontend methods ---
 // These functions are hooked up as R frontend methods. They call into our
 // global `RMain` singleton.
@@ -1669,19 +2101,37 @@ pub unsafe extern "C" fn r_polled_events() {
 // This hook is called like a user onLoad hook but for every package to be
 // loaded in the session
 #[harp::register]
-unsafe extern "C" fn ps_onload_hook(pkg: SEXP, _path: SEXP) -> anyhow::Result<SEXP> {
-    // NOTE: `_path` might be NULL for a compat reason, see comments on the R side
+unsafe extern "C" fn ps_onload_hook(pkg: SEXP, path: SEXP) -> anyhow::Result<SEXP> {
+    // NOTE: `path` might be NULL for a compat reason, see comments on the R side
 
     let pkg: String = RObject::view(pkg).try_into()?;
+    let path: Option<String> = r_null_or_try_into(RObject::view(path))?;
 
     // Need to reset parent as this might run in the context of another thread's R task
     let _span = tracing::trace_span!(parent: None, "onload_hook", pkg = pkg).entered();
 
-    // Populate fake source refs if needed
-    if do_resource_namespaces() {
+    // The help of a reloaded package may have changed
+    invalidate_package(&pkg);
+
+    // Real source refs for packages loaded with `pkgload::load_all()`
+    let dev_path = path.filter(|_| do_resource_dev_packages());
+
+    // Fake source refs for the functions that still don't have any
+    let resource_namespace = do_resource_namespaces();
+
+    if dev_path.is_some() || resource_namespace {
         r_task::spawn_idle(|| async move {
-            if let Err(err) = ns_populate_srcref(pkg.clone()).await {
-                log::error!("Can't populate srcref for `{pkg}`: {err:?}");
+            // Must run first so that fake source refs don't take precedence
+            if let Some(path) = dev_path {
+                if let Err(err) = dev_package_populate_srcref(&pkg, &path) {
+                    log::error!("Can't populate srcref for dev package `{pkg}`: {err:?}");
+                }
+            }
+
+            if resource_namespace {
+                if let Err(err) = ns_populate_srcref(pkg.clone()).await {
+                    log::error!("Can't populate srcref for `{pkg}`: {err:?}");
+                }
             }
         });
     }
@@ -1689,6 +2139,25 @@ unsafe extern "C" fn ps_onload_hook(pkg: SEXP, _path: SEXP) -> anyhow::Result<SE
     Ok(RObject::null().sexp)
 }
 
+#[harp::register]
+unsafe extern "C" fn ps_session_mode() -> anyhow::Result<SEXP> {
+    let mode = RMain::get().session_mode();
+    Ok(RObject::from(mode.as_str()).sexp)
+}
+
+/// How long to wait on shutdown for IOPub to forward pending outputs,
+/// configurable in seconds with the `ark.shutdown.iopub_timeout` option.
+fn iopub_drain_timeout() -> Duration {
+    let opt: Option<f64> = r_null_or_try_into(harp::get_option("ark.shutdown.iopub_timeout"))
+        .ok()
+        .flatten();
+
+    match opt {
+        Some(secs) if secs.is_finite() && secs >= 0.0 => Duration::from_secs_f64(secs),
+        _ => IOPUB_DRAIN_TIMEOUT,
+    }
+}
+
 fn do_resource_namespaces() -> bool {
     let opt: Option<bool> = r_null_or_try_into(harp::get_option("ark.resource_namespaces"))
         .ok()
@@ -1696,6 +2165,16 @@ fn do_resource_namespaces() -> bool {
     opt.unwrap_or(true)
 }
 
+/// Whether to retain source references of packages loaded in dev mode with
+/// `pkgload::load_all()`. Packages loaded from a library are never affected
+/// so this doesn't cost memory for regular installs.
+fn do_resource_dev_packages() -> bool {
+    let opt: Option<bool> = r_null_or_try_into(harp::get_option("ark.resource_dev_packages"))
+        .ok()
+        .flatten();
+    opt.unwrap_or(true)
+}
+
 /// Are we auto-printing?
 ///
 /// We consider that we are auto-printing when the call stack is empty or when
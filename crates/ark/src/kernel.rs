@@ -115,6 +115,13 @@ impl Kernel {
         Ok(())
     }
 
+    /// Record a working directory change that the frontend already knows
+    /// about because it requested it, so that polling doesn't send it back
+    /// as an event.
+    pub fn sync_working_directory(&mut self, directory: PathBuf) {
+        self.working_directory = directory;
+    }
+
     /// Check if the Positron frontend is connected
     pub fn ui_connected(&self) -> bool {
         self.ui_comm_tx.is_some()
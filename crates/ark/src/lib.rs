@@ -6,6 +6,7 @@
 //
 
 pub mod browser;
+pub mod check;
 pub mod connections;
 pub mod control;
 pub mod dap;
@@ -19,6 +20,8 @@ pub mod json;
 pub mod kernel;
 pub mod logger;
 pub mod logger_hprof;
+pub mod logger_r;
+pub mod logger_rotate;
 pub mod lsp;
 pub mod modules;
 pub mod modules_utils;
@@ -28,6 +31,7 @@ pub mod request;
 pub mod shell;
 pub mod signals;
 pub mod srcref;
+pub mod start;
 pub mod startup;
 pub mod sys;
 pub mod test;
@@ -5,66 +5,154 @@
 //
 //
 
+use std::str::FromStr;
 use std::sync::Once;
 
 use once_cell::sync::OnceCell;
-use regex::Regex;
+use tracing::field::Field;
+use tracing::field::Visit;
+use tracing::Event;
+use tracing::Subscriber;
 use tracing_appender::non_blocking::WorkerGuard;
+use tracing_log::NormalizeEvent;
+use tracing_subscriber::fmt::format;
 use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::fmt::FmtContext;
+use tracing_subscriber::fmt::FormatEvent;
+use tracing_subscriber::fmt::FormatFields;
 use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::EnvFilter;
 use tracing_subscriber::Layer;
 
 use crate::logger_hprof;
+use crate::logger_hprof::ProfileFormat;
+use crate::logger_rotate::LogRotation;
+use crate::logger_rotate::RotatingFile;
 
-pub fn init(log_file: Option<&str>, profile_file: Option<&str>) {
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LogFormat {
+    /// Human-readable multi-line records
+    #[default]
+    Text,
+    /// One JSON object per line, for log aggregators
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("Invalid log format: '{s}' (expected text or json)")),
+        }
+    }
+}
+
+const DEFAULT_LOG_DIRECTIVES: &str = "ark=info";
+
+// Propagate 'ark' verbosity to internal crates that don't have their own
+// directive. Returns the directives to add to the filter.
+fn propagated_directives(spec: &str) -> Vec<String> {
+    let directives: Vec<(&str, Option<&str>)> = spec
+        .split(',')
+        .map(|part| match part.split_once('=') {
+            Some((target, level)) => (target.trim(), Some(level.trim())),
+            None => (part.trim(), None),
+        })
+        .collect();
+
+    let Some(level) = directives
+        .iter()
+        .find_map(|(target, level)| (*target == "ark").then_some(*level).flatten())
+    else {
+        return Vec::new();
+    };
+
+    ["amalthea", "harp", "stdext"]
+        .into_iter()
+        .filter(|pkg| !directives.iter().any(|(target, _)| target == pkg))
+        .map(|pkg| format!("{pkg}={level}"))
+        .collect()
+}
+
+/// Initializes the global logger.
+///
+/// `log_level` takes `RUST_LOG`-style directives, e.g. `"info,ark::lsp=trace"`.
+/// When supplied (from `--log-level`), it takes precedence over the `RUST_LOG`
+/// environment variable, which is otherwise used. If neither is set, or if
+/// the directives are invalid, `ark=info` is used. The level of `ark` is
+/// propagated to our internal crates unless they have their own directive.
+///
+/// The log file is rotated by size when `log_rotation` is supplied.
+pub fn init(
+    log_file: Option<&str>,
+    log_level: Option<&str>,
+    log_rotation: Option<LogRotation>,
+    profile_file: Option<&str>,
+    profile_format: ProfileFormat,
+    format: LogFormat,
+) {
     static ONCE: Once = Once::new();
 
     ONCE.call_once(|| {
-        // Parse `RUST_LOG`
-        let mut env_filter = EnvFilter::from_default_env();
-
-        // Propagate 'ark' verbosity to internal crates
-        let re = Regex::new(r"ark=([a-zA-Z]+)(,|$)").unwrap();
-        let rust_log = std::env::var("RUST_LOG")
-            .ok()
-            .unwrap_or_else(|| String::from("ark=info"));
-        if let Some(level) = re
-            .captures(&rust_log)
-            .and_then(|c| c.get(1))
-            .map(|c| c.as_str())
-        {
-            for pkg in vec!["amalthea", "harp", "stdext"] {
-                if let Ok(directive) = format!("{pkg}={level}").parse() {
-                    env_filter = env_filter.add_directive(directive);
-                }
+        let spec = log_level
+            .map(String::from)
+            .or_else(|| std::env::var("RUST_LOG").ok())
+            .unwrap_or_else(|| String::from(DEFAULT_LOG_DIRECTIVES));
+
+        let (mut env_filter, spec) = match EnvFilter::try_new(&spec) {
+            Ok(env_filter) => (env_filter, spec),
+            Err(err) => {
+                eprintln!(
+                    "Invalid log directives '{spec}': {err}. Using '{DEFAULT_LOG_DIRECTIVES}'."
+                );
+                let spec = String::from(DEFAULT_LOG_DIRECTIVES);
+                (EnvFilter::new(&spec), spec)
+            },
+        };
+
+        for directive in propagated_directives(&spec) {
+            if let Ok(directive) = directive.parse() {
+                env_filter = env_filter.add_directive(directive);
             }
         }
 
         // Spawn appender thread for non-blocking writes
         static mut LOG_GUARD: OnceCell<WorkerGuard> = OnceCell::new();
-        let log_writer = non_blocking(log_file, unsafe { &mut LOG_GUARD });
-
-        let log = tracing_subscriber::fmt::layer()
-            // Use pretty representation. This has more spacing
-            // and a clearer layout for fields.
-            .pretty()
-            // Disable ANSI escapes, those are not supported in Code
-            .with_ansi(false)
-            // Display source code file paths
-            .with_file(true)
-            // Display source code line numbers
-            .with_line_number(true)
-            // Don't display the thread ID
-            .with_thread_ids(false)
-            // Don't display the event's target (module path).
-            // Mostly redundant with file paths.
-            .with_target(false)
-            // Use our custom file writer
-            .with_writer(log_writer)
-            // Filter based on `RUST_LOG` envvar
-            .with_filter(env_filter);
+        let log_writer = non_blocking(log_file, log_rotation, unsafe { &mut LOG_GUARD });
+
+        let log = match format {
+            LogFormat::Text => tracing_subscriber::fmt::layer()
+                // Use pretty representation. This has more spacing
+                // and a clearer layout for fields.
+                .pretty()
+                // Disable ANSI escapes, those are not supported in Code
+                .with_ansi(false)
+                // Display source code file paths
+                .with_file(true)
+                // Display source code line numbers
+                .with_line_number(true)
+                // Don't display the thread ID
+                .with_thread_ids(false)
+                // Don't display the event's target (module path).
+                // Mostly redundant with file paths.
+                .with_target(false)
+                // Use our custom file writer
+                .with_writer(log_writer)
+                // Filter based on `RUST_LOG` envvar
+                .with_filter(env_filter)
+                .boxed(),
+            LogFormat::Json => tracing_subscriber::fmt::layer()
+                .event_format(JsonFormat)
+                .with_ansi(false)
+                .with_writer(log_writer)
+                .with_filter(env_filter)
+                .boxed(),
+        };
 
         // Subscriber for adding span information to errors
         // https://docs.rs/tracing-error/latest/tracing_error
@@ -77,12 +165,17 @@ pub fn init(log_file: Option<&str>, profile_file: Option<&str>) {
         // Only log profile if requested
         if profile_file.is_some() {
             static mut PROFILE_GUARD: OnceCell<WorkerGuard> = OnceCell::new();
-            let profile_writer = non_blocking(profile_file, unsafe { &mut PROFILE_GUARD });
+            let profile_writer = match profile_format {
+                ProfileFormat::Text => {
+                    non_blocking(profile_file, None, unsafe { &mut PROFILE_GUARD })
+                },
+                ProfileFormat::Speedscope => truncating(profile_file),
+            };
 
             // Profile anything taking over 50ms by default
             let config = std::env::var("ARK_PROFILE").unwrap_or("*>50".into());
 
-            let profile = logger_hprof::layer(&config, profile_writer);
+            let profile = logger_hprof::layer(&config, profile_writer, profile_format);
             subscriber.with(profile).try_init().unwrap();
         } else {
             subscriber.try_init().unwrap();
@@ -91,24 +184,154 @@ pub fn init(log_file: Option<&str>, profile_file: Option<&str>) {
 }
 
 // Returns a boxed value for genericity
-fn non_blocking(file: Option<&str>, cell: &mut OnceCell<WorkerGuard>) -> BoxMakeWriter {
-    let file = file.and_then(|file| {
-        std::fs::OpenOptions::new()
+fn non_blocking(
+    file: Option<&str>,
+    rotation: Option<LogRotation>,
+    cell: &mut OnceCell<WorkerGuard>,
+) -> BoxMakeWriter {
+    let Some(file) = file else {
+        return BoxMakeWriter::new(std::io::stderr);
+    };
+
+    let (writer, guard) = match rotation {
+        Some(rotation) => match RotatingFile::open(file, rotation) {
+            Ok(file) => tracing_appender::non_blocking(file),
+            Err(_) => return BoxMakeWriter::new(std::io::stderr),
+        },
+        None => match std::fs::OpenOptions::new()
             .write(true)
             .append(true)
             .create(true)
             .open(file)
-            .ok()
-    });
+        {
+            Ok(file) => tracing_appender::non_blocking(file),
+            Err(_) => return BoxMakeWriter::new(std::io::stderr),
+        },
+    };
+
+    // Save the guard forever
+    cell.set(guard).unwrap();
+
+    BoxMakeWriter::new(writer)
+}
+
+// Speedscope documents can't be appended to, so the profile file is
+// truncated each time the document is rewritten
+fn truncating(file: Option<&str>) -> BoxMakeWriter {
+    let Some(file) = file else {
+        return BoxMakeWriter::new(std::io::stderr);
+    };
+    let file = file.to_string();
+
+    BoxMakeWriter::new(move || -> Box<dyn std::io::Write> {
+        match std::fs::File::create(&file) {
+            Ok(file) => Box::new(file),
+            Err(_) => Box::new(std::io::stderr()),
+        }
+    })
+}
+
+/// Formats each event as a single-line JSON object. Newlines in messages
+/// (e.g. panic backtraces) are escaped by the JSON encoder so that records
+/// can't span several lines.
+struct JsonFormat;
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _ctx: &FmtContext<'_, S, N>,
+        mut writer: format::Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        // Events forwarded from the `log` crate carry their real metadata
+        // in `log.` fields
+        let normalized = event.normalized_metadata();
+        let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+
+        let mut visitor = JsonVisitor::default();
+        event.record(&mut visitor);
+
+        let thread = std::thread::current();
+
+        let mut record = serde_json::Map::new();
+        record.insert(
+            "timestamp".into(),
+            chrono::Utc::now()
+                .to_rfc3339_opts(chrono::SecondsFormat::Micros, true)
+                .into(),
+        );
+        record.insert("level".into(), metadata.level().as_str().into());
+        record.insert("target".into(), metadata.target().into());
+        record.insert("thread".into(), thread.name().unwrap_or("").into());
+        record.insert("message".into(), visitor.message.into());
+
+        if !visitor.fields.is_empty() {
+            record.insert("fields".into(), visitor.fields.into());
+        }
+
+        let record = serde_json::Value::Object(record);
+        writeln!(writer, "{record}")
+    }
+}
+
+#[derive(Default)]
+struct JsonVisitor {
+    message: String,
+    fields: serde_json::Map<String, serde_json::Value>,
+}
 
-    if let Some(file) = file {
-        let (writer, guard) = tracing_appender::non_blocking(file);
+impl Visit for JsonVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record_value(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.record_value(field, format!("{value:?}"));
+    }
+}
+
+impl JsonVisitor {
+    fn record_value(&mut self, field: &Field, value: String) {
+        match field.name() {
+            "message" => self.message = value,
+            // Already part of the normalized metadata
+            name if name.starts_with("log.") => {},
+            name => {
+                self.fields.insert(name.to_string(), value.into());
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::EnvFilter;
+
+    use crate::logger::propagated_directives;
+
+    #[test]
+    fn test_log_directives_validation() {
+        assert!(EnvFilter::try_new("info,ark::lsp=trace").is_ok());
+        assert!(EnvFilter::try_new("ark=loud").is_err());
+    }
 
-        // Save the guard forever
-        cell.set(guard).unwrap();
+    #[test]
+    fn test_propagated_directives() {
+        assert_eq!(
+            propagated_directives("ark=debug,harp=warn"),
+            vec!["amalthea=debug", "stdext=debug"]
+        );
+        assert_eq!(
+            propagated_directives(" ark = trace "),
+            vec!["amalthea=trace", "harp=trace", "stdext=trace"]
+        );
 
-        BoxMakeWriter::new(writer)
-    } else {
-        BoxMakeWriter::new(std::io::stderr)
+        // Module-level directives are not propagated
+        assert!(propagated_directives("ark::lsp=trace").is_empty());
+        assert!(propagated_directives("info").is_empty());
     }
 }
@@ -36,9 +36,21 @@
 //  8.35ms    4      middle
 //    2.13ms    2      leaf
 // ```
-
+//
+// With `ProfileFormat::Speedscope`, the profile file instead contains a
+// single speedscope document in the `evented` schema, with one profile per
+// top-level span. The document is rewritten whole by a background thread
+// after top-level spans close, so the file can be opened at
+// https://www.speedscope.app at any point of the session. Only the most
+// recent profiles are kept to bound memory usage and the cost of rewrites.
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::fmt::Write;
 use std::mem;
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::thread::JoinHandle;
 use std::time::Duration;
 use std::time::Instant;
 
@@ -60,12 +72,36 @@ use tracing_subscriber::Registry;
 
 use crate::logger_hprof;
 
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ProfileFormat {
+    /// Indented tree of span timings
+    #[default]
+    Text,
+    /// A speedscope JSON document with one profile per top-level span
+    Speedscope,
+}
+
+impl FromStr for ProfileFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ProfileFormat::Text),
+            "speedscope" => Ok(ProfileFormat::Speedscope),
+            _ => Err(format!(
+                "Invalid profile format: '{s}' (expected text or speedscope)"
+            )),
+        }
+    }
+}
+
 pub fn init(spec: &str) -> tracing::subscriber::DefaultGuard {
-    let subscriber = Registry::default().with(layer(spec, std::io::stderr));
+    let subscriber =
+        Registry::default().with(layer(spec, std::io::stderr, ProfileFormat::default()));
     tracing::subscriber::set_default(subscriber)
 }
 
-pub fn layer<W, S>(spec: &str, make_writer: W) -> impl Layer<S>
+pub fn layer<W, S>(spec: &str, make_writer: W, format: ProfileFormat) -> impl Layer<S>
 where
     S: Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
     W: for<'writer> MakeWriter<'writer> + 'static + Send + Sync,
@@ -88,10 +124,15 @@ where
             !metadata.target().starts_with("chalk")
     });
 
+    let output = match format {
+        ProfileFormat::Text => Output::Text(make_writer),
+        ProfileFormat::Speedscope => Output::Speedscope(SpeedscopeWriter::new(make_writer)),
+    };
+
     logger_hprof::SpanTree {
         aggregate: false,
         write_filter,
-        make_writer,
+        output,
     }
     .with_filter(profile_filter)
 }
@@ -100,7 +141,13 @@ where
 pub(crate) struct SpanTree<W = fn() -> std::io::Stderr> {
     aggregate: bool,
     write_filter: WriteFilter,
-    make_writer: W,
+    output: Output<W>,
+}
+
+#[derive(Debug)]
+enum Output<W> {
+    Text(W),
+    Speedscope(SpeedscopeWriter),
 }
 
 struct Data {
@@ -128,6 +175,7 @@ impl Data {
         Node {
             name,
             fields: self.fields,
+            start: Some(self.start),
             count: 1,
             duration: self.start.elapsed(),
             children: self.children,
@@ -177,8 +225,17 @@ where
                 if self.aggregate {
                     node.aggregate()
                 }
-                let mut writer = self.make_writer.make_writer();
-                node.print(&self.write_filter, &mut writer)
+                match &self.output {
+                    Output::Text(make_writer) => {
+                        let mut writer = make_writer.make_writer();
+                        node.print(&self.write_filter, &mut writer)
+                    },
+                    Output::Speedscope(writer) => {
+                        if let Some(profile) = node.speedscope_profile(&self.write_filter) {
+                            writer.send(profile);
+                        }
+                    },
+                }
             },
         }
     }
@@ -188,6 +245,8 @@ where
 struct Node {
     name: &'static str,
     fields: String,
+    // `None` for aggregated nodes, which don't have a single start time
+    start: Option<Instant>,
     count: u32,
     duration: Duration,
     children: Vec<Node>,
@@ -228,6 +287,67 @@ impl Node {
         }
     }
 
+    // Returns `None` if the span didn't take long enough to be written
+    fn speedscope_profile(&self, filter: &WriteFilter) -> Option<SpeedscopeProfile> {
+        if self.duration <= filter.longer_than {
+            return None;
+        }
+
+        let mut events = Vec::new();
+        let origin = self.start.unwrap_or_else(Instant::now);
+        self.speedscope_events(0, origin, Duration::ZERO, filter, &mut events);
+
+        Some(SpeedscopeProfile {
+            name: self.name,
+            duration: self.duration,
+            events,
+        })
+    }
+
+    // Aggregated children don't have a start time, so they are laid out
+    // one after the other from `offset`
+    fn speedscope_events(
+        &self,
+        level: usize,
+        origin: Instant,
+        offset: Duration,
+        filter: &WriteFilter,
+        events: &mut Vec<SpeedscopeEvent>,
+    ) {
+        if level > 0 && (self.duration <= filter.longer_than || level >= filter.depth) {
+            return;
+        }
+
+        let name = if self.fields.is_empty() {
+            self.name.to_string()
+        } else {
+            format!("{} @ {}", self.name, self.fields.trim_end())
+        };
+
+        let open = match self.start {
+            Some(start) => start.saturating_duration_since(origin),
+            None => offset,
+        };
+
+        events.push(SpeedscopeEvent {
+            open: true,
+            frame: name.clone(),
+            at: open,
+        });
+
+        let mut child_offset = open;
+        for child in &self.children {
+            child.speedscope_events(level + 1, origin, child_offset, filter, events);
+            child_offset += child.duration;
+        }
+
+        events.push(SpeedscopeEvent {
+            open: false,
+            frame: name,
+            at: open + self.duration,
+        });
+    }
+
     fn aggregate(&mut self) {
         if self.children.is_empty() {
             return;
@@ -239,6 +359,7 @@ impl Node {
             if self.children[idx].name == self.children[i].name {
                 let child = mem::take(&mut self.children[i]);
                 self.children[idx].duration += child.duration;
+                self.children[idx].start = None;
                 self.children[idx].count += child.count;
                 self.children[idx].children.extend(child.children);
             } else {
@@ -254,6 +375,171 @@ impl Node {
     }
 }
 
+/// The number of events kept in the speedscope document. Older profiles are
+/// dropped once the document contains more events than this.
+const SPEEDSCOPE_MAX_EVENTS: usize = 100_000;
+
+/// The profile of a top-level span, before its frames are interned
+#[derive(Debug)]
+struct SpeedscopeProfile {
+    name: &'static str,
+    duration: Duration,
+    events: Vec<SpeedscopeEvent>,
+}
+
+#[derive(Debug)]
+struct SpeedscopeEvent {
+    open: bool,
+    frame: String,
+    at: Duration,
+}
+
+/// Writes the speedscope document on a background thread, so that closing a
+/// top-level span doesn't block on serializing and writing the document.
+/// Profiles closed while the document is being written are coalesced into
+/// the next write.
+#[derive(Debug)]
+struct SpeedscopeWriter {
+    tx: Option<mpsc::Sender<SpeedscopeProfile>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SpeedscopeWriter {
+    fn new<W>(make_writer: W) -> Self
+    where
+        W: for<'writer> MakeWriter<'writer> + 'static + Send,
+    {
+        let (tx, rx) = mpsc::channel::<SpeedscopeProfile>();
+
+        let thread = stdext::spawn!("ark-speedscope", move || {
+            let mut document = SpeedscopeDocument::new(SPEEDSCOPE_MAX_EVENTS);
+
+            while let Ok(profile) = rx.recv() {
+                document.push(profile);
+                for profile in rx.try_iter() {
+                    document.push(profile);
+                }
+
+                let mut writer = make_writer.make_writer();
+                document.print(&mut writer);
+            }
+        });
+
+        Self {
+            tx: Some(tx),
+            thread: Some(thread),
+        }
+    }
+
+    fn send(&self, profile: SpeedscopeProfile) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(profile);
+        }
+    }
+}
+
+impl Drop for SpeedscopeWriter {
+    // Flush the pending profiles
+    fn drop(&mut self) {
+        drop(self.tx.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The most recent profiles of top-level spans, sharing a single frame table
+#[derive(Debug)]
+struct SpeedscopeDocument {
+    frames: Vec<String>,
+    frame_indices: HashMap<String, usize>,
+    profiles: VecDeque<(usize, serde_json::Value)>,
+    n_events: usize,
+    max_events: usize,
+}
+
+impl SpeedscopeDocument {
+    fn new(max_events: usize) -> Self {
+        Self {
+            frames: Vec::new(),
+            frame_indices: HashMap::new(),
+            profiles: VecDeque::new(),
+            n_events: 0,
+            max_events,
+        }
+    }
+
+    fn push(&mut self, profile: SpeedscopeProfile) {
+        let events: Vec<_> = profile
+            .events
+            .into_iter()
+            .map(|event| {
+                serde_json::json!({
+                    "type": if event.open { "O" } else { "C" },
+                    "frame": self.frame(event.frame),
+                    "at": millis(event.at),
+                })
+            })
+            .collect();
+        let n = events.len();
+
+        let profile = serde_json::json!({
+            "type": "evented",
+            "name": profile.name,
+            "unit": "milliseconds",
+            "startValue": 0,
+            "endValue": millis(profile.duration),
+            "events": events,
+        });
+        self.profiles.push_back((n, profile));
+        self.n_events += n;
+
+        // Always keep the latest profile, even if it is too large
+        while self.n_events > self.max_events && self.profiles.len() > 1 {
+            let (n, _) = self.profiles.pop_front().unwrap();
+            self.n_events -= n;
+        }
+    }
+
+    fn frame(&mut self, name: String) -> usize {
+        if let Some(index) = self.frame_indices.get(&name) {
+            return *index;
+        }
+        let index = self.frames.len();
+        self.frames.push(name.clone());
+        self.frame_indices.insert(name, index);
+        index
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let frames: Vec<_> = self
+            .frames
+            .iter()
+            .map(|name| serde_json::json!({ "name": name }))
+            .collect();
+        let profiles: Vec<_> = self.profiles.iter().map(|(_, profile)| profile).collect();
+
+        serde_json::json!({
+            "$schema": "https://www.speedscope.app/file-format-schema.json",
+            "exporter": "ark",
+            "activeProfileIndex": 0,
+            "shared": { "frames": frames },
+            "profiles": profiles,
+        })
+    }
+
+    fn print<W>(&self, writer: &mut W)
+    where
+        W: std::io::Write,
+    {
+        let _ = writeln!(writer, "{}", self.to_json());
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
 #[derive(Default, Clone, Debug)]
 pub(crate) struct WriteFilter {
     depth: usize,
@@ -297,3 +583,130 @@ impl std::fmt::Display for ms {
         write!(f, "{n:5}ms")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::Registry;
+
+    use crate::logger_hprof::layer;
+    use crate::logger_hprof::ProfileFormat;
+    use crate::logger_hprof::SpeedscopeDocument;
+    use crate::logger_hprof::SpeedscopeEvent;
+    use crate::logger_hprof::SpeedscopeProfile;
+
+    /// Output truncated on each `make_writer()` call, like the profile file
+    #[derive(Clone, Default)]
+    struct TestOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for TestOutput {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_speedscope_layer() {
+        let output = TestOutput::default();
+        let make_writer = {
+            let output = output.clone();
+            move || {
+                output.0.lock().unwrap().clear();
+                output.clone()
+            }
+        };
+
+        let profile = layer("*>5", make_writer, ProfileFormat::Speedscope);
+        let subscriber = Registry::default().with(profile);
+
+        let sleep = || std::thread::sleep(Duration::from_millis(10));
+
+        // Dropping the subscriber flushes the profiles
+        tracing::subscriber::with_default(subscriber, || {
+            {
+                let _top = tracing::info_span!("top_level").entered();
+                let _leaf = tracing::info_span!("leaf").entered();
+                sleep();
+            }
+
+            // Too short to be written
+            drop(tracing::info_span!("quick").entered());
+
+            {
+                let _top = tracing::info_span!("top_level").entered();
+                drop(tracing::info_span!("quick").entered());
+                let _other = tracing::info_span!("other", n = 1).entered();
+                sleep();
+            }
+        });
+
+        // The output is a single document containing all the profiles
+        let output = output.0.lock().unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(
+            json["$schema"],
+            "https://www.speedscope.app/file-format-schema.json"
+        );
+        let frames = serde_json::json!([
+            { "name": "top_level" },
+            { "name": "leaf" },
+            { "name": "other @ n = 1" },
+        ]);
+        assert_eq!(json["shared"]["frames"], frames);
+
+        let profiles = json["profiles"].as_array().unwrap();
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(profiles[1]["type"], "evented");
+        assert_eq!(profiles[1]["name"], "top_level");
+        assert!(profiles[1]["endValue"].as_f64().unwrap() >= 10.0);
+
+        let events: Vec<_> = profiles[1]["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|event| format!("{}{}", event["type"].as_str().unwrap(), event["frame"]))
+            .collect();
+        assert_eq!(events, vec!["O0", "O2", "C2", "C0"]);
+    }
+
+    #[test]
+    fn test_speedscope_document_max_events() {
+        let profile = |name: &str| SpeedscopeProfile {
+            name: "top_level",
+            duration: Duration::from_millis(1),
+            events: vec![
+                SpeedscopeEvent {
+                    open: true,
+                    frame: name.to_string(),
+                    at: Duration::ZERO,
+                },
+                SpeedscopeEvent {
+                    open: false,
+                    frame: name.to_string(),
+                    at: Duration::from_millis(1),
+                },
+            ],
+        };
+
+        let mut document = SpeedscopeDocument::new(4);
+        document.push(profile("a"));
+        document.push(profile("b"));
+        document.push(profile("c"));
+
+        // The oldest profile was dropped
+        let json = document.to_json();
+        let profiles = json["profiles"].as_array().unwrap();
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(profiles[0]["events"][0]["frame"], 1);
+        assert_eq!(profiles[1]["events"][0]["frame"], 2);
+    }
+}
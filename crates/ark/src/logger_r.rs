@@ -0,0 +1,90 @@
+//
+// logger_r.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+use harp::environment::R_ENVS;
+use harp::eval::r_parse_eval0;
+use harp::object::RObject;
+use libr::R_NilValue;
+use libr::SEXP;
+
+/// The log target of the diagnostics emitted by R
+pub const R_LOG_TARGET: &str = "ark::r";
+
+/// Whether R conditions are forwarded to the log. Set to `false` with
+/// `--no-log-r-conditions`, and once the REPL is live since R's messages
+/// are then shown in the console.
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Forwards R messages and warnings to the log under `R_LOG_TARGET`. The
+/// handlers don't muffle the conditions, so R and Ark's own handlers still
+/// see them.
+const R_LOG_HANDLERS: &str = r#"
+local({
+    # `globalCallingHandlers()` was added in R 4.0.0
+    if (!exists("globalCallingHandlers", baseenv())) {
+        return(FALSE)
+    }
+
+    handler <- function(cnd) {
+        kind <- if (inherits(cnd, "warning")) "warning" else "message"
+        try(
+            .Call("ps_log_r_condition", kind, conditionMessage(cnd), PACKAGE = "(embedding)"),
+            silent = TRUE
+        )
+    }
+    globalCallingHandlers(message = handler, warning = handler)
+    TRUE
+})
+"#;
+
+/// Installs global calling handlers that forward R conditions to the log.
+///
+/// Should be called as early as possible during startup, after routines
+/// are registered, so that diagnostics from the startup files and profiles
+/// are interleaved with ours in the log.
+pub fn install() {
+    if !enabled() {
+        return;
+    }
+
+    match r_parse_eval0(R_LOG_HANDLERS, R_ENVS.base).and_then(|x| x.try_into()) {
+        Ok(true) => log::trace!("Forwarding R conditions to the log"),
+        Ok(false) => log::info!("Can't forward R conditions to the log on R < 4.0.0"),
+        Err(err) => log::error!("Can't forward R conditions to the log: {err:?}"),
+    }
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Stops forwarding R conditions to the log. The handlers stay installed
+/// but become no-ops.
+pub fn disable() {
+    ENABLED.store(false, Ordering::Relaxed);
+}
+
+#[harp::register]
+unsafe extern "C" fn ps_log_r_condition(kind: SEXP, message: SEXP) -> anyhow::Result<SEXP> {
+    if !enabled() {
+        return Ok(R_NilValue);
+    }
+
+    let kind: String = RObject::view(kind).try_into()?;
+    let message: String = RObject::view(message).try_into()?;
+    let message = message.trim_end();
+
+    match kind.as_str() {
+        "warning" => log::warn!(target: R_LOG_TARGET, "{message}"),
+        _ => log::info!(target: R_LOG_TARGET, "{message}"),
+    }
+
+    Ok(R_NilValue)
+}
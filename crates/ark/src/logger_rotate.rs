@@ -0,0 +1,174 @@
+//
+// logger_rotate.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Size-based rotation settings for the log file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogRotation {
+    /// Rotate once the log file would grow beyond this many bytes
+    pub max_size: u64,
+    /// Number of rotated files (`FILE.1`, ..., `FILE.N`) to keep
+    pub max_files: usize,
+}
+
+/// A log file that is rotated `FILE` -> `FILE.1` -> `FILE.2` ... when it
+/// reaches `max_size`. Files beyond `max_files` are deleted.
+///
+/// This writer is owned by the non-blocking appender's worker thread, which
+/// serialises the writes of all logging threads, so rotation never races
+/// with a concurrent write.
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    rotation: LogRotation,
+}
+
+impl RotatingFile {
+    pub fn open(path: impl Into<PathBuf>, rotation: LogRotation) -> std::io::Result<Self> {
+        let path = path.into();
+        let file = open_append(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            file,
+            size,
+            rotation,
+        })
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{index}"));
+        PathBuf::from(path)
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file.flush()?;
+
+        if self.rotation.max_files == 0 {
+            // Nothing to keep, start over
+            self.file = File::create(&self.path)?;
+            self.size = 0;
+            return Ok(());
+        }
+
+        // Files beyond the limit may exist if `max_files` was lowered
+        // between sessions, only the last one is overwritten here
+        let oldest = self.rotated_path(self.rotation.max_files);
+        if oldest.exists() {
+            std::fs::remove_file(&oldest)?;
+        }
+
+        for index in (1..self.rotation.max_files).rev() {
+            let from = self.rotated_path(index);
+            if from.exists() {
+                std::fs::rename(&from, self.rotated_path(index + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, self.rotated_path(1))?;
+
+        self.file = open_append(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        // Records are never split across files, so a single record larger
+        // than `max_size` still ends up in a file of its own
+        if self.size > 0 && self.size + buf.len() as u64 > self.rotation.max_size {
+            if let Err(err) = self.rotate() {
+                // Keep logging to the current file rather than losing records
+                eprintln!("Can't rotate log file '{}': {err}", self.path.display());
+            }
+        }
+
+        let n = self.file.write(buf)?;
+        self.size += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn open_append(path: &PathBuf) -> std::io::Result<File> {
+    std::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use crate::logger_rotate::LogRotation;
+    use crate::logger_rotate::RotatingFile;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ark-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_rotating_file() {
+        let dir = test_dir("log-rotation");
+        let path = dir.join("ark.log");
+
+        let rotation = LogRotation {
+            max_size: 10,
+            max_files: 2,
+        };
+        let mut file = RotatingFile::open(&path, rotation).unwrap();
+
+        for record in ["aaaaaaaa\n", "bbbbbbbb\n", "cccccccc\n", "dddddddd\n"] {
+            file.write_all(record.as_bytes()).unwrap();
+        }
+        file.flush().unwrap();
+
+        let read = |path: PathBuf| std::fs::read_to_string(path).unwrap();
+        assert_eq!(read(path.clone()), "dddddddd\n");
+        assert_eq!(read(dir.join("ark.log.1")), "cccccccc\n");
+        assert_eq!(read(dir.join("ark.log.2")), "bbbbbbbb\n");
+        assert!(!dir.join("ark.log.3").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rotating_file_appends_to_existing_file() {
+        let dir = test_dir("log-rotation-existing");
+        let path = dir.join("ark.log");
+        std::fs::write(&path, "existing\n").unwrap();
+
+        let rotation = LogRotation {
+            max_size: 12,
+            max_files: 1,
+        };
+        let mut file = RotatingFile::open(&path, rotation).unwrap();
+        file.write_all(b"new\n").unwrap();
+        file.flush().unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new\n");
+        assert_eq!(
+            std::fs::read_to_string(dir.join("ark.log.1")).unwrap(),
+            "existing\n"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -87,10 +87,14 @@ pub(crate) enum LspRequest {
     GotoDefinition(GotoDefinitionParams),
     GotoImplementation(GotoImplementationParams),
     SelectionRange(SelectionRangeParams),
+    FoldingRange(FoldingRangeParams),
     References(ReferenceParams),
+    Rename(RenameParams),
     StatementRange(StatementRangeParams),
     HelpTopic(HelpTopicParams),
     OnTypeFormatting(DocumentOnTypeFormattingParams),
+    Formatting(DocumentFormattingParams),
+    RangeFormatting(DocumentRangeFormattingParams),
     VirtualDocument(VirtualDocumentParams),
 }
 
@@ -108,10 +112,14 @@ pub(crate) enum LspResponse {
     GotoDefinition(Option<GotoDefinitionResponse>),
     GotoImplementation(Option<GotoImplementationResponse>),
     SelectionRange(Option<Vec<SelectionRange>>),
+    FoldingRange(Option<Vec<FoldingRange>>),
     References(Option<Vec<Location>>),
+    Rename(Option<WorkspaceEdit>),
     StatementRange(Option<StatementRangeResponse>),
     HelpTopic(Option<HelpTopicResponse>),
     OnTypeFormatting(Option<Vec<TextEdit>>),
+    Formatting(Option<Vec<TextEdit>>),
+    RangeFormatting(Option<Vec<TextEdit>>),
     VirtualDocument(VirtualDocumentResponse),
 }
 
@@ -283,6 +291,13 @@ impl LanguageServer for Backend {
         )
     }
 
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        cast_response!(
+            self.request(LspRequest::FoldingRange(params)).await,
+            LspResponse::FoldingRange
+        )
+    }
+
     async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
         cast_response!(
             self.request(LspRequest::References(params)).await,
@@ -290,6 +305,13 @@ impl LanguageServer for Backend {
         )
     }
 
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        cast_response!(
+            self.request(LspRequest::Rename(params)).await,
+            LspResponse::Rename
+        )
+    }
+
     async fn on_type_formatting(
         &self,
         params: DocumentOnTypeFormattingParams,
@@ -299,6 +321,23 @@ impl LanguageServer for Backend {
             LspResponse::OnTypeFormatting
         )
     }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        cast_response!(
+            self.request(LspRequest::Formatting(params)).await,
+            LspResponse::Formatting
+        )
+    }
+
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        cast_response!(
+            self.request(LspRequest::RangeFormatting(params)).await,
+            LspResponse::RangeFormatting
+        )
+    }
 }
 
 // Custom methods for the backend.
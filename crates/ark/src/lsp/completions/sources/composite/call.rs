@@ -257,7 +257,9 @@ fn completions_from_workspace_arguments(
     let mut completions = vec![];
 
     match entry.data {
-        indexer::IndexEntryData::Function { name, arguments } => {
+        indexer::IndexEntryData::Function {
+            name, arguments, ..
+        } => {
             for argument in arguments {
                 match completion_item_from_parameter(argument.as_str(), name.as_str(), context) {
                     Ok(item) => completions.push(item),
@@ -265,7 +267,10 @@ fn completions_from_workspace_arguments(
                 }
             }
         },
-        indexer::IndexEntryData::Section { level: _, title: _ } => {
+        indexer::IndexEntryData::Section { .. } |
+        indexer::IndexEntryData::Variable { .. } |
+        indexer::IndexEntryData::Method { .. } |
+        indexer::IndexEntryData::Class { .. } => {
             // Not a function
             return Ok(None);
         },
@@ -6,22 +6,40 @@
 //
 
 use anyhow::Result;
-use harp::error::Error;
-use harp::eval::r_parse_eval;
-use harp::eval::RParseEvalOptions;
 use harp::object::RObject;
-use stdext::local;
-use stdext::IntoOption;
 use tower_lsp::lsp_types::CompletionItem;
 use tree_sitter::Node;
 
 use crate::lsp::completions::sources::utils::completions_from_object_names;
+use crate::lsp::completions::sources::utils::resolve_object;
 use crate::lsp::document_context::DocumentContext;
+use crate::lsp::traits::point::PointExt;
 use crate::lsp::traits::rope::RopeExt;
 use crate::treesitter::BinaryOperatorType;
 use crate::treesitter::NodeType;
 use crate::treesitter::NodeTypeExt;
 
+/// Verbs that evaluate their arguments in the context of the data frame
+/// passed as first argument
+const DATA_MASKING_VERBS: &[&str] = &[
+    "arrange",
+    "count",
+    "distinct",
+    "filter",
+    "group_by",
+    "mutate",
+    "pull",
+    "reframe",
+    "relocate",
+    "rename",
+    "select",
+    "slice_max",
+    "slice_min",
+    "summarise",
+    "summarize",
+    "transmute",
+];
+
 #[derive(Clone)]
 pub(super) struct PipeRoot {
     pub(super) name: String,
@@ -84,61 +102,57 @@ pub(super) fn find_pipe_root(context: &DocumentContext) -> Option<PipeRoot> {
         return None;
     }
 
-    let name = find_pipe_root_name(context, &node);
+    let root = match find_pipe_root_node(context, node) {
+        Some(root) => pipe_root_lhs(context, root),
+        None => data_masking_argument(context, &node),
+    }?;
 
-    let object = match &name {
-        Some(name) => eval_pipe_root(name),
-        None => None,
-    };
+    let contents = &context.document.contents;
+    let name = contents.node_slice(&root).ok()?.to_string();
+    let object = resolve_object(&root, contents);
 
-    name.map(|name| PipeRoot { name, object })
+    Some(PipeRoot { name, object })
 }
 
-fn eval_pipe_root(name: &str) -> Option<RObject> {
-    let options = RParseEvalOptions {
-        forbid_function_calls: true,
-        ..Default::default()
-    };
-
-    let value = r_parse_eval(name, options);
-
-    // If we get an `UnsafeEvaluationError` here from setting
-    // `forbid_function_calls`, we don't want that to prevent
-    // other sources from contributing completions
-    let value = match value {
-        Ok(value) => value,
-        Err(err) => match err {
-            Error::UnsafeEvaluationError(_) => return None,
-            _ => {
-                log::error!("{err:?}");
-                return None;
-            },
-        },
-    };
-
-    Some(value)
+/// Get the left-hand side of the outermost pipe in the chain starting at `root`
+fn pipe_root_lhs<'a>(context: &DocumentContext, root: Node<'a>) -> Option<Node<'a>> {
+    let mut lhs = root.child_by_field_name("lhs")?;
+    while is_pipe_operator(context, &lhs) {
+        lhs = lhs.child_by_field_name("lhs")?;
+    }
+    Some(lhs)
 }
 
-fn find_pipe_root_name(context: &DocumentContext, node: &Node) -> Option<String> {
-    // Try to figure out the code associated with the 'root' of the pipe expression.
-    let root = local! {
+/// Outside of pipes, data-masking verbs like `dplyr::mutate(df, <here>)` take
+/// the data frame whose columns are in scope as their first argument
+fn data_masking_argument<'a>(context: &DocumentContext, call: &Node<'a>) -> Option<Node<'a>> {
+    let contents = &context.document.contents;
 
-        let root = find_pipe_root_node(context, *node)?;
-        is_pipe_operator(context, &root).into_option()?;
+    let callee = call.child_by_field_name("function")?;
+    let callee = contents.node_slice(&callee).ok()?.to_string();
+    let callee = callee.strip_prefix("dplyr::").unwrap_or(callee.as_str());
 
-        // Get the left-hand side of the pipe expression.
-        let mut lhs = root.child_by_field_name("lhs")?;
-        while is_pipe_operator(context, &lhs) {
-            lhs = lhs.child_by_field_name("lhs")?;
-        }
+    if !DATA_MASKING_VERBS.contains(&callee) {
+        return None;
+    }
 
-        // Try to evaluate the left-hand side
-        let root = context.document.contents.node_slice(&lhs).ok()?.to_string();
-        Some(root)
+    let arguments = call.child_by_field_name("arguments")?;
+    let mut cursor = arguments.walk();
+    let first = arguments
+        .children_by_field_name("argument", &mut cursor)
+        .next()?;
 
-    };
+    // Named first arguments (e.g. `.data = df`) are not supported
+    if first.child_by_field_name("name").is_some() {
+        return None;
+    }
 
-    root.map(|x| x.to_string())
+    // Don't complete columns while the data argument itself is being typed
+    if context.point.is_before_or_equal(first.end_position()) {
+        return None;
+    }
+
+    first.child_by_field_name("value")
 }
 
 fn find_pipe_root_node<'a>(context: &DocumentContext, mut node: Node<'a>) -> Option<Node<'a>> {
@@ -246,4 +260,38 @@ mod tests {
             r_parse_eval("remove(x)", options.clone()).unwrap();
         });
     }
+
+    #[test]
+    fn test_find_pipe_root_in_data_masking_verbs() {
+        r_test(|| {
+            let options = RParseEvalOptions {
+                forbid_function_calls: false,
+                ..Default::default()
+            };
+            r_parse_eval("x <- data.frame(a = 1)", options.clone()).unwrap();
+
+            // Place cursor after the comma
+            let point = Point { row: 0, column: 16 };
+            let document = Document::new("dplyr::mutate(x, )", None);
+            let context = DocumentContext::new(&document, point, None);
+
+            let root = find_pipe_root(&context).unwrap();
+            assert_eq!(root.name, "x".to_string());
+            assert!(root.object.is_some());
+
+            // Not while typing the data argument itself
+            let point = Point { row: 0, column: 15 };
+            let context = DocumentContext::new(&document, point, None);
+            assert!(find_pipe_root(&context).is_none());
+
+            // Not for other functions
+            let point = Point { row: 0, column: 6 };
+            let document = Document::new("foo(x, )", None);
+            let context = DocumentContext::new(&document, point, None);
+            assert!(find_pipe_root(&context).is_none());
+
+            // Clean up
+            r_parse_eval("remove(x)", options.clone()).unwrap();
+        });
+    }
 }
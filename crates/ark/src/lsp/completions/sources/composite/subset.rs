@@ -13,7 +13,6 @@ use tree_sitter::Point;
 use crate::lsp::completions::sources::utils::completions_from_evaluated_object_names;
 use crate::lsp::document_context::DocumentContext;
 use crate::lsp::traits::point::PointExt;
-use crate::lsp::traits::rope::RopeExt;
 use crate::treesitter::NodeType;
 use crate::treesitter::NodeTypeExt;
 
@@ -68,9 +67,7 @@ pub(super) fn completions_from_subset(
         return Ok(Some(vec![]));
     };
 
-    let text = context.document.contents.node_slice(&child)?.to_string();
-
-    completions_from_evaluated_object_names(&text, ENQUOTE)
+    completions_from_evaluated_object_names(&child, &context.document.contents, ENQUOTE)
 }
 
 fn is_within_subset_delimiters(x: &Point, subset_node: &Node, subset_type: &NodeType) -> bool {
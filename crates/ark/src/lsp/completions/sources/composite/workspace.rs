@@ -62,7 +62,11 @@ pub(super) fn completions_from_workspace(
         }
 
         match &entry.data {
-            indexer::IndexEntryData::Function { name, arguments } => {
+            indexer::IndexEntryData::Function {
+                name,
+                arguments,
+                documentation,
+            } => {
                 let mut completion = unwrap!(completion_item_from_function(name, None, arguments), Err(error) => {
                     error!("{:?}", error);
                     return;
@@ -81,11 +85,14 @@ pub(super) fn completions_from_workspace(
                     }
                 }
 
-                let value = format!(
+                let mut value = format!(
                     "Defined in `{}` on line {}.",
                     path,
                     entry.range.start.line + 1
                 );
+                if let Some(documentation) = documentation {
+                    value = format!("{documentation}\n\n{value}");
+                }
                 let markup = MarkupContent {
                     kind: MarkupKind::Markdown,
                     value,
@@ -95,7 +102,10 @@ pub(super) fn completions_from_workspace(
                 completions.push(completion);
             },
 
-            indexer::IndexEntryData::Section { level: _, title: _ } => {},
+            indexer::IndexEntryData::Section { .. } |
+            indexer::IndexEntryData::Variable { .. } |
+            indexer::IndexEntryData::Method { .. } |
+            indexer::IndexEntryData::Class { .. } => {},
         }
     });
 
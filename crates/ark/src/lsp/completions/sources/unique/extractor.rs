@@ -6,19 +6,20 @@
 //
 
 use anyhow::Result;
-use harp::eval::r_parse_eval;
-use harp::eval::RParseEvalOptions;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
 use harp::r_symbol;
 use harp::utils::r_env_has;
+use harp::utils::r_is_data_frame;
 use harp::utils::r_typeof;
-use harp::Error;
 use libr::STRSXP;
+use ropey::Rope;
 use tower_lsp::lsp_types::CompletionItem;
+use tower_lsp::lsp_types::CompletionItemKind;
 use tree_sitter::Node;
 
 use crate::lsp::completions::completion_item::completion_item_from_data_variable;
+use crate::lsp::completions::sources::utils::resolve_object;
 use crate::lsp::completions::sources::utils::set_sort_text_by_first_appearance;
 use crate::lsp::document_context::DocumentContext;
 use crate::lsp::traits::rope::RopeExt;
@@ -68,10 +69,11 @@ fn completions_from_extractor(
         return Ok(Some(completions));
     };
 
-    // Extract out its name from the document
-    let text = context.document.contents.node_slice(&node)?.to_string();
-
-    completions.append(&mut completions_from_extractor_object(text.as_str(), fun)?);
+    completions.append(&mut completions_from_extractor_object(
+        &node,
+        &context.document.contents,
+        fun,
+    )?);
 
     Ok(Some(completions))
 }
@@ -103,7 +105,15 @@ fn locate_extractor_node(node: Node, node_type: NodeType) -> Option<Node> {
     }
 }
 
-fn completions_from_extractor_object(text: &str, fun: &str) -> Result<Vec<CompletionItem>> {
+fn completions_from_extractor_object(
+    node: &Node,
+    contents: &Rope,
+    fun: &str,
+) -> Result<Vec<CompletionItem>> {
+    // Extract out its name from the document
+    let text = contents.node_slice(node)?.to_string();
+    let text = text.as_str();
+
     log::info!("completions_from_extractor_object({text:?}, {fun:?})");
 
     const ENQUOTE: bool = false;
@@ -119,23 +129,15 @@ fn completions_from_extractor_object(text: &str, fun: &str) -> Result<Vec<Comple
             return Ok(completions);
         }
 
-        let options = RParseEvalOptions {
-            forbid_function_calls: true,
-            ..Default::default()
-        };
-
-        let object = match r_parse_eval(text, options) {
-            Ok(object) => object,
-            Err(err) => match err {
-                // LHS of the call was too complex to evaluate. This is fine, we know
-                // we are on the RHS of a `$` or `@`, so we return an empty "unique"
-                // completion list to stop the completions search.
-                Error::UnsafeEvaluationError(_) => return Ok(completions),
-                // LHS of the call evaluated to an error. Totally possible if the
-                // user is writing pseudocode. Don't want to propagate an error here.
-                _ => return Ok(completions),
-            },
+        // The LHS might not exist (totally possible if the user is writing
+        // pseudocode) or might be too complex to resolve without side effects.
+        // This is fine, we know we are on the RHS of a `$` or `@`, so we
+        // return an empty "unique" completion list to stop the completions
+        // search.
+        let Some(object) = resolve_object(node, contents) else {
+            return Ok(completions);
         };
+        let is_data_frame = r_is_data_frame(object.sexp);
 
         let names = RFunction::new("utils", fun).add(object).call()?;
 
@@ -148,7 +150,12 @@ fn completions_from_extractor_object(text: &str, fun: &str) -> Result<Vec<Comple
 
         for name in names {
             match completion_item_from_data_variable(&name, text, ENQUOTE) {
-                Ok(item) => completions.push(item),
+                Ok(mut item) => {
+                    if is_data_frame {
+                        item.kind = Some(CompletionItemKind::FIELD);
+                    }
+                    completions.push(item)
+                },
                 Err(err) => log::error!("{err:?}"),
             }
         }
@@ -166,6 +173,7 @@ mod tests {
     use harp::eval::r_parse_eval;
     use harp::eval::RParseEvalOptions;
     use harp::object::r_lgl_get;
+    use tower_lsp::lsp_types::CompletionItemKind;
 
     use crate::lsp::completions::sources::unique::extractor::completions_from_dollar;
     use crate::lsp::document_context::DocumentContext;
@@ -250,8 +258,8 @@ mod tests {
             let context = DocumentContext::new(&document, point, None);
 
             // No error and empty completions list
-            // We know we are on the RHS of a `$`, but `r_parse_eval()` will fail on the
-            // LHS "object" because it is too complex, so the right thing to do is to
+            // We know we are on the RHS of a `$`, but we can't resolve the LHS
+            // "object" because it is too complex, so the right thing to do is to
             // return an empty completion set to prevent other completion sources from
             // running.
             let completions = completions_from_dollar(&context).unwrap().unwrap();
@@ -259,6 +267,61 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_dollar_completions_on_data_frame_columns() {
+        r_test(|| {
+            let options = RParseEvalOptions {
+                forbid_function_calls: false,
+                ..Default::default()
+            };
+
+            r_parse_eval(
+                "foo <- list(df = data.frame(x = 1, y = 2))",
+                options.clone(),
+            )
+            .unwrap();
+
+            let (text, point) = point_from_cursor("foo$df$@");
+            let document = Document::new(text.as_str(), None);
+            let context = DocumentContext::new(&document, point, None);
+
+            let completions = completions_from_dollar(&context).unwrap().unwrap();
+            assert_eq!(completions.len(), 2);
+            assert_eq!(completions[0].label, "x".to_string());
+            assert_eq!(completions[0].kind, Some(CompletionItemKind::FIELD));
+
+            r_parse_eval("remove(foo)", options.clone()).unwrap();
+        })
+    }
+
+    #[test]
+    fn test_dollar_completions_dont_force_promises() {
+        r_test(|| {
+            let options = RParseEvalOptions {
+                forbid_function_calls: false,
+                ..Default::default()
+            };
+
+            r_parse_eval(
+                "delayedAssign('foo', {forced <- TRUE; data.frame(x = 1)})",
+                options.clone(),
+            )
+            .unwrap();
+
+            let (text, point) = point_from_cursor("foo$@");
+            let document = Document::new(text.as_str(), None);
+            let context = DocumentContext::new(&document, point, None);
+
+            let completions = completions_from_dollar(&context).unwrap().unwrap();
+            assert_eq!(completions.len(), 0);
+
+            let forced = r_parse_eval("exists('forced')", options.clone()).unwrap();
+            assert_eq!(r_lgl_get(forced.sexp, 0), 0);
+
+            r_parse_eval("remove(foo)", options.clone()).unwrap();
+        })
+    }
+
     #[test]
     fn test_dollar_completions_before_the_dollar() {
         r_test(|| {
@@ -6,14 +6,26 @@
 //
 
 use anyhow::Result;
-use harp::error::Error;
-use harp::eval::r_parse_eval;
-use harp::eval::RParseEvalOptions;
+use harp::environment::Environment;
+use harp::environment::R_ENVS;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
+use harp::object::list_get;
 use harp::object::RObject;
+use harp::symbol::RSymbol;
+use harp::utils::r_is_data_frame;
+use harp::utils::r_is_promise;
+use harp::utils::r_names2;
+use harp::utils::r_promise_force_with_rollback;
+use harp::utils::r_promise_is_forced;
+use harp::utils::r_promise_is_lazy_load_binding;
+use harp::utils::r_promise_value;
+use harp::utils::r_typeof;
+use libr::VECSXP;
 use regex::Regex;
+use ropey::Rope;
 use tower_lsp::lsp_types::CompletionItem;
+use tower_lsp::lsp_types::CompletionItemKind;
 use tree_sitter::Node;
 use tree_sitter::Point;
 
@@ -22,6 +34,7 @@ use crate::lsp::document_context::DocumentContext;
 use crate::lsp::traits::node::NodeExt;
 use crate::lsp::traits::point::PointExt;
 use crate::lsp::traits::rope::RopeExt;
+use crate::treesitter::ExtractOperatorType;
 use crate::treesitter::NodeType;
 use crate::treesitter::NodeTypeExt;
 
@@ -169,39 +182,137 @@ fn call_prev_leaf_position_type(node: &Node, allow_ambiguous: bool) -> CallNodeP
 }
 
 pub(super) fn completions_from_evaluated_object_names(
-    name: &str,
+    node: &Node,
+    contents: &Rope,
     enquote: bool,
 ) -> Result<Option<Vec<CompletionItem>>> {
+    let name = contents.node_slice(node)?.to_string();
     log::info!("completions_from_evaluated_object_names({name:?})");
 
-    let options = RParseEvalOptions {
-        forbid_function_calls: true,
-        ..Default::default()
+    // If the user is writing pseudocode, this object might not exist yet, or
+    // it might be too complex to resolve safely. In both cases we just
+    // provide typical completions.
+    let Some(object) = resolve_object(node, contents) else {
+        return Ok(None);
     };
 
-    // Try to evaluate the object
-    let object = r_parse_eval(name, options);
-
-    // If the user is writing pseudocode, this object might not exist yet,
-    // in which case we just want to ignore the error from trying to evaluate it
-    // and just provide typical completions.
-    // If we get an `UnsafeEvaluationError` here from setting
-    // `forbid_function_calls`, we don't even log that one, as that is
-    // expected to happen with complex inputs.
-    let object = match object {
-        Ok(object) => object,
-        Err(err) => match err {
-            Error::UnsafeEvaluationError(_) => return Ok(None),
-            _ => {
-                log::info!(
-                    "completions_from_evaluated_object_names(): Failed to evaluate first argument: {err}"
-                );
-                return Ok(None);
-            },
+    Ok(Some(completions_from_object_names(
+        object,
+        name.as_str(),
+        enquote,
+    )?))
+}
+
+/// Resolve the object that `node` refers to in the global environment
+///
+/// Unlike evaluating the code of `node`, this never has side effects. Only
+/// symbols and chains of `$` and `[[` extractions with literal names are
+/// resolved:
+///
+/// - Symbols bound to active bindings or to promises that haven't been forced
+///   yet are not resolved. Lazy-loaded data is the exception.
+/// - Extraction only descends into lists and data frames, and is done
+///   without dispatching to `$` or `[[` methods.
+pub(super) fn resolve_object(node: &Node, contents: &Rope) -> Option<RObject> {
+    match node.node_type() {
+        NodeType::Identifier => resolve_symbol(contents.node_slice(node).ok()?.to_string()),
+
+        NodeType::ExtractOperator(ExtractOperatorType::Dollar) => {
+            let lhs = node.child_by_field_name("lhs")?;
+            let rhs = node.child_by_field_name("rhs")?;
+            if !rhs.is_identifier_or_string() {
+                return None;
+            }
+
+            let object = resolve_object(&lhs, contents)?;
+            let name = node_name(&rhs, contents)?;
+            list_get_by_name(object, name.as_str())
         },
-    };
 
-    Ok(Some(completions_from_object_names(object, name, enquote)?))
+        NodeType::Subset2 => {
+            let lhs = node.child_by_field_name("function")?;
+            let arguments = node.child_by_field_name("arguments")?;
+
+            let mut cursor = arguments.walk();
+            let mut arguments = arguments.children_by_field_name("argument", &mut cursor);
+            let argument = arguments.next()?;
+            if arguments.next().is_some() || argument.child_by_field_name("name").is_some() {
+                return None;
+            }
+
+            let value = argument.child_by_field_name("value")?;
+            if !value.is_string() {
+                return None;
+            }
+
+            let object = resolve_object(&lhs, contents)?;
+            let name = node_name(&value, contents)?;
+            list_get_by_name(object, name.as_str())
+        },
+
+        _ => None,
+    }
+}
+
+fn resolve_symbol(name: String) -> Option<RObject> {
+    let global = Environment::view(R_ENVS.global);
+
+    for env in global.ancestors() {
+        if !env.exists(name.as_str()) {
+            continue;
+        }
+
+        // Active bindings run arbitrary code when accessed
+        if env.is_active(RSymbol::from(name.as_str())).ok()? {
+            return None;
+        }
+
+        let value = env.find(name.as_str()).ok()?;
+
+        if r_is_promise(value) {
+            if r_promise_is_forced(value) {
+                return Some(RObject::new(r_promise_value(value)));
+            }
+
+            // Lazy-loaded data, e.g. from the datasets package, can be
+            // loaded without side effects
+            if unsafe { r_promise_is_lazy_load_binding(value) } {
+                return r_promise_force_with_rollback(value).ok();
+            }
+
+            return None;
+        }
+
+        return Some(RObject::new(value));
+    }
+
+    None
+}
+
+fn list_get_by_name(object: RObject, name: &str) -> Option<RObject> {
+    // Objects with a class other than data frames might implement methods
+    // for `$` or `[[` that we don't want to bypass
+    if r_typeof(object.sexp) != VECSXP || (object.is_object() && !r_is_data_frame(object.sexp)) {
+        return None;
+    }
+
+    let names = RObject::view(r_names2(object.sexp));
+    let names: Vec<Option<String>> = names.try_into().ok()?;
+
+    let index = names.iter().position(|x| x.as_deref() == Some(name))?;
+
+    Some(RObject::new(list_get(object.sexp, index as isize)))
+}
+
+fn node_name(node: &Node, contents: &Rope) -> Option<String> {
+    let text = contents.node_slice(node).ok()?.to_string();
+
+    if node.is_string() {
+        let text = text.trim_matches(|c| c == '"' || c == '\'');
+        return Some(text.to_string());
+    }
+
+    Some(text.trim_matches('`').to_string())
 }
 
 pub(super) fn completions_from_object_names(
@@ -219,9 +330,16 @@ pub(super) fn completions_from_object_names(
             .call()?
             .to::<Vec<String>>()?;
 
+        let is_data_frame = r_is_data_frame(object.sexp);
+
         for variable_name in variable_names {
             match completion_item_from_data_variable(&variable_name, name, enquote) {
-                Ok(item) => completions.push(item),
+                Ok(mut item) => {
+                    if is_data_frame {
+                        item.kind = Some(CompletionItemKind::FIELD);
+                    }
+                    completions.push(item)
+                },
                 Err(err) => log::error!("{err:?}"),
             }
         }
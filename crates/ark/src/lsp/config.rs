@@ -4,11 +4,14 @@ use struct_field_names_as_array::FieldNamesAsArray;
 
 use crate::lsp;
 use crate::lsp::diagnostics::DiagnosticsConfig;
+use crate::lsp::formatting::FormatterBackend;
+use crate::lsp::formatting::FormattingConfig;
 
 /// Configuration of the LSP
 #[derive(Clone, Debug)]
 pub(crate) struct LspConfig {
     pub(crate) diagnostics: DiagnosticsConfig,
+    pub(crate) formatting: FormattingConfig,
 }
 
 /// Configuration of a document.
@@ -51,6 +54,13 @@ pub(crate) struct VscDocumentConfig {
 pub(crate) struct VscDiagnosticsConfig {
     // DEV NOTE: Update `section_from_key()` method after adding a field
     pub enable: bool,
+    pub lintr: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, FieldNamesAsArray, Clone, Debug)]
+pub(crate) struct VscFormattingConfig {
+    // DEV NOTE: Update `section_from_key()` method after adding a field
+    pub formatter: FormatterBackend,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -64,6 +74,7 @@ impl Default for LspConfig {
     fn default() -> Self {
         Self {
             diagnostics: Default::default(),
+            formatting: Default::default(),
         }
     }
 }
@@ -121,6 +132,7 @@ impl VscDiagnosticsConfig {
     pub(crate) fn section_from_key(key: &str) -> &str {
         match key {
             "enable" => "positron.r.diagnostics.enable",
+            "lintr" => "positron.r.diagnostics.lintr",
             _ => "unknown", // To be caught via downstream errors
         }
     }
@@ -130,6 +142,24 @@ impl From<VscDiagnosticsConfig> for DiagnosticsConfig {
     fn from(value: VscDiagnosticsConfig) -> Self {
         Self {
             enable: value.enable,
+            lintr: value.lintr.unwrap_or(true),
+        }
+    }
+}
+
+impl VscFormattingConfig {
+    pub(crate) fn section_from_key(key: &str) -> &str {
+        match key {
+            "formatter" => "positron.r.formatting.formatter",
+            _ => "unknown", // To be caught via downstream errors
+        }
+    }
+}
+
+impl From<VscFormattingConfig> for FormattingConfig {
+    fn from(value: VscFormattingConfig) -> Self {
+        Self {
+            formatter: value.formatter,
         }
     }
 }
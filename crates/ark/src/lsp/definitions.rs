@@ -6,11 +6,16 @@
 //
 
 use anyhow::Result;
+use harp::eval::r_parse_eval;
+use harp::eval::RParseEvalOptions;
+use libr::CLOSXP;
 use tower_lsp::lsp_types::GotoDefinitionParams;
 use tower_lsp::lsp_types::GotoDefinitionResponse;
 use tower_lsp::lsp_types::LocationLink;
+use tower_lsp::lsp_types::Position;
 use tower_lsp::lsp_types::Range;
 use tower_lsp::lsp_types::Url;
+use tree_sitter::Node;
 
 use crate::lsp::documents::Document;
 use crate::lsp::encoding::convert_point_to_position;
@@ -18,8 +23,21 @@ use crate::lsp::encoding::convert_position_to_point;
 use crate::lsp::indexer;
 use crate::lsp::traits::node::NodeExt;
 use crate::lsp::traits::rope::RopeExt;
+use crate::r_task;
+use crate::srcref::srcref_location;
+use crate::srcref::SrcrefLocation;
 use crate::treesitter::NodeTypeExt;
 
+/// The result of looking up a symbol in the R session
+enum SessionDefinition {
+    /// The symbol is a function with source references
+    Location(SrcrefLocation),
+    /// The symbol is a function but we don't know where it's defined
+    Unknown,
+    /// The symbol is not a function in the session
+    NotFound,
+}
+
 pub unsafe fn goto_definition<'a>(
     document: &'a Document,
     params: GotoDefinitionParams,
@@ -57,11 +75,36 @@ pub unsafe fn goto_definition<'a>(
         }
     }
 
+    // search for a function defined in the R session
+    if let Some(code) = session_symbol(&node, document)? {
+        match r_task(|| session_definition(&code)) {
+            SessionDefinition::Location(location) => {
+                let Some(target_uri) = location_uri(&location.file) else {
+                    return Ok(None);
+                };
+                let target_range = Range {
+                    start: Position::new(location.line, location.column),
+                    end: Position::new(location.end_line, location.end_column),
+                };
+                let link = LocationLink {
+                    origin_selection_range: Some(range),
+                    target_uri,
+                    target_range,
+                    target_selection_range: target_range,
+                };
+                let response = GotoDefinitionResponse::Link(vec![link]);
+                return Ok(Some(response));
+            },
+            // Don't return a bogus location for functions without srcrefs
+            SessionDefinition::Unknown => return Ok(None),
+            SessionDefinition::NotFound => (),
+        }
+    }
+
     // TODO: We should see if we can find the referenced item in:
     //
     // 1. The document's current AST,
     // 2. The public functions from other documents in the project,
-    // 3. A definition in the R session (which we could open in a virtual document)
     //
     // If we can't find a definition, then we can return the referenced item itself,
     // which will tell Positron to instead try to look for references for that symbol.
@@ -75,3 +118,52 @@ pub unsafe fn goto_definition<'a>(
     let response = GotoDefinitionResponse::Link(vec![link]);
     Ok(Some(response))
 }
+
+/// Returns the code to evaluate to look up `node` in the session: the
+/// identifier itself, or the whole `pkg::fun` expression if the identifier
+/// is the right-hand side of a namespace operator.
+fn session_symbol(node: &Node, document: &Document) -> Result<Option<String>> {
+    if !node.is_identifier() {
+        return Ok(None);
+    }
+
+    let node = match node.parent() {
+        Some(parent) if parent.is_namespace_operator() => parent,
+        _ => *node,
+    };
+
+    Ok(Some(document.contents.node_slice(&node)?.to_string()))
+}
+
+fn session_definition(code: &str) -> SessionDefinition {
+    let object = r_parse_eval(code, RParseEvalOptions {
+        forbid_function_calls: true,
+        ..Default::default()
+    });
+
+    let Ok(object) = object else {
+        return SessionDefinition::NotFound;
+    };
+
+    // Only closures can have source references
+    if object.kind() != CLOSXP {
+        return SessionDefinition::NotFound;
+    }
+
+    match srcref_location(&object) {
+        Ok(Some(location)) => SessionDefinition::Location(location),
+        Ok(None) => SessionDefinition::Unknown,
+        Err(err) => {
+            log::error!("Can't get source location of `{code}`: {err:?}");
+            SessionDefinition::Unknown
+        },
+    }
+}
+
+fn location_uri(file: &str) -> Option<Url> {
+    if file.starts_with("ark:") {
+        Url::parse(file).ok()
+    } else {
+        Url::from_file_path(file).ok()
+    }
+}
@@ -33,6 +33,9 @@ use crate::treesitter::UnmatchedDelimiterType;
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct DiagnosticsConfig {
     pub enable: bool,
+
+    /// Whether to include lints from the lintr package, when installed
+    pub lintr: bool,
 }
 
 #[derive(Clone)]
@@ -62,7 +65,10 @@ pub struct DiagnosticContext<'a> {
 
 impl Default for DiagnosticsConfig {
     fn default() -> Self {
-        Self { enable: true }
+        Self {
+            enable: true,
+            lintr: true,
+        }
     }
 }
 
@@ -120,7 +126,8 @@ pub(crate) fn generate_diagnostics(doc: Document, state: WorldState) -> Vec<Diag
 
         // Add the current workspace symbols.
         indexer::map(|_path, _symbol, entry| match &entry.data {
-            indexer::IndexEntryData::Function { name, arguments: _ } => {
+            indexer::IndexEntryData::Function { name, .. } |
+            indexer::IndexEntryData::Variable { name } => {
                 context.workspace_symbols.insert(name.to_string());
             },
             _ => {},
@@ -114,10 +114,14 @@ impl Document {
         parser: &mut Parser,
         change: &TextDocumentContentChangeEvent,
     ) -> Result<()> {
-        // Extract edit range. Nothing to do if there wasn't an edit.
-        let range = match change.range {
-            Some(r) => r,
-            None => return Ok(()),
+        // A change without a range replaces the whole document, so there is
+        // no previous tree to reuse
+        let Some(range) = change.range else {
+            self.contents = Rope::from(change.text.as_str());
+            self.ast = parser
+                .parse(change.text.as_str(), None)
+                .ok_or(anyhow!("Failed to parse document"))?;
+            return Ok(());
         };
 
         // Update the AST. We do this before updating the underlying document
@@ -202,8 +206,137 @@ impl Document {
 
 #[cfg(test)]
 mod tests {
+    use tower_lsp::lsp_types::Position;
+    use tower_lsp::lsp_types::Range;
+    use tower_lsp::lsp_types::VersionedTextDocumentIdentifier;
+
     use super::*;
 
+    fn apply_change(
+        doc: &mut Document,
+        parser: &mut Parser,
+        range: Option<((u32, u32), (u32, u32))>,
+        text: &str,
+    ) {
+        let version = doc.version.unwrap_or(0) + 1;
+        let range = range.map(|(start, end)| {
+            Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1))
+        });
+
+        let params = DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier {
+                uri: url::Url::parse("file:///test.R").unwrap(),
+                version,
+            },
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range,
+                range_length: None,
+                text: text.to_string(),
+            }],
+        };
+
+        doc.on_did_change(parser, &params);
+    }
+
+    // The incrementally reparsed tree must be the same as a tree parsed
+    // from scratch, including byte offsets
+    fn expect_consistent_tree(doc: &Document) {
+        let contents = doc.contents.to_string();
+        let fresh = Document::new(&contents, None);
+
+        let mut incremental = doc.ast.walk();
+        let mut expected = fresh.ast.walk();
+        loop {
+            let node = incremental.node();
+            let expected_node = expected.node();
+            assert_eq!(node.kind(), expected_node.kind());
+            assert_eq!(node.byte_range(), expected_node.byte_range());
+            assert_eq!(node.start_position(), expected_node.start_position());
+            assert_eq!(node.end_position(), expected_node.end_position());
+
+            if incremental.goto_first_child() {
+                assert!(expected.goto_first_child());
+                continue;
+            }
+            loop {
+                if incremental.goto_next_sibling() {
+                    assert!(expected.goto_next_sibling());
+                    break;
+                }
+                if !incremental.goto_parent() {
+                    return;
+                }
+                assert!(expected.goto_parent());
+            }
+        }
+    }
+
+    fn new_parser() -> Parser {
+        let mut parser = Parser::new();
+        parser.set_language(&tree_sitter_r::language()).unwrap();
+        parser
+    }
+
+    #[test]
+    fn test_incremental_edit_after_multibyte_characters() {
+        let mut parser = new_parser();
+        let mut doc = Document::new_with_parser("x <- \"é😀\"; y <- 1\n", &mut parser, Some(0));
+
+        // `é` is 1 UTF-16 unit and `😀` is 2, so `y` starts at UTF-16 offset 12
+        apply_change(&mut doc, &mut parser, Some(((0, 12), (0, 13))), "foo");
+        assert_eq!(doc.contents.to_string(), "x <- \"é😀\"; foo <- 1\n");
+        expect_consistent_tree(&doc);
+
+        // Replace the emoji, which spans UTF-16 offsets 7 to 9
+        apply_change(&mut doc, &mut parser, Some(((0, 7), (0, 9))), "ab");
+        assert_eq!(doc.contents.to_string(), "x <- \"éab\"; foo <- 1\n");
+        expect_consistent_tree(&doc);
+    }
+
+    #[test]
+    fn test_incremental_edit_spanning_multibyte_characters() {
+        let mut parser = new_parser();
+        let mut doc =
+            Document::new_with_parser("a <- \"😀\"\nb <- \"ü\"\nc <- 3\n", &mut parser, Some(0));
+
+        // Delete from inside the first string to inside the second one
+        apply_change(&mut doc, &mut parser, Some(((0, 6), (1, 6))), "");
+        assert_eq!(doc.contents.to_string(), "a <- \"ü\"\nc <- 3\n");
+        expect_consistent_tree(&doc);
+
+        // Insert multibyte text spanning several lines
+        apply_change(
+            &mut doc,
+            &mut parser,
+            Some(((1, 0), (1, 0))),
+            "d <- \"ß\n😀\"\n",
+        );
+        assert_eq!(
+            doc.contents.to_string(),
+            "a <- \"ü\"\nd <- \"ß\n😀\"\nc <- 3\n"
+        );
+        expect_consistent_tree(&doc);
+
+        // Edit after the inserted multibyte text
+        apply_change(&mut doc, &mut parser, Some(((3, 5), (3, 6))), "42");
+        assert_eq!(
+            doc.contents.to_string(),
+            "a <- \"ü\"\nd <- \"ß\n😀\"\nc <- 42\n"
+        );
+        expect_consistent_tree(&doc);
+    }
+
+    #[test]
+    fn test_full_document_change() {
+        let mut parser = new_parser();
+        let mut doc = Document::new_with_parser("x <- 1\n", &mut parser, Some(0));
+
+        apply_change(&mut doc, &mut parser, None, "y <- \"ö\"\n");
+        assert_eq!(doc.contents.to_string(), "y <- \"ö\"\n");
+        assert_eq!(doc.version, Some(1));
+        expect_consistent_tree(&doc);
+    }
+
     #[test]
     fn test_point_computation() {
         // empty strings shouldn't do anything
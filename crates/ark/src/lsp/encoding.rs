@@ -99,36 +99,39 @@ where
 }
 
 /// Converts a character offset into a particular line from UTF-16 to UTF-8
+///
+/// As per the LSP specification, an offset past the end of the line refers to
+/// the end of the line (before the line terminator).
 fn convert_character_from_utf16_to_utf8(x: &str, character: usize) -> usize {
+    let x = x.trim_end_matches(|c| c == '\n' || c == '\r');
+
     if x.is_ascii() {
         // Fast pass
-        return character;
-    }
-
-    // Initial check, since loop would skip this case
-    if character == 0 {
-        return character;
+        return character.min(x.len());
     }
 
     let mut n = 0;
 
     // For each `u32` sized `char`, figure out the equivalent size in UTF-16
-    // world of that `char`. Once we hit the requested number of `character`s,
-    // that means we have indexed into `x` to the correct position, at which
-    // point we can take the current bytes based `pos` that marks the start of
-    // this `char`, and add on its UTF-8 based size to return an adjusted column
-    // offset. We use `==` because I'm fairly certain they should always align
-    // exactly, and it would be good to log if that isn't the case.
+    // world of that `char`. Once we reach the requested number of `character`s,
+    // we have indexed into `x` to the correct position, at which point the
+    // current bytes based `pos` that marks the start of this `char` is the
+    // adjusted column offset. Offsets should always align with a `char`
+    // boundary, it would be good to log if that isn't the case (i.e. the
+    // offset points into the middle of a surrogate pair).
     for (pos, char) in x.char_indices() {
-        n += char.len_utf16();
-
-        if n == character {
-            return pos + char.len_utf8();
+        if n >= character {
+            if n > character {
+                log::error!(
+                    "UTF-16 offset {character} is not on a character boundary. Line: '{x}'."
+                );
+            }
+            return pos;
         }
+        n += char.len_utf16();
     }
 
-    log::error!("Failed to locate UTF-16 offset of {character}. Line: '{x}'.");
-    return 0;
+    x.len()
 }
 
 /// Converts a character offset into a particular line from UTF-8 to UTF-16
@@ -152,3 +155,46 @@ fn convert_character_from_utf8_to_utf16(x: &str, character: usize) -> usize {
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+    use tower_lsp::lsp_types::Position;
+    use tree_sitter::Point;
+
+    use crate::lsp::encoding::convert_point_to_position;
+    use crate::lsp::encoding::convert_position_to_point;
+
+    #[test]
+    fn test_convert_position_to_point_multibyte() {
+        // `é` is 2 bytes and 1 UTF-16 unit, `😀` is 4 bytes and 2 UTF-16 units
+        let x = Rope::from("aé😀b\nc\n");
+
+        let point = |character| convert_position_to_point(&x, Position::new(0, character));
+        assert_eq!(point(0), Point::new(0, 0));
+        assert_eq!(point(1), Point::new(0, 1));
+        assert_eq!(point(2), Point::new(0, 3));
+        assert_eq!(point(4), Point::new(0, 7));
+        assert_eq!(point(5), Point::new(0, 8));
+
+        // Past the end of the line, before the newline
+        assert_eq!(point(10), Point::new(0, 8));
+
+        let x = Rope::from("abc\n");
+        assert_eq!(
+            convert_position_to_point(&x, Position::new(0, 10)),
+            Point::new(0, 3)
+        );
+    }
+
+    #[test]
+    fn test_convert_point_to_position_multibyte() {
+        let x = Rope::from("aé😀b\nc\n");
+
+        let position = |column| convert_point_to_position(&x, Point::new(0, column));
+        assert_eq!(position(1), Position::new(0, 1));
+        assert_eq!(position(3), Position::new(0, 2));
+        assert_eq!(position(7), Position::new(0, 4));
+        assert_eq!(position(8), Position::new(0, 5));
+    }
+}
@@ -0,0 +1,208 @@
+//
+// folding_range.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use ropey::Rope;
+use tower_lsp::lsp_types::FoldingRange;
+use tower_lsp::lsp_types::FoldingRangeKind;
+use tree_sitter::Node;
+use tree_sitter::Tree;
+
+use crate::lsp::traits::rope::RopeExt;
+use crate::treesitter::NodeTypeExt;
+
+/// Computes the folding ranges of a document, sorted by start line:
+///
+/// - Braced expressions, e.g. function bodies and `if` or `for` blocks. The
+///   line of the closing brace stays visible when folded.
+/// - Function definitions whose body isn't braced but spans multiple lines.
+/// - Runs of consecutive comment lines. Roxygen comments (`#'`) and regular
+///   comments are folded separately.
+///
+/// Lines are zero-based, as tree-sitter rows, which is what LSP clients
+/// expect.
+pub fn folding_range(tree: &Tree, contents: &Rope) -> Vec<FoldingRange> {
+    let mut ranges = Vec::new();
+    let mut comments = Vec::new();
+
+    collect_ranges(tree.root_node(), contents, &mut ranges, &mut comments);
+    comment_ranges(comments, &mut ranges);
+
+    ranges.sort_by_key(|range| (range.start_line, range.end_line));
+    ranges
+}
+
+/// A comment alone on its line
+struct CommentLine {
+    row: usize,
+    roxygen: bool,
+}
+
+fn collect_ranges(
+    node: Node,
+    contents: &Rope,
+    ranges: &mut Vec<FoldingRange>,
+    comments: &mut Vec<CommentLine>,
+) {
+    if node.is_braced_expression() {
+        // Keep the closing brace visible
+        let end = node.end_position().row.saturating_sub(1);
+        push_range(ranges, node.start_position().row, end, None);
+    } else if node.is_function_definition() {
+        let braced = node
+            .child_by_field_name("body")
+            .map_or(false, |body| body.is_braced_expression());
+        if !braced {
+            push_range(
+                ranges,
+                node.start_position().row,
+                node.end_position().row,
+                None,
+            );
+        }
+    } else if node.is_comment() {
+        if let Some(comment) = comment_line(node, contents) {
+            comments.push(comment);
+        }
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_ranges(child, contents, ranges, comments);
+    }
+}
+
+fn comment_line(node: Node, contents: &Rope) -> Option<CommentLine> {
+    let start = node.start_position();
+
+    // Skip comments trailing code
+    let line = contents.get_line(start.row)?.to_string();
+    if !line.get(..start.column)?.trim().is_empty() {
+        return None;
+    }
+
+    let text = contents.node_slice(&node).ok()?.to_string();
+
+    Some(CommentLine {
+        row: start.row,
+        roxygen: text.starts_with("#'"),
+    })
+}
+
+fn comment_ranges(mut comments: Vec<CommentLine>, ranges: &mut Vec<FoldingRange>) {
+    comments.sort_by_key(|comment| comment.row);
+
+    let mut comments = comments.into_iter();
+    let Some(first) = comments.next() else {
+        return;
+    };
+
+    let mut start = first.row;
+    let mut end = first.row;
+    let mut roxygen = first.roxygen;
+
+    for comment in comments {
+        if comment.row == end + 1 && comment.roxygen == roxygen {
+            end = comment.row;
+            continue;
+        }
+
+        push_range(ranges, start, end, Some(FoldingRangeKind::Comment));
+        start = comment.row;
+        end = comment.row;
+        roxygen = comment.roxygen;
+    }
+
+    push_range(ranges, start, end, Some(FoldingRangeKind::Comment));
+}
+
+fn push_range(
+    ranges: &mut Vec<FoldingRange>,
+    start: usize,
+    end: usize,
+    kind: Option<FoldingRangeKind>,
+) {
+    // Single lines can't be folded
+    if end <= start {
+        return;
+    }
+
+    ranges.push(FoldingRange {
+        start_line: start as u32,
+        end_line: end as u32,
+        kind,
+        ..Default::default()
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::FoldingRangeKind;
+
+    use crate::lsp::documents::Document;
+    use crate::lsp::folding_range::folding_range;
+
+    fn ranges(text: &str) -> Vec<(u32, u32, Option<FoldingRangeKind>)> {
+        let document = Document::new(text, None);
+        folding_range(&document.ast, &document.contents)
+            .into_iter()
+            .map(|range| (range.start_line, range.end_line, range.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_folding_range_nested() {
+        let text = "
+#' Outer function
+#'
+#' @param x A number.
+#' @export
+outer <- function(x) {
+  # Helper
+  # defined inline
+  inner <- function(y) {
+    if (y > 0) {
+      y
+    } else {
+      -y
+    }
+  }
+  short <- function(z)
+    z + 1
+  inner(x) # trailing
+  # not a run
+}
+";
+        let comment = Some(FoldingRangeKind::Comment);
+
+        assert_eq!(ranges(text), vec![
+            (1, 4, comment.clone()),
+            (5, 18, None),
+            (6, 7, comment.clone()),
+            (8, 13, None),
+            (9, 10, None),
+            (11, 12, None),
+            (15, 16, None),
+        ]);
+    }
+
+    #[test]
+    fn test_folding_range_comment_kinds() {
+        let text = "
+# Regular comment
+# continued
+#' Roxygen comment
+#' continued
+x <- 1
+
+# Single comment
+y <- { 1 }
+";
+        let comment = Some(FoldingRangeKind::Comment);
+
+        assert_eq!(ranges(text), vec![(1, 2, comment.clone()), (3, 4, comment)]);
+    }
+}
@@ -0,0 +1,285 @@
+//
+// formatting.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use harp::utils::r_is_null;
+use serde::Deserialize;
+use serde::Serialize;
+use tower_lsp::lsp_types::MessageType;
+
+use crate::lsp;
+use crate::lsp::config::IndentStyle;
+use crate::lsp::config::IndentationConfig;
+use crate::lsp::documents::Document;
+use crate::lsp::offset::ArkPoint;
+use crate::lsp::offset::ArkRange;
+use crate::lsp::offset::ArkTextEdit;
+use crate::r_task;
+
+/// Whether we've already told the user that the formatter package is missing.
+/// We only warn once per session to avoid nagging on every format request.
+static WARNED_MISSING_FORMATTER: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FormattingConfig {
+    pub formatter: FormatterBackend,
+}
+
+/// The R package used to format code
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum FormatterBackend {
+    #[default]
+    #[serde(rename = "styler")]
+    Styler,
+    #[serde(rename = "formatR")]
+    FormatR,
+}
+
+impl Default for FormattingConfig {
+    fn default() -> Self {
+        Self {
+            formatter: FormatterBackend::default(),
+        }
+    }
+}
+
+impl FormatterBackend {
+    fn package(&self) -> &'static str {
+        match self {
+            FormatterBackend::Styler => "styler",
+            FormatterBackend::FormatR => "formatR",
+        }
+    }
+}
+
+/// Format a whole document
+///
+/// Returns `None` when the document doesn't need any changes, or when the
+/// formatter is not available.
+pub fn format_document(
+    doc: &Document,
+    config: &FormattingConfig,
+) -> anyhow::Result<Option<Vec<ArkTextEdit>>> {
+    let text = doc.contents.to_string();
+    format_lines(&text, 0, &doc.config.indent, config)
+}
+
+/// Format the lines spanned by a range
+///
+/// Formatters work on complete expressions so we widen the range to whole
+/// lines before sending it to the formatter.
+pub fn format_range(
+    doc: &Document,
+    range: ArkRange,
+    config: &FormattingConfig,
+) -> anyhow::Result<Option<Vec<ArkTextEdit>>> {
+    let contents = &doc.contents;
+
+    let start_line = range.start.row.min(contents.len_lines() - 1);
+    let mut end_line = range.end.row.min(contents.len_lines() - 1);
+
+    // A selection ending at the very start of a line doesn't include that line
+    if range.end.column == 0 && end_line > start_line {
+        end_line = end_line - 1;
+    }
+
+    let start = contents.line_to_char(start_line);
+    let end = contents.line_to_char(end_line + 1);
+    let text = contents.slice(start..end).to_string();
+
+    format_lines(&text, start_line, &doc.config.indent, config)
+}
+
+fn format_lines(
+    text: &str,
+    first_line: usize,
+    indent: &IndentationConfig,
+    config: &FormattingConfig,
+) -> anyhow::Result<Option<Vec<ArkTextEdit>>> {
+    if text.trim().is_empty() {
+        return Ok(None);
+    }
+
+    let formatted = r_task(|| r_format(text, indent, config.formatter))?;
+
+    let Some(formatted) = formatted else {
+        if !WARNED_MISSING_FORMATTER.swap(true, Ordering::Relaxed) {
+            let package = config.formatter.package();
+            lsp::show_message(
+                MessageType::WARNING,
+                format!("Can't format R code: the {package} package is not installed."),
+            );
+        }
+        return Ok(None);
+    };
+
+    let formatted = reindent(formatted, indent);
+
+    Ok(diff_lines(text, &formatted, first_line).map(|edit| vec![edit]))
+}
+
+/// Run the formatter on the R side
+///
+/// Returns `None` if the formatter package is not installed.
+fn r_format(
+    text: &str,
+    indent: &IndentationConfig,
+    formatter: FormatterBackend,
+) -> anyhow::Result<Option<String>> {
+    let lines = RFunction::from(".ps.format.formatCode")
+        .param("code", text)
+        .param("formatter", formatter.package())
+        .param("indent", indent.indent_size as i32)
+        .call()?;
+
+    if r_is_null(lines.sexp) {
+        return Ok(None);
+    }
+
+    let lines: Vec<String> = lines.try_into()?;
+    let mut formatted = lines.join("\n");
+
+    // Formatters return a vector of lines, restore the trailing newline
+    if text.ends_with('\n') {
+        formatted.push('\n');
+    }
+
+    Ok(Some(formatted))
+}
+
+/// Formatters always indent with spaces, convert the leading indentation
+/// to tabs if requested by the client.
+fn reindent(text: String, config: &IndentationConfig) -> String {
+    let IndentStyle::Tab = config.indent_style else {
+        return text;
+    };
+
+    let indent_size = config.indent_size.max(1);
+
+    text.split_inclusive('\n')
+        .map(|line| {
+            let n_spaces = line.len() - line.trim_start_matches(' ').len();
+            let tabs = "\t".repeat(n_spaces / indent_size);
+            let spaces = " ".repeat(n_spaces % indent_size);
+            format!("{tabs}{spaces}{}", &line[n_spaces..])
+        })
+        .collect()
+}
+
+/// Compute a single edit replacing the lines that differ between `old` and
+/// `new`. Unchanged leading and trailing lines are left alone so that cursors
+/// and folds outside of the changed region are preserved.
+///
+/// `first_line` is the row of the first line of `old` in the document.
+fn diff_lines(old: &str, new: &str, first_line: usize) -> Option<ArkTextEdit> {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+
+    let n_prefix = std::iter::zip(&old_lines, &new_lines)
+        .take_while(|(old, new)| old == new)
+        .count();
+
+    if n_prefix == old_lines.len() && n_prefix == new_lines.len() {
+        return None;
+    }
+
+    let n_suffix = std::iter::zip(old_lines.iter().rev(), new_lines.iter().rev())
+        .take(old_lines.len().min(new_lines.len()) - n_prefix)
+        .take_while(|(old, new)| old == new)
+        .count();
+
+    let old_end = old_lines.len() - n_suffix;
+    let new_end = new_lines.len() - n_suffix;
+
+    let start = ArkPoint {
+        row: first_line + n_prefix,
+        column: 0,
+    };
+
+    // If the replaced region extends to the end of a text that doesn't end
+    // with a newline, the end position is at the end of the last line rather
+    // than at the start of the following one
+    let end = if old_end == old_lines.len() && !old.is_empty() && !old.ends_with('\n') {
+        ArkPoint {
+            row: first_line + old_end - 1,
+            column: old_lines[old_end - 1].len(),
+        }
+    } else {
+        ArkPoint {
+            row: first_line + old_end,
+            column: 0,
+        }
+    };
+
+    Some(ArkTextEdit {
+        range: ArkRange { start, end },
+        new_text: new_lines[n_prefix..new_end].concat(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lsp::config::IndentStyle;
+    use crate::lsp::config::IndentationConfig;
+    use crate::lsp::formatting::diff_lines;
+    use crate::lsp::formatting::reindent;
+    use crate::lsp::offset::apply_text_edits;
+
+    fn apply_diff(old: &str, new: &str) -> String {
+        let mut text = old.to_string();
+        let edits = diff_lines(old, new, 0).into_iter().collect();
+        apply_text_edits(edits, &mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn test_diff_lines_no_changes() {
+        assert!(diff_lines("a\nb\n", "a\nb\n", 0).is_none());
+        assert!(diff_lines("a\nb", "a\nb", 0).is_none());
+    }
+
+    #[test]
+    fn test_diff_lines_only_replaces_changed_lines() {
+        let edit = diff_lines("a\nb<-1\nc\n", "a\nb <- 1\nc\n", 3).unwrap();
+        assert_eq!(edit.range.start.row, 4);
+        assert_eq!(edit.range.start.column, 0);
+        assert_eq!(edit.range.end.row, 5);
+        assert_eq!(edit.range.end.column, 0);
+        assert_eq!(edit.new_text, "b <- 1\n");
+    }
+
+    #[test]
+    fn test_diff_lines_roundtrip() {
+        let cases = [
+            ("a\nb<-1\nc\n", "a\nb <- 1\nc\n"),
+            ("a<-1", "a <- 1"),
+            ("a\nb<-1", "a\nb <- 1"),
+            ("f(a,\nb)\n", "f(a, b)\n"),
+            ("f(a, b)\n", "f(\n  a,\n  b\n)\n"),
+            ("x\nx\n", "x\n"),
+            ("x\n", "x\nx\n"),
+        ];
+
+        for (old, new) in cases {
+            assert_eq!(apply_diff(old, new), new);
+        }
+    }
+
+    #[test]
+    fn test_reindent() {
+        let mut config = IndentationConfig::default();
+        let text = String::from("f(\n  a,\n    b,\n   c\n)\n");
+        assert_eq!(reindent(text.clone(), &config), text);
+
+        config.indent_style = IndentStyle::Tab;
+        assert_eq!(reindent(text, &config), "f(\n\ta,\n\t\tb,\n\t c\n)\n");
+    }
+}
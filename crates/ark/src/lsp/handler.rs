@@ -26,7 +26,13 @@ pub struct Lsp {
 impl Lsp {
     pub fn new(kernel_init_rx: BusReader<KernelInfo>) -> Self {
         Self {
-            runtime: Arc::new(tokio::runtime::Runtime::new().unwrap()),
+            runtime: Arc::new(
+                tokio::runtime::Builder::new_multi_thread()
+                    .enable_all()
+                    .thread_name("ark-lsp-worker")
+                    .build()
+                    .unwrap(),
+            ),
             kernel_init_rx,
             kernel_initialized: false,
         }
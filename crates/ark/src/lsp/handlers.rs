@@ -5,6 +5,8 @@
 //
 //
 
+use std::collections::HashMap;
+
 use anyhow::anyhow;
 use dashmap::DashMap;
 use once_cell::sync::Lazy;
@@ -14,9 +16,13 @@ use struct_field_names_as_array::FieldNamesAsArray;
 use tower_lsp::lsp_types::CompletionItem;
 use tower_lsp::lsp_types::CompletionParams;
 use tower_lsp::lsp_types::CompletionResponse;
+use tower_lsp::lsp_types::DocumentFormattingParams;
 use tower_lsp::lsp_types::DocumentOnTypeFormattingParams;
+use tower_lsp::lsp_types::DocumentRangeFormattingParams;
 use tower_lsp::lsp_types::DocumentSymbolParams;
 use tower_lsp::lsp_types::DocumentSymbolResponse;
+use tower_lsp::lsp_types::FoldingRange;
+use tower_lsp::lsp_types::FoldingRangeParams;
 use tower_lsp::lsp_types::GotoDefinitionParams;
 use tower_lsp::lsp_types::GotoDefinitionResponse;
 use tower_lsp::lsp_types::Hover;
@@ -26,6 +32,7 @@ use tower_lsp::lsp_types::Location;
 use tower_lsp::lsp_types::MessageType;
 use tower_lsp::lsp_types::ReferenceParams;
 use tower_lsp::lsp_types::Registration;
+use tower_lsp::lsp_types::RenameParams;
 use tower_lsp::lsp_types::SelectionRange;
 use tower_lsp::lsp_types::SelectionRangeParams;
 use tower_lsp::lsp_types::SignatureHelp;
@@ -43,17 +50,23 @@ use crate::lsp::completions::provide_completions;
 use crate::lsp::completions::resolve_completion;
 use crate::lsp::config::VscDiagnosticsConfig;
 use crate::lsp::config::VscDocumentConfig;
+use crate::lsp::config::VscFormattingConfig;
 use crate::lsp::definitions::goto_definition;
 use crate::lsp::document_context::DocumentContext;
 use crate::lsp::encoding::convert_position_to_point;
+use crate::lsp::folding_range::folding_range;
+use crate::lsp::formatting::format_document;
+use crate::lsp::formatting::format_range;
 use crate::lsp::help_topic::help_topic;
 use crate::lsp::help_topic::HelpTopicParams;
 use crate::lsp::help_topic::HelpTopicResponse;
 use crate::lsp::hover::r_hover;
 use crate::lsp::indent::indent_edit;
 use crate::lsp::main_loop::LspState;
+use crate::lsp::offset::ArkRange;
 use crate::lsp::offset::IntoLspOffset;
 use crate::lsp::references::find_references;
+use crate::lsp::rename::rename;
 use crate::lsp::selection_range::convert_selection_range_from_tree_sitter_to_lsp;
 use crate::lsp::selection_range::selection_range;
 use crate::lsp::signature_help::r_signature_help;
@@ -102,8 +115,14 @@ pub(crate) async fn handle_initialized(
             VscDiagnosticsConfig::section_from_key,
         );
 
+        let mut config_formatting_regs: Vec<Registration> = collect_regs(
+            VscFormattingConfig::FIELD_NAMES_AS_ARRAY.to_vec(),
+            VscFormattingConfig::section_from_key,
+        );
+
         regs.append(&mut config_document_regs);
         regs.append(&mut config_diagnostics_regs);
+        regs.append(&mut config_formatting_regs);
     }
 
     client
@@ -309,6 +328,17 @@ pub(crate) fn handle_selection_range(
     Ok(Some(selections))
 }
 
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_folding_range(
+    params: FoldingRangeParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<FoldingRange>>> {
+    let uri = params.text_document.uri;
+    let document = state.get_document(&uri)?;
+
+    Ok(Some(folding_range(&document.ast, &document.contents)))
+}
+
 #[tracing::instrument(level = "info", skip_all)]
 pub(crate) fn handle_references(
     params: ReferenceParams,
@@ -381,6 +411,62 @@ pub(crate) fn handle_indent(
     })
 }
 
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_formatting(
+    params: DocumentFormattingParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<TextEdit>>> {
+    let uri = params.text_document.uri;
+    let doc = state.get_document(&uri)?;
+
+    let res = format_document(doc, &state.config.formatting);
+
+    Result::map(res, |opt| {
+        Option::map(opt, |edits| edits.into_lsp_offset(&doc.contents))
+    })
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_range_formatting(
+    params: DocumentRangeFormattingParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<TextEdit>>> {
+    let uri = params.text_document.uri;
+    let doc = state.get_document(&uri)?;
+
+    let range = ArkRange {
+        start: convert_position_to_point(&doc.contents, params.range.start),
+        end: convert_position_to_point(&doc.contents, params.range.end),
+    };
+
+    let res = format_range(doc, range, &state.config.formatting);
+
+    Result::map(res, |opt| {
+        Option::map(opt, |edits| edits.into_lsp_offset(&doc.contents))
+    })
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_rename(
+    params: RenameParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<WorkspaceEdit>> {
+    let uri = params.text_document_position.text_document.uri;
+    let doc = state.get_document(&uri)?;
+
+    let point = convert_position_to_point(&doc.contents, params.text_document_position.position);
+
+    let Some(edits) = rename(doc, point, &params.new_name)? else {
+        return Ok(None);
+    };
+    let edits = edits.into_lsp_offset(&doc.contents);
+
+    Ok(Some(WorkspaceEdit {
+        changes: Some(HashMap::from([(uri, edits)])),
+        ..Default::default()
+    }))
+}
+
 // TODO: Should be in WorldState and updated via message passing
 pub static mut ARK_VDOCS: Lazy<DashMap<String, String>> = Lazy::new(|| DashMap::new());
 
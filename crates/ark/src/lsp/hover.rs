@@ -6,15 +6,22 @@
 //
 
 use anyhow::*;
+use stdext::push;
 use stdext::unwrap;
 use stdext::unwrap::IntoResult;
 use tower_lsp::lsp_types::MarkupContent;
 use tower_lsp::lsp_types::MarkupKind;
 use tree_sitter::Node;
 
+use crate::help::markdown::render_topic_summary_markdown;
+use crate::help::markdown::topic_package;
 use crate::lsp::document_context::DocumentContext;
-use crate::lsp::help::RHtmlHelp;
+use crate::lsp::markdown::md_codeblock;
+use crate::lsp::markdown::md_newline;
 use crate::lsp::traits::rope::RopeExt;
+use crate::treesitter::node_roxygen_block;
+use crate::treesitter::BinaryOperatorType;
+use crate::treesitter::NodeType;
 use crate::treesitter::NodeTypeExt;
 
 enum HoverContext {
@@ -80,21 +87,117 @@ pub(crate) unsafe fn r_hover(context: &DocumentContext) -> anyhow::Result<Option
         return Ok(None);
     });
 
-    let help = match ctx {
+    let markdown = match ctx {
         HoverContext::QualifiedTopic { package, topic } => {
-            RHtmlHelp::new(topic.as_str(), Some(package.as_str()))?
+            render_topic_summary_markdown(package.as_str(), topic.as_str())?
         },
 
-        HoverContext::Topic { topic } => RHtmlHelp::new(topic.as_str(), None)?,
+        HoverContext::Topic { topic } => {
+            let help = match topic_package(topic.as_str())? {
+                Some(package) => render_topic_summary_markdown(package.as_str(), topic.as_str())?,
+                None => None,
+            };
+            match help {
+                Some(markdown) => Some(markdown),
+                None => document_markdown(context, topic.as_str())?,
+            }
+        },
     };
 
-    let help = unwrap!(help, None => {
+    let markdown = unwrap!(markdown, None => {
         return Ok(None);
     });
 
-    let markdown = help.markdown()?;
     Ok(Some(MarkupContent {
         kind: MarkupKind::Markdown,
         value: markdown,
     }))
 }
+
+/// Synthesize documentation for a function defined in the document from its
+/// signature and the roxygen block preceding its definition, if any.
+fn document_markdown(context: &DocumentContext, topic: &str) -> anyhow::Result<Option<String>> {
+    let contents = &context.document.contents;
+    let root = context.document.ast.root_node();
+
+    let mut cursor = root.walk();
+    let definition = root.children(&mut cursor).find(|node| {
+        matches!(
+            node.node_type(),
+            NodeType::BinaryOperator(BinaryOperatorType::LeftAssignment) |
+                NodeType::BinaryOperator(BinaryOperatorType::EqualsAssignment)
+        ) && node
+            .child_by_field_name("lhs")
+            .and_then(|lhs| contents.node_slice(&lhs).ok())
+            .map_or(false, |lhs| lhs == topic)
+    });
+
+    let definition = unwrap!(definition, None => {
+        return Ok(None);
+    });
+
+    let function = definition.child_by_field_name("rhs").into_result()?;
+    if !function.is_function_definition() {
+        return Ok(None);
+    }
+
+    let parameters = function.child_by_field_name("parameters").into_result()?;
+    let parameters = contents.node_slice(&parameters)?.to_string();
+
+    let mut markdown = md_codeblock("r", format!("{topic}{parameters}").as_str());
+
+    if let Some(roxygen) = node_roxygen_block(&function, contents) {
+        push!(markdown, md_newline(), roxygen.markdown());
+    }
+
+    Ok(Some(markdown))
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Point;
+
+    use crate::lsp::document_context::DocumentContext;
+    use crate::lsp::documents::Document;
+    use crate::lsp::hover::document_markdown;
+    use crate::lsp::hover::r_hover;
+    use crate::test::r_test;
+
+    #[test]
+    fn test_hover_help_summary() {
+        r_test(|| {
+            let document = Document::new("paste('a')", None);
+            let context = DocumentContext::new(&document, Point::new(0, 1), None);
+
+            let markdown = unsafe { r_hover(&context) }.unwrap().unwrap().value;
+            assert!(markdown.contains("### Usage"));
+            assert!(markdown.contains("### Arguments"));
+            assert!(!markdown.contains("### Examples"));
+            assert!(!markdown.contains("### See Also"));
+        })
+    }
+
+    #[test]
+    fn test_hover_user_defined_function() {
+        let text = "
+#' Add numbers
+#'
+#' @param x A number.
+#' @param y Another number.
+#' @export
+add <- function(x, y = 1) x + y
+
+add(1)
+";
+        let document = Document::new(text, None);
+        let context = DocumentContext::new(&document, Point::new(8, 1), None);
+
+        let markdown = document_markdown(&context, "add").unwrap().unwrap();
+        assert!(markdown.starts_with("``` r\nadd(x, y = 1)\n```\n"));
+        assert!(markdown.contains("Add numbers\n"));
+        assert!(markdown.contains("- `x`: A number.\n"));
+        assert!(!markdown.contains("export"));
+
+        assert!(document_markdown(&context, "x").unwrap().is_none());
+    }
+}
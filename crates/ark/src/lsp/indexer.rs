@@ -26,6 +26,7 @@ use crate::lsp;
 use crate::lsp::documents::Document;
 use crate::lsp::encoding::convert_point_to_position;
 use crate::lsp::traits::rope::RopeExt;
+use crate::treesitter::node_roxygen_block;
 use crate::treesitter::BinaryOperatorType;
 use crate::treesitter::NodeType;
 use crate::treesitter::NodeTypeExt;
@@ -35,11 +36,23 @@ pub enum IndexEntryData {
     Function {
         name: String,
         arguments: Vec<String>,
+        /// The roxygen block documenting the function, if any
+        documentation: Option<String>,
     },
     Section {
         level: usize,
         title: String,
     },
+    Variable {
+        name: String,
+    },
+    Method {
+        name: String,
+        signature: String,
+    },
+    Class {
+        name: String,
+    },
 }
 
 #[derive(Clone, Debug)]
@@ -118,6 +131,17 @@ fn insert(path: &Path, entry: IndexEntry) -> anyhow::Result<()> {
     let path = str_from_path(path)?;
 
     let index = index.entry(path.to_string()).or_default();
+
+    // Reassignments such as `f <- memoise(f)` would otherwise hide the
+    // definition of `f`, so functions are only replaced by other functions
+    if let Some(existing) = index.get(&entry.key) {
+        if matches!(existing.data, IndexEntryData::Function { .. }) &&
+            !matches!(entry.data, IndexEntryData::Function { .. })
+        {
+            return Ok(());
+        }
+    }
+
     index.insert(entry.key.clone(), entry);
 
     Ok(())
@@ -206,6 +230,14 @@ fn index_node(path: &Path, contents: &Rope, node: &Node) -> anyhow::Result<Optio
         return Ok(Some(entry));
     }
 
+    if let Ok(Some(entry)) = index_s4(path, contents, node) {
+        return Ok(Some(entry));
+    }
+
+    if let Ok(Some(entry)) = index_variable(path, contents, node) {
+        return Ok(Some(entry));
+    }
+
     if let Ok(Some(entry)) = index_comment(path, contents, node) {
         return Ok(Some(entry));
     }
@@ -253,16 +285,138 @@ fn index_function(
     let start = convert_point_to_position(contents, lhs.start_position());
     let end = convert_point_to_position(contents, lhs.end_position());
 
+    let documentation = roxygen_documentation(&rhs, contents);
+
     Ok(Some(IndexEntry {
         key: name.clone(),
         range: Range { start, end },
         data: IndexEntryData::Function {
             name: name.clone(),
             arguments,
+            documentation,
         },
     }))
 }
 
+fn roxygen_documentation(definition: &Node, contents: &Rope) -> Option<String> {
+    node_roxygen_block(definition, contents).map(|block| block.markdown())
+}
+
+fn index_variable(
+    _path: &Path,
+    contents: &Rope,
+    node: &Node,
+) -> anyhow::Result<Option<IndexEntry>> {
+    // Check for assignment.
+    matches!(
+        node.node_type(),
+        NodeType::BinaryOperator(BinaryOperatorType::LeftAssignment) |
+            NodeType::BinaryOperator(BinaryOperatorType::EqualsAssignment)
+    )
+    .into_result()?;
+
+    // Check for identifier on left-hand side. Function definitions on the
+    // right-hand side have already been handled by `index_function()`.
+    let lhs = node.child_by_field_name("lhs").into_result()?;
+    lhs.is_identifier_or_string().into_result()?;
+
+    let name = unquote(&contents.node_slice(&lhs)?.to_string());
+
+    let start = convert_point_to_position(contents, lhs.start_position());
+    let end = convert_point_to_position(contents, lhs.end_position());
+
+    Ok(Some(IndexEntry {
+        key: name.clone(),
+        range: Range { start, end },
+        data: IndexEntryData::Variable { name },
+    }))
+}
+
+/// Index S4 definitions made with `setGeneric()`, `setMethod()`, and
+/// `setClass()`. Generics are indexed as functions so that their arguments
+/// are available for completions.
+fn index_s4(_path: &Path, contents: &Rope, node: &Node) -> anyhow::Result<Option<IndexEntry>> {
+    node.is_call().into_result()?;
+
+    let function = node.child_by_field_name("function").into_result()?;
+    let function = contents.node_slice(&function)?.to_string();
+
+    let arguments = node.child_by_field_name("arguments").into_result()?;
+    let mut cursor = arguments.walk();
+    let values: Vec<Node> = arguments
+        .children_by_field_name("argument", &mut cursor)
+        .filter_map(|argument| argument.child_by_field_name("value"))
+        .collect();
+
+    let name_node = values.first().into_result()?;
+    name_node.is_string().into_result()?;
+    let name = unquote(&contents.node_slice(name_node)?.to_string());
+
+    let start = convert_point_to_position(contents, name_node.start_position());
+    let end = convert_point_to_position(contents, name_node.end_position());
+    let range = Range { start, end };
+
+    match function.as_str() {
+        "setGeneric" | "methods::setGeneric" => {
+            let mut arguments = Vec::new();
+            let mut documentation = None;
+
+            if let Some(definition) = values.get(1) {
+                if definition.is_function_definition() {
+                    documentation = roxygen_documentation(definition, contents);
+                    let parameters = definition.child_by_field_name("parameters").into_result()?;
+                    let mut cursor = parameters.walk();
+                    for child in parameters.children(&mut cursor) {
+                        let name = unwrap!(child.child_by_field_name("name"), None => continue);
+                        if name.is_identifier() {
+                            arguments.push(contents.node_slice(&name)?.to_string());
+                        }
+                    }
+                }
+            }
+
+            Ok(Some(IndexEntry {
+                key: name.clone(),
+                range,
+                data: IndexEntryData::Function {
+                    name,
+                    arguments,
+                    documentation,
+                },
+            }))
+        },
+
+        "setMethod" | "methods::setMethod" => {
+            let signature = values.get(1).into_result()?;
+            let signature = contents.node_slice(signature)?.to_string();
+            let signature = unquote(&signature);
+
+            // Methods are keyed by generic and signature so that they don't
+            // shadow the generic or each other
+            Ok(Some(IndexEntry {
+                key: format!("{name},{signature}"),
+                range,
+                data: IndexEntryData::Method { name, signature },
+            }))
+        },
+
+        "setClass" | "methods::setClass" | "setRefClass" | "methods::setRefClass" => {
+            Ok(Some(IndexEntry {
+                key: name.clone(),
+                range,
+                data: IndexEntryData::Class { name },
+            }))
+        },
+
+        _ => Ok(None),
+    }
+}
+
+pub(crate) fn unquote(text: &str) -> String {
+    let text = text.trim_matches(|c| c == '"' || c == '\'');
+    text.trim_matches('`').to_string()
+}
+
 fn index_comment(_path: &Path, contents: &Rope, node: &Node) -> anyhow::Result<Option<IndexEntry>> {
     // check for comment
     node.is_comment().into_result()?;
@@ -293,3 +447,93 @@ fn index_comment(_path: &Path, contents: &Rope, node: &Node) -> anyhow::Result<O
         data: IndexEntryData::Section { level, title },
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::lsp::documents::Document;
+    use crate::lsp::indexer::index_document;
+    use crate::lsp::indexer::IndexEntry;
+    use crate::lsp::indexer::IndexEntryData;
+    use crate::lsp::indexer::WORKSPACE_INDEX;
+
+    // Each test indexes its own path since the index is global
+    fn index(path: &str, text: &str) -> Vec<IndexEntry> {
+        let document = Document::new(text, None);
+        index_document(&document, Path::new(path));
+
+        let index = WORKSPACE_INDEX.lock().unwrap();
+        let mut entries: Vec<IndexEntry> = index
+            .get(path)
+            .map(|index| index.values().cloned().collect())
+            .unwrap_or_default();
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+        entries
+    }
+
+    fn function_arguments(entry: &IndexEntry) -> Option<&Vec<String>> {
+        match &entry.data {
+            IndexEntryData::Function { arguments, .. } => Some(arguments),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_index_functions_and_variables() {
+        let entries = index("/test/index_basic.R", "f <- function(x, y) x\nz <- 1\n");
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            function_arguments(&entries[0]),
+            Some(&vec!["x".to_string(), "y".to_string()])
+        );
+        assert!(matches!(&entries[1].data, IndexEntryData::Variable { name } if name == "z"));
+    }
+
+    #[test]
+    fn test_index_function_not_replaced_by_variable() {
+        let entries = index(
+            "/test/index_memoise.R",
+            "f <- function(x) x\nf <- memoise(f)\n",
+        );
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            function_arguments(&entries[0]),
+            Some(&vec!["x".to_string()])
+        );
+
+        // But a variable is replaced by a function, and a function by another one
+        let entries = index(
+            "/test/index_redefined.R",
+            "f <- NULL\nf <- function(x) x\nf <- function(y) y\n",
+        );
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            function_arguments(&entries[0]),
+            Some(&vec!["y".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_index_s4() {
+        let entries = index(
+            "/test/index_s4.R",
+            "setGeneric(\"area\", function(shape) standardGeneric(\"area\"))
+setMethod(\"area\", \"Square\", function(shape) shape@side^2)
+setClass(\"Square\", representation(side = \"numeric\"))
+",
+        );
+        let keys: Vec<&str> = entries.iter().map(|entry| entry.key.as_str()).collect();
+        assert_eq!(keys, vec!["Square", "area", "area,Square"]);
+
+        assert!(matches!(&entries[0].data, IndexEntryData::Class { .. }));
+        assert_eq!(
+            function_arguments(&entries[1]),
+            Some(&vec!["shape".to_string()])
+        );
+        assert!(matches!(
+            &entries[2].data,
+            IndexEntryData::Method { signature, .. } if signature == "Square"
+        ));
+    }
+}
@@ -0,0 +1,292 @@
+//
+// lint.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use dashmap::DashMap;
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use harp::utils::r_is_null;
+use once_cell::sync::Lazy;
+use ropey::Rope;
+use tokio::task::AbortHandle;
+use tower_lsp::lsp_types::Diagnostic;
+use tower_lsp::lsp_types::DiagnosticSeverity;
+use tower_lsp::lsp_types::NumberOrString;
+use tower_lsp::lsp_types::Range;
+use tower_lsp::lsp_types::Url;
+use tree_sitter::Point;
+
+use crate::lsp;
+use crate::lsp::diagnostics;
+use crate::lsp::documents::Document;
+use crate::lsp::encoding::convert_point_to_position;
+use crate::lsp::main_loop::AuxiliaryEvent;
+use crate::lsp::state::WorldState;
+use crate::r_task;
+
+/// How long to wait after the last change before linting a document
+const LINT_DEBOUNCE: Duration = Duration::from_millis(750);
+
+/// The latest lints of each document. They may have been computed for an
+/// older version of the document until the next debounced lint runs.
+static LINTS: Lazy<DashMap<Url, Vec<Diagnostic>>> = Lazy::new(DashMap::new);
+
+/// Pending lint requests of each document. A new request aborts the pending
+/// one, so that only the latest version of a document actually runs lintr.
+static PENDING: Lazy<DashMap<Url, PendingLint>> = Lazy::new(DashMap::new);
+
+/// Whether we've already logged that lintr is missing
+static WARNED_MISSING_LINTR: AtomicBool = AtomicBool::new(false);
+
+struct PendingLint {
+    version: Option<i32>,
+    abort_handle: AbortHandle,
+}
+
+struct Lint {
+    line: i32,
+    column: i32,
+    kind: String,
+    message: String,
+    linter: String,
+}
+
+/// Schedule linting of a document
+///
+/// Linting runs after a debounce delay so that we don't call into R on
+/// every keystroke. Once lints are available, the diagnostics of the
+/// document are published again with the lints included.
+pub(crate) fn spawn_lint(uri: Url, document: Document, state: WorldState) {
+    if !is_enabled(&state) {
+        return;
+    }
+
+    let version = document.version;
+
+    let abort_handle = lsp::spawn({
+        let uri = uri.clone();
+        async move {
+            tokio::time::sleep(LINT_DEBOUNCE).await;
+
+            // The wait is over, linting can't be aborted anymore. Newer
+            // versions are still checked for once lints are computed.
+            tokio::task::spawn_blocking(move || lint_document(uri, document, state)).await?
+        }
+    });
+
+    let pending = PendingLint {
+        version,
+        abort_handle,
+    };
+    if let Some(previous) = PENDING.insert(uri, pending) {
+        previous.abort_handle.abort();
+    }
+}
+
+fn lint_document(
+    uri: Url,
+    document: Document,
+    state: WorldState,
+) -> anyhow::Result<Option<AuxiliaryEvent>> {
+    let _s = tracing::info_span!("lint", uri = %uri).entered();
+
+    let path = uri
+        .to_file_path()
+        .ok()
+        .and_then(|path| path.to_str().map(String::from))
+        .unwrap_or_default();
+    let text = document.contents.to_string();
+
+    let Some(lints) = r_task(|| r_lint(&path, &text))? else {
+        if !WARNED_MISSING_LINTR.swap(true, Ordering::Relaxed) {
+            lsp::log_warn!("Can't lint R code: the lintr package is not installed.");
+        }
+        return Ok(None);
+    };
+
+    let lints: Vec<Diagnostic> = lints
+        .into_iter()
+        .map(|lint| lint_diagnostic(lint, &document.contents))
+        .collect();
+
+    // Bail if the document has changed or was closed while we were linting
+    let version = document.version;
+    if !is_pending(&uri, version) {
+        return Ok(None);
+    }
+    LINTS.insert(uri.clone(), lints);
+
+    let mut diagnostics = diagnostics::generate_diagnostics(document, state.clone());
+    diagnostics.append(&mut lints_for(&uri, &state));
+
+    Ok(Some(AuxiliaryEvent::PublishDiagnostics(
+        uri,
+        diagnostics,
+        version,
+    )))
+}
+
+fn is_pending(uri: &Url, version: Option<i32>) -> bool {
+    PENDING
+        .get(uri)
+        .map_or(false, |pending| pending.version == version)
+}
+
+/// Get the latest lints of a document
+///
+/// After an edit, these are the lints of a previous version of the document.
+/// They are published as is until the debounced lint of the new version
+/// replaces them, so that lints don't disappear on every keystroke.
+///
+/// Returns an empty vector if linting is disabled.
+pub(crate) fn lints_for(uri: &Url, state: &WorldState) -> Vec<Diagnostic> {
+    if !is_enabled(state) {
+        return Vec::new();
+    }
+
+    LINTS.get(uri).map_or_else(Vec::new, |lints| lints.clone())
+}
+
+/// Forget about the lints of a closed document
+pub(crate) fn clear(uri: &Url) {
+    if let Some((_, pending)) = PENDING.remove(uri) {
+        pending.abort_handle.abort();
+    }
+    LINTS.remove(uri);
+}
+
+fn is_enabled(state: &WorldState) -> bool {
+    state.config.diagnostics.enable && state.config.diagnostics.lintr
+}
+
+/// Run lintr on the R side
+///
+/// Returns `None` if lintr is not installed.
+fn r_lint(path: &str, text: &str) -> anyhow::Result<Option<Vec<Lint>>> {
+    let lints = RFunction::from(".ps.lint.lintText")
+        .param("path", path)
+        .param("text", text)
+        .call()?;
+
+    if r_is_null(lints.sexp) {
+        return Ok(None);
+    }
+
+    let line: Vec<i32> = lints.vector_elt(0)?.try_into()?;
+    let column: Vec<i32> = lints.vector_elt(1)?.try_into()?;
+    let kind: Vec<String> = lints.vector_elt(2)?.try_into()?;
+    let message: Vec<String> = lints.vector_elt(3)?.try_into()?;
+    let linter: Vec<String> = lints.vector_elt(4)?.try_into()?;
+
+    let lints = itertools::izip!(line, column, kind, message, linter)
+        .map(|(line, column, kind, message, linter)| Lint {
+            line,
+            column,
+            kind,
+            message,
+            linter,
+        })
+        .collect();
+
+    Ok(Some(lints))
+}
+
+fn lint_diagnostic(lint: Lint, contents: &Rope) -> Diagnostic {
+    // lintr positions are one-based and count characters rather than bytes
+    let row = ((lint.line - 1).max(0) as usize).min(contents.len_lines() - 1);
+    let line = contents.line(row);
+    let column = ((lint.column - 1).max(0) as usize).min(line.len_chars());
+    let column = line.char_to_byte(column);
+
+    let position = convert_point_to_position(contents, Point::new(row, column));
+
+    Diagnostic {
+        range: Range::new(position, position),
+        severity: Some(lint_severity(&lint.kind)),
+        code: Some(NumberOrString::String(lint.linter)),
+        source: Some(String::from("lintr")),
+        message: lint.message,
+        ..Default::default()
+    }
+}
+
+fn lint_severity(kind: &str) -> DiagnosticSeverity {
+    match kind {
+        "error" => DiagnosticSeverity::ERROR,
+        "warning" => DiagnosticSeverity::WARNING,
+        "style" => DiagnosticSeverity::INFORMATION,
+        _ => DiagnosticSeverity::HINT,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+    use tower_lsp::lsp_types::Diagnostic;
+    use tower_lsp::lsp_types::DiagnosticSeverity;
+    use tower_lsp::lsp_types::Position;
+    use tower_lsp::lsp_types::Url;
+
+    use crate::lsp::lint::clear;
+    use crate::lsp::lint::lint_diagnostic;
+    use crate::lsp::lint::lint_severity;
+    use crate::lsp::lint::lints_for;
+    use crate::lsp::lint::Lint;
+    use crate::lsp::lint::LINTS;
+    use crate::lsp::state::WorldState;
+
+    #[test]
+    fn test_lint_severity() {
+        assert_eq!(lint_severity("error"), DiagnosticSeverity::ERROR);
+        assert_eq!(lint_severity("warning"), DiagnosticSeverity::WARNING);
+        assert_eq!(lint_severity("style"), DiagnosticSeverity::INFORMATION);
+        assert_eq!(lint_severity("other"), DiagnosticSeverity::HINT);
+    }
+
+    #[test]
+    fn test_lint_diagnostic_position() {
+        let contents = Rope::from("x <- 1\n\"é\"; y=2\n");
+        let lint = Lint {
+            line: 2,
+            column: 8,
+            kind: String::from("style"),
+            message: String::from("Put spaces around `=`."),
+            linter: String::from("infix_spaces_linter"),
+        };
+
+        let diagnostic = lint_diagnostic(lint, &contents);
+        assert_eq!(diagnostic.range.start, Position::new(1, 7));
+        assert_eq!(diagnostic.source.as_deref(), Some("lintr"));
+    }
+
+    #[test]
+    fn test_lints_kept_until_replaced() {
+        let uri = Url::parse("file:///test_lints_kept_until_replaced.R").unwrap();
+        let mut state = WorldState::default();
+        let lint = Diagnostic {
+            message: String::from("Put spaces around `=`."),
+            ..Default::default()
+        };
+
+        // The lints of the last linted version are published for newer
+        // versions too, until they are replaced
+        assert!(lints_for(&uri, &state).is_empty());
+        LINTS.insert(uri.clone(), vec![lint.clone()]);
+        assert_eq!(lints_for(&uri, &state), vec![lint]);
+
+        state.config.diagnostics.lintr = false;
+        assert!(lints_for(&uri, &state).is_empty());
+        state.config.diagnostics.lintr = true;
+
+        // Closing the document forgets them
+        clear(&uri);
+        assert!(lints_for(&uri, &state).is_empty());
+    }
+}
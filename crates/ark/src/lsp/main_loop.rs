@@ -27,6 +27,7 @@ use crate::lsp::backend::LspResponse;
 use crate::lsp::diagnostics;
 use crate::lsp::documents::Document;
 use crate::lsp::handlers;
+use crate::lsp::lint;
 use crate::lsp::state::WorldState;
 use crate::lsp::state_handlers;
 use crate::lsp::state_handlers::ConsoleInputs;
@@ -69,6 +70,7 @@ pub(crate) enum KernelNotification {
 #[derive(Debug)]
 pub(crate) enum AuxiliaryEvent {
     Log(lsp_types::MessageType, String),
+    ShowMessage(lsp_types::MessageType, String),
     PublishDiagnostics(Url, Vec<Diagnostic>, Option<i32>),
     SpawnedTask(JoinHandle<anyhow::Result<Option<AuxiliaryEvent>>>),
 }
@@ -287,9 +289,15 @@ impl GlobalState {
                         LspRequest::SelectionRange(params) => {
                             respond(tx, handlers::handle_selection_range(params, &self.world), LspResponse::SelectionRange)?;
                         },
+                        LspRequest::FoldingRange(params) => {
+                            respond(tx, handlers::handle_folding_range(params, &self.world), LspResponse::FoldingRange)?;
+                        },
                         LspRequest::References(params) => {
                             respond(tx, handlers::handle_references(params, &self.world), LspResponse::References)?;
                         },
+                        LspRequest::Rename(params) => {
+                            respond(tx, handlers::handle_rename(params, &self.world), LspResponse::Rename)?;
+                        },
                         LspRequest::StatementRange(params) => {
                             respond(tx, handlers::handle_statement_range(params, &self.world), LspResponse::StatementRange)?;
                         },
@@ -300,6 +308,14 @@ impl GlobalState {
                             state_handlers::did_change_formatting_options(&params.text_document_position.text_document.uri, &params.options, &mut self.world);
                             respond(tx, handlers::handle_indent(params, &self.world), LspResponse::OnTypeFormatting)?;
                         },
+                        LspRequest::Formatting(params) => {
+                            state_handlers::did_change_formatting_options(&params.text_document.uri, &params.options, &mut self.world);
+                            respond(tx, handlers::handle_formatting(params, &self.world), LspResponse::Formatting)?;
+                        },
+                        LspRequest::RangeFormatting(params) => {
+                            state_handlers::did_change_formatting_options(&params.text_document.uri, &params.options, &mut self.world);
+                            respond(tx, handlers::handle_range_formatting(params, &self.world), LspResponse::RangeFormatting)?;
+                        },
                         LspRequest::VirtualDocument(params) => {
                             respond(tx, handlers::handle_virtual_document(params), LspResponse::VirtualDocument)?;
                         },
@@ -431,6 +447,9 @@ impl AuxiliaryState {
         loop {
             match self.next_event().await {
                 AuxiliaryEvent::Log(level, message) => self.log(level, message).await,
+                AuxiliaryEvent::ShowMessage(level, message) => {
+                    self.client.show_message(level, message).await
+                },
                 AuxiliaryEvent::SpawnedTask(handle) => self.tasks.push(Box::pin(handle)),
                 AuxiliaryEvent::PublishDiagnostics(uri, diagnostics, version) => {
                     self.client
@@ -450,6 +469,9 @@ impl AuxiliaryState {
                     // A joined task returned an event for us, handle it
                     Ok(Ok(Some(event))) => return event,
 
+                    // Tasks may be aborted on purpose, e.g. debounced ones
+                    Err(err) if err.is_cancelled() => (),
+
                     // Otherwise relay any errors and loop back into select
                     Err(err) => self.log_error(format!("A task panicked:\n{err:?}")).await,
                     Ok(Err(err)) => self.log_error(format!("A task failed:\n{err:?}")).await,
@@ -504,6 +526,17 @@ pub(crate) fn log(level: lsp_types::MessageType, message: String) {
     };
 }
 
+/// Show a message to the user. Unlike `log()`, the message is displayed in
+/// the UI rather than in the LSP output channel, so use it sparingly.
+pub(crate) fn show_message(level: lsp_types::MessageType, message: String) {
+    // We're not connected to an LSP client when running unit tests
+    if cfg!(test) {
+        return;
+    }
+
+    send_auxiliary(AuxiliaryEvent::ShowMessage(level, message));
+}
+
 /// Spawn a blocking task
 ///
 /// This runs tasks that do semantic analysis on a separate thread pool to avoid
@@ -523,12 +556,32 @@ where
     send_auxiliary(AuxiliaryEvent::SpawnedTask(handle));
 }
 
+/// Spawn an asynchronous task
+///
+/// Like `spawn_blocking()` but for tasks that mostly wait, e.g. on a timer.
+/// They run on the async runtime and don't hold a thread of the blocking
+/// pool. Returns a handle to abort the task.
+pub(crate) fn spawn<Fut>(future: Fut) -> tokio::task::AbortHandle
+where
+    Fut: future::Future<Output = anyhow::Result<Option<AuxiliaryEvent>>>,
+    Fut: Send + 'static,
+{
+    let handle = tokio::task::spawn(future);
+    let abort_handle = handle.abort_handle();
+
+    send_auxiliary(AuxiliaryEvent::SpawnedTask(handle));
+    abort_handle
+}
+
 pub(crate) fn spawn_diagnostics_refresh(uri: Url, document: Document, state: WorldState) {
     lsp::spawn_blocking(move || {
         let _s = tracing::info_span!("diagnostics_refresh", uri = %uri).entered();
 
         let version = document.version;
-        let diagnostics = diagnostics::generate_diagnostics(document, state);
+        let mut lints = lint::lints_for(&uri, &state);
+
+        let mut diagnostics = diagnostics::generate_diagnostics(document, state);
+        diagnostics.append(&mut lints);
 
         Ok(Some(AuxiliaryEvent::PublishDiagnostics(
             uri,
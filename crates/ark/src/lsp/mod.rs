@@ -16,6 +16,8 @@ pub mod document_context;
 pub mod documents;
 pub mod encoding;
 pub mod events;
+pub mod folding_range;
+pub mod formatting;
 pub mod handler;
 pub mod handlers;
 pub mod help;
@@ -23,10 +25,12 @@ pub mod help_topic;
 pub mod hover;
 pub mod indent;
 pub mod indexer;
+pub mod lint;
 pub mod main_loop;
 pub mod markdown;
 pub mod offset;
 pub mod references;
+pub mod rename;
 pub mod selection_range;
 pub mod signature_help;
 pub mod state;
@@ -58,6 +62,8 @@ pub(crate) use log_error;
 pub(crate) use log_info;
 pub(crate) use log_warn;
 pub(crate) use main_loop::publish_diagnostics;
+pub(crate) use main_loop::show_message;
+pub(crate) use main_loop::spawn;
 pub(crate) use main_loop::spawn_blocking;
 pub(crate) use main_loop::spawn_diagnostics_refresh;
 pub(crate) use main_loop::spawn_diagnostics_refresh_all;
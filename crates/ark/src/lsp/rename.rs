@@ -0,0 +1,267 @@
+//
+// rename.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use anyhow::anyhow;
+use harp::utils::is_symbol_valid;
+use ropey::Rope;
+use tree_sitter::Node;
+use tree_sitter::Point;
+
+use crate::lsp::documents::Document;
+use crate::lsp::offset::ArkRange;
+use crate::lsp::offset::ArkTextEdit;
+use crate::lsp::traits::node::NodeExt;
+use crate::lsp::traits::rope::RopeExt;
+use crate::treesitter::BinaryOperatorType;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+
+/// Rename the symbol at `point` within its lexical scope
+///
+/// The scope of a symbol is the innermost function that binds it, either as
+/// a parameter or through a local assignment, or the whole document if no
+/// function does. Occurrences in nested functions that bind a symbol of the
+/// same name are left alone since they refer to a different variable.
+///
+/// Returns `None` if there is no symbol at `point`.
+pub(crate) fn rename(
+    document: &Document,
+    point: Point,
+    new_name: &str,
+) -> anyhow::Result<Option<Vec<ArkTextEdit>>> {
+    let contents = &document.contents;
+    let root = document.ast.root_node();
+
+    let Some(node) = find_symbol(root, point) else {
+        return Ok(None);
+    };
+
+    if !is_symbol_valid(new_name) {
+        return Err(anyhow!(
+            "Can't rename: `{new_name}` is not a valid R symbol."
+        ));
+    }
+
+    let symbol = contents.node_slice(&node)?.to_string();
+    if symbol == new_name {
+        return Ok(Some(Vec::new()));
+    }
+
+    let scope = node
+        .ancestors()
+        .find(|scope| is_scope(scope) && scope_binds(scope, &symbol, contents))
+        .unwrap_or(root);
+
+    if scope_binds(&scope, new_name, contents) {
+        return Err(anyhow!(
+            "Can't rename: `{new_name}` is already defined in this scope."
+        ));
+    }
+
+    let mut occurrences = Vec::new();
+    collect_occurrences(scope, &scope, &symbol, contents, &mut occurrences);
+
+    let edits = occurrences
+        .into_iter()
+        .map(|node| ArkTextEdit {
+            range: ArkRange {
+                start: node.start_position(),
+                end: node.end_position(),
+            },
+            new_text: new_name.to_string(),
+        })
+        .collect();
+
+    Ok(Some(edits))
+}
+
+fn find_symbol(root: Node, point: Point) -> Option<Node> {
+    let node = root.descendant_for_point_range(point, point)?;
+    if is_symbol(&node) {
+        return Some(node);
+    }
+
+    // The cursor might be right after the identifier
+    if point.column == 0 {
+        return None;
+    }
+    let point = Point::new(point.row, point.column - 1);
+    let node = root.descendant_for_point_range(point, point)?;
+
+    is_symbol(&node).then_some(node)
+}
+
+/// Is `node` an identifier that refers to a variable? This excludes names
+/// following `$` and `@`, argument names in calls, and namespaced symbols.
+fn is_symbol(node: &Node) -> bool {
+    if !node.is_identifier() {
+        return false;
+    }
+
+    let Some(parent) = node.parent() else {
+        return true;
+    };
+
+    match parent.node_type() {
+        NodeType::ExtractOperator(_) => parent.child_by_field_name("rhs") != Some(*node),
+        NodeType::NamespaceOperator(_) => false,
+        NodeType::Argument => parent.child_by_field_name("name") != Some(*node),
+        _ => true,
+    }
+}
+
+fn is_scope(node: &Node) -> bool {
+    matches!(
+        node.node_type(),
+        NodeType::FunctionDefinition | NodeType::Program
+    )
+}
+
+/// Does `scope` bind `symbol` as a parameter or through a local assignment?
+fn scope_binds(scope: &Node, symbol: &str, contents: &Rope) -> bool {
+    if scope.node_type() == NodeType::FunctionDefinition {
+        if let Some(parameters) = scope.child_by_field_name("parameters") {
+            let mut cursor = parameters.walk();
+            for parameter in parameters.children(&mut cursor) {
+                let Some(name) = parameter.child_by_field_name("name") else {
+                    continue;
+                };
+                if node_is_symbol(&name, symbol, contents) {
+                    return true;
+                }
+            }
+        }
+
+        return match scope.child_by_field_name("body") {
+            Some(body) => binds(&body, symbol, contents),
+            None => false,
+        };
+    }
+
+    binds(scope, symbol, contents)
+}
+
+fn binds(node: &Node, symbol: &str, contents: &Rope) -> bool {
+    // Nested functions have their own scope
+    if node.node_type() == NodeType::FunctionDefinition {
+        return false;
+    }
+
+    let target = match node.node_type() {
+        NodeType::BinaryOperator(BinaryOperatorType::LeftAssignment) |
+        NodeType::BinaryOperator(BinaryOperatorType::EqualsAssignment) => {
+            node.child_by_field_name("lhs")
+        },
+        NodeType::BinaryOperator(BinaryOperatorType::RightAssignment) => {
+            node.child_by_field_name("rhs")
+        },
+        NodeType::ForStatement => node.child_by_field_name("variable"),
+        _ => None,
+    };
+
+    if let Some(target) = target {
+        if node_is_symbol(&target, symbol, contents) {
+            return true;
+        }
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if binds(&child, symbol, contents) {
+            return true;
+        }
+    }
+
+    false
+}
+
+fn collect_occurrences<'tree>(
+    node: Node<'tree>,
+    scope: &Node<'tree>,
+    symbol: &str,
+    contents: &Rope,
+    occurrences: &mut Vec<Node<'tree>>,
+) {
+    // Skip nested functions that shadow the symbol
+    if node != *scope &&
+        node.node_type() == NodeType::FunctionDefinition &&
+        scope_binds(&node, symbol, contents)
+    {
+        return;
+    }
+
+    if node_is_symbol(&node, symbol, contents) && is_symbol(&node) {
+        occurrences.push(node);
+        return;
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_occurrences(child, scope, symbol, contents, occurrences);
+    }
+}
+
+fn node_is_symbol(node: &Node, symbol: &str, contents: &Rope) -> bool {
+    if !node.is_identifier() {
+        return false;
+    }
+
+    match contents.node_slice(node) {
+        Ok(text) => text == symbol,
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lsp::documents::Document;
+    use crate::lsp::offset::apply_text_edits;
+    use crate::lsp::rename::rename;
+    use crate::test::point_from_cursor;
+
+    fn rename_at_cursor(text: &str, new_name: &str) -> anyhow::Result<String> {
+        let (mut text, point) = point_from_cursor(text);
+        let document = Document::new(&text, None);
+        let edits = rename(&document, point, new_name)?.unwrap();
+        apply_text_edits(edits, &mut text)?;
+        Ok(text)
+    }
+
+    #[test]
+    fn test_rename_local_variable() {
+        let text = "x <- 1\nf <- function() {\n  @x <- 2\n  x + 1\n}\nx\n";
+        assert_eq!(
+            rename_at_cursor(text, "y").unwrap(),
+            "x <- 1\nf <- function() {\n  y <- 2\n  y + 1\n}\nx\n"
+        );
+    }
+
+    #[test]
+    fn test_rename_parameter() {
+        let text = "f <- function(@x, y = x) {\n  g <- function() x\n  list(x = x$x)\n}\n";
+        assert_eq!(
+            rename_at_cursor(text, "z").unwrap(),
+            "f <- function(z, y = z) {\n  g <- function() z\n  list(x = z$x)\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_rename_skips_shadowed_bindings() {
+        let text = "@x <- 1\nf <- function(x) x\ng <- function() {\n  x <- 2\n}\nx\n";
+        assert_eq!(
+            rename_at_cursor(text, "y").unwrap(),
+            "y <- 1\nf <- function(x) x\ng <- function() {\n  x <- 2\n}\ny\n"
+        );
+    }
+
+    #[test]
+    fn test_rename_rejects_collisions() {
+        let text = "f <- function(@x, y) x + y\n";
+        assert!(rename_at_cursor(text, "y").is_err());
+        assert!(rename_at_cursor(text, "not valid").is_err());
+    }
+}
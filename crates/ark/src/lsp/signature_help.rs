@@ -7,10 +7,12 @@
 
 use harp::eval::r_parse_eval;
 use harp::eval::RParseEvalOptions;
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use harp::object::RObject;
 use harp::utils::r_formals;
 use harp::utils::r_is_function;
 use log::info;
-use stdext::unwrap;
 use stdext::unwrap::IntoResult;
 use tower_lsp::lsp_types::Documentation;
 use tower_lsp::lsp_types::ParameterInformation;
@@ -18,14 +20,12 @@ use tower_lsp::lsp_types::ParameterLabel;
 use tower_lsp::lsp_types::SignatureHelp;
 use tower_lsp::lsp_types::SignatureInformation;
 use tree_sitter::Node;
-use tree_sitter::Point;
 
 use crate::lsp::document_context::DocumentContext;
 use crate::lsp::help::RHtmlHelp;
-use crate::lsp::traits::node::NodeExt;
 use crate::lsp::traits::point::PointExt;
 use crate::lsp::traits::rope::RopeExt;
-use crate::treesitter::NodeType;
+use crate::treesitter::call_context_at;
 use crate::treesitter::NodeTypeExt;
 
 // TODO: We should probably take a pass through `signature_help()` and rewrite it from
@@ -37,44 +37,20 @@ use crate::treesitter::NodeTypeExt;
 pub(crate) unsafe fn r_signature_help(
     context: &DocumentContext,
 ) -> anyhow::Result<Option<SignatureHelp>> {
-    // Get document AST + completion position.
-    let ast = &context.document.ast;
-
-    // Find the node closest to the completion point.
-    let node = ast.root_node();
-    let Some(mut node) = node.find_closest_node_to_point(context.point) else {
+    // Find the call surrounding the completion point. We only want to
+    // provide signature help when inside `fn(<here>)`.
+    let Some(call_context) = call_context_at(
+        context.document.ast.root_node(),
+        context.point,
+        &context.document.contents,
+    ) else {
         return Ok(None);
     };
+    let callee = call_context.callee;
 
-    // If we landed on a comma before the cursor position, move to the next sibling node.
-    // We need to check the position as, if the cursor is "on" the comma as in
-    //
-    //    foo (x = ,)
-    //
-    // then the current context is associated with 'x = ' and not with what follows
-    // the comma.
-    if node.node_type() == NodeType::Comma && node.start_position().is_before(context.point) {
-        if let Some(sibling) = node.next_sibling() {
-            node = sibling;
-        }
-    }
-
-    if node.node_type() == NodeType::Anonymous(String::from(")")) {
-        if let Some(sibling) = node.prev_sibling() {
-            node = sibling;
-        }
-    }
-
-    // Get the current node.
-    let mut parent = match node.parent() {
-        Some(parent) => parent,
-        None => return Ok(None),
-    };
-
-    // Look for a call node. Keep track of other relevant context while we search for it.
-    // We want to figure out which of the current formals is currently "active". This is
-    // a bit tricky for R functions, as one can supply named and unnamed arguments in any
-    // order. For example:
+    // We want to figure out which of the current formals is currently
+    // "active". This is a bit tricky for R functions, as one can supply named
+    // and unnamed arguments in any order. For example:
     //
     //   foo(a = 1, b, c = 2, d)
     //
@@ -84,78 +60,48 @@ pub(crate) unsafe fn r_signature_help(
     // The list of arguments that have been explicitly specified.
     let mut explicit_parameters = vec![];
 
-    // The number of unnamed arguments that have been supplied.
-    let mut num_unnamed_arguments = 0;
-
     // The active argument, if any. Relevant for cases where the cursor is lying after 'x = <...>',
     // so we know that 'x' must be active.
     let mut active_argument = None;
 
-    // Whether we've found the child node we were looking for.
-    let mut found_child = false;
+    // The number of named arguments supplied before the cursor
+    let mut num_named_arguments: usize = 0;
 
-    // The computed argument offset.
-    let mut offset: Option<u32> = None;
-
-    let call = loop {
-        // If we found an 'arguments' node, then use that to infer the current offset.
-        if parent.node_type() == NodeType::Arguments {
-            // If the cursor lies upon a named argument, use that as an override.
-            if let Some(name) = node.child_by_field_name("name") {
-                let name = context.document.contents.node_slice(&name)?.to_string();
-                active_argument = Some(name);
-            }
+    // The call is missing for incomplete code parsed as an `ERROR` node, in
+    // which case only the position of the cursor is known
+    let call = callee.parent().filter(|call| call.is_call());
 
-            let mut cursor = parent.walk();
-            let children = parent.children(&mut cursor);
-            for child in children {
-                if let Some(name) = child.child_by_field_name("name") {
-                    // If this is a named argument, add it to the list.
-                    let name = context.document.contents.node_slice(&name)?.to_string();
-                    explicit_parameters.push(name);
-
-                    // Subtract 1 from the number of unnamed arguments, as
-                    // the next comma we see won't be associated with an
-                    // unnamed argument.
-                    num_unnamed_arguments -= 1;
-                }
-
-                // If we find a comma, add to the offset.
-                if !found_child && child.node_type() == NodeType::Comma {
-                    num_unnamed_arguments += 1;
-                }
+    if let Some(arguments) = call.and_then(|call| call.child_by_field_name("arguments")) {
+        let mut cursor = arguments.walk();
+        for argument in arguments.children_by_field_name("argument", &mut cursor) {
+            let Some(name) = argument.child_by_field_name("name") else {
+                continue;
+            };
+            let name = context.document.contents.node_slice(&name)?.to_string();
 
-                // If we've now walked up to the current node, we can quit.
-                if child == node {
-                    found_child = true;
+            if argument.start_position().is_before(context.point) {
+                num_named_arguments += 1;
+                if call_context.named {
+                    active_argument = Some(name.clone());
                 }
             }
-        }
 
-        // If we find the 'call' node, we can quit.
-        if parent.is_call() {
-            break parent;
+            explicit_parameters.push(name);
         }
+    }
 
-        // Update.
-        node = parent;
-        parent = match node.parent() {
-            Some(parent) => parent,
-            None => return Ok(None),
-        };
-    };
-
-    // Totally possible that `node.find_closest_node_to_point(context.point)` finds a
-    // call node that is technically the closest node to the point, but is completely
-    // before the point. We only want to provide signature help when inside `fn(<here>)`!
-    if !is_within_call_parentheses(&context.point, &call) {
-        return Ok(None);
+    // The argument at the cursor is not one of the named arguments before it
+    if call_context.named {
+        num_named_arguments = num_named_arguments.saturating_sub(1);
     }
 
-    // Get the left-hand side of the call.
-    let callee = unwrap!(call.child(0), None => {
-        return Ok(None);
-    });
+    // The number of unnamed arguments that have been supplied.
+    let mut num_unnamed_arguments = call_context
+        .argument_index
+        .saturating_sub(num_named_arguments);
+
+    // The computed argument offset.
+    let mut offset: Option<u32> = None;
 
     // TODO: Should we search the document and / or the workspace index
     // before asking the R session for a definition? Which should take precedence?
@@ -195,7 +141,16 @@ pub(crate) unsafe fn r_signature_help(
         return Ok(None);
     }
 
-    // Get the formal parameter names associated with this function.
+    // If the function is an S3 generic, show the signature of the method the
+    // call would dispatch to. This is best-effort: we only look at the first
+    // argument and only if it can be evaluated without side effects.
+    let object = match call {
+        Some(call) => resolve_s3_method(object, &call, context),
+        None => object,
+    };
+
+    // Get the formal parameter names associated with this function. Primitives
+    // are handled by `r_formals()` through `args()`.
     let formals = r_formals(*object)?;
 
     // Get the help documentation associated with this function.
@@ -306,38 +261,66 @@ pub(crate) unsafe fn r_signature_help(
     Ok(Some(help))
 }
 
-fn is_within_call_parentheses(x: &Point, node: &Node) -> bool {
-    if node.node_type() != NodeType::Call {
-        // This would be very weird
-        log::error!("`is_within_call_parentheses()` called on a non-`call` node.");
-        return false;
-    }
+/// Resolve a generic to the S3 method that would be dispatched to, based on
+/// the first unnamed argument of `call`. Returns `callable` as is if it's not a
+/// generic, if the first argument can't be safely evaluated, or if there's no
+/// suitable method.
+unsafe fn resolve_s3_method(callable: RObject, call: &Node, context: &DocumentContext) -> RObject {
+    let Some(arguments) = call.child_by_field_name("arguments") else {
+        return callable;
+    };
+
+    let mut cursor = arguments.walk();
+    let first = arguments
+        .children_by_field_name("argument", &mut cursor)
+        .next();
 
-    let Some(arguments) = node.child_by_field_name("arguments") else {
-        return false;
+    let Some(first) = first else {
+        return callable;
     };
 
-    let n_children = arguments.child_count();
-    if n_children < 2 {
-        log::error!("`arguments` node only has {n_children} children.");
-        return false;
+    // A named first argument might not be the dispatch argument
+    if first.child_by_field_name("name").is_some() {
+        return callable;
     }
 
-    let open = arguments.child(1 - 1).unwrap();
-    let close = arguments.child(n_children - 1).unwrap();
+    let Some(value) = first.child_by_field_name("value") else {
+        return callable;
+    };
 
-    if open.node_type() != NodeType::Anonymous(String::from("(")) {
-        return false;
-    }
-    if close.node_type() != NodeType::Anonymous(String::from(")")) {
-        return false;
-    }
+    let Ok(code) = context.document.contents.node_slice(&value) else {
+        return callable;
+    };
+
+    let object = r_parse_eval(&code.to_string(), RParseEvalOptions {
+        forbid_function_calls: true,
+        ..Default::default()
+    });
+
+    // The object might not exist yet or might be too complex to evaluate
+    let Ok(object) = object else {
+        return callable;
+    };
 
-    x.is_after_or_equal(open.end_position()) && x.is_before_or_equal(close.start_position())
+    let method = RFunction::from(".ps.s3.resolveMethod")
+        .add(callable.clone())
+        .add(object)
+        .call();
+
+    match method {
+        Ok(method) if r_is_function(*method) => method,
+        Ok(_) => callable,
+        Err(err) => {
+            log::info!("Can't resolve S3 method for signature help: {err}");
+            callable
+        },
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use harp::environment::R_ENVS;
+    use harp::eval::r_parse_eval0;
     use harp::test::r_test;
     use tower_lsp::lsp_types::ParameterLabel;
 
@@ -364,6 +347,63 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_signature_help_dispatches_s3_methods() {
+        r_test(|| {
+            r_parse_eval0(
+                "
+                sig_generic <- function(x, ...) UseMethod('sig_generic')
+                sig_generic.sig_class <- function(x, foo, bar) NULL
+                sig_object <- structure(list(), class = 'sig_class')
+                ",
+                R_ENVS.global,
+            )
+            .unwrap();
+
+            let (text, point) = point_from_cursor("sig_generic(sig_object, @)");
+            let document = Document::new(&text, None);
+            let context = DocumentContext::new(&document, point, None);
+
+            let help = unsafe { r_signature_help(&context) };
+            let help = help.unwrap().unwrap();
+
+            let signature = help.signatures.get(0).unwrap();
+            assert_eq!(signature.label, "sig_generic(x, foo, bar)");
+            assert_eq!(help.active_parameter, Some(1));
+
+            r_parse_eval0(
+                "rm(sig_generic, sig_generic.sig_class, sig_object)",
+                R_ENVS.global,
+            )
+            .unwrap();
+        })
+    }
+
+    #[test]
+    fn test_signature_help_active_parameter() {
+        r_test(|| {
+            r_parse_eval0("sig_fn <- function(a, b, c) NULL", R_ENVS.global).unwrap();
+
+            let active_parameter = |code: &str| {
+                let (text, point) = point_from_cursor(code);
+                let document = Document::new(&text, None);
+                let context = DocumentContext::new(&document, point, None);
+                let help = unsafe { r_signature_help(&context) };
+                help.unwrap().unwrap().active_parameter
+            };
+
+            assert_eq!(active_parameter("sig_fn(@)"), Some(0));
+            assert_eq!(active_parameter("sig_fn(1, @)"), Some(1));
+            assert_eq!(active_parameter("sig_fn(c = 1, @)"), Some(0));
+            assert_eq!(active_parameter("sig_fn(1, c = @)"), Some(2));
+
+            // Incomplete calls
+            assert_eq!(active_parameter("sig_fn(1, @"), Some(1));
+
+            r_parse_eval0("rm(sig_fn)", R_ENVS.global).unwrap();
+        })
+    }
+
     #[test]
     fn test_no_signature_help_outside_parentheses() {
         r_test(|| {
@@ -18,6 +18,7 @@ use tower_lsp::lsp_types::DidCloseTextDocumentParams;
 use tower_lsp::lsp_types::DidOpenTextDocumentParams;
 use tower_lsp::lsp_types::DocumentOnTypeFormattingOptions;
 use tower_lsp::lsp_types::ExecuteCommandOptions;
+use tower_lsp::lsp_types::FoldingRangeProviderCapability;
 use tower_lsp::lsp_types::FormattingOptions;
 use tower_lsp::lsp_types::HoverProviderCapability;
 use tower_lsp::lsp_types::ImplementationProviderCapability;
@@ -42,10 +43,13 @@ use crate::lsp::config::indent_style_from_lsp;
 use crate::lsp::config::DocumentConfig;
 use crate::lsp::config::VscDiagnosticsConfig;
 use crate::lsp::config::VscDocumentConfig;
+use crate::lsp::config::VscFormattingConfig;
 use crate::lsp::diagnostics::DiagnosticsConfig;
 use crate::lsp::documents::Document;
 use crate::lsp::encoding::get_position_encoding_kind;
+use crate::lsp::formatting::FormattingConfig;
 use crate::lsp::indexer;
+use crate::lsp::lint;
 use crate::lsp::main_loop::LspState;
 use crate::lsp::state::workspace_uris;
 use crate::lsp::state::WorldState;
@@ -62,8 +66,8 @@ pub struct ConsoleInputs {
     /// information.
     pub console_scopes: Vec<Vec<String>>,
 
-    /// Packages currently installed in the library path. TODO: Should send
-    /// library paths instead and inspect and cache package information in the LSP.
+    /// Packages currently installed in the library paths. Only collected
+    /// again when the library paths change.
     pub installed_packages: Vec<String>,
 }
 
@@ -114,6 +118,7 @@ pub(crate) fn initialize(
                 TextDocumentSyncKind::INCREMENTAL,
             )),
             selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+            folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
             hover_provider: Some(HoverProviderCapability::from(true)),
             completion_provider: Some(CompletionOptions {
                 resolve_provider: Some(true),
@@ -133,6 +138,7 @@ pub(crate) fn initialize(
             type_definition_provider: None,
             implementation_provider: Some(ImplementationProviderCapability::Simple(true)),
             references_provider: Some(OneOf::Left(true)),
+            rename_provider: Some(OneOf::Left(true)),
             document_symbol_provider: Some(OneOf::Left(true)),
             workspace_symbol_provider: Some(OneOf::Left(true)),
             execute_command_provider: Some(ExecuteCommandOptions {
@@ -150,6 +156,8 @@ pub(crate) fn initialize(
                 first_trigger_character: String::from("\n"),
                 more_trigger_character: None,
             }),
+            document_formatting_provider: Some(OneOf::Left(true)),
+            document_range_formatting_provider: Some(OneOf::Left(true)),
             ..ServerCapabilities::default()
         },
     })
@@ -178,7 +186,8 @@ pub(crate) fn did_open(
     // update_config(vec![uri]).await;
 
     update_index(&uri, &document);
-    lsp::spawn_diagnostics_refresh(uri, document, state.clone());
+    lsp::spawn_diagnostics_refresh(uri.clone(), document.clone(), state.clone());
+    lint::spawn_lint(uri, document, state.clone());
 
     Ok(())
 }
@@ -201,6 +210,7 @@ pub(crate) fn did_change(
 
     update_index(uri, doc);
     lsp::spawn_diagnostics_refresh(uri.clone(), doc.clone(), state.clone());
+    lint::spawn_lint(uri.clone(), doc.clone(), state.clone());
 
     Ok(())
 }
@@ -214,6 +224,7 @@ pub(crate) fn did_close(
     let uri = params.text_document.uri;
 
     // Publish empty set of diagnostics to clear them
+    lint::clear(&uri);
     lsp::publish_diagnostics(uri.clone(), Vec::new(), None);
 
     state
@@ -292,6 +303,16 @@ async fn update_config(
         .collect();
     items.append(&mut diagnostics_items);
 
+    let formatting_keys = VscFormattingConfig::FIELD_NAMES_AS_ARRAY;
+    let mut formatting_items: Vec<ConfigurationItem> = formatting_keys
+        .iter()
+        .map(|key| ConfigurationItem {
+            scope_uri: None,
+            section: Some(VscFormattingConfig::section_from_key(key).into()),
+        })
+        .collect();
+    items.append(&mut formatting_items);
+
     // For document configs we collect all pairs of URIs and config keys of
     // interest in a flat vector
     let document_keys = VscDocumentConfig::FIELD_NAMES_AS_ARRAY;
@@ -312,7 +333,8 @@ async fn update_config(
     // by chunk
     let n_document_items = document_keys.len();
     let n_diagnostics_items = diagnostics_keys.len();
-    let n_items = n_diagnostics_items + (n_document_items * uris.len());
+    let n_formatting_items = formatting_keys.len();
+    let n_items = n_diagnostics_items + n_formatting_items + (n_document_items * uris.len());
 
     if configs.len() != n_items {
         return Err(anyhow!(
@@ -347,6 +369,24 @@ async fn update_config(
         lsp::spawn_diagnostics_refresh_all(state.clone());
     }
 
+    // --- Formatting
+    let keys = formatting_keys.into_iter();
+    let items: Vec<Value> = configs.by_ref().take(n_formatting_items).collect();
+
+    let mut map = serde_json::Map::new();
+    std::iter::zip(keys, items).for_each(|(key, item)| {
+        map.insert(key.into(), item);
+    });
+
+    // A missing setting comes back as `null`, in which case we keep the
+    // default formatter
+    let config: FormattingConfig =
+        match serde_json::from_value::<VscFormattingConfig>(serde_json::Value::Object(map)) {
+            Ok(config) => config.into(),
+            Err(_) => FormattingConfig::default(),
+        };
+    state.config.formatting = config;
+
     // --- Documents
     // For each document, deserialise the vector of JSON values into a typed config
     for uri in uris.into_iter() {
@@ -23,8 +23,10 @@ use tower_lsp::lsp_types::Url;
 use tower_lsp::lsp_types::WorkspaceSymbolParams;
 use tree_sitter::Node;
 
+use crate::lsp::documents::Document;
 use crate::lsp::encoding::convert_point_to_position;
 use crate::lsp::indexer;
+use crate::lsp::indexer::unquote;
 use crate::lsp::indexer::IndexEntryData;
 use crate::lsp::state::WorldState;
 use crate::lsp::traits::rope::RopeExt;
@@ -42,15 +44,25 @@ pub fn symbols(params: &WorkspaceSymbolParams) -> anyhow::Result<Vec<SymbolInfor
             return;
         }
 
+        let uri = match Url::from_file_path(path) {
+            Ok(uri) => uri,
+            Err(_) => {
+                // Skip this symbol rather than failing the whole request
+                error!("Can't convert path {} to a URI", path.display());
+                return;
+            },
+        };
+        let location = Location {
+            uri,
+            range: entry.range,
+        };
+
         match &entry.data {
-            IndexEntryData::Function { name, arguments: _ } => {
+            IndexEntryData::Function { name, .. } => {
                 info.push(SymbolInformation {
                     name: name.to_string(),
                     kind: SymbolKind::FUNCTION,
-                    location: Location {
-                        uri: Url::from_file_path(path).unwrap(),
-                        range: entry.range,
-                    },
+                    location,
                     tags: None,
                     deprecated: None,
                     container_name: None,
@@ -61,10 +73,40 @@ pub fn symbols(params: &WorkspaceSymbolParams) -> anyhow::Result<Vec<SymbolInfor
                 info.push(SymbolInformation {
                     name: title.to_string(),
                     kind: SymbolKind::MODULE,
-                    location: Location {
-                        uri: Url::from_file_path(path).unwrap(),
-                        range: entry.range,
-                    },
+                    location,
+                    tags: None,
+                    deprecated: None,
+                    container_name: None,
+                });
+            },
+
+            IndexEntryData::Variable { name } => {
+                info.push(SymbolInformation {
+                    name: name.to_string(),
+                    kind: SymbolKind::VARIABLE,
+                    location,
+                    tags: None,
+                    deprecated: None,
+                    container_name: None,
+                });
+            },
+
+            IndexEntryData::Method { name, signature } => {
+                info.push(SymbolInformation {
+                    name: name.to_string(),
+                    kind: SymbolKind::METHOD,
+                    location,
+                    tags: None,
+                    deprecated: None,
+                    container_name: Some(signature.to_string()),
+                });
+            },
+
+            IndexEntryData::Class { name } => {
+                info.push(SymbolInformation {
+                    name: name.to_string(),
+                    kind: SymbolKind::CLASS,
+                    location,
                     tags: None,
                     deprecated: None,
                     container_name: None,
@@ -80,10 +122,16 @@ pub(crate) fn document_symbols(
     state: &WorldState,
     params: &DocumentSymbolParams,
 ) -> anyhow::Result<Vec<DocumentSymbol>> {
-    let mut symbols: Vec<DocumentSymbol> = Vec::new();
-
     let uri = &params.text_document.uri;
     let document = state.documents.get(uri).into_result()?;
+    document_symbols_for(&document)
+}
+
+/// Builds the outline of a document. Functions defined within functions or
+/// S4 methods are nested under their parent.
+fn document_symbols_for(document: &Document) -> anyhow::Result<Vec<DocumentSymbol>> {
+    let mut symbols: Vec<DocumentSymbol> = Vec::new();
+
     let ast = &document.ast;
     let contents = &document.contents;
 
@@ -142,6 +190,18 @@ fn index_node(
         }
     }
 
+    // if we find an S4 definition, index it
+    if node.is_call() {
+        match index_s4_call(node, contents, parent, symbols) {
+            Ok(handled) => {
+                if handled {
+                    return Ok(true);
+                }
+            },
+            Err(error) => error!("{:?}", error),
+        }
+    }
+
     // by default, recurse into children
     let mut cursor = node.walk();
     for child in node.children(&mut cursor) {
@@ -184,8 +244,11 @@ fn index_assignment(
     // otherwise, just index as generic object
     let name = contents.node_slice(&lhs)?.to_string();
 
-    let start = convert_point_to_position(contents, lhs.start_position());
-    let end = convert_point_to_position(contents, lhs.end_position());
+    let start = convert_point_to_position(contents, node.start_position());
+    let end = convert_point_to_position(contents, node.end_position());
+
+    let name_start = convert_point_to_position(contents, lhs.start_position());
+    let name_end = convert_point_to_position(contents, lhs.end_position());
 
     let symbol = DocumentSymbol {
         name,
@@ -195,12 +258,16 @@ fn index_assignment(
         deprecated: None,
         tags: None,
         range: Range::new(start, end),
-        selection_range: Range::new(start, end),
+        selection_range: Range::new(name_start, name_end),
     };
 
     // add this symbol to the parent node
     parent.children.as_mut().unwrap().push(symbol);
 
+    // recurse into the value, e.g. for `gen <- setClass("Foo")`
+    let parent = parent.children.as_mut().unwrap().last_mut().unwrap();
+    index_node(&rhs, contents, parent, symbols)?;
+
     Ok(true)
 }
 
@@ -255,3 +322,208 @@ fn index_assignment_with_function(
 
     Ok(true)
 }
+
+/// Index S4 definitions made with `setClass()`, `setGeneric()`, and
+/// `setMethod()`. Definitions nested in their arguments, e.g. functions
+/// defined in the body of a method, are indexed as their children.
+fn index_s4_call(
+    node: &Node,
+    contents: &Rope,
+    parent: &mut DocumentSymbol,
+    symbols: &mut Vec<DocumentSymbol>,
+) -> Result<bool> {
+    let function = node.child_by_field_name("function").into_result()?;
+    let function = contents.node_slice(&function)?.to_string();
+
+    if !matches!(
+        function.as_str(),
+        "setClass" |
+            "methods::setClass" |
+            "setRefClass" |
+            "methods::setRefClass" |
+            "setGeneric" |
+            "methods::setGeneric" |
+            "setMethod" |
+            "methods::setMethod"
+    ) {
+        return Ok(false);
+    }
+
+    let arguments = node.child_by_field_name("arguments").into_result()?;
+    let mut cursor = arguments.walk();
+    let values: Vec<Node> = arguments
+        .children_by_field_name("argument", &mut cursor)
+        .filter_map(|argument| argument.child_by_field_name("value"))
+        .collect();
+
+    // the first argument is the name of the class or generic
+    let Some(name_node) = values.first() else {
+        return Ok(false);
+    };
+    if !name_node.is_string() {
+        return Ok(false);
+    }
+    let name = unquote(&contents.node_slice(name_node)?.to_string());
+
+    let (kind, detail) = match function.as_str() {
+        "setGeneric" | "methods::setGeneric" => {
+            (SymbolKind::FUNCTION, Some(String::from("generic")))
+        },
+        "setMethod" | "methods::setMethod" => {
+            let signature = match values.get(1) {
+                Some(signature) => Some(unquote(&contents.node_slice(signature)?.to_string())),
+                None => None,
+            };
+            (SymbolKind::METHOD, signature)
+        },
+        _ => (SymbolKind::CLASS, None),
+    };
+
+    let symbol = DocumentSymbol {
+        name,
+        kind,
+        detail,
+        children: Some(Vec::new()),
+        deprecated: None,
+        tags: None,
+        range: Range {
+            start: convert_point_to_position(contents, node.start_position()),
+            end: convert_point_to_position(contents, node.end_position()),
+        },
+        selection_range: Range {
+            start: convert_point_to_position(contents, name_node.start_position()),
+            end: convert_point_to_position(contents, name_node.end_position()),
+        },
+    };
+
+    // add this symbol to the parent node
+    parent.children.as_mut().unwrap().push(symbol);
+
+    // recurse into the remaining arguments, e.g. the method definition
+    let parent = parent.children.as_mut().unwrap().last_mut().unwrap();
+    for value in values.iter().skip(1) {
+        index_node(value, contents, parent, symbols)?;
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::DocumentSymbol;
+    use tower_lsp::lsp_types::Position;
+    use tower_lsp::lsp_types::Range;
+    use tower_lsp::lsp_types::SymbolKind;
+
+    use crate::lsp::documents::Document;
+    use crate::lsp::symbols::document_symbols_for;
+
+    fn summary(symbol: &DocumentSymbol) -> (String, SymbolKind, Vec<String>) {
+        let children = symbol
+            .children
+            .iter()
+            .flatten()
+            .map(|child| child.name.clone())
+            .collect();
+        (symbol.name.clone(), symbol.kind, children)
+    }
+
+    #[test]
+    fn test_document_symbols_nested() {
+        let text = "
+outer <- function(x) {
+  inner <- function(y) y
+  setMethod(\"show\", \"Foo\", function(object) {
+    helper <- function() NULL
+  })
+}
+x <- 1
+";
+        let document = Document::new(text, None);
+        let symbols = document_symbols_for(&document).unwrap();
+        assert_eq!(symbols.len(), 2);
+
+        let outer = &symbols[0];
+        assert_eq!(
+            summary(outer),
+            (
+                String::from("outer"),
+                SymbolKind::FUNCTION,
+                vec![String::from("inner"), String::from("show")]
+            )
+        );
+
+        // The range covers the whole definition, the selection range only
+        // the name
+        assert_eq!(
+            outer.range,
+            Range::new(Position::new(1, 0), Position::new(6, 1))
+        );
+        assert_eq!(
+            outer.selection_range,
+            Range::new(Position::new(1, 0), Position::new(1, 5))
+        );
+
+        let method = &outer.children.as_ref().unwrap()[1];
+        assert_eq!(
+            summary(method),
+            (
+                String::from("show"),
+                SymbolKind::METHOD,
+                vec![String::from("helper")]
+            )
+        );
+        assert_eq!(method.detail.as_deref(), Some("Foo"));
+        assert_eq!(
+            method.range,
+            Range::new(Position::new(3, 2), Position::new(5, 4))
+        );
+        assert_eq!(
+            method.selection_range,
+            Range::new(Position::new(3, 12), Position::new(3, 18))
+        );
+
+        let x = &symbols[1];
+        assert_eq!(summary(x), (String::from("x"), SymbolKind::OBJECT, vec![]));
+        assert_eq!(
+            x.range,
+            Range::new(Position::new(7, 0), Position::new(7, 6))
+        );
+        assert_eq!(
+            x.selection_range,
+            Range::new(Position::new(7, 0), Position::new(7, 1))
+        );
+    }
+
+    #[test]
+    fn test_document_symbols_s4() {
+        let text = "
+setGeneric(\"area\", function(shape) standardGeneric(\"area\"))
+Circle <- setClass(\"Circle\", representation(r = \"numeric\"))
+methods::setRefClass(\"Account\")
+setMethod(\"area\", signature(\"Circle\"), function(shape) pi * shape@r^2)
+";
+        let document = Document::new(text, None);
+        let symbols = document_symbols_for(&document).unwrap();
+
+        let summaries: Vec<_> = symbols.iter().map(summary).collect();
+        assert_eq!(
+            summaries,
+            vec![
+                (String::from("area"), SymbolKind::FUNCTION, vec![]),
+                (
+                    String::from("Circle"),
+                    SymbolKind::OBJECT,
+                    vec![String::from("Circle")]
+                ),
+                (String::from("Account"), SymbolKind::CLASS, vec![]),
+                (String::from("area"), SymbolKind::METHOD, vec![]),
+            ]
+        );
+
+        let class = &symbols[1].children.as_ref().unwrap()[0];
+        assert_eq!(class.kind, SymbolKind::CLASS);
+        assert_eq!(symbols[0].detail.as_deref(), Some("generic"));
+        assert_eq!(symbols[3].detail.as_deref(), Some("signature(\"Circle\")"));
+    }
+}
@@ -9,26 +9,26 @@
 
 use std::cell::Cell;
 use std::env;
-use std::sync::Arc;
-use std::sync::Mutex;
 
 use amalthea::connection_file::ConnectionFile;
-use amalthea::kernel::Kernel;
+use amalthea::kernel_dirs::InstallLocation;
 use amalthea::kernel_spec::KernelSpec;
-use amalthea::socket::stdin::StdInRequest;
-use ark::control::Control;
-use ark::dap;
+use ark::check::check_r_startup;
+use ark::check::run_checks;
 use ark::interface::SessionMode;
 use ark::logger;
-use ark::lsp;
-use ark::request::KernelRequest;
-use ark::request::RRequest;
-use ark::shell::Shell;
+use ark::logger::LogFormat;
+use ark::logger_hprof::ProfileFormat;
+use ark::logger_r;
+use ark::logger_rotate::LogRotation;
 use ark::signals::initialize_signal_block;
+use ark::start::start_kernel;
+use ark::startup;
+use ark::startup::StartupOption;
 use ark::traps::register_trap_handlers;
 use ark::version::detect_r;
-use bus::Bus;
-use crossbeam::channel::bounded;
+use ark::version::detect_r_at;
+use ark::version::RVersion;
 use crossbeam::channel::unbounded;
 use log::*;
 use notify::Watcher;
@@ -38,121 +38,20 @@ thread_local! {
     pub static ON_R_THREAD: Cell<bool> = Cell::new(false);
 }
 
-fn start_kernel(
-    connection_file: ConnectionFile,
-    r_args: Vec<String>,
-    startup_file: Option<String>,
-    session_mode: SessionMode,
-    capture_streams: bool,
-) {
-    // Create a new kernel from the connection file
-    let mut kernel = match Kernel::new("ark", connection_file) {
-        Ok(k) => k,
-        Err(e) => {
-            error!("Failed to create kernel: {}", e);
-            return;
-        },
-    };
-
-    // Create the channels used for communication. These are created here
-    // as they need to be shared across different components / threads.
-    let iopub_tx = kernel.create_iopub_tx();
-
-    // A broadcast channel (bus) used to notify clients when the kernel
-    // has finished initialization.
-    let mut kernel_init_tx = Bus::new(1);
-
-    // A channel pair used for shell requests.
-    // These events are used to manage the runtime state, and also to
-    // handle message delivery, among other things.
-    let (r_request_tx, r_request_rx) = bounded::<RRequest>(1);
-    let (kernel_request_tx, kernel_request_rx) = bounded::<KernelRequest>(1);
-
-    // Create the LSP and DAP clients.
-    // Not all Amalthea kernels provide these, but ark does.
-    // They must be able to deliver messages to the shell channel directly.
-    let lsp = Arc::new(Mutex::new(lsp::handler::Lsp::new(kernel_init_tx.add_rx())));
-
-    // DAP needs the `RRequest` channel to communicate with
-    // `read_console()` and send commands to the debug interpreter
-    let dap = dap::Dap::new_shared(r_request_tx.clone());
-
-    // Communication channel between the R main thread and the Amalthea
-    // StdIn socket thread
-    let (stdin_request_tx, stdin_request_rx) = bounded::<StdInRequest>(1);
-
-    // Communication channel for `CommEvent`
-    let comm_manager_tx = kernel.create_comm_manager_tx();
-
-    // Create the shell.
-    let kernel_init_rx = kernel_init_tx.add_rx();
-    let shell = Shell::new(
-        comm_manager_tx.clone(),
-        iopub_tx.clone(),
-        r_request_tx.clone(),
-        stdin_request_tx.clone(),
-        kernel_init_rx,
-        kernel_request_tx,
-        kernel_request_rx,
-    );
-
-    // Create the control handler; this is used to handle shutdown/interrupt and
-    // related requests
-    let control = Arc::new(Mutex::new(Control::new(r_request_tx.clone())));
-
-    // Create the stream behavior; this determines whether the kernel should
-    // capture stdout/stderr and send them to the frontend as IOPub messages
-    let stream_behavior = match capture_streams {
-        true => amalthea::kernel::StreamBehavior::Capture,
-        false => amalthea::kernel::StreamBehavior::None,
-    };
-
-    // Create the kernel
-    let kernel_clone = shell.kernel.clone();
-    let shell = Arc::new(Mutex::new(shell));
-
-    let (stdin_reply_tx, stdin_reply_rx) = unbounded();
-
-    let res = kernel.connect(
-        shell,
-        control,
-        Some(lsp),
-        Some(dap.clone()),
-        stream_behavior,
-        stdin_request_rx,
-        stdin_reply_tx,
-    );
-    if let Err(err) = res {
-        panic!("Couldn't connect to frontend: {err:?}");
-    }
-
-    // Start the R REPL (does not return for the duration of the session)
-    ark::interface::start_r(
-        r_args,
-        startup_file,
-        kernel_clone,
-        comm_manager_tx,
-        r_request_rx,
-        stdin_request_tx,
-        stdin_reply_rx,
-        iopub_tx,
-        kernel_init_tx,
-        dap,
-        session_mode,
-    )
-}
-
 // Installs the kernelspec JSON file into one of Jupyter's search paths.
-fn install_kernel_spec() {
+fn install_kernel_spec(r_version: Option<RVersion>, location: InstallLocation) {
     // Create the environment set for the kernel spec
     let mut env = serde_json::Map::new();
 
-    // Detect the active version of R and set the R_HOME environment variable
-    // accordingly
-    let r_version = detect_r().unwrap();
+    // Detect the active version of R, unless pinned with `--r-home`, and set
+    // the R_HOME environment variable accordingly
+    let r_version = match r_version {
+        Some(r_version) => r_version,
+        None => detect_r().unwrap(),
+    };
     env.insert(
         "R_HOME".to_string(),
-        serde_json::Value::String(r_version.r_home.clone()),
+        serde_json::Value::String(r_version.r_home.to_string_lossy().to_string()),
     );
 
     // Point `LD_LIBRARY_PATH` to a folder with some `libR.so`. It doesn't
@@ -160,7 +59,7 @@ fn install_kernel_spec() {
     // name, even though we won't use it for symbol resolution.
     // https://github.com/posit-dev/positron/issues/1619#issuecomment-1971552522
     if cfg!(target_os = "linux") {
-        let lib = format!("{}/lib", r_version.r_home.clone());
+        let lib = r_version.lib_dir.to_string_lossy().to_string();
         env.insert("LD_LIBRARY_PATH".into(), serde_json::Value::String(lib));
     }
 
@@ -183,7 +82,14 @@ fn install_kernel_spec() {
         env,
     };
 
-    let dest = unwrap!(spec.install(String::from("ark")), Err(error) => {
+    // Report where the kernelspec goes before writing it
+    let dir = unwrap!(KernelSpec::install_dir(location, String::from("ark")), Err(error) => {
+        eprintln!("Failed to install Ark's Jupyter kernelspec. {}", error);
+        return;
+    });
+    println!("Installing Ark Jupyter kernelspec to {}", dir.display());
+
+    let dest = unwrap!(spec.install_at(location, String::from("ark")), Err(error) => {
         eprintln!("Failed to install Ark's Jupyter kernelspec. {}", error);
         return;
     });
@@ -191,13 +97,11 @@ fn install_kernel_spec() {
     println!(
         "Successfully installed Ark Jupyter kernelspec.
 
-    R ({}.{}.{}): {}
+    R ({}): {}
     Kernel: {}
     ",
-        r_version.major,
-        r_version.minor,
-        r_version.patch,
-        r_version.r_home,
+        r_version,
+        r_version.r_home.display(),
         dest.to_string_lossy()
     );
 }
@@ -205,9 +109,13 @@ fn install_kernel_spec() {
 fn parse_file(
     connection_file: &String,
     r_args: Vec<String>,
-    startup_file: Option<String>,
+    startup_files: Vec<String>,
+    startup_options: Vec<StartupOption>,
+    modules_dir: Option<String>,
     session_mode: SessionMode,
     capture_streams: bool,
+    ready_file: Option<String>,
+    history_file: Option<String>,
 ) {
     match ConnectionFile::from_file(connection_file) {
         Ok(connection) => {
@@ -215,13 +123,23 @@ fn parse_file(
                 "Loaded connection information from frontend in {}",
                 connection_file
             );
+            if let Some(kernel_name) = &connection.kernel_name {
+                info!("Started from kernel spec '{kernel_name}'");
+            }
+            if let Some(jupyter_session) = &connection.jupyter_session {
+                info!("Frontend session: {jupyter_session}");
+            }
             debug!("Connection data: {:?}", connection);
             start_kernel(
                 connection,
                 r_args,
-                startup_file,
+                startup_files,
+                startup_options,
+                modules_dir,
                 session_mode,
                 capture_streams,
+                ready_file,
+                history_file,
             );
         },
         Err(error) => {
@@ -245,13 +163,40 @@ Available options:
                          (see the Jupyter kernel documentation for details)
 -- arg1 arg2 ...         Set the argument list to pass to R; defaults to
                          --interactive
---startup-file FILE      An R file to run on session startup
+--startup-file FILE      An R file to run on session startup (may be repeated)
+--startup-dir DIR        Run the .R files of this directory on session startup,
+                         sorted by name
+--ready-file FILE        Write a JSON file with the pid and the ports of the kernel
+                         once it is connected and R is initialized
+--history-file FILE      Persist executed code to FILE so that it can be retrieved
+                         with history requests after a restart
+--startup-options OPTS   R options to set before any profile or startup file
+                         runs, e.g. "warn=1,stringsAsFactors=FALSE". Options
+                         can also be set with ARK_STARTUP_OPTIONS, which this
+                         argument takes precedence over
+--modules-dir DIR        Source the .R files of this trusted directory into Ark's
+                         private namespace after the built-in modules
 --session-mode MODE      The mode in which the session is running (console, notebook, background)
+--r-home DIR             Use the R installation at DIR rather than the one found
+                         through R_HOME or the PATH
 --no-capture-streams     Do not capture stdout/stderr from R
+--no-log-r-conditions    Do not forward R messages and warnings emitted during
+                         startup to the log
 --version                Print the version of Ark
 --log FILE               Log to the given file (if not specified, stdout/stderr
                          will be used)
+--log-max-size BYTES     Rotate the log file once it reaches this size
+--log-max-files N        Number of rotated log files to keep (defaults to 5)
+--log-level DIRECTIVES   Log levels as RUST_LOG-style directives, e.g.
+                         "info,ark::lsp=trace" (takes precedence over RUST_LOG)
+--log-format FORMAT      The format of log records (text, json); defaults to text
+--profile-format FORMAT  The format of the --profile output (text, speedscope);
+                         defaults to text
+--check                  Check that R, the connection file (if any), and the
+                         kernel spec directory are usable, then exit
 --install                Install the kernel spec for Ark
+--sys-prefix             With --install, install the kernel spec into the active
+                         Python environment (VIRTUAL_ENV or CONDA_PREFIX)
 --help                   Print this help message
 "#
     );
@@ -270,15 +215,28 @@ fn main() {
     argv.next();
 
     let mut connection_file: Option<String> = None;
-    let mut startup_file: Option<String> = None;
+    let mut startup_files: Vec<String> = Vec::new();
+    let mut startup_options: Vec<StartupOption> = Vec::new();
+    let mut modules_dir: Option<String> = None;
     let mut session_mode = SessionMode::Console;
     let mut log_file: Option<String> = None;
+    let mut log_level: Option<String> = None;
+    let mut log_max_size: Option<u64> = None;
+    let mut log_max_files: usize = 5;
+    let mut log_format = LogFormat::default();
     let mut profile_file: Option<String> = None;
+    let mut profile_format = ProfileFormat::default();
     let mut startup_notifier_file: Option<String> = None;
+    let mut ready_file: Option<String> = None;
+    let mut history_file: Option<String> = None;
     let mut startup_delay: Option<std::time::Duration> = None;
     let mut r_args: Vec<String> = Vec::new();
     let mut has_action = false;
     let mut capture_streams = true;
+    let mut install = false;
+    let mut check = false;
+    let mut install_location = InstallLocation::User;
+    let mut r_version: Option<RVersion> = None;
 
     // Process remaining arguments. TODO: Need an argument that can passthrough args to R
     while let Some(arg) = argv.next() {
@@ -296,13 +254,56 @@ fn main() {
             },
             "--startup-file" => {
                 if let Some(file) = argv.next() {
-                    startup_file = Some(file);
+                    startup_files.push(file);
                     has_action = true;
                 } else {
                     eprintln!("A startup file must be specified with the --startup-file argument.");
                     break;
                 }
             },
+            "--startup-dir" => {
+                if let Some(dir) = argv.next() {
+                    match startup::startup_dir_files(&dir) {
+                        Ok(files) => {
+                            startup_files.extend(files);
+                            has_action = true;
+                        },
+                        Err(err) => {
+                            eprintln!("Can't read startup directory '{dir}': {err}");
+                            break;
+                        },
+                    }
+                } else {
+                    eprintln!(
+                        "A startup directory must be specified with the --startup-dir argument."
+                    );
+                    break;
+                }
+            },
+            "--startup-options" => {
+                if let Some(spec) = argv.next() {
+                    match startup::parse_startup_options(&spec) {
+                        Ok(options) => startup_options.extend(options),
+                        Err(err) => {
+                            eprintln!("Invalid startup options '{spec}': {err}");
+                            break;
+                        },
+                    }
+                } else {
+                    eprintln!(
+                        "Startup options must be specified with the --startup-options argument."
+                    );
+                    break;
+                }
+            },
+            "--modules-dir" => {
+                if let Some(dir) = argv.next() {
+                    modules_dir = Some(dir);
+                } else {
+                    eprintln!("A directory must be specified with the --modules-dir argument.");
+                    break;
+                }
+            },
             "--session-mode" => {
                 if let Some(mode) = argv.next() {
                     session_mode = match mode.as_str() {
@@ -324,14 +325,40 @@ fn main() {
                 has_action = true;
             },
             "--install" => {
-                install_kernel_spec();
+                install = true;
+                has_action = true;
+            },
+            "--check" => {
+                check = true;
                 has_action = true;
             },
+            "--sys-prefix" => {
+                install_location = InstallLocation::SysPrefix;
+            },
+            "--r-home" => {
+                if let Some(dir) = argv.next() {
+                    match detect_r_at(std::path::Path::new(&dir)) {
+                        Ok(version) => {
+                            // Picked up by `start_r()` when loading R
+                            std::env::set_var("R_HOME", &version.r_home);
+                            r_version = Some(version);
+                        },
+                        Err(err) => {
+                            eprintln!("Can't use R home '{dir}': {err}");
+                            break;
+                        },
+                    }
+                } else {
+                    eprintln!("A directory must be specified with the --r-home argument.");
+                    break;
+                }
+            },
             "--help" => {
                 print_usage();
                 has_action = true;
             },
             "--no-capture-streams" => capture_streams = false,
+            "--no-log-r-conditions" => logger_r::disable(),
             "--log" => {
                 if let Some(file) = argv.next() {
                     log_file = Some(file);
@@ -340,6 +367,58 @@ fn main() {
                     break;
                 }
             },
+            "--log-max-size" => {
+                if let Some(size) = argv.next() {
+                    if let Ok(size) = size.parse::<u64>() {
+                        log_max_size = Some(size);
+                    } else {
+                        eprintln!("Can't parse log file size in bytes");
+                        break;
+                    }
+                } else {
+                    eprintln!(
+                        "A size in bytes must be specified with the --log-max-size argument."
+                    );
+                    break;
+                }
+            },
+            "--log-max-files" => {
+                if let Some(n) = argv.next() {
+                    if let Ok(n) = n.parse::<usize>() {
+                        log_max_files = n;
+                    } else {
+                        eprintln!("Can't parse number of log files");
+                        break;
+                    }
+                } else {
+                    eprintln!(
+                        "A number of files must be specified with the --log-max-files argument."
+                    );
+                    break;
+                }
+            },
+            "--log-level" => {
+                if let Some(directives) = argv.next() {
+                    log_level = Some(directives);
+                } else {
+                    eprintln!("Log directives must be specified with the --log-level argument.");
+                    break;
+                }
+            },
+            "--log-format" => {
+                if let Some(format) = argv.next() {
+                    log_format = match format.parse() {
+                        Ok(format) => format,
+                        Err(err) => {
+                            eprintln!("{err}");
+                            break;
+                        },
+                    };
+                } else {
+                    eprintln!("A log format must be specified with the --log-format argument.");
+                    break;
+                }
+            },
             "--profile" => {
                 if let Some(file) = argv.next() {
                     profile_file = Some(file);
@@ -348,6 +427,38 @@ fn main() {
                     break;
                 }
             },
+            "--profile-format" => {
+                if let Some(format) = argv.next() {
+                    profile_format = match format.parse() {
+                        Ok(format) => format,
+                        Err(err) => {
+                            eprintln!("{err}");
+                            break;
+                        },
+                    };
+                } else {
+                    eprintln!(
+                        "A profile format must be specified with the --profile-format argument."
+                    );
+                    break;
+                }
+            },
+            "--ready-file" => {
+                if let Some(file) = argv.next() {
+                    ready_file = Some(file);
+                } else {
+                    eprintln!("A ready file must be specified with the --ready-file argument.");
+                    break;
+                }
+            },
+            "--history-file" => {
+                if let Some(file) = argv.next() {
+                    history_file = Some(file);
+                } else {
+                    eprintln!("A history file must be specified with the --history-file argument.");
+                    break;
+                }
+            },
             "--startup-notifier-file" => {
                 if let Some(file) = argv.next() {
                     startup_notifier_file = Some(file);
@@ -387,8 +498,38 @@ fn main() {
         }
     }
 
+    // Installing the kernel spec is deferred until all arguments are parsed
+    // so that it honours `--r-home` regardless of argument order
+    if install {
+        install_kernel_spec(r_version.clone(), install_location);
+    }
+
+    // Report on the environment and exit without starting the kernel. Runs
+    // after all arguments are parsed for the same reason.
+    if check {
+        let results = run_checks(r_version, connection_file.as_deref(), install_location);
+        for result in &results {
+            println!("{result}");
+        }
+        let failed = results.iter().any(|result| result.result.is_err());
+        std::process::exit(if failed { 1 } else { 0 });
+    }
+
     // Initialize the logger.
-    logger::init(log_file.as_deref(), profile_file.as_deref());
+    // Log files are only rotated when a maximum size is requested
+    let log_rotation = log_max_size.map(|max_size| LogRotation {
+        max_size,
+        max_files: log_max_files,
+    });
+
+    logger::init(
+        log_file.as_deref(),
+        log_level.as_deref(),
+        log_rotation,
+        profile_file.as_deref(),
+        profile_format,
+        log_format,
+    );
 
     if let Some(file) = startup_notifier_file {
         let path = std::path::Path::new(&file);
@@ -438,6 +579,16 @@ fn main() {
         return;
     }
 
+    // Fail fast if R can't be loaded, before any kernel thread spins up.
+    // Otherwise this would be an opaque panic deep in the R initialisation.
+    if connection_file.is_some() {
+        if let Err(err) = check_r_startup() {
+            eprintln!("{err:#}");
+            log::error!("{err:#}");
+            std::process::exit(1);
+        }
+    }
+
     // Register segfault handler to get a backtrace. Should be after
     // initialising `log!`. Note that R will not override this handler
     // because we set `R_SignalHandlers` to 0 before startup.
@@ -458,10 +609,17 @@ fn main() {
     std::panic::set_hook(Box::new(move |panic_info| {
         let info = panic_info.payload();
 
+        let thread = std::thread::current();
+        let thread = thread.name().unwrap_or("<unnamed>");
+
         let loc = if let Some(location) = panic_info.location() {
-            format!("In file '{}' at line {}:", location.file(), location.line(),)
+            format!(
+                "In thread '{thread}', in file '{}' at line {}:",
+                location.file(),
+                location.line(),
+            )
         } else {
-            String::from("No location information:")
+            format!("In thread '{thread}', no location information:")
         };
 
         let append_trace = |info: &str| -> String {
@@ -499,14 +657,31 @@ fn main() {
         std::process::abort();
     }));
 
+    // Options of the environment are applied first so that the command line
+    // takes precedence
+    match startup::startup_options_from_env() {
+        Ok(mut options) => {
+            options.extend(startup_options);
+            startup_options = options;
+        },
+        Err(err) => log::error!(
+            "Invalid startup options in `{}`: {err}",
+            startup::STARTUP_OPTIONS_ENV_VAR
+        ),
+    }
+
     // Parse the connection file and start the kernel
     if let Some(connection) = connection_file {
         parse_file(
             &connection,
             r_args,
-            startup_file,
+            startup_files,
+            startup_options,
+            modules_dir,
             session_mode,
             capture_streams,
+            ready_file,
+            history_file,
         );
     }
 }
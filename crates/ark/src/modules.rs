@@ -5,6 +5,9 @@
 //
 //
 
+use std::path::Path;
+use std::path::PathBuf;
+
 use anyhow::anyhow;
 use harp::environment::Environment;
 use harp::environment::R_ENVS;
@@ -282,6 +285,36 @@ mod debug {
     }
 }
 
+/// Source the `.R` files of `dir` into the private Positron namespace, in
+/// sorted order. Meant for site-specific hooks living in a trusted directory,
+/// so it must be called after `initialize()`. Exported functions (`#' @export`)
+/// are attached to `tools:positron` like those of the built-in modules.
+///
+/// A file that fails to load is logged and skipped so the others still load.
+pub fn load_extra(dir: &Path) -> anyhow::Result<()> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "R"))
+        .collect();
+    files.sort();
+
+    for file in files {
+        let path = file.display().to_string();
+
+        let result = RFunction::new("", "import_positron_path")
+            .param("path", path.as_str())
+            .call_in(ARK_ENVS.positron_ns);
+
+        match result {
+            Ok(_) => log::info!("Loaded extra R module '{path}'"),
+            Err(err) => log::error!("Can't load extra R module '{path}': {err:?}"),
+        }
+    }
+
+    Ok(())
+}
+
 fn r_poke_option_ark_testing() {
     unsafe {
         let value = Rf_ScalarLogical(1);
@@ -296,6 +329,8 @@ mod tests {
     use harp::eval::r_parse_eval0;
     use libr::CLOENV;
 
+    use crate::modules::load_extra;
+    use crate::modules::ARK_ENVS;
     use crate::test::r_test;
 
     fn get_namespace(exports: Environment, fun: &str) -> Environment {
@@ -325,4 +360,39 @@ mod tests {
             assert!(rstudio_ns.is_locked());
         })
     }
+
+    #[test]
+    fn test_load_extra() {
+        r_test(|| {
+            let dir =
+                std::env::temp_dir().join(format!("ark-extra-modules-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+
+            // `a.R` fails halfway through, `b.R` relies on `c.R` being loaded after it
+            std::fs::write(dir.join("a.R"), "extra_a <- 1\nstop('oops')\n").unwrap();
+            std::fs::write(dir.join("b.R"), "extra_b <- function() extra_c\n").unwrap();
+            std::fs::write(
+                dir.join("c.R"),
+                "#' @export\n.ps.extra_c <- function() 3L\nextra_c <- 3L\n",
+            )
+            .unwrap();
+            std::fs::write(dir.join("d.txt"), "stop('not an R file')").unwrap();
+
+            load_extra(&dir).unwrap();
+
+            let ns = Environment::view(ARK_ENVS.positron_ns);
+            assert!(ns.exists("extra_a"));
+            assert!(ns.exists("extra_b"));
+            assert!(ns.exists("extra_c"));
+            assert!(ns.is_locked());
+
+            let value = r_parse_eval0("extra_b()", ARK_ENVS.positron_ns).unwrap();
+            assert_eq!(i32::try_from(value).unwrap(), 3);
+
+            let exports = r_parse_eval0("as.environment('tools:positron')", R_ENVS.base).unwrap();
+            assert!(Environment::new(exports).exists(".ps.extra_c"));
+
+            std::fs::remove_dir_all(&dir).unwrap();
+        })
+    }
 }
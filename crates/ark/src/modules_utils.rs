@@ -1,3 +1,7 @@
+use harp::environment::Environment;
+use harp::object::RObject;
+use harp::utils::r_is_promise;
+use harp::utils::r_promise_is_forced;
 use libr::SEXP;
 
 #[harp::register]
@@ -6,6 +10,23 @@ pub unsafe extern "C" fn ark_node_poke_cdr(node: SEXP, cdr: SEXP) -> anyhow::Res
     return Ok(harp::r_null());
 }
 
+/// Whether a binding is a promise that hasn't been forced yet. Returns `FALSE`
+/// for active bindings without calling them.
+#[harp::register]
+pub unsafe extern "C" fn ark_env_binding_is_lazy(env: SEXP, name: SEXP) -> anyhow::Result<SEXP> {
+    let env = Environment::view(env);
+    let name: String = RObject::view(name).try_into()?;
+
+    if env.is_active((&name).into())? {
+        return Ok(RObject::from(false).sexp);
+    }
+
+    let value = env.find(&name)?;
+    let lazy = r_is_promise(value) && !r_promise_is_forced(value);
+
+    Ok(RObject::from(lazy).sexp)
+}
+
 #[harp::register]
 pub unsafe extern "C" fn ps_deep_sleep(secs: SEXP) -> anyhow::Result<SEXP> {
     let secs = libr::Rf_asInteger(secs);
@@ -14,3 +35,42 @@ pub unsafe extern "C" fn ps_deep_sleep(secs: SEXP) -> anyhow::Result<SEXP> {
 
     return Ok(harp::r_null());
 }
+
+#[cfg(test)]
+mod tests {
+    use harp::environment::R_ENVS;
+    use harp::eval::r_parse_eval0;
+    use harp::object::RObject;
+    use libr::SEXP;
+
+    use crate::modules::ARK_ENVS;
+    use crate::test::r_test;
+
+    // Registered like any other routine, without being listed anywhere else
+    #[harp::register]
+    unsafe extern "C" fn ps_test_registered_routine(x: SEXP) -> anyhow::Result<SEXP> {
+        let x: i32 = RObject::view(x).try_into()?;
+        Ok(RObject::from(x + 1).sexp)
+    }
+
+    #[test]
+    fn test_registered_routines_are_callable() {
+        r_test(|| {
+            let out = r_parse_eval0(
+                ".ps.Call('ps_test_registered_routine', 41L)",
+                ARK_ENVS.positron_ns,
+            )
+            .unwrap();
+            assert_eq!(i32::try_from(out).unwrap(), 42);
+
+            let routines = r_parse_eval0(
+                "names(getDLLRegisteredRoutines('(embedding)')$.Call)",
+                R_ENVS.base,
+            )
+            .unwrap();
+            let routines = Vec::<String>::try_from(routines).unwrap();
+            assert!(routines.contains(&String::from("ps_test_registered_routine")));
+            assert!(routines.contains(&String::from("ps_deep_sleep")));
+        })
+    }
+}
@@ -23,6 +23,7 @@
 /// https://github.com/rstudio/rstudio/blob/main/src/cpp/r/session/graphics/RGraphicsDevice.cpp
 ///
 use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::fs::File;
 use std::io::BufReader;
 use std::io::Read;
@@ -34,6 +35,7 @@ use amalthea::comm::plot_comm::PlotBackendRequest;
 use amalthea::comm::plot_comm::PlotFrontendEvent;
 use amalthea::comm::plot_comm::PlotResult;
 use amalthea::comm::plot_comm::RenderFormat;
+use amalthea::comm::plot_comm::RenderImageParams;
 use amalthea::socket::comm::CommInitiator;
 use amalthea::socket::comm::CommSocket;
 use amalthea::socket::iopub::IOPubMessage;
@@ -47,6 +49,7 @@ use crossbeam::channel::Select;
 use crossbeam::channel::Sender;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
+use harp::object::r_null_or_try_into;
 use harp::object::RObject;
 use libr::pDevDesc;
 use libr::pGEcontext;
@@ -63,6 +66,14 @@ use crate::r_task;
 
 const POSITRON_PLOT_CHANNEL_ID: &str = "positron.plot";
 
+/// The largest width or height, in device pixels, of a rendered image. Guards
+/// against allocating gigantic devices.
+const MAX_RENDER_DIMENSION: f64 = 10000.0;
+
+/// Default number of plots retained in the history, configurable with the
+/// `ark.plots.max_history` option.
+const DEFAULT_MAX_HISTORY: usize = 50;
+
 macro_rules! trace {
     ($($tts:tt)*) => {{
         let message = format!($($tts)*);
@@ -104,6 +115,16 @@ struct DeviceContext {
     // for accessing indexed plots, e.g. for the Plots pane history.
     pub _id: Option<String>,
 
+    // Pages completed during the current execution that haven't been sent
+    // to the frontend yet, in drawing order. The flag indicates whether the
+    // page is new or an update of a page the frontend already knows about.
+    pub _pending: Vec<(String, bool)>,
+
+    // IDs of the plots sent to the frontend, oldest first. Each of them can
+    // be re-rendered from its snapshot, e.g. when the frontend pages through
+    // the history or resizes a plot.
+    pub _history: VecDeque<String>,
+
     // A map, mapping plot IDs to the communication channels used
     // for communicating their rendered results to the frontend.
     pub _channels: HashMap<String, CommSocket>,
@@ -123,10 +144,24 @@ impl DeviceContext {
     }
 
     pub fn new_page(&mut self, _dd: pGEcontext, _dev: pDevDesc) {
+        // Queue the page we're leaving so it doesn't get lost when several
+        // plots are drawn within a single execution. Its display list was
+        // recorded by the `before.plot.new` hooks.
+        if self._changes {
+            if let Some(id) = self._id.take() {
+                self._pending.push((id, self._new_page));
+            }
+        }
+
         // Create a new id for this new plot page and note that this is a new page
         let id = Uuid::new_v4().to_string();
         self._id = Some(id.clone());
         self._new_page = true;
+
+        // A new page is a change in itself. Don't rely on `mode()` to flag
+        // it, as grid output such as a printed `ggplot` may be drawn
+        // without the device mode being toggled afterwards.
+        self._changes = true;
     }
 
     pub fn on_did_execute_request(
@@ -178,6 +213,13 @@ impl DeviceContext {
             return;
         });
 
+        // The comm was closed, either by the frontend or because it went
+        // stale; release the plot
+        if let CommMsg::Close = message {
+            self.remove_plot(plot_id);
+            return;
+        }
+
         // Get the RPC request.
         if socket.handle_request(message, |req| self.handle_rpc(req, plot_id)) {
             return;
@@ -205,6 +247,15 @@ impl DeviceContext {
                     mime_type: mime_type.to_string(),
                 }))
             },
+            PlotBackendRequest::RenderImage(params) => {
+                let data = self.render_image(&plot_id, &params)?;
+
+                let mime_type = Self::get_mime_type(&params.format);
+                Ok(PlotBackendReply::RenderImageReply(PlotResult {
+                    data,
+                    mime_type,
+                }))
+            },
         }
     }
 
@@ -223,19 +274,93 @@ impl DeviceContext {
         iopub_tx: Sender<IOPubMessage>,
         positron_connected: bool,
     ) {
+        for (id, new_page) in std::mem::take(&mut self._pending) {
+            self.process_page(
+                id.as_str(),
+                new_page,
+                comm_manager_tx.clone(),
+                iopub_tx.clone(),
+                positron_connected,
+            );
+        }
+
         let id = unwrap!(self._id.clone(), None => {
             log::error!("Unexpected uninitialized `id`.");
             return;
         });
 
-        if self._new_page {
-            self._new_page = false;
-            self.process_new_plot(id.as_str(), comm_manager_tx, iopub_tx, positron_connected);
+        let new_page = self._new_page;
+        self._new_page = false;
+        self.process_page(
+            id.as_str(),
+            new_page,
+            comm_manager_tx,
+            iopub_tx,
+            positron_connected,
+        );
+
+        self.trim_history(positron_connected);
+    }
+
+    fn process_page(
+        &mut self,
+        id: &str,
+        new_page: bool,
+        comm_manager_tx: Sender<CommManagerEvent>,
+        iopub_tx: Sender<IOPubMessage>,
+        positron_connected: bool,
+    ) {
+        if new_page {
+            self._history.push_back(id.to_string());
+            self.process_new_plot(id, comm_manager_tx, iopub_tx, positron_connected);
         } else {
-            self.process_update_plot(id.as_str(), iopub_tx, positron_connected);
+            self.process_update_plot(id, iopub_tx, positron_connected);
+        }
+    }
+
+    /// Drops the oldest plots once the history grows past
+    /// `ark.plots.max_history`, closing their comms and removing their
+    /// snapshots.
+    fn trim_history(&mut self, positron_connected: bool) {
+        let max_history = r_task(max_history);
+
+        while self._history.len() > max_history {
+            let Some(id) = self._history.pop_front() else {
+                break;
+            };
+            log::info!("Removing plot {id} from the history.");
+
+            if positron_connected {
+                if let Some(socket) = self._channels.remove(&id) {
+                    socket
+                        .outgoing_tx
+                        .send(CommMsg::Close)
+                        .or_log_error(&format!("Failed to close comm for plot {id}"));
+                }
+            }
+
+            Self::remove_snapshot(&id);
         }
     }
 
+    /// Forgets a plot whose comm has been closed.
+    fn remove_plot(&mut self, id: &str) {
+        log::info!("Plot {id} was closed; removing it.");
+        self._channels.remove(id);
+        self._history.retain(|history_id| history_id != id);
+        Self::remove_snapshot(id);
+    }
+
+    fn remove_snapshot(id: &str) {
+        r_task(|| {
+            RFunction::from(".ps.graphics.removeSnapshot")
+                .param("id", id)
+                .call()
+                .map(|_| ())
+        })
+        .or_log_error(&format!("Failed to remove snapshot for plot {id}"));
+    }
+
     fn process_new_plot(
         &mut self,
         id: &str,
@@ -397,17 +522,86 @@ impl DeviceContext {
             bail!("Failed to render plot with id {plot_id} due to: {error}.");
         });
 
-        // Read contents into bytes.
-        let conn = File::open(image_path)?;
-        let mut reader = BufReader::new(conn);
+        read_image(&image_path)
+    }
 
-        let mut buffer = vec![];
-        reader.read_to_end(&mut buffer)?;
+    /// Renders a plot to a temporary file rather than the files backing the
+    /// displayed plot, so that the plot shown by the frontend isn't affected.
+    fn render_image(
+        &mut self,
+        plot_id: &str,
+        params: &RenderImageParams,
+    ) -> anyhow::Result<String> {
+        check_render_size(params.size.width, params.size.height, params.pixel_ratio)?;
+
+        self._rendering = true;
+        let image_path = r_task(|| unsafe {
+            RFunction::from(".ps.graphics.renderImage")
+                .param("id", plot_id)
+                .param("width", RObject::try_from(params.size.width)?)
+                .param("height", RObject::try_from(params.size.height)?)
+                .param("dpr", params.pixel_ratio)
+                .param("format", params.format.to_string().to_lowercase())
+                .param("dpi", params.dpi)
+                .call()?
+                .to::<String>()
+        });
+        self._rendering = false;
 
-        // what an odd interface
-        let data = general_purpose::STANDARD_NO_PAD.encode(buffer);
+        let image_path = unwrap!(image_path, Err(error) => {
+            bail!("Failed to render image for plot with id {plot_id} due to: {error}.");
+        });
+
+        let data = read_image(&image_path);
+        std::fs::remove_file(&image_path)
+            .or_log_warning(&format!("Could not remove temporary image '{image_path}'"));
 
-        Ok(data)
+        data
+    }
+}
+
+/// Reads a rendered image and encodes it in base64.
+fn read_image(path: &str) -> anyhow::Result<String> {
+    // Read contents into bytes.
+    let conn = File::open(path)?;
+    let mut reader = BufReader::new(conn);
+
+    let mut buffer = vec![];
+    reader.read_to_end(&mut buffer)?;
+
+    // what an odd interface
+    let data = general_purpose::STANDARD_NO_PAD.encode(buffer);
+
+    Ok(data)
+}
+
+fn check_render_size(width: i64, height: i64, pixel_ratio: f64) -> anyhow::Result<()> {
+    if width <= 0 || height <= 0 {
+        bail!("Can't render an image of size {width}x{height}.");
+    }
+    if !pixel_ratio.is_finite() || pixel_ratio <= 0.0 {
+        bail!("Can't render an image with a pixel ratio of {pixel_ratio}.");
+    }
+
+    let max = width.max(height) as f64 * pixel_ratio;
+    if max > MAX_RENDER_DIMENSION {
+        bail!(
+            "Can't render an image of size {width}x{height} at a pixel ratio of {pixel_ratio}, \
+             the maximum dimension is {MAX_RENDER_DIMENSION} pixels."
+        );
+    }
+
+    Ok(())
+}
+
+fn max_history() -> usize {
+    let opt: Option<i32> = r_null_or_try_into(harp::get_option("ark.plots.max_history"))
+        .ok()
+        .flatten();
+
+    match opt {
+        Some(n) if n > 0 => n as usize,
+        _ => DEFAULT_MAX_HISTORY,
     }
 }
 
@@ -589,3 +783,23 @@ unsafe extern "C" fn ps_graphics_event(_name: SEXP) -> anyhow::Result<SEXP> {
 
     Ok(Rf_ScalarLogical(1))
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::plots::graphics_device::check_render_size;
+
+    #[test]
+    fn test_check_render_size() {
+        assert!(check_render_size(800, 600, 2.0).is_ok());
+        assert!(check_render_size(10000, 1, 1.0).is_ok());
+
+        assert!(check_render_size(0, 600, 1.0).is_err());
+        assert!(check_render_size(800, -1, 1.0).is_err());
+        assert!(check_render_size(800, 600, 0.0).is_err());
+        assert!(check_render_size(800, 600, f64::NAN).is_err());
+
+        // The maximum applies to device pixels
+        assert!(check_render_size(6000, 600, 2.0).is_err());
+        assert!(check_render_size(20000, 600, 1.0).is_err());
+    }
+}
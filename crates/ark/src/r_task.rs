@@ -14,6 +14,7 @@ use std::time::Duration;
 
 use crossbeam::channel::bounded;
 use crossbeam::channel::Sender;
+use futures::channel::oneshot;
 use harp::test::R_TASK_BYPASS;
 use uuid::Uuid;
 
@@ -50,6 +51,30 @@ pub struct RTaskWaker {
     pub start_info: RTaskStartInfo,
 }
 
+#[derive(Debug)]
+pub enum RTaskError {
+    /// The task didn't complete within the given duration
+    Timeout(Duration),
+
+    /// The task was requested from the R thread, which can't wait on itself
+    Reentrant,
+}
+
+impl std::fmt::Display for RTaskError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RTaskError::Timeout(duration) => {
+                write!(f, "R task timed out after {} ms", duration.as_millis())
+            },
+            RTaskError::Reentrant => {
+                write!(f, "R task was requested from the R thread")
+            },
+        }
+    }
+}
+
+impl std::error::Error for RTaskError {}
+
 #[derive(Debug)]
 pub enum RTaskStatus {
     Started,
@@ -152,8 +177,10 @@ where
 
     // Recursive case: If we're on ark-r-main already, just run the
     // task and return. This allows `r_task(|| { r_task(|| {}) })`
-    // to run without deadlocking.
+    // to run without deadlocking, since queuing the task would block the
+    // R thread on a task that only the R thread can run.
     if RMain::on_main_thread() {
+        log::trace!("Running reentrant R task inline");
         return f();
     }
 
@@ -230,6 +257,120 @@ where
     return result.lock().unwrap().take().unwrap();
 }
 
+/// Like `r_task()` but gives up waiting after `timeout`, e.g. when the R
+/// thread is stuck in a long computation. Returns `RTaskError::Timeout` if
+/// the task hasn't completed in time.
+///
+/// A task that hasn't started yet when the timeout expires is dropped
+/// without running. However a task that had already started keeps running
+/// until completion on the R thread and its result is discarded. Since the
+/// caller doesn't wait for it, `f` must own the data it captures.
+///
+/// Returns `RTaskError::Reentrant` when called from the R thread. The task
+/// could only run inline there, which would defeat the timeout, so this
+/// reports the reentrant call instead of blocking.
+pub fn r_task_timeout<F, T>(f: F, timeout: Duration) -> Result<T, RTaskError>
+where
+    F: FnOnce() -> T,
+    F: 'static + Send,
+    T: 'static + Send,
+{
+    // Escape hatch for unit tests
+    if unsafe { R_TASK_BYPASS } {
+        return Ok(f());
+    }
+
+    if RMain::on_main_thread() {
+        return Err(RTaskError::Reentrant);
+    }
+
+    let deadline = std::time::Instant::now() + timeout;
+
+    let result = SharedOption::default();
+
+    let closure = {
+        let result = Arc::clone(&result);
+        move || {
+            *result.lock().unwrap() = Some(f());
+        }
+    };
+
+    // Dropping the receiver on timeout lets the R thread know that it
+    // shouldn't run the task if it hasn't started yet
+    let (status_tx, status_rx) = bounded::<RTaskStatus>(0);
+
+    let task = RTask::Sync(RTaskSync {
+        fun: Box::new(closure),
+        status_tx: Some(status_tx),
+        start_info: RTaskStartInfo::new(false),
+    });
+    get_tasks_interrupt_tx().send(task).unwrap();
+
+    let Ok(status) = status_rx.recv_deadline(deadline) else {
+        return Err(RTaskError::Timeout(timeout));
+    };
+
+    let RTaskStatus::Started = status else {
+        panic!("Task `status` value must be `Started`: {status:?}");
+    };
+
+    let Ok(status) = status_rx.recv_deadline(deadline) else {
+        return Err(RTaskError::Timeout(timeout));
+    };
+
+    let RTaskStatus::Finished(status) = status else {
+        panic!("Task `status` value must be `Finished`: {status:?}");
+    };
+
+    if let Err(err) = status {
+        let trace = std::backtrace::Backtrace::force_capture();
+        panic!(
+            "While running task: {err:?}\n\
+             Backtrace of calling thread:\n\n\
+             {trace}"
+        );
+    }
+
+    let result = result.lock().unwrap().take().unwrap();
+    Ok(result)
+}
+
+/// Submit `f` to the R thread without blocking. The returned receiver
+/// completes with the result of `f` once it has run, and can either be
+/// awaited or polled with `try_recv()`.
+///
+/// If `f` fails, e.g. because of an R error or an interrupt, the sender is
+/// dropped and the receiver completes with `Canceled`. Dropping the receiver
+/// doesn't cancel the task, its result is then discarded.
+pub fn r_task_async<F, T>(f: F) -> oneshot::Receiver<T>
+where
+    F: FnOnce() -> T,
+    F: 'static + Send,
+    T: 'static + Send,
+{
+    let (result_tx, result_rx) = oneshot::channel::<T>();
+
+    let closure = move || {
+        // The receiver may have been dropped by now
+        let _ = result_tx.send(f());
+    };
+
+    // Escape hatch for unit tests, and recursive case. See `r_task()`.
+    if unsafe { R_TASK_BYPASS } || RMain::on_main_thread() {
+        closure();
+        return result_rx;
+    }
+
+    let task = RTask::Sync(RTaskSync {
+        fun: Box::new(closure),
+        status_tx: None,
+        start_info: RTaskStartInfo::new(false),
+    });
+    get_tasks_interrupt_tx().send(task).unwrap();
+
+    result_rx
+}
+
 #[allow(dead_code)] // Currently unused
 pub(crate) fn spawn_idle<F, Fut>(fun: F)
 where
@@ -8,6 +8,8 @@
 use amalthea::wire::execute_request::ExecuteRequest;
 use amalthea::wire::execute_response::ExecuteResponse;
 use amalthea::wire::originator::Originator;
+use crossbeam::channel::unbounded;
+use crossbeam::channel::Receiver;
 use crossbeam::channel::Sender;
 
 use crate::ui::UiCommMessage;
@@ -24,6 +26,23 @@ pub enum RRequest {
 
     /// Commands from the debugger frontend
     DebugCommand(DebugRequest),
+
+    /// An interrupt forwarded from SIGINT by `signals::forward_interrupts()`.
+    /// The interrupt flag is already set, this wakes up `ReadConsole()` so a
+    /// pending `readline()` is interrupted right away.
+    Interrupt,
+}
+
+/// Creates the channel of execution requests from the shell to the R thread.
+///
+/// Only `RRequest::ExecuteCode` requests go through this channel, so they
+/// don't compete with requests from the control and debugger threads for the
+/// bounded `RRequest` channel. The shell itself doesn't block on executions:
+/// Amalthea queues execute requests while code is running and hands them to
+/// `Shell::handle_execute_request()` one at a time, in submission order, e.g.
+/// during a burst of requests from a notebook's "Run All".
+pub fn execute_queue() -> (Sender<RRequest>, Receiver<RRequest>) {
+    unbounded()
 }
 
 #[derive(Debug, Clone)]
@@ -5,6 +5,7 @@
 //
 //
 
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::sync::Mutex;
 
@@ -21,6 +22,7 @@ use amalthea::wire::execute_reply::ExecuteReply;
 use amalthea::wire::execute_reply_exception::ExecuteReplyException;
 use amalthea::wire::execute_request::ExecuteRequest;
 use amalthea::wire::execute_response::ExecuteResponse;
+use amalthea::wire::help_link::HelpLink;
 use amalthea::wire::inspect_reply::InspectReply;
 use amalthea::wire::inspect_request::InspectRequest;
 use amalthea::wire::is_complete_reply::IsComplete;
@@ -40,6 +42,8 @@ use crossbeam::channel::Sender;
 use harp::environment::R_ENVS;
 use harp::exec::r_parse_vector;
 use harp::exec::ParseResult;
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
 use harp::line_ending::convert_line_endings;
 use harp::line_ending::LineEnding;
 use harp::object::RObject;
@@ -53,17 +57,19 @@ use crate::help_proxy;
 use crate::interface::KernelInfo;
 use crate::interface::RMain;
 use crate::kernel::Kernel;
+use crate::modules::ARK_ENVS;
 use crate::plots::graphics_device;
 use crate::r_task;
 use crate::request::KernelRequest;
 use crate::request::RRequest;
 use crate::ui::UiComm;
 use crate::variables::r_variables::RVariables;
+use crate::version::parse_r_version;
 
 pub struct Shell {
     comm_manager_tx: Sender<CommManagerEvent>,
     iopub_tx: Sender<IOPubMessage>,
-    r_request_tx: Sender<RRequest>,
+    execute_request_tx: Sender<RRequest>,
     stdin_request_tx: Sender<StdInRequest>,
     pub kernel: Arc<Mutex<Kernel>>,
     kernel_request_tx: Sender<KernelRequest>,
@@ -81,7 +87,7 @@ impl Shell {
     pub fn new(
         comm_manager_tx: Sender<CommManagerEvent>,
         iopub_tx: Sender<IOPubMessage>,
-        r_request_tx: Sender<RRequest>,
+        execute_request_tx: Sender<RRequest>,
         stdin_request_tx: Sender<StdInRequest>,
         kernel_init_rx: BusReader<KernelInfo>,
         kernel_request_tx: Sender<KernelRequest>,
@@ -98,7 +104,7 @@ impl Shell {
         Self {
             comm_manager_tx,
             iopub_tx,
-            r_request_tx,
+            execute_request_tx,
             stdin_request_tx,
             kernel,
             kernel_request_tx,
@@ -106,27 +112,6 @@ impl Shell {
             kernel_info: None,
         }
     }
-
-    /// SAFETY: Requires the R runtime lock.
-    unsafe fn handle_is_complete_request_impl(
-        &self,
-        req: &IsCompleteRequest,
-    ) -> Result<IsCompleteReply, Exception> {
-        match r_parse_vector(req.code.as_str()) {
-            Ok(ParseResult::Complete(_)) => Ok(IsCompleteReply {
-                status: IsComplete::Complete,
-                indent: String::from(""),
-            }),
-            Ok(ParseResult::Incomplete) => Ok(IsCompleteReply {
-                status: IsComplete::Incomplete,
-                indent: String::from("+"),
-            }),
-            Err(_) => Ok(IsCompleteReply {
-                status: IsComplete::Invalid,
-                indent: String::from(""),
-            }),
-        }
-    }
 }
 
 #[async_trait]
@@ -152,41 +137,29 @@ impl ShellHandler for Shell {
         }
         let kernel_info = self.kernel_info.as_ref().unwrap();
 
-        let info = LanguageInfo {
-            name: String::from("R"),
-            version: kernel_info.version.clone(),
-            file_extension: String::from(".R"),
-            mimetype: String::from("text/r"),
-            pygments_lexer: String::new(),
-            codemirror_mode: String::new(),
-            nbconvert_exporter: String::new(),
-            positron: Some(LanguageInfoPositron {
-                input_prompt: kernel_info.input_prompt.clone(),
-                continuation_prompt: kernel_info.continuation_prompt.clone(),
-            }),
-        };
-        Ok(KernelInfoReply {
-            status: Status::Ok,
-            banner: kernel_info.banner.clone(),
-            debugger: false,
-            protocol_version: String::from("5.3"),
-            help_links: Vec::new(),
-            language_info: info,
-        })
+        Ok(kernel_info_reply(kernel_info))
     }
 
+    /// Handles a request for completions, e.g. tab-completion in notebooks.
+    /// This is distinct from the LSP completions and uses R's built-in
+    /// completion engine.
     async fn handle_complete_request(
         &self,
-        _req: &CompleteRequest,
+        req: &CompleteRequest,
     ) -> Result<CompleteReply, Exception> {
-        // No matches in this toy implementation.
-        Ok(CompleteReply {
-            matches: Vec::new(),
-            status: Status::Ok,
-            cursor_start: 0,
-            cursor_end: 0,
-            metadata: json!({}),
-        })
+        let reply = r_task(|| r_complete(req.code.as_str(), req.cursor_pos));
+
+        // Failing to complete isn't worth reporting to the user
+        Ok(reply.unwrap_or_else(|err| {
+            log::error!("Failed to complete {:?}: {err:?}", req.code);
+            CompleteReply {
+                matches: Vec::new(),
+                status: Status::Ok,
+                cursor_start: req.cursor_pos,
+                cursor_end: req.cursor_pos,
+                metadata: json!({}),
+            }
+        }))
     }
 
     /// Handle a request to test code for completion.
@@ -194,11 +167,11 @@ impl ShellHandler for Shell {
         &self,
         req: &IsCompleteRequest,
     ) -> Result<IsCompleteReply, Exception> {
-        r_task(|| unsafe { self.handle_is_complete_request_impl(req) })
+        r_task(|| unsafe { Ok(r_is_complete(req.code.as_str())) })
     }
 
     /// Handles an ExecuteRequest by sending the code to the R execution thread
-    /// for processing.
+    /// for processing and waiting for its result.
     async fn handle_execute_request(
         &mut self,
         originator: Option<Originator>,
@@ -207,7 +180,7 @@ impl ShellHandler for Shell {
         let (response_tx, response_rx) = unbounded::<ExecuteResponse>();
         let mut req_clone = req.clone();
         req_clone.code = convert_line_endings(&req_clone.code, LineEnding::Posix);
-        if let Err(err) = self.r_request_tx.send(RRequest::ExecuteCode(
+        if let Err(err) = self.execute_request_tx.send(RRequest::ExecuteCode(
             req_clone.clone(),
             originator,
             response_tx,
@@ -328,19 +301,139 @@ fn handle_comm_open_help(comm: CommSocket) -> Result<bool, Exception> {
         });
 
         // Start the R Help handler that routes help requests
-        let help_event_tx = unwrap!(RHelp::start(comm, r_port, proxy_port), Err(err) => {
+        let help_comm = comm.clone();
+        unwrap!(RHelp::start(comm, r_port, proxy_port), Err(err) => {
             log::error!("Could not start R Help handler: {err:?}");
             return Ok(false);
         });
 
-        // Send the help event channel to the main R thread so it can
-        // emit help events, to be delivered over the help comm.
-        RMain::with_mut(|main| main.set_help_fields(help_event_tx, r_port));
+        // Send the help comm to the main R thread so it can emit help
+        // events, to be delivered over the help comm.
+        RMain::with_mut(|main| main.set_help_fields(help_comm, r_port));
 
         Ok(true)
     })
 }
 
+fn kernel_info_reply(kernel_info: &KernelInfo) -> KernelInfoReply {
+    // `R.version.string` is e.g. `R version 4.3.1 (2023-06-16)` but frontends
+    // expect a plain version number
+    let version = match parse_r_version(&kernel_info.version) {
+        Ok((major, minor, patch)) => format!("{major}.{minor}.{patch}"),
+        Err(_) => kernel_info.version.clone(),
+    };
+
+    let info = LanguageInfo {
+        name: String::from("R"),
+        version,
+        file_extension: String::from(".R"),
+        mimetype: String::from("text/x-r-source"),
+        pygments_lexer: String::from("r"),
+        codemirror_mode: String::from("r"),
+        nbconvert_exporter: String::new(),
+        positron: Some(LanguageInfoPositron {
+            input_prompt: kernel_info.input_prompt.clone(),
+            continuation_prompt: kernel_info.continuation_prompt.clone(),
+            session_mode: Some(String::from(kernel_info.session_mode.as_str())),
+        }),
+    };
+
+    let mut help_links = vec![
+        HelpLink {
+            text: String::from("CRAN"),
+            url: String::from("https://cran.r-project.org/"),
+        },
+        HelpLink {
+            text: String::from("R Manuals"),
+            url: String::from("https://cran.r-project.org/manuals.html"),
+        },
+    ];
+    if let Some(port) = kernel_info.help_port {
+        help_links.push(HelpLink {
+            text: String::from("R Help"),
+            url: format!("http://127.0.0.1:{port}/doc/html/index.html"),
+        });
+    }
+
+    KernelInfoReply {
+        status: Status::Ok,
+        banner: kernel_info.banner.clone(),
+        debugger: false,
+        protocol_version: String::from("5.3"),
+        help_links,
+        language_info: info,
+    }
+}
+
+/// Uses R's parser to find out whether `code` is a complete expression.
+/// Unclosed delimiters, trailing binary operators, and unterminated strings
+/// make the parser ask for more input, which we report as incomplete.
+///
+/// SAFETY: Requires the R runtime lock.
+unsafe fn r_is_complete(code: &str) -> IsCompleteReply {
+    match r_parse_vector(code) {
+        Ok(ParseResult::Complete(_)) => IsCompleteReply {
+            status: IsComplete::Complete,
+            indent: String::from(""),
+        },
+        // The indent is whitespace inserted by the client at the start of
+        // the next line, not a prompt. Keep the indentation of the last line.
+        Ok(ParseResult::Incomplete) => IsCompleteReply {
+            status: IsComplete::Incomplete,
+            indent: continuation_indent(code),
+        },
+        Err(_) => IsCompleteReply {
+            status: IsComplete::Invalid,
+            indent: String::from(""),
+        },
+    }
+}
+
+fn continuation_indent(code: &str) -> String {
+    let last = code.lines().last().unwrap_or("");
+    last.chars().take_while(|c| c.is_whitespace()).collect()
+}
+
+/// Completes the token before `cursor_pos` in `code`. Positions are in
+/// characters, as in the Jupyter protocol.
+pub fn r_complete(code: &str, cursor_pos: u32) -> anyhow::Result<CompleteReply> {
+    let result = RFunction::new("", "completions_complete_token")
+        .add(code)
+        .add(cursor_pos as i32)
+        .call_in(ARK_ENVS.positron_ns)?;
+    let mut result: HashMap<String, RObject> = result.try_into()?;
+
+    let mut field = |name: &str| {
+        result
+            .remove(name)
+            .ok_or_else(|| anyhow::anyhow!("Missing completion field '{name}'"))
+    };
+    let matches: Vec<String> = field("matches")?.try_into()?;
+    let cursor_start: i32 = field("start")?.try_into()?;
+    let cursor_end: i32 = field("end")?.try_into()?;
+    let types: Vec<String> = field("types")?.try_into()?;
+
+    // Per-match metadata in the format understood by JupyterLab
+    let types: Vec<serde_json::Value> = std::iter::zip(&matches, &types)
+        .map(|(text, kind)| {
+            json!({
+                "start": cursor_start,
+                "end": cursor_end,
+                "text": text,
+                "type": kind,
+            })
+        })
+        .collect();
+
+    Ok(CompleteReply {
+        matches,
+        status: Status::Ok,
+        cursor_start: cursor_start as u32,
+        cursor_end: cursor_end as u32,
+        metadata: json!({ "_jupyter_types_experimental": types }),
+    })
+}
+
 // Kernel is shared with the main R thread
 fn listen(kernel_mutex: Arc<Mutex<Kernel>>, kernel_request_rx: Receiver<KernelRequest>) {
     loop {
@@ -354,3 +447,152 @@ fn listen(kernel_mutex: Arc<Mutex<Kernel>>, kernel_request_rx: Receiver<KernelRe
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use amalthea::wire::is_complete_reply::IsComplete;
+    use harp::environment::R_ENVS;
+    use harp::eval::r_parse_eval0;
+
+    use crate::interface::KernelInfo;
+    use crate::interface::SessionMode;
+    use crate::shell::continuation_indent;
+    use crate::shell::kernel_info_reply;
+    use crate::shell::r_complete;
+    use crate::shell::r_is_complete;
+    use crate::test::r_test;
+
+    fn status(code: &str) -> IsComplete {
+        unsafe { r_is_complete(code).status }
+    }
+
+    #[test]
+    fn test_is_complete_braces() {
+        r_test(|| {
+            assert_eq!(status("{ 1 }"), IsComplete::Complete);
+            assert_eq!(status("function(x) {\n  x\n}"), IsComplete::Complete);
+            assert_eq!(status("{ 1"), IsComplete::Incomplete);
+            assert_eq!(status("mean("), IsComplete::Incomplete);
+            assert_eq!(status("x[[1"), IsComplete::Incomplete);
+            assert_eq!(status("}"), IsComplete::Invalid);
+            assert_eq!(status("mean(1))"), IsComplete::Invalid);
+        })
+    }
+
+    #[test]
+    fn test_is_complete_binary_operators() {
+        r_test(|| {
+            assert_eq!(status("1 + 1"), IsComplete::Complete);
+            assert_eq!(status("1 +"), IsComplete::Incomplete);
+            assert_eq!(status("x <-"), IsComplete::Incomplete);
+            assert_eq!(status("x |>"), IsComplete::Incomplete);
+            assert_eq!(status("a %in%"), IsComplete::Incomplete);
+            assert_eq!(status("1 +\n2"), IsComplete::Complete);
+            assert_eq!(status("1 + * 2"), IsComplete::Invalid);
+        })
+    }
+
+    #[test]
+    fn test_is_complete_strings() {
+        r_test(|| {
+            assert_eq!(status("'abc'"), IsComplete::Complete);
+            assert_eq!(status("\"abc"), IsComplete::Incomplete);
+            assert_eq!(status("'abc"), IsComplete::Incomplete);
+            assert_eq!(status("`abc"), IsComplete::Incomplete);
+        })
+    }
+
+    #[test]
+    fn test_continuation_indent() {
+        assert_eq!(continuation_indent("1 +"), "");
+        assert_eq!(continuation_indent("f <- function(x) {\n  x +"), "  ");
+        assert_eq!(continuation_indent("{\n\t"), "\t");
+        assert_eq!(continuation_indent(""), "");
+    }
+
+    fn complete(code: &str) -> Vec<String> {
+        let reply = r_complete(code, code.chars().count() as u32).unwrap();
+        assert_eq!(reply.cursor_end as usize, code.chars().count());
+        reply.matches
+    }
+
+    #[test]
+    fn test_complete_request() {
+        r_test(|| {
+            let reply = r_complete("x <- me", 7).unwrap();
+            assert!(reply.matches.contains(&String::from("mean")));
+            assert_eq!(reply.cursor_start, 5);
+            assert_eq!(reply.cursor_end, 7);
+
+            let types = &reply.metadata["_jupyter_types_experimental"];
+            let mean = types
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|x| x["text"] == "mean")
+                .unwrap();
+            assert_eq!(mean["type"], "function");
+
+            // Only the token before the cursor is completed
+            let reply = r_complete("1\nme + 1", 4).unwrap();
+            assert!(reply.matches.contains(&String::from("mean")));
+            assert_eq!(reply.cursor_start, 2);
+
+            // Columns of a data frame
+            r_parse_eval0("data.frm <- data.frame(alpha = 1, beta = 2)", R_ENVS.global).unwrap();
+            let matches = complete("data.frm$");
+            assert!(matches.contains(&String::from("data.frm$alpha")));
+            assert!(matches.contains(&String::from("data.frm$beta")));
+            r_parse_eval0("rm(data.frm)", R_ENVS.global).unwrap();
+
+            // Namespaced objects
+            assert!(complete("utils::hea").contains(&String::from("utils::head")));
+
+            // Files
+            let dir = std::env::temp_dir().join("ark-complete-request");
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("data-file.csv"), "").unwrap();
+            let dir = dir.to_string_lossy().replace('\\', "/");
+            let code = format!("read.csv(\"{dir}/data-fi");
+            let matches = complete(&code);
+            assert!(matches.contains(&format!("{dir}/data-file.csv")));
+        })
+    }
+
+    #[test]
+    fn test_kernel_info_reply() {
+        let mut kernel_info = KernelInfo {
+            version: String::from("R version 4.3.1 (2023-06-16)"),
+            banner: String::from("banner"),
+            input_prompt: Some(String::from("> ")),
+            continuation_prompt: Some(String::from("+ ")),
+            session_mode: SessionMode::Notebook,
+            help_port: Some(1234),
+        };
+
+        let reply = kernel_info_reply(&kernel_info);
+        let info = &reply.language_info;
+        assert_eq!(info.name, "R");
+        assert_eq!(info.version, "4.3.1");
+        assert_eq!(info.mimetype, "text/x-r-source");
+        assert_eq!(info.file_extension, ".R");
+        assert_eq!(info.pygments_lexer, "r");
+        assert_eq!(info.codemirror_mode, "r");
+        assert_eq!(reply.banner, "banner");
+
+        let positron = info.positron.as_ref().unwrap();
+        assert_eq!(positron.session_mode.as_deref(), Some("notebook"));
+
+        let urls: Vec<&str> = reply.help_links.iter().map(|x| x.url.as_str()).collect();
+        assert!(urls.contains(&"https://cran.r-project.org/"));
+        assert!(urls.contains(&"http://127.0.0.1:1234/doc/html/index.html"));
+
+        // No local help link without a help server
+        kernel_info.help_port = None;
+        let reply = kernel_info_reply(&kernel_info);
+        assert!(!reply
+            .help_links
+            .iter()
+            .any(|x| x.url.starts_with("http://127.0.0.1")));
+    }
+}
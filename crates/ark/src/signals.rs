@@ -5,6 +5,7 @@
  *
  */
 
+pub use crate::sys::signals::forward_interrupts;
 pub use crate::sys::signals::initialize_signal_block;
 pub use crate::sys::signals::initialize_signal_handlers;
 pub use crate::sys::signals::interrupts_pending;
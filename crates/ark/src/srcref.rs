@@ -1,3 +1,7 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use dashmap::DashMap;
 use harp::call::r_expr_quote;
 use harp::environment::r_ns_env;
 use harp::environment::Binding;
@@ -6,8 +10,10 @@ use harp::exec::RFunctionExt;
 use harp::object::r_length;
 use harp::object::RObject;
 use harp::r_symbol;
+use harp::utils::r_is_null;
 use harp::utils::r_typeof;
 use libr::*;
+use once_cell::sync::Lazy;
 
 use crate::lsp::handlers::ARK_VDOCS;
 use crate::modules::ARK_ENVS;
@@ -15,6 +21,137 @@ use crate::r_task;
 use crate::variables::variable::is_binding_fancy;
 use crate::variables::variable::plain_binding_force_with_rollback;
 
+/// The location of the source of a function, as recorded in its `srcref`
+/// attribute. Lines and columns are zero-based and the end column is
+/// exclusive, as in LSP ranges.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SrcrefLocation {
+    /// A file path, or an `ark:` URI for virtual documents
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+    pub end_line: u32,
+    pub end_column: u32,
+}
+
+/// Find the source location of function `x`. Returns `None` if `x` doesn't
+/// have source references, e.g. because it was defined without
+/// `keep.source`, or if the source file doesn't exist.
+pub(crate) fn srcref_location(x: &RObject) -> anyhow::Result<Option<SrcrefLocation>> {
+    let location = RFunction::new("", "srcref_location")
+        .add(x.clone())
+        .call_in(ARK_ENVS.positron_ns)?;
+
+    if r_is_null(location.sexp) {
+        return Ok(None);
+    }
+
+    let position = |i: isize| -> anyhow::Result<i32> { Ok(location.vector_elt(i)?.try_into()?) };
+
+    Ok(Some(SrcrefLocation::from_srcref(
+        location.vector_elt(0)?.try_into()?,
+        [position(1)?, position(2)?, position(3)?, position(4)?],
+    )))
+}
+
+impl SrcrefLocation {
+    /// Converts the first line, first column, last line, and last column of
+    /// a srcref. These are one-based and the last column is inclusive, so it
+    /// is also the zero-based exclusive end column.
+    fn from_srcref(file: String, [line, column, end_line, end_column]: [i32; 4]) -> Self {
+        let zero_based = |value: i32| (value - 1).max(0) as u32;
+
+        Self {
+            file,
+            line: zero_based(line),
+            column: zero_based(column),
+            end_line: zero_based(end_line),
+            end_column: end_column.max(0) as u32,
+        }
+    }
+}
+
+/// Prefix of the file name of the synthetic srcfiles of notebook cells
+const CELL_SRCFILE_PREFIX: &str = "ark-cell:";
+
+/// The source of a notebook cell, registered before the cell is executed.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CellSource {
+    pub cell_id: String,
+    pub code: String,
+}
+
+/// Maximum number of notebook cells whose source is kept around
+const CELL_SOURCES_CAPACITY: usize = 500;
+
+/// Notebook cells keyed by the file name of their synthetic srcfile, least
+/// recently registered first
+static CELL_SOURCES: Lazy<Mutex<VecDeque<(String, CellSource)>>> =
+    Lazy::new(|| Mutex::new(VecDeque::new()));
+
+/// Register the source of a notebook cell before executing it. Returns the
+/// file name of the synthetic srcfile that the cell's code should be parsed
+/// with, so that source references of the cell can be mapped back to it by
+/// `cell_source()`.
+///
+/// Re-executing a cell replaces its source, and only the most recently
+/// registered cells are kept.
+pub(crate) fn register_cell_source(cell_id: &str, code: &str) -> String {
+    let filename = format!("{CELL_SRCFILE_PREFIX}{cell_id}");
+
+    let mut cells = CELL_SOURCES.lock().unwrap();
+    cells.retain(|(name, _)| name != &filename);
+    cells.push_back((filename.clone(), CellSource {
+        cell_id: cell_id.to_string(),
+        code: code.to_string(),
+    }));
+    while cells.len() > CELL_SOURCES_CAPACITY {
+        cells.pop_front();
+    }
+
+    filename
+}
+
+/// Whether a notebook cell ID can be used in the file name of a synthetic
+/// srcfile. The nbformat restricts IDs to these characters, which don't need
+/// escaping in a `#line` directive.
+pub(crate) fn is_valid_cell_id(cell_id: &str) -> bool {
+    !cell_id.is_empty() &&
+        cell_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Look up the notebook cell that a srcfile named `filename` was created
+/// for. Since the cell's code is parsed with a `#line 1` directive, lines of
+/// source references into the srcfile are lines of the cell.
+pub(crate) fn cell_source(filename: &str) -> Option<CellSource> {
+    if !filename.starts_with(CELL_SRCFILE_PREFIX) {
+        return None;
+    }
+    let cells = CELL_SOURCES.lock().unwrap();
+    cells
+        .iter()
+        .find(|(name, _)| name == filename)
+        .map(|(_, cell)| cell.clone())
+}
+
+#[harp::register]
+unsafe extern "C" fn ps_cell_source(filename: SEXP) -> anyhow::Result<SEXP> {
+    let filename: String = RObject::view(filename).try_into()?;
+
+    let Some(cell) = cell_source(&filename) else {
+        return Ok(harp::r_null());
+    };
+
+    let out = RFunction::new("base", "list")
+        .param("cell_id", cell.cell_id)
+        .param("code", cell.code)
+        .call()?;
+
+    Ok(out.sexp)
+}
+
 #[tracing::instrument(level = "trace")]
 pub(crate) fn resource_loaded_namespaces() -> anyhow::Result<()> {
     let loaded = RFunction::new("base", "loadedNamespaces").call()?;
@@ -105,6 +242,25 @@ pub(crate) async fn ns_populate_srcref(ns_name: String) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Inject source references pointing to the package's R files in the
+/// functions of a package loaded with `pkgload::load_all()`, so that the
+/// debugger can step into them. Does nothing for packages loaded from a
+/// library, or for functions that already have source references.
+#[tracing::instrument(level = "trace")]
+pub(crate) fn dev_package_populate_srcref(pkg: &str, path: &str) -> anyhow::Result<()> {
+    let n: i32 = RFunction::new("", "dev_package_populate_srcref")
+        .add(pkg)
+        .add(path)
+        .call_in(ARK_ENVS.positron_ns)?
+        .try_into()?;
+
+    if n > 0 {
+        log::trace!("Injected source references in {n} functions of dev package `{pkg}`");
+    }
+
+    Ok(())
+}
+
 #[tracing::instrument(level = "trace", skip_all, fields(name = %binding.name))]
 fn generate_source(
     binding: &Binding,
@@ -145,43 +301,113 @@ fn generate_source(
 
     // Inject source references in functions. This is slightly unsafe but we
     // couldn't think of a dire failure mode.
-    unsafe {
-        // First replace the body which contains expressions tagged with srcrefs
-        // such as calls to `{`. Compiled functions are a little more tricky.
-
-        let body = BODY(old.sexp);
-        if r_typeof(body) == BCODESXP {
-            // This is a compiled function. We could recompile the fresh
-            // function we just created but the compiler is very slow. Instead,
-            // update the expression stored in the bytecode. This expression is
-            // used by `eval()` when stepping with the debugger.
-
-            // Get the constant pool: BCODE_CONSTS = CDR
-            let consts = CDR(body);
-
-            // The original body expression is stored as first element
-            // of the constant pool
-            if r_length(consts) > 0 {
-                // Inject new body instrumented with source references
-                SET_VECTOR_ELT(consts, 0, R_ClosureExpr(new));
-            }
-        } else {
-            SET_BODY(old.sexp, BODY(new));
-        }
-
-        // Finally push the srcref attribute for the whole function
-        Rf_setAttrib(
-            old.sexp,
-            r_symbol!("srcref"),
-            Rf_getAttrib(new, r_symbol!("srcref")),
-        );
-    }
+    unsafe { fn_inject_srcref(old.sexp, new) };
 
     let text: Vec<String> = RObject::view(text).try_into()?;
     Ok(Some(text))
 }
 
+/// Inject the body and source references of `new` into `old`, in place, so
+/// that existing references to `old` (e.g. registered S3 methods or exports)
+/// see them too. `new` must be a lossless reparse of `old`.
+unsafe fn fn_inject_srcref(old: SEXP, new: SEXP) {
+    // First replace the body which contains expressions tagged with srcrefs
+    // such as calls to `{`. Compiled functions are a little more tricky.
+
+    let body = BODY(old);
+    if r_typeof(body) == BCODESXP {
+        // This is a compiled function. We could recompile the fresh
+        // function we just created but the compiler is very slow. Instead,
+        // update the expression stored in the bytecode. This expression is
+        // used by `eval()` when stepping with the debugger.
+
+        // Get the constant pool: BCODE_CONSTS = CDR
+        let consts = CDR(body);
+
+        // The original body expression is stored as first element
+        // of the constant pool
+        if r_length(consts) > 0 {
+            // Inject new body instrumented with source references
+            SET_VECTOR_ELT(consts, 0, R_ClosureExpr(new));
+        }
+    } else {
+        SET_BODY(old, BODY(new));
+    }
+
+    // Finally push the srcref attribute for the whole function
+    Rf_setAttrib(
+        old,
+        r_symbol!("srcref"),
+        Rf_getAttrib(new, r_symbol!("srcref")),
+    );
+}
+
+#[harp::register]
+unsafe extern "C" fn ps_fn_inject_srcref(old: SEXP, new: SEXP) -> anyhow::Result<SEXP> {
+    fn_inject_srcref(old, new);
+    Ok(harp::r_null())
+}
+
 #[harp::register]
 pub extern "C" fn ark_zap_srcref(x: SEXP) -> anyhow::Result<SEXP> {
     Ok(harp::attrib::zap_srcref(x).sexp)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::srcref::cell_source;
+    use crate::srcref::register_cell_source;
+    use crate::srcref::CellSource;
+    use crate::srcref::SrcrefLocation;
+    use crate::srcref::CELL_SOURCES_CAPACITY;
+
+    #[test]
+    fn test_srcref_location_from_srcref() {
+        // `f <- function() 1` on the second line: the srcref of the function
+        // spans columns 6 to 17
+        let location = SrcrefLocation::from_srcref(String::from("file.R"), [2, 6, 2, 17]);
+        assert_eq!(location, SrcrefLocation {
+            file: String::from("file.R"),
+            line: 1,
+            column: 5,
+            end_line: 1,
+            end_column: 17,
+        });
+    }
+
+    #[test]
+    fn test_cell_source() {
+        let filename = register_cell_source("12", "x <- 1\nstop('foo')");
+        assert_eq!(filename, "ark-cell:12");
+
+        assert_eq!(
+            cell_source(&filename),
+            Some(CellSource {
+                cell_id: String::from("12"),
+                code: String::from("x <- 1\nstop('foo')"),
+            })
+        );
+
+        assert_eq!(cell_source("ark-cell:unknown"), None);
+        assert_eq!(cell_source("script.R"), None);
+    }
+
+    #[test]
+    fn test_cell_source_is_replaced_on_reexecution() {
+        let filename = register_cell_source("cell-replaced", "x <- 1");
+        register_cell_source("cell-replaced", "x <- 2");
+
+        assert_eq!(cell_source(&filename).unwrap().code, "x <- 2");
+    }
+
+    #[test]
+    fn test_cell_sources_are_bounded() {
+        let first = register_cell_source("cell-bounded-0", "1");
+        for i in 1..=CELL_SOURCES_CAPACITY {
+            register_cell_source(&format!("cell-bounded-{i}"), "1");
+        }
+
+        assert_eq!(cell_source(&first), None);
+        assert!(cell_source(&format!("ark-cell:cell-bounded-{CELL_SOURCES_CAPACITY}")).is_some());
+    }
+}
@@ -0,0 +1,238 @@
+//
+// start.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use amalthea::comm::comm_manager::CommKeepalive;
+use amalthea::connection_file::ConnectionFile;
+use amalthea::kernel::Kernel;
+use amalthea::socket::stdin::StdInRequest;
+use bus::Bus;
+use bus::BusReader;
+use crossbeam::channel::bounded;
+use crossbeam::channel::unbounded;
+use log::*;
+use stdext::spawn;
+
+use crate::control::Control;
+use crate::dap;
+use crate::interface::KernelInfo;
+use crate::interface::SessionMode;
+use crate::lsp;
+use crate::request::execute_queue;
+use crate::request::KernelRequest;
+use crate::request::RRequest;
+use crate::shell::Shell;
+use crate::startup::StartupOption;
+
+/// Connects a new kernel to the frontend described by `connection_file` and
+/// starts the R REPL on the current thread. Does not return for the duration
+/// of the session.
+pub fn start_kernel(
+    connection_file: ConnectionFile,
+    r_args: Vec<String>,
+    startup_files: Vec<String>,
+    startup_options: Vec<StartupOption>,
+    modules_dir: Option<String>,
+    session_mode: SessionMode,
+    capture_streams: bool,
+    ready_file: Option<String>,
+    history_file: Option<String>,
+) {
+    // Record the ports before the connection file is consumed by the kernel,
+    // along with the frontend's session information when supplied
+    let mut ready = serde_json::json!({
+        "ports": {
+            "shell": connection_file.shell_port,
+            "control": connection_file.control_port,
+            "stdin": connection_file.stdin_port,
+            "iopub": connection_file.iopub_port,
+            "hb": connection_file.hb_port,
+        },
+    });
+    if let Some(kernel_name) = &connection_file.kernel_name {
+        ready["kernel_name"] = serde_json::Value::from(kernel_name.clone());
+    }
+    if let Some(jupyter_session) = &connection_file.jupyter_session {
+        ready["jupyter_session"] = serde_json::Value::from(jupyter_session.clone());
+    }
+
+    // Create a new kernel from the connection file
+    let mut kernel = match Kernel::new("ark", connection_file) {
+        Ok(k) => k,
+        Err(e) => {
+            error!("Failed to create kernel: {}", e);
+            return;
+        },
+    };
+
+    // Persist execution history across restarts, if requested
+    kernel.set_history_file(history_file.map(PathBuf::from));
+
+    // Periodically ping open comms and close the ones that have gone quiet,
+    // if requested
+    kernel.set_comm_keepalive(comm_keepalive());
+
+    // Create the channels used for communication. These are created here
+    // as they need to be shared across different components / threads.
+    let iopub_tx = kernel.create_iopub_tx();
+
+    // A broadcast channel (bus) used to notify clients when the kernel
+    // has finished initialization.
+    let mut kernel_init_tx = Bus::new(1);
+
+    // A channel pair used for shell requests.
+    // These events are used to manage the runtime state, and also to
+    // handle message delivery, among other things.
+    let (r_request_tx, r_request_rx) = bounded::<RRequest>(1);
+
+    // Execution requests submitted by the shell
+    let (execute_request_tx, execute_request_rx) = execute_queue();
+    let (kernel_request_tx, kernel_request_rx) = bounded::<KernelRequest>(1);
+
+    // Create the LSP and DAP clients.
+    // Not all Amalthea kernels provide these, but ark does.
+    // They must be able to deliver messages to the shell channel directly.
+    let lsp = Arc::new(Mutex::new(lsp::handler::Lsp::new(kernel_init_tx.add_rx())));
+
+    // DAP needs the `RRequest` channel to communicate with
+    // `read_console()` and send commands to the debug interpreter
+    let dap = dap::Dap::new_shared(r_request_tx.clone());
+
+    // Communication channel between the R main thread and the Amalthea
+    // StdIn socket thread
+    let (stdin_request_tx, stdin_request_rx) = bounded::<StdInRequest>(1);
+
+    // Communication channel for `CommEvent`
+    let comm_manager_tx = kernel.create_comm_manager_tx();
+
+    // Create the shell.
+    let kernel_init_rx = kernel_init_tx.add_rx();
+    let shell = Shell::new(
+        comm_manager_tx.clone(),
+        iopub_tx.clone(),
+        execute_request_tx,
+        stdin_request_tx.clone(),
+        kernel_init_rx,
+        kernel_request_tx,
+        kernel_request_rx,
+    );
+
+    // Create the control handler; this is used to handle shutdown/interrupt and
+    // related requests
+    let control = Arc::new(Mutex::new(Control::new(r_request_tx.clone())));
+
+    // Create the stream behavior; this determines whether the kernel should
+    // capture stdout/stderr and send them to the frontend as IOPub messages
+    let stream_behavior = match capture_streams {
+        true => amalthea::kernel::StreamBehavior::Capture,
+        false => amalthea::kernel::StreamBehavior::None,
+    };
+
+    // Create the kernel
+    let kernel_clone = shell.kernel.clone();
+    let shell = Arc::new(Mutex::new(shell));
+
+    let (stdin_reply_tx, stdin_reply_rx) = unbounded();
+
+    let res = kernel.connect(
+        shell,
+        control,
+        Some(lsp),
+        Some(dap.clone()),
+        stream_behavior,
+        stdin_request_rx,
+        stdin_reply_tx,
+    );
+    if let Err(err) = res {
+        panic!("Couldn't connect to frontend: {err:?}");
+    }
+
+    // Signal readiness once R has also finished initializing
+    if let Some(file) = ready_file {
+        spawn_ready_file_writer(file, ready, kernel_init_tx.add_rx());
+    }
+
+    // Start the R REPL (does not return for the duration of the session)
+    crate::interface::start_r(
+        r_args,
+        startup_files,
+        startup_options,
+        modules_dir,
+        kernel_clone,
+        comm_manager_tx,
+        r_request_rx,
+        execute_request_rx,
+        stdin_request_tx,
+        stdin_reply_rx,
+        iopub_tx,
+        kernel_init_tx,
+        dap,
+        session_mode,
+    )
+}
+
+/// Write `file` once the kernel is connected and R has finished
+/// initializing, so that supervisors can detect readiness without polling
+/// the log. Contains the pid and the ports of the kernel sockets.
+fn spawn_ready_file_writer(
+    file: String,
+    mut contents: serde_json::Value,
+    mut kernel_init_rx: BusReader<KernelInfo>,
+) {
+    spawn!("ark-ready-file", move || {
+        if let Err(err) = kernel_init_rx.recv() {
+            log::error!("Can't wait for R initialization to write ready file: {err:?}");
+            return;
+        }
+
+        contents["pid"] = serde_json::Value::from(std::process::id());
+
+        // Write to a temporary file first so that the ready file never
+        // appears partially written
+        let tmp = format!("{file}.tmp");
+        let result = std::fs::write(&tmp, contents.to_string() + "\n")
+            .and_then(|_| std::fs::rename(&tmp, &file));
+
+        match result {
+            Ok(_) => log::info!("Wrote ready file '{file}'"),
+            Err(err) => log::error!("Can't write ready file '{file}': {err:?}"),
+        }
+    });
+}
+
+/// Reads the comm keepalive configuration from the environment. The
+/// keepalive is opt-in: it's only enabled when `ARK_COMM_KEEPALIVE_TIMEOUT`
+/// is set to a non-zero value. `ARK_COMM_KEEPALIVE_INTERVAL` and
+/// `ARK_COMM_KEEPALIVE_TIMEOUT` are given in seconds.
+fn comm_keepalive() -> Option<CommKeepalive> {
+    let seconds = |var: &str| -> Option<Duration> {
+        let value = std::env::var(var).ok()?;
+        match value.parse::<u64>() {
+            Ok(value) => Some(Duration::from_secs(value)),
+            Err(err) => {
+                log::warn!("Ignoring invalid `{var}` value '{value}': {err}");
+                None
+            },
+        }
+    };
+
+    let timeout = seconds("ARK_COMM_KEEPALIVE_TIMEOUT")?;
+    if timeout.is_zero() {
+        return None;
+    }
+
+    let interval =
+        seconds("ARK_COMM_KEEPALIVE_INTERVAL").unwrap_or(CommKeepalive::default().interval);
+
+    // Pinging more often than once a second is never useful
+    let interval = interval.max(Duration::from_secs(1));
+    Some(CommKeepalive { interval, timeout })
+}
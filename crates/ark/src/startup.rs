@@ -14,11 +14,167 @@ use amalthea::wire::stream::StreamOutput;
 use harp::environment::R_ENVS;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
+use harp::object::RObject;
 use libr::Rf_eval;
 
 use crate::interface::RMain;
 use crate::sys;
 
+/// The environment variable from which startup options are read, with the
+/// same syntax as `--startup-options`
+pub const STARTUP_OPTIONS_ENV_VAR: &str = "ARK_STARTUP_OPTIONS";
+
+/// An R option set with `--startup-options` or `ARK_STARTUP_OPTIONS`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StartupOption {
+    pub name: String,
+    pub value: StartupOptionValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StartupOptionValue {
+    Logical(bool),
+    Integer(i32),
+    Double(f64),
+    String(String),
+}
+
+impl std::fmt::Display for StartupOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.value {
+            StartupOptionValue::Logical(true) => write!(f, "{} = TRUE", self.name),
+            StartupOptionValue::Logical(false) => write!(f, "{} = FALSE", self.name),
+            StartupOptionValue::Integer(x) => write!(f, "{} = {x}L", self.name),
+            StartupOptionValue::Double(x) => write!(f, "{} = {x}", self.name),
+            StartupOptionValue::String(x) => write!(f, "{} = {x:?}", self.name),
+        }
+    }
+}
+
+impl From<StartupOptionValue> for RObject {
+    fn from(value: StartupOptionValue) -> Self {
+        match value {
+            StartupOptionValue::Logical(x) => RObject::from(x),
+            StartupOptionValue::Integer(x) => RObject::from(x),
+            StartupOptionValue::Double(x) => RObject::from(x),
+            StartupOptionValue::String(x) => RObject::from(x),
+        }
+    }
+}
+
+/// Parse a `--startup-options` specification such as
+/// `warn=1,stringsAsFactors=FALSE`. Values are interpreted like R scalar
+/// literals: `TRUE`/`FALSE`, integers with an `L` suffix, doubles, and
+/// strings, which may be quoted to include commas.
+pub fn parse_startup_options(spec: &str) -> Result<Vec<StartupOption>, String> {
+    let mut options = Vec::new();
+
+    for part in split_unquoted_commas(spec)?
+        .iter()
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+    {
+        let Some((name, value)) = part.split_once('=') else {
+            return Err(format!("expected `KEY=VALUE`, got '{part}'"));
+        };
+
+        let name = name.trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(format!("invalid option name '{name}'"));
+        }
+
+        options.push(StartupOption {
+            name: name.to_string(),
+            value: parse_startup_option_value(value.trim()),
+        });
+    }
+
+    Ok(options)
+}
+
+/// Parse the startup options set with `ARK_STARTUP_OPTIONS`, if any
+pub fn startup_options_from_env() -> Result<Vec<StartupOption>, String> {
+    match std::env::var(STARTUP_OPTIONS_ENV_VAR) {
+        Ok(spec) => parse_startup_options(&spec),
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+fn parse_startup_option_value(value: &str) -> StartupOptionValue {
+    match value {
+        "TRUE" | "T" => return StartupOptionValue::Logical(true),
+        "FALSE" | "F" => return StartupOptionValue::Logical(false),
+        _ => {},
+    }
+
+    for quote in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return StartupOptionValue::String(value[1..value.len() - 1].to_string());
+        }
+    }
+
+    // Rust also parses e.g. `inf` and `NaN` as doubles, R doesn't
+    let is_numeric = value
+        .chars()
+        .all(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E' | 'L'));
+
+    if is_numeric {
+        if let Some(Ok(x)) = value.strip_suffix('L').map(str::parse::<i32>) {
+            return StartupOptionValue::Integer(x);
+        }
+        if let Ok(x) = value.parse::<f64>() {
+            return StartupOptionValue::Double(x);
+        }
+    }
+
+    StartupOptionValue::String(value.to_string())
+}
+
+fn split_unquoted_commas(spec: &str) -> Result<Vec<String>, String> {
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut quote: Option<char> = None;
+
+    for c in spec.chars() {
+        match (c, quote) {
+            (',', None) => parts.push(std::mem::take(&mut part)),
+            ('"' | '\'', None) => {
+                quote = Some(c);
+                part.push(c);
+            },
+            (c, Some(q)) if c == q => {
+                quote = None;
+                part.push(c);
+            },
+            _ => part.push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err(format!("unterminated string in '{spec}'"));
+    }
+
+    parts.push(part);
+    Ok(parts)
+}
+
+/// Apply the options passed with `ARK_STARTUP_OPTIONS` and
+/// `--startup-options`. Called before the R profiles and startup files are
+/// sourced so that these see the options. Note that the site and user
+/// profiles run afterwards and can still override them.
+pub(crate) fn apply_startup_options(options: &[StartupOption]) {
+    for option in options {
+        let result = RFunction::new("base", "options")
+            .param(&option.name, option.value.clone())
+            .call();
+
+        match result {
+            Ok(_) => log::debug!("Applied startup option `{option}`"),
+            Err(err) => log::error!("Can't apply startup option `{option}`: {err:?}"),
+        }
+    }
+}
+
 pub(crate) fn should_ignore_site_r_profile(args: &Vec<String>) -> bool {
     args.iter()
         .any(|arg| arg == "--no-site-file" || arg == "--vanilla")
@@ -89,11 +245,61 @@ fn source_r_profile(path: &PathBuf) {
         unreachable!("Only `TopLevelExecError` errors should be thrown.");
     };
 
+    let message = format!("Error while sourcing R profile file at path '{path}':\n{message}");
+    send_startup_error(message);
+}
+
+/// Source the startup files passed with `--startup-file` or found in a
+/// `--startup-dir`, in order. Startup is aborted at the first file that
+/// throws: the remaining files are skipped and the error is shown in the
+/// console, but the session still starts.
+pub(crate) fn source_startup_files(files: &[String]) {
+    for (i, file) in files.iter().enumerate() {
+        log::info!("Sourcing startup file '{file}'");
+
+        let Err(err) = harp::exec::r_source(file) else {
+            continue;
+        };
+
+        log::error!("Failed to source startup file '{file}' due to: {err:?}");
+
+        let mut message = format!("Error while sourcing startup file at path '{file}':\n{err}");
+
+        let skipped = &files[i + 1..];
+        if !skipped.is_empty() {
+            let skipped = skipped.join("', '");
+            message.push_str(&format!("\nSkipped remaining startup files: '{skipped}'"));
+        }
+
+        send_startup_error(message);
+        return;
+    }
+}
+
+/// The startup files of `dir`, i.e. its `.R` files sorted by name
+pub fn startup_dir_files(dir: &str) -> std::io::Result<Vec<String>> {
+    let mut files = Vec::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        let is_r_file = path
+            .extension()
+            .map_or(false, |ext| ext == "R" || ext == "r");
+
+        if is_r_file && path.is_file() {
+            files.push(path.to_string_lossy().to_string());
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+fn send_startup_error(message: String) {
     // Forward the message on to the frontend to be shown in the console.
     // This technically happens outside of any parent context, but that is allowed.
     // https://jupyter-client.readthedocs.io/en/stable/messaging.html#parent-header
-    let message = format!("Error while sourcing R profile file at path '{path}':\n{message}");
-
     let message = IOPubMessage::Stream(StreamOutput {
         name: Stream::Stderr,
         text: message,
@@ -161,3 +367,87 @@ fn find_user_r_profile() -> Option<PathBuf> {
 
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::startup::parse_startup_options;
+    use crate::startup::startup_dir_files;
+    use crate::startup::startup_options_from_env;
+    use crate::startup::StartupOption;
+    use crate::startup::StartupOptionValue;
+    use crate::startup::STARTUP_OPTIONS_ENV_VAR;
+
+    #[test]
+    fn test_parse_startup_options() {
+        let option = |name: &str, value| StartupOption {
+            name: String::from(name),
+            value,
+        };
+
+        assert_eq!(
+            parse_startup_options("warn=1, stringsAsFactors=FALSE,digits=4L").unwrap(),
+            vec![
+                option("warn", StartupOptionValue::Double(1.0)),
+                option("stringsAsFactors", StartupOptionValue::Logical(false)),
+                option("digits", StartupOptionValue::Integer(4)),
+            ]
+        );
+
+        assert_eq!(
+            parse_startup_options("OutDec=\",\",repos='a,b',editor=vim,x=inf").unwrap(),
+            vec![
+                option("OutDec", StartupOptionValue::String(String::from(","))),
+                option("repos", StartupOptionValue::String(String::from("a,b"))),
+                option("editor", StartupOptionValue::String(String::from("vim"))),
+                option("x", StartupOptionValue::String(String::from("inf"))),
+            ]
+        );
+
+        assert_eq!(parse_startup_options("").unwrap(), vec![]);
+        assert!(parse_startup_options("warn").is_err());
+        assert!(parse_startup_options("=1").is_err());
+        assert!(parse_startup_options("x='foo").is_err());
+    }
+
+    #[test]
+    fn test_startup_options_from_env() {
+        std::env::set_var(STARTUP_OPTIONS_ENV_VAR, "warn=1");
+        let options = startup_options_from_env().unwrap();
+        std::env::remove_var(STARTUP_OPTIONS_ENV_VAR);
+
+        let expected = StartupOption {
+            name: String::from("warn"),
+            value: StartupOptionValue::Double(1.0),
+        };
+        assert_eq!(options, vec![expected]);
+        assert_eq!(startup_options_from_env().unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_startup_dir_files() {
+        let dir = std::env::temp_dir().join(format!("ark-startup-dir-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("nested.R")).unwrap();
+
+        for file in ["20-b.R", "10-a.r", "notes.txt", "30-c.R"] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+
+        let files = startup_dir_files(&dir.to_string_lossy()).unwrap();
+        let names: Vec<String> = files
+            .iter()
+            .map(|file| {
+                std::path::Path::new(file)
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(names, vec!["10-a.r", "20-b.R", "30-c.R"]);
+
+        assert!(startup_dir_files(&dir.join("missing").to_string_lossy()).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
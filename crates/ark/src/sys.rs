@@ -14,3 +14,16 @@ cfg_if::cfg_if! {
         pub use self::windows::*;
     }
 }
+
+/// Operations that launch external processes, which differ by platform.
+/// Implemented by `sys::platform::Platform` for the current target.
+pub trait PlatformOps {
+    /// Opens `path` with its default application
+    fn open_path(&self, path: &std::path::Path) -> anyhow::Result<()>;
+
+    /// Opens `url` in the default web browser
+    fn open_url(&self, url: &str) -> anyhow::Result<()>;
+
+    /// Shows `path` in the system file manager, selected if possible
+    fn reveal_in_file_manager(&self, path: &std::path::Path) -> anyhow::Result<()>;
+}
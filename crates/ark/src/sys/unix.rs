@@ -9,5 +9,6 @@ pub mod console;
 pub mod control;
 pub mod interface;
 pub mod path;
+pub mod platform;
 pub mod signals;
 pub mod traps;
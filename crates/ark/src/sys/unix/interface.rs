@@ -15,6 +15,7 @@ use libr::ptr_R_WriteConsole;
 use libr::ptr_R_WriteConsoleEx;
 use libr::run_Rmainloop;
 use libr::setup_Rmainloop;
+use libr::R_CStackLimit;
 use libr::R_Consolefile;
 use libr::R_HomeDir;
 use libr::R_InputHandlers;
@@ -65,6 +66,12 @@ pub fn setup_r(mut args: Vec<*mut c_char>) {
         libr::set(ptr_R_ShowMessage, Some(r_show_message));
         libr::set(ptr_R_Busy, Some(r_busy));
 
+        // The in-process test kernel runs R on a background thread, which
+        // R's C stack checks don't expect
+        if crate::test::is_test_kernel() {
+            libr::set(R_CStackLimit, usize::MAX);
+        }
+
         // Set up main loop
         setup_Rmainloop();
     }
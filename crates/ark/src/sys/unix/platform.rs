@@ -0,0 +1,62 @@
+/*
+ * platform.rs
+ *
+ * Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+ *
+ */
+
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::anyhow;
+use stdext::spawn;
+
+use crate::sys::PlatformOps;
+
+pub struct Platform;
+
+/// `open` on macOS, and the freedesktop.org launcher on other Unixes
+#[cfg(target_os = "macos")]
+const OPENER: &str = "open";
+#[cfg(not(target_os = "macos"))]
+const OPENER: &str = "xdg-open";
+
+impl PlatformOps for Platform {
+    fn open_path(&self, path: &Path) -> anyhow::Result<()> {
+        run(Command::new(OPENER).arg(path))
+    }
+
+    fn open_url(&self, url: &str) -> anyhow::Result<()> {
+        run(Command::new(OPENER).arg(url))
+    }
+
+    fn reveal_in_file_manager(&self, path: &Path) -> anyhow::Result<()> {
+        if cfg!(target_os = "macos") {
+            return run(Command::new("open").arg("-R").arg(path));
+        }
+
+        // There is no portable way of selecting a file, so open the folder
+        // containing it instead
+        let folder = if path.is_dir() {
+            path
+        } else {
+            path.parent().unwrap_or(path)
+        };
+        run(Command::new(OPENER).arg(folder))
+    }
+}
+
+// The launchers may not return until the application they started exits,
+// so we don't wait for them and only report failures to launch them. They
+// are reaped in the background so they don't linger as zombies.
+fn run(command: &mut Command) -> anyhow::Result<()> {
+    let mut child = command
+        .spawn()
+        .map_err(|err| anyhow!("Can't run {command:?}: {err}"))?;
+
+    spawn!("ark-launcher", move || {
+        let _ = child.wait();
+    });
+
+    Ok(())
+}
@@ -5,8 +5,12 @@
  *
  */
 
+use crossbeam::channel::Sender;
 use libr::R_interrupts_pending;
 use nix::sys::signal::*;
+use stdext::spawn;
+
+use crate::request::RRequest;
 
 /// Reset the signal block.
 ///
@@ -52,6 +56,53 @@ pub fn initialize_signal_block() {
     sigprocmask(SigmaskHow::SIG_BLOCK, Some(&sigset), None).unwrap();
 }
 
+/// Forward SIGINT to R from a dedicated thread.
+///
+/// This is an alternative to `initialize_signal_handlers()` for embeddings
+/// where the R thread shouldn't receive signals itself. SIGINT stays blocked
+/// on all threads, including the workers spawned after
+/// `initialize_signal_block()`, and is instead accepted synchronously with
+/// `sigwait()` by a thread spawned here. Each SIGINT flags an interrupt for
+/// R and sends `RRequest::Interrupt` to wake up `ReadConsole()` in case R is
+/// waiting for input, e.g. in `readline()`.
+///
+/// Since ark sets `R_SignalHandlers` to 0 before starting R, R doesn't
+/// install a SIGINT handler of its own and only learns about interrupts
+/// through `R_interrupts_pending`, which it checks in
+/// `R_CheckUserInterrupt()`. That's the flag set here, so interrupts take
+/// the same path as with `initialize_signal_handlers()`. Don't call both
+/// functions: a thread that unblocks SIGINT would race with this one for
+/// the signal.
+pub fn forward_interrupts(tx: Sender<RRequest>) {
+    spawn!("ark-signals", move || {
+        let mut sigset = SigSet::empty();
+        sigset.add(SIGINT);
+
+        // In case this thread wasn't spawned from a thread blocking SIGINT,
+        // `sigwait()` requires the signal to be blocked
+        if let Err(err) = pthread_sigmask(SigmaskHow::SIG_BLOCK, Some(&sigset), None) {
+            log::error!("Can't block SIGINT on signal forwarding thread: {err:?}");
+            return;
+        }
+
+        loop {
+            match sigset.wait() {
+                Ok(SIGINT) => {
+                    set_interrupts_pending(true);
+
+                    // R is busy if the channel is full, the flag is enough then
+                    let _ = tx.try_send(RRequest::Interrupt);
+                },
+                Ok(signal) => log::warn!("Unexpected signal {signal} while forwarding interrupts"),
+                Err(err) => {
+                    log::error!("Can't wait for SIGINT, no longer forwarding interrupts: {err:?}");
+                    return;
+                },
+            }
+        }
+    });
+}
+
 pub fn interrupts_pending() -> bool {
     unsafe { libr::get(R_interrupts_pending) == 1 }
 }
@@ -9,6 +9,7 @@ pub mod console;
 pub mod control;
 pub mod interface;
 pub mod path;
+pub mod platform;
 pub mod signals;
 mod strings;
 pub mod traps;
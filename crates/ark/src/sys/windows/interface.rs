@@ -16,6 +16,7 @@ use libr::get_R_HOME;
 use libr::readconsolecfg;
 use libr::run_Rmainloop;
 use libr::setup_Rmainloop;
+use libr::R_CStackLimit;
 use libr::R_DefParamsEx;
 use libr::R_HomeDir;
 use libr::R_SetParams;
@@ -94,6 +95,12 @@ pub fn setup_r(mut _args: Vec<*mut c_char>) {
         let home = CStr::from_ptr(R_HomeDir());
         log::trace!("R_HOME: {:?}", home);
 
+        // The in-process test kernel runs R on a background thread, which
+        // R's C stack checks don't expect
+        if crate::test::is_test_kernel() {
+            libr::set(R_CStackLimit, usize::MAX);
+        }
+
         // Set up main loop
         setup_Rmainloop();
     }
@@ -0,0 +1,42 @@
+/*
+ * platform.rs
+ *
+ * Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+ *
+ */
+
+use std::os::windows::process::CommandExt;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::anyhow;
+
+use crate::sys::PlatformOps;
+
+pub struct Platform;
+
+impl PlatformOps for Platform {
+    fn open_path(&self, path: &Path) -> anyhow::Result<()> {
+        spawn(Command::new("explorer").arg(path))
+    }
+
+    fn open_url(&self, url: &str) -> anyhow::Result<()> {
+        spawn(Command::new("explorer").arg(url))
+    }
+
+    fn reveal_in_file_manager(&self, path: &Path) -> anyhow::Result<()> {
+        // `explorer` expects the path to be quoted after the comma, which
+        // the default quoting of arguments doesn't do
+        let select = format!("/select,\"{}\"", path.display());
+        spawn(Command::new("explorer").raw_arg(select))
+    }
+}
+
+// The exit status of `explorer` is not meaningful, it is non-zero even when
+// it succeeds. So we only report failures to launch it.
+fn spawn(command: &mut Command) -> anyhow::Result<()> {
+    command
+        .spawn()
+        .map_err(|err| anyhow!("Can't run {command:?}: {err}"))?;
+    Ok(())
+}
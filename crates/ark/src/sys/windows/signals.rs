@@ -5,10 +5,13 @@
  *
  */
 
+use crossbeam::channel::Sender;
 use libr::Rboolean_FALSE;
 use libr::Rboolean_TRUE;
 use libr::UserBreak;
 
+use crate::request::RRequest;
+
 pub fn initialize_signal_handlers() {
     // Nothing to do on Windows. Signal blocking is POSIX only.
 }
@@ -17,6 +20,10 @@ pub fn initialize_signal_block() {
     // Nothing to do on Windows. Signal blocking is POSIX only.
 }
 
+pub fn forward_interrupts(_tx: Sender<RRequest>) {
+    // Nothing to do on Windows. Signal blocking is POSIX only.
+}
+
 pub fn interrupts_pending() -> bool {
     unsafe { libr::get(UserBreak) == Rboolean_TRUE }
 }
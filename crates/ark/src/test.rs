@@ -8,6 +8,8 @@
 // Wrapper around `harp::r_test_impl()` that also initializes the ark level R
 // modules, so they can be utilized in the tests
 
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 use std::sync::Once;
 
 use amalthea::comm::comm_channel::CommMsg;
@@ -18,6 +20,11 @@ use tree_sitter::Point;
 
 use crate::modules;
 
+#[cfg(feature = "testing")]
+mod kernel;
+#[cfg(feature = "testing")]
+pub use self::kernel::*;
+
 pub fn r_test<F: FnOnce()>(f: F) {
     let f = || {
         initialize_ark();
@@ -93,6 +100,14 @@ where
     }
 }
 
+pub(crate) static TEST_KERNEL: AtomicBool = AtomicBool::new(false);
+
+/// Whether R is run by a `TestKernel`, i.e. on a background thread of a
+/// test process rather than on the main thread of `ark`
+pub(crate) fn is_test_kernel() -> bool {
+    TEST_KERNEL.load(Ordering::SeqCst)
+}
+
 #[cfg(test)]
 mod tests {
     use tree_sitter::Point;
@@ -0,0 +1,196 @@
+//
+// kernel.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::Ordering;
+
+use amalthea::fixtures::dummy_frontend::DummyFrontend;
+use amalthea::wire::execute_request::ExecuteRequest;
+use amalthea::wire::interrupt_request::InterruptRequest;
+use amalthea::wire::jupyter_message::Message;
+use amalthea::wire::jupyter_message::Status;
+use amalthea::wire::kernel_info_request::KernelInfoRequest;
+use amalthea::wire::shutdown_request::ShutdownRequest;
+use amalthea::wire::status::ExecutionState;
+use stdext::spawn;
+
+use crate::interface::SessionMode;
+use crate::start::start_kernel;
+use crate::test::TEST_KERNEL;
+
+/// An in-process ark kernel connected to a `DummyFrontend`, used to test the
+/// full path of a request, from the shell socket to R and back to IOPub.
+///
+/// R can only be started once per process and this can't be combined with
+/// `r_test()`, so a `TestKernel` should live in its own integration test
+/// file with a single test.
+pub struct TestKernel {
+    frontend: DummyFrontend,
+}
+
+/// The messages produced by an `execute_request`
+pub struct TestExecution {
+    /// Either an `ExecuteReply` or an `ExecuteReplyException`
+    pub reply: Message,
+
+    /// The IOPub messages sent on behalf of the request between the busy
+    /// and idle statuses, in order
+    pub iopub: Vec<Message>,
+}
+
+impl TestKernel {
+    /// Starts R and the kernel sockets on a background thread, and waits
+    /// until R is ready to accept requests.
+    pub fn start() -> Self {
+        if TEST_KERNEL.swap(true, Ordering::SeqCst) {
+            panic!("Can't start more than one `TestKernel` per process");
+        }
+
+        // Set up R_HOME if necessary
+        if std::env::var("R_HOME").is_err() {
+            let result = Command::new("R").arg("RHOME").output().unwrap();
+            let r_home = String::from_utf8(result.stdout).unwrap();
+            std::env::set_var("R_HOME", PathBuf::from(r_home.trim()));
+        }
+
+        let frontend = DummyFrontend::new();
+        let connection_file = frontend.get_connection_file();
+
+        spawn!("ark-test-kernel", move || {
+            start_kernel(
+                connection_file,
+                vec![
+                    String::from("--interactive"),
+                    String::from("--no-save"),
+                    String::from("--no-restore"),
+                ],
+                vec![],
+                vec![],
+                None,
+                SessionMode::Console,
+                false,
+                None,
+                None,
+            )
+        });
+
+        frontend.complete_intialization();
+
+        // The kernel info reply is only sent once R is initialized
+        frontend.send_shell(KernelInfoRequest {});
+        match frontend.receive_shell() {
+            Message::KernelInfoReply(_) => {},
+            msg => panic!("Expected a kernel info reply, got {msg:?}"),
+        }
+
+        Self { frontend }
+    }
+
+    /// Executes `code` at top level and collects the messages it produced.
+    pub fn execute(&self, code: &str) -> TestExecution {
+        let id = self.send_execute(code);
+        self.receive_execution(&id)
+    }
+
+    /// Like `execute()` but sends a custom request, e.g. with
+    /// `user_expressions`. See `TestKernel::execute_request()`.
+    pub fn execute_with(&self, req: ExecuteRequest) -> TestExecution {
+        let id = self.frontend.send_shell(req);
+        self.receive_execution(&id)
+    }
+
+    /// Sends an `execute_request` without waiting for its completion, e.g.
+    /// to interrupt it. Returns the ID to pass to `receive_execution()`.
+    pub fn send_execute(&self, code: &str) -> String {
+        self.frontend.send_shell(Self::execute_request(code))
+    }
+
+    /// Creates the `execute_request` sent by `execute()`. The frontend
+    /// doesn't answer input requests, so `allow_stdin` is `false`.
+    pub fn execute_request(code: &str) -> ExecuteRequest {
+        ExecuteRequest {
+            code: String::from(code),
+            silent: false,
+            store_history: true,
+            user_expressions: serde_json::Value::Null,
+            allow_stdin: false,
+            stop_on_error: false,
+        }
+    }
+
+    /// Waits for the request `id` to complete and collects its messages.
+    pub fn receive_execution(&self, id: &str) -> TestExecution {
+        let reply = self.frontend.receive_shell();
+
+        let mut iopub = Vec::new();
+        loop {
+            let msg = self.frontend.receive_iopub();
+
+            // Skip leftovers from earlier requests
+            if !is_reply_to(&msg, id) {
+                continue;
+            }
+
+            match msg {
+                Message::Status(ref status) => {
+                    if let ExecutionState::Idle = status.content.execution_state {
+                        break;
+                    }
+                },
+                msg => iopub.push(msg),
+            }
+        }
+
+        TestExecution { reply, iopub }
+    }
+
+    /// Sends an `interrupt_request` and waits for the reply.
+    pub fn interrupt(&self) {
+        self.frontend.send_control(InterruptRequest {});
+
+        match self.frontend.receive_control() {
+            Message::InterruptReply(reply) => {
+                assert_eq!(reply.content.status, Status::Ok);
+            },
+            msg => panic!("Expected an interrupt reply, got {msg:?}"),
+        }
+    }
+
+    /// Asks the kernel to shut down and waits for the acknowledgement. R
+    /// exits the process shortly after, so this must be the last call of
+    /// the test.
+    pub fn shutdown(self) {
+        self.frontend
+            .send_control(ShutdownRequest { restart: false });
+
+        match self.frontend.receive_control() {
+            Message::ShutdownReply(reply) => {
+                assert_eq!(reply.content.status, Status::Ok);
+            },
+            msg => panic!("Expected a shutdown reply, got {msg:?}"),
+        }
+    }
+}
+
+fn is_reply_to(msg: &Message, id: &str) -> bool {
+    let parent = match msg {
+        Message::Status(msg) => &msg.parent_header,
+        Message::ExecuteInput(msg) => &msg.parent_header,
+        Message::ExecuteResult(msg) => &msg.parent_header,
+        Message::ExecuteError(msg) => &msg.parent_header,
+        Message::StreamOutput(msg) => &msg.parent_header,
+        Message::DisplayData(msg) => &msg.parent_header,
+        Message::UpdateDisplayData(msg) => &msg.parent_header,
+        Message::CommOpen(msg) => &msg.parent_header,
+        Message::CommMsg(msg) => &msg.parent_header,
+        Message::CommClose(msg) => &msg.parent_header,
+        _ => return false,
+    };
+
+    parent.as_ref().is_some_and(|header| header.msg_id == id)
+}
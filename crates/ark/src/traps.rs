@@ -5,6 +5,8 @@
 //
 //
 
+use std::sync::Mutex;
+
 // Call this after initialising the `log` package. Instruments
 // SIGSEGV, SIGILL, and SIGBUS (on Unix) to generate a backtrace with `info`
 // verbosity (lowest level so it's always reported).
@@ -22,6 +24,39 @@
 // and set this up now.
 pub use crate::sys::traps::register_trap_handlers;
 
+/// The top-level input currently evaluated by R. Recorded by `ReadConsole()`
+/// so that crash reports can show which R code was running. Walking the R
+/// stack from the handler isn't safe since the crash might have left R in an
+/// inconsistent state, so we never call into R there.
+static CURRENT_INPUT: Mutex<String> = Mutex::new(String::new());
+
+/// Inputs are truncated to this many bytes when recorded
+const CURRENT_INPUT_MAX_LEN: usize = 2000;
+
+/// Record the top-level input that R is about to evaluate
+pub fn set_current_input(input: &str) {
+    let mut end = input.len().min(CURRENT_INPUT_MAX_LEN);
+    while !input.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    if let Ok(mut current) = CURRENT_INPUT.lock() {
+        // Reuses the existing allocation if large enough
+        current.clear();
+        current.push_str(&input[..end]);
+        if end < input.len() {
+            current.push_str("\n...");
+        }
+    }
+}
+
+/// Record that R is back at top level and no longer evaluating input
+pub fn clear_current_input() {
+    if let Ok(mut current) = CURRENT_INPUT.lock() {
+        current.clear();
+    }
+}
+
 pub extern "C" fn backtrace_handler(signum: libc::c_int) {
     // Prevent infloop into the handler
     unsafe {
@@ -38,5 +73,40 @@ pub extern "C" fn backtrace_handler(signum: libc::c_int) {
     // always delivered to the thread that caused it, so we can just
     // capture the current thread's backtrace
     let bt = std::backtrace::Backtrace::force_capture();
-    log::error!("{}\n{}", header, bt);
+
+    // Only `try_lock()` the input, the crash might have happened while the
+    // lock was held on this thread. Fall back to the Rust-only report when
+    // the input isn't available.
+    let input = match CURRENT_INPUT.try_lock() {
+        Ok(input) if !input.is_empty() => {
+            format!("\n>>> While evaluating R input:\n{}\n", input.as_str())
+        },
+        _ => String::new(),
+    };
+
+    log::error!("{}\n{}{}", header, input, bt);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::traps::clear_current_input;
+    use crate::traps::set_current_input;
+    use crate::traps::CURRENT_INPUT;
+    use crate::traps::CURRENT_INPUT_MAX_LEN;
+
+    #[test]
+    fn test_current_input() {
+        set_current_input("f(x)");
+        assert_eq!(CURRENT_INPUT.lock().unwrap().as_str(), "f(x)");
+
+        // Truncated on a char boundary
+        let input = "é".repeat(CURRENT_INPUT_MAX_LEN);
+        set_current_input(&input);
+        let current = CURRENT_INPUT.lock().unwrap().clone();
+        assert!(current.ends_with("é\n..."));
+        assert!(current.len() <= CURRENT_INPUT_MAX_LEN + 4);
+
+        clear_current_input();
+        assert!(CURRENT_INPUT.lock().unwrap().is_empty());
+    }
 }
@@ -1,5 +1,12 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use stdext::push;
 use tree_sitter::Node;
+use tree_sitter::Point;
 
+use crate::lsp::markdown::md_bold;
+use crate::lsp::traits::node::NodeExt;
+use crate::lsp::traits::point::PointExt;
 use crate::lsp::traits::rope::RopeExt;
 
 #[derive(Debug, PartialEq)]
@@ -445,3 +452,467 @@ pub(crate) fn args_find_call_args<'tree>(
     let call = args_find_call(args, name, contents)?;
     call.child_by_field_name("arguments")
 }
+
+// roxygen2 comments can contain 1 or more leading `#` before the `'`
+static RE_ROXYGEN_COMMENT: Lazy<Regex> = Lazy::new(|| Regex::new(r"^#+'\s?(.*)$").unwrap());
+
+/// A block of contiguous roxygen comments (lines starting with `#'`)
+#[derive(Debug, PartialEq)]
+pub(crate) struct RoxygenBlock {
+    /// The lines of the block, without the `#'` prefix
+    pub lines: Vec<String>,
+    pub range: tree_sitter::Range,
+}
+
+impl RoxygenBlock {
+    /// Formats the block as Markdown. `@param` and `@return` tags are
+    /// rendered as a list of arguments and a value section, other tags such
+    /// as `@export` are dropped.
+    pub fn markdown(&self) -> String {
+        let mut markdown = String::new();
+
+        for line in self.lines.iter() {
+            if let Some(param) = line.strip_prefix("@param ") {
+                let (name, description) = param.split_once(' ').unwrap_or((param, ""));
+                push!(markdown, "- `", name, "`: ", description, "\n");
+            } else if let Some(value) = line.strip_prefix("@return ") {
+                push!(markdown, "\n", md_bold("Value"), ": ", value, "\n");
+            } else if line.starts_with('@') {
+                continue;
+            } else {
+                push!(markdown, line, "\n");
+            }
+        }
+
+        markdown
+    }
+}
+
+/// Finds the roxygen block documenting a function definition.
+///
+/// The block must directly precede the statement that defines the function,
+/// either an assignment like `f <- function() {}` or an S4 registration like
+/// `setGeneric("f", function(x) standardGeneric("f"))`. A blank line between
+/// the comments and the statement, or between two comments, breaks the
+/// association.
+pub(crate) fn node_roxygen_block(node: &Node, contents: &ropey::Rope) -> Option<RoxygenBlock> {
+    if !node.is_function_definition() {
+        return None;
+    }
+
+    let statement = function_definition_statement(node, contents)?;
+
+    let mut comments = Vec::new();
+    let mut row = statement.start_position().row;
+    let mut current = statement;
+
+    while let Some(previous) = current.prev_sibling() {
+        if !previous.is_comment() || previous.end_position().row + 1 != row {
+            break;
+        }
+
+        let text = node_text(&previous, contents)?;
+        let Some(line) = RE_ROXYGEN_COMMENT.captures(&text) else {
+            break;
+        };
+        if !node_starts_line(&previous, contents) {
+            break;
+        }
+
+        row = previous.start_position().row;
+        comments.push((previous, line[1].to_string()));
+        current = previous;
+    }
+
+    let (first, _) = comments.last()?;
+    let (last, _) = comments.first()?;
+    let range = tree_sitter::Range {
+        start_byte: first.start_byte(),
+        end_byte: last.end_byte(),
+        start_point: first.start_position(),
+        end_point: last.end_position(),
+    };
+
+    let lines = comments.into_iter().rev().map(|(_, line)| line).collect();
+
+    Some(RoxygenBlock { lines, range })
+}
+
+// The statement that defines a function, i.e. the assignment or the S4
+// registration call it's part of
+fn function_definition_statement<'tree>(
+    node: &Node<'tree>,
+    contents: &ropey::Rope,
+) -> Option<Node<'tree>> {
+    let parent = node.parent()?;
+
+    match parent.node_type() {
+        NodeType::BinaryOperator(
+            BinaryOperatorType::LeftAssignment |
+            BinaryOperatorType::LeftSuperAssignment |
+            BinaryOperatorType::EqualsAssignment,
+        ) => {
+            let rhs = parent.child_by_field_name("rhs")?;
+            (rhs == *node).then_some(parent)
+        },
+        NodeType::Argument => {
+            let call = parent.parent()?.parent()?;
+            if !call.is_call() {
+                return None;
+            }
+            let fun = node_text(&call.child_by_field_name("function")?, contents)?;
+            let fun = fun.strip_prefix("methods::").unwrap_or(&fun);
+            let is_s4 = matches!(
+                fun,
+                "setGeneric" | "setMethod" | "setReplaceMethod" | "setValidity"
+            );
+            is_s4.then_some(call)
+        },
+        _ => None,
+    }
+}
+
+// Whether only whitespace precedes `node` on its line, so that trailing
+// comments of a previous statement are not taken for documentation
+fn node_starts_line(node: &Node, contents: &ropey::Rope) -> bool {
+    let start = node.start_position();
+    let line_start = contents.line_to_byte(start.row);
+
+    match contents.get_byte_slice(line_start..line_start + start.column) {
+        Some(prefix) => prefix.chars().all(char::is_whitespace),
+        None => false,
+    }
+}
+
+/// The call surrounding a position, as needed by signature help and
+/// argument completions
+#[derive(Debug)]
+pub(crate) struct CallContext<'tree> {
+    /// The function being called, e.g. `fn` or `pkg::fn`
+    pub callee: Node<'tree>,
+    /// 0-based index of the argument at the position. For calls on the
+    /// right-hand side of a pipe, the piped argument counts as the first one.
+    pub argument_index: usize,
+    /// Whether the argument at the position is supplied by name
+    pub named: bool,
+}
+
+/// Finds the innermost call whose parentheses contain `point`.
+///
+/// Works on leaves rather than on `call` nodes so that incomplete code where
+/// the closing parenthesis is missing, which tree-sitter may parse as an
+/// `ERROR` node, is supported too. Returns `None` outside of a call, or when
+/// `point` is inside a nested block (e.g. a `{` in a function argument).
+pub(crate) fn call_context_at<'tree>(
+    root: Node<'tree>,
+    point: Point,
+    contents: &ropey::Rope,
+) -> Option<CallContext<'tree>> {
+    let start = root.find_closest_node_to_point(point)?;
+
+    let mut depth = 0;
+    let mut argument_index = 0;
+    let mut named = false;
+    let mut in_current_argument = true;
+
+    // Leaves starting at the cursor are after it
+    let leaves = std::iter::once(start)
+        .chain(start.bwd_leaf_iter())
+        .filter(|leaf| leaf.start_position().is_before(point) && !leaf.is_missing());
+
+    for leaf in leaves {
+        match leaf.kind() {
+            ")" | "]" | "]]" | "}" => depth += 1,
+            "[" | "[[" | "{" if depth > 0 => depth -= 1,
+            "(" if depth > 0 => depth -= 1,
+            // Inside a block or a subset, not directly in call arguments
+            "[" | "[[" | "{" => return None,
+            "(" => {
+                let callee = paren_callee(&leaf)?;
+                if call_is_piped(&leaf, contents) {
+                    argument_index += 1;
+                }
+                return Some(CallContext {
+                    callee,
+                    argument_index,
+                    named,
+                });
+            },
+            "," if depth == 0 => {
+                argument_index += 1;
+                in_current_argument = false;
+            },
+            "=" if depth == 0 && in_current_argument => named = true,
+            _ => {},
+        }
+    }
+
+    None
+}
+
+// The callee of a call given its opening parenthesis. `None` if the
+// parenthesis is not a call's, e.g. in `function(` or `if (`.
+fn paren_callee<'tree>(paren: &Node<'tree>) -> Option<Node<'tree>> {
+    let parent = paren.parent()?;
+
+    if parent.node_type() == NodeType::Arguments {
+        let call = parent.parent()?;
+        return match call.is_call() {
+            true => call.child_by_field_name("function"),
+            false => None,
+        };
+    }
+
+    // Incomplete code, the call is not recognised as such but the callee
+    // should still be right before the parenthesis
+    if parent.node_type() == NodeType::Error {
+        let callee = paren.prev_sibling()?;
+        let is_callee = matches!(
+            callee.node_type(),
+            NodeType::Identifier |
+                NodeType::String |
+                NodeType::NamespaceOperator(_) |
+                NodeType::ExtractOperator(_) |
+                NodeType::Call
+        );
+        if is_callee && callee.end_byte() == paren.start_byte() {
+            return Some(callee);
+        }
+    }
+
+    None
+}
+
+// Whether the call is the right-hand side of `|>` or `%>%`
+fn call_is_piped(paren: &Node, contents: &ropey::Rope) -> bool {
+    let Some(call) = paren.parent().and_then(|arguments| arguments.parent()) else {
+        return false;
+    };
+    let Some(parent) = call.parent() else {
+        return false;
+    };
+    if parent.child_by_field_name("rhs") != Some(call) {
+        return false;
+    }
+
+    match parent.node_type() {
+        NodeType::BinaryOperator(BinaryOperatorType::Pipe) => true,
+        NodeType::BinaryOperator(BinaryOperatorType::Special) => parent
+            .child_by_field_name("operator")
+            .and_then(|op| node_text(&op, contents))
+            .is_some_and(|op| op == "%>%"),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Node;
+
+    use crate::lsp::documents::Document;
+    use crate::lsp::traits::rope::RopeExt;
+    use crate::test::point_from_cursor;
+    use crate::treesitter::call_context_at;
+    use crate::treesitter::node_roxygen_block;
+    use crate::treesitter::NodeTypeExt;
+
+    fn find_function_definition(node: Node) -> Option<Node> {
+        if node.is_function_definition() {
+            return Some(node);
+        }
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.children(&mut cursor).collect();
+        children.into_iter().find_map(find_function_definition)
+    }
+
+    fn roxygen_lines(text: &str) -> Option<Vec<String>> {
+        let doc = Document::new(text, None);
+        let node = find_function_definition(doc.ast.root_node()).unwrap();
+        node_roxygen_block(&node, &doc.contents).map(|block| block.lines)
+    }
+
+    #[test]
+    fn test_roxygen_block_assignment() {
+        let text = "
+#' Title
+#'
+#' @param x A value
+f <- function(x) x
+";
+        assert_eq!(
+            roxygen_lines(text),
+            Some(vec![
+                "Title".to_string(),
+                "".to_string(),
+                "@param x A value".to_string()
+            ])
+        );
+
+        let doc = Document::new(text, None);
+        let node = find_function_definition(doc.ast.root_node()).unwrap();
+        let block = node_roxygen_block(&node, &doc.contents).unwrap();
+        assert_eq!(block.range.start_point.row, 1);
+        assert_eq!(block.range.end_point.row, 3);
+    }
+
+    #[test]
+    fn test_roxygen_block_only_includes_roxygen_comments() {
+        let text = "
+# Not documentation
+#' Title
+f <- function(x) x
+";
+        assert_eq!(roxygen_lines(text), Some(vec!["Title".to_string()]));
+    }
+
+    #[test]
+    fn test_roxygen_block_broken_by_blank_lines() {
+        let text = "
+#' Title
+
+f <- function(x) x
+";
+        assert_eq!(roxygen_lines(text), None);
+
+        let text = "
+#' Unrelated
+
+#' Title
+f <- function(x) x
+";
+        assert_eq!(roxygen_lines(text), Some(vec!["Title".to_string()]));
+    }
+
+    #[test]
+    fn test_roxygen_block_ignores_trailing_comments() {
+        let text = "
+x <- 1 #' Not documentation
+f <- function(x) x
+";
+        assert_eq!(roxygen_lines(text), None);
+    }
+
+    #[test]
+    fn test_roxygen_block_s4() {
+        let text = "
+#' A generic
+setGeneric(\"area\", function(shape) standardGeneric(\"area\"))
+";
+        assert_eq!(roxygen_lines(text), Some(vec!["A generic".to_string()]));
+
+        let text = "
+#' A method
+setMethod(\"area\", \"Square\", function(shape) shape@side^2)
+";
+        assert_eq!(roxygen_lines(text), Some(vec!["A method".to_string()]));
+    }
+
+    #[test]
+    fn test_roxygen_block_requires_definition_statement() {
+        // The function is an argument of an unrelated call
+        let text = "
+#' Title
+lapply(xs, function(x) x)
+";
+        assert_eq!(roxygen_lines(text), None);
+    }
+
+    #[test]
+    fn test_roxygen_block_multiple_hashes() {
+        let text = "
+##' Title
+f <- function(x) x
+";
+        assert_eq!(roxygen_lines(text), Some(vec!["Title".to_string()]));
+    }
+
+    #[test]
+    fn test_roxygen_block_markdown() {
+        let text = "
+#' Title
+#'
+#' @param x A value
+#' @return The value
+#' @export
+f <- function(x) x
+";
+        let doc = Document::new(text, None);
+        let node = find_function_definition(doc.ast.root_node()).unwrap();
+        let block = node_roxygen_block(&node, &doc.contents).unwrap();
+        assert_eq!(
+            block.markdown(),
+            "Title\n\n- `x`: A value\n\n**Value**: The value\n"
+        );
+    }
+
+    // Returns the callee text, argument index, and whether it's named
+    fn call_context(text: &str) -> Option<(String, usize, bool)> {
+        let (text, point) = point_from_cursor(text);
+        let doc = Document::new(&text, None);
+        let context = call_context_at(doc.ast.root_node(), point, &doc.contents)?;
+        let callee = doc
+            .contents
+            .node_slice(&context.callee)
+            .unwrap()
+            .to_string();
+        Some((callee, context.argument_index, context.named))
+    }
+
+    fn expect_call(text: &str, callee: &str, argument_index: usize, named: bool) {
+        assert_eq!(
+            call_context(text),
+            Some((callee.to_string(), argument_index, named)),
+            "{text}"
+        );
+    }
+
+    #[test]
+    fn test_call_context_arguments() {
+        expect_call("fn(@)", "fn", 0, false);
+        expect_call("fn(x@)", "fn", 0, false);
+        expect_call("fn(x, @)", "fn", 1, false);
+        expect_call("fn(x, y = @)", "fn", 1, true);
+        expect_call("fn(x, y = 1, z@)", "fn", 2, false);
+        expect_call("pkg::fn(x, @)", "pkg::fn", 1, false);
+        expect_call("fn(\"a, b\", @)", "fn", 1, false);
+    }
+
+    #[test]
+    fn test_call_context_nested_calls() {
+        expect_call("outer(x, inner(@))", "inner", 0, false);
+        expect_call("outer(x, inner(a, b)@)", "outer", 1, false);
+        expect_call("outer(x, inner(a, b), @)", "outer", 2, false);
+        expect_call(
+            "outer(x = inner(a), y = list(1)[[1]], @)",
+            "outer",
+            2,
+            false,
+        );
+    }
+
+    #[test]
+    fn test_call_context_pipes() {
+        expect_call("x |> fn(@)", "fn", 1, false);
+        expect_call("x |> fn(a, @)", "fn", 2, false);
+        expect_call("x %>% fn(@)", "fn", 1, false);
+        expect_call("fn(x |> @)", "fn", 0, false);
+    }
+
+    #[test]
+    fn test_call_context_incomplete_code() {
+        expect_call("fn(x, @", "fn", 1, false);
+        expect_call("fn(x, y = @", "fn", 1, true);
+        expect_call("outer(inner(a, @", "inner", 1, false);
+    }
+
+    #[test]
+    fn test_call_context_outside_call() {
+        assert_eq!(call_context("x <- 1@"), None);
+        assert_eq!(call_context("fn(x)@"), None);
+        assert_eq!(call_context("@fn(x)"), None);
+        assert_eq!(call_context("function(x@) x"), None);
+        assert_eq!(call_context("if (x@) y"), None);
+        assert_eq!(call_context("fn(function() { @ })"), None);
+        assert_eq!(call_context("x[1, @]"), None);
+    }
+}
@@ -5,10 +5,16 @@
 //
 //
 
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
 use amalthea::comm::ui_comm::ExecuteCommandParams;
 use amalthea::comm::ui_comm::OpenEditorParams;
 use amalthea::comm::ui_comm::OpenWorkspaceParams;
 use amalthea::comm::ui_comm::Position;
+use amalthea::comm::ui_comm::ProgressParams;
 use amalthea::comm::ui_comm::Range;
 use amalthea::comm::ui_comm::SetEditorSelectionsParams;
 use amalthea::comm::ui_comm::ShowMessageParams;
@@ -16,10 +22,19 @@ use amalthea::comm::ui_comm::ShowUrlParams;
 use amalthea::comm::ui_comm::UiFrontendEvent;
 use harp::object::RObject;
 use libr::R_NilValue;
+use libr::Rf_ScalarLogical;
 use libr::SEXP;
+use once_cell::sync::Lazy;
 
 use crate::interface::RMain;
 
+/// Minimum delay between two updates of the same progress bar. Loops may
+/// update a bar thousands of times per second, which would flood IOPub.
+const PROGRESS_THROTTLE: Duration = Duration::from_millis(100);
+
+/// The progress bars open in the frontend
+static PROGRESS_BARS: Lazy<Mutex<ProgressBars>> = Lazy::new(|| Mutex::new(ProgressBars::default()));
+
 #[harp::register]
 pub unsafe extern "C" fn ps_ui_show_message(message: SEXP) -> anyhow::Result<SEXP> {
     let params = ShowMessageParams {
@@ -63,13 +78,18 @@ pub unsafe extern "C" fn ps_ui_open_workspace(
 #[harp::register]
 pub unsafe extern "C" fn ps_ui_navigate_to_file(
     file: SEXP,
-    _line: SEXP,
-    _column: SEXP,
+    line: SEXP,
+    column: SEXP,
 ) -> anyhow::Result<SEXP> {
+    // Negative positions (the R-side default) mean "don't move the cursor",
+    // which the frontend expects to be encoded as 0
+    let line: i32 = RObject::view(line).try_into()?;
+    let column: i32 = RObject::view(column).try_into()?;
+
     let params = OpenEditorParams {
         file: RObject::view(file).try_into()?,
-        line: 0,
-        column: 0,
+        line: line.max(0) as i64,
+        column: column.max(0) as i64,
     };
 
     let main = RMain::get();
@@ -78,6 +98,15 @@ pub unsafe extern "C" fn ps_ui_navigate_to_file(
     Ok(R_NilValue)
 }
 
+/// Is a frontend UI comm currently connected? R-side handlers use this to
+/// fall back to their default behaviour (e.g. an external editor) when
+/// nobody would receive the events.
+#[harp::register]
+pub unsafe extern "C" fn ps_ui_is_connected() -> anyhow::Result<SEXP> {
+    let connected = RMain::with(|main| main.get_kernel().lock().unwrap().ui_connected());
+    Ok(Rf_ScalarLogical(connected as i32))
+}
+
 #[harp::register]
 pub unsafe extern "C" fn ps_ui_set_selection_ranges(ranges: SEXP) -> anyhow::Result<SEXP> {
     let selections = ps_ui_robj_as_ranges(ranges)?;
@@ -102,6 +131,93 @@ pub unsafe extern "C" fn ps_ui_show_url(url: SEXP) -> anyhow::Result<SEXP> {
     Ok(R_NilValue)
 }
 
+#[harp::register]
+pub unsafe extern "C" fn ps_ui_progress(
+    id: SEXP,
+    current: SEXP,
+    total: SEXP,
+    done: SEXP,
+) -> anyhow::Result<SEXP> {
+    let params = ProgressParams {
+        id: RObject::view(id).try_into()?,
+        current: RObject::view(current).try_into()?,
+        total: RObject::view(total).try_into()?,
+        done: RObject::view(done).try_into()?,
+    };
+
+    if !PROGRESS_BARS
+        .lock()
+        .unwrap()
+        .should_update(&params, Instant::now())
+    {
+        return Ok(R_NilValue);
+    }
+
+    let main = RMain::get();
+    let event = UiFrontendEvent::Progress(params);
+    main.send_frontend_event(event);
+    Ok(R_NilValue)
+}
+
+/// Forgets the progress bars that are still open, and returns the events that
+/// close them in the frontend. Called once R is back at top level, since bars
+/// are not closed when the code updating them fails or is interrupted.
+pub fn progress_close_all() -> Vec<ProgressParams> {
+    PROGRESS_BARS.lock().unwrap().close_all()
+}
+
+/// The progress bars open in the frontend, by id
+#[derive(Default)]
+struct ProgressBars {
+    bars: HashMap<String, ProgressBar>,
+}
+
+struct ProgressBar {
+    /// When the progress bar was last reported to the frontend
+    reported: Instant,
+
+    /// The latest progress, reported or not
+    params: ProgressParams,
+}
+
+impl ProgressBars {
+    /// Throttles the updates of a progress bar. The first update, the
+    /// completion of the work, and the closing of the bar are always reported.
+    fn should_update(&mut self, params: &ProgressParams, now: Instant) -> bool {
+        if params.done {
+            self.bars.remove(&params.id);
+            return true;
+        }
+
+        if let Some(bar) = self.bars.get_mut(&params.id) {
+            let complete = params.current >= params.total;
+            if !complete && now.duration_since(bar.reported) < PROGRESS_THROTTLE {
+                bar.params = params.clone();
+                return false;
+            }
+        }
+
+        let bar = ProgressBar {
+            reported: now,
+            params: params.clone(),
+        };
+        self.bars.insert(params.id.clone(), bar);
+        true
+    }
+
+    /// Forgets the open progress bars, and returns the events that close them
+    /// with their latest progress
+    fn close_all(&mut self) -> Vec<ProgressParams> {
+        self.bars
+            .drain()
+            .map(|(_, bar)| ProgressParams {
+                done: true,
+                ..bar.params
+            })
+            .collect()
+    }
+}
+
 pub fn ps_ui_robj_as_ranges(ranges: SEXP) -> anyhow::Result<Vec<Range>> {
     let ranges_as_r_objects: Vec<RObject> = RObject::view(ranges).try_into()?;
     let ranges_as_result: Result<Vec<Vec<i32>>, _> = ranges_as_r_objects
@@ -124,3 +240,60 @@ pub fn ps_ui_robj_as_ranges(ranges: SEXP) -> anyhow::Result<Vec<Range>> {
         .collect();
     Ok(selections)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use amalthea::comm::ui_comm::ProgressParams;
+
+    use crate::ui::events::ProgressBars;
+    use crate::ui::events::PROGRESS_THROTTLE;
+
+    fn progress(current: f64, done: bool) -> ProgressParams {
+        ProgressParams {
+            id: String::from("test-progress"),
+            current,
+            total: 10.0,
+            done,
+        }
+    }
+
+    #[test]
+    fn test_progress_throttle() {
+        let mut bars = ProgressBars::default();
+        let start = Instant::now();
+        let later = start + PROGRESS_THROTTLE + Duration::from_millis(1);
+
+        // The first update goes through, quick successive ones don't
+        assert!(bars.should_update(&progress(1.0, false), start));
+        assert!(!bars.should_update(&progress(2.0, false), start));
+        assert!(bars.should_update(&progress(3.0, false), later));
+        assert!(!bars.should_update(&progress(4.0, false), later));
+
+        // Completion and closing are always reported
+        assert!(bars.should_update(&progress(10.0, false), later));
+        assert!(bars.should_update(&progress(10.0, true), later));
+
+        // A closed bar starts over
+        assert!(bars.should_update(&progress(1.0, false), later));
+        assert!(bars.should_update(&progress(1.0, true), later));
+        assert!(bars.close_all().is_empty());
+    }
+
+    #[test]
+    fn test_progress_close_all() {
+        let mut bars = ProgressBars::default();
+        let start = Instant::now();
+
+        // A bar left open, e.g. by an error, is closed with its latest
+        // progress, even if that progress was throttled
+        assert!(bars.should_update(&progress(1.0, false), start));
+        assert!(!bars.should_update(&progress(2.0, false), start));
+        assert_eq!(bars.close_all(), vec![progress(2.0, true)]);
+
+        // Closed bars are forgotten
+        assert!(bars.close_all().is_empty());
+    }
+}
@@ -5,7 +5,13 @@
 //
 //
 
+use std::sync::Mutex;
+
 use amalthea::comm::comm_channel::CommMsg;
+use amalthea::comm::ui_comm::CallMethodParams;
+use amalthea::comm::ui_comm::DidChangeThemeParams;
+use amalthea::comm::ui_comm::DidChangeThemeTheme;
+use amalthea::comm::ui_comm::SetWorkingDirectoryParams;
 use amalthea::comm::ui_comm::UiBackendReply;
 use amalthea::comm::ui_comm::UiBackendRequest;
 use amalthea::comm::ui_comm::UiFrontendEvent;
@@ -18,12 +24,19 @@ use crossbeam::select;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
 use harp::object::RObject;
+use libr::R_NilValue;
+use libr::SEXP;
 use serde_json::Value;
 use stdext::spawn;
 use stdext::unwrap;
 
+use crate::interface::RMain;
 use crate::r_task;
 
+/// The color theme of the frontend, as last reported with a `did_change_theme`
+/// notification. `None` until the frontend reports one.
+static COLOR_THEME: Mutex<Option<DidChangeThemeTheme>> = Mutex::new(None);
+
 #[derive(Debug)]
 pub enum UiCommMessage {
     Event(UiFrontendEvent),
@@ -138,10 +151,19 @@ impl UiComm {
         &self,
         request: UiBackendRequest,
     ) -> anyhow::Result<UiBackendReply, anyhow::Error> {
-        let request = match request {
-            UiBackendRequest::CallMethod(request) => request,
-        };
+        match request {
+            UiBackendRequest::CallMethod(request) => self.handle_call_method(request),
+            UiBackendRequest::SetWorkingDirectory(params) => {
+                self.handle_set_working_directory(params)
+            },
+            UiBackendRequest::DidChangeTheme(params) => self.handle_did_change_theme(params),
+        }
+    }
 
+    fn handle_call_method(
+        &self,
+        request: CallMethodParams,
+    ) -> anyhow::Result<UiBackendReply, anyhow::Error> {
         log::trace!("Handling '{}' frontend RPC method", request.method);
 
         // Today, all RPCs are fulfilled by R directly. Check to see if an R
@@ -180,6 +202,57 @@ impl UiComm {
         Ok(UiBackendReply::CallMethodReply(result))
     }
 
+    fn handle_set_working_directory(
+        &self,
+        params: SetWorkingDirectoryParams,
+    ) -> anyhow::Result<UiBackendReply, anyhow::Error> {
+        log::trace!("Setting working directory to '{}'", params.directory);
+
+        let directory = r_task(|| -> anyhow::Result<String> {
+            // Go through `setwd()` for tilde expansion and R-level errors
+            RFunction::new("base", "setwd")
+                .add(params.directory.clone())
+                .call()?;
+
+            let directory = RFunction::new("base", "normalizePath")
+                .add(RFunction::new("base", "getwd").call()?)
+                .param("winslash", "/")
+                .call()?;
+
+            // The frontend learns about the new directory from the reply.
+            // Don't notify it again with a `WorkingDirectory` event.
+            if RMain::initialized() {
+                let current_dir = std::env::current_dir()?;
+                let kernel = RMain::get().get_kernel();
+                kernel.lock().unwrap().sync_working_directory(current_dir);
+            }
+
+            Ok(String::try_from(directory)?)
+        })?;
+
+        Ok(UiBackendReply::SetWorkingDirectoryReply(directory))
+    }
+
+    fn handle_did_change_theme(
+        &self,
+        params: DidChangeThemeParams,
+    ) -> anyhow::Result<UiBackendReply, anyhow::Error> {
+        log::trace!("Frontend switched to the {:?} color theme", params.theme);
+
+        *COLOR_THEME.lock().unwrap() = Some(params.theme.clone());
+
+        // Expose the theme as an option so that packages rendering HTML or
+        // plots can pick a matching background
+        r_task(|| -> anyhow::Result<()> {
+            RFunction::new("base", "options")
+                .param("ark.theme", color_theme_name(&params.theme))
+                .call()?;
+            Ok(())
+        })?;
+
+        Ok(UiBackendReply::DidChangeThemeReply())
+    }
+
     /**
      * Send an RPC request to the frontend.
      */
@@ -190,3 +263,21 @@ impl UiComm {
         Ok(())
     }
 }
+
+fn color_theme_name(theme: &DidChangeThemeTheme) -> &'static str {
+    match theme {
+        DidChangeThemeTheme::Light => "light",
+        DidChangeThemeTheme::Dark => "dark",
+    }
+}
+
+/// Returns the color theme of the frontend, `"light"` or `"dark"`, or `NULL`
+/// if the frontend hasn't reported one yet.
+#[harp::register]
+pub unsafe extern "C" fn ps_ui_theme() -> anyhow::Result<SEXP> {
+    let theme = COLOR_THEME.lock().unwrap();
+    match theme.as_ref() {
+        Some(theme) => Ok(RObject::from(color_theme_name(theme)).sexp),
+        None => Ok(R_NilValue),
+    }
+}
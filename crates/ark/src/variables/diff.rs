@@ -0,0 +1,248 @@
+//
+// diff.rs
+//
+// Copyright (C) 2024 by Posit Software, PBC
+//
+//
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hash;
+use std::hash::Hasher;
+
+use harp::environment::Binding;
+use harp::environment::BindingValue;
+use harp::object::r_length;
+use harp::utils::r_classes;
+use harp::utils::r_is_altrep;
+use harp::utils::r_typeof;
+use libr::*;
+
+/// Vectors up to this length have their contents hashed in their fingerprint
+const HASH_MAX_LENGTH: isize = 1000;
+
+/// A cheap summary of a value. Values modified in place keep their address,
+/// so comparing bindings by address doesn't detect them, e.g. after
+/// `data.table::set()` or a `:=` assignment. Comparing fingerprints does,
+/// without deep-comparing potentially large objects.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fingerprint {
+    length: isize,
+    class: Option<Vec<String>>,
+    hash: Option<u64>,
+}
+
+impl Fingerprint {
+    /// Must be called on the R thread
+    pub fn new(x: SEXP) -> Self {
+        let class = r_classes(x).map(|classes| classes.iter().flatten().collect());
+
+        Self {
+            length: r_length(x),
+            class,
+            hash: hash(x),
+        }
+    }
+}
+
+/// Hashes the contents of small vectors. ALTREP vectors are not hashed since
+/// that would materialize them. Strings and list elements are hashed by
+/// address, which is enough to detect replaced elements as `CHARSXP`s are
+/// cached by R.
+fn hash(x: SEXP) -> Option<u64> {
+    if r_is_altrep(x) {
+        return None;
+    }
+
+    let n = r_length(x);
+    if n > HASH_MAX_LENGTH {
+        return None;
+    }
+
+    let mut hasher = DefaultHasher::new();
+    let len = n as usize;
+
+    unsafe {
+        match r_typeof(x) {
+            LGLSXP => std::slice::from_raw_parts(LOGICAL(x), len).hash(&mut hasher),
+            INTSXP => std::slice::from_raw_parts(INTEGER(x), len).hash(&mut hasher),
+            RAWSXP => std::slice::from_raw_parts(RAW(x), len).hash(&mut hasher),
+            REALSXP => {
+                for value in std::slice::from_raw_parts(REAL(x), len) {
+                    value.to_bits().hash(&mut hasher);
+                }
+            },
+            STRSXP => {
+                for i in 0..n {
+                    (STRING_ELT(x, i) as usize).hash(&mut hasher);
+                }
+            },
+            VECSXP => {
+                for i in 0..n {
+                    (VECTOR_ELT(x, i) as usize).hash(&mut hasher);
+                }
+            },
+            _ => return None,
+        }
+    }
+
+    Some(hasher.finish())
+}
+
+/// A binding of a snapshot of the variables sent to the frontend
+pub struct SnapshotBinding {
+    pub binding: Binding,
+
+    /// Promises and active bindings are not fingerprinted because that would
+    /// require evaluating them
+    pub fingerprint: Option<Fingerprint>,
+}
+
+impl SnapshotBinding {
+    /// Must be called on the R thread
+    pub fn new(binding: Binding) -> Self {
+        let fingerprint = match &binding.value {
+            BindingValue::Standard { object, .. } | BindingValue::Altrep { object, .. } => {
+                Some(Fingerprint::new(object.sexp))
+            },
+            BindingValue::Promise { .. } | BindingValue::Active { .. } => None,
+        };
+
+        Self {
+            binding,
+            fingerprint,
+        }
+    }
+
+    fn changed(&self, other: &Self) -> bool {
+        self.binding.value != other.binding.value || self.fingerprint != other.fingerprint
+    }
+}
+
+/// The differences between two snapshots of variables
+#[derive(Debug, Default)]
+pub struct VariablesDiff<'a> {
+    /// Bindings of the new snapshot not in the old one
+    pub added: Vec<&'a Binding>,
+
+    /// Bindings of the new snapshot whose value differs from the old one
+    pub changed: Vec<&'a Binding>,
+
+    /// Names of the bindings of the old snapshot not in the new one
+    pub removed: Vec<String>,
+}
+
+impl<'a> VariablesDiff<'a> {
+    /// Diffs two snapshots, both sorted by name
+    pub fn new(old: &[SnapshotBinding], new: &'a [SnapshotBinding]) -> Self {
+        let mut diff = Self::default();
+
+        let mut old_iter = old.iter().peekable();
+        let mut new_iter = new.iter().peekable();
+
+        loop {
+            match (old_iter.peek(), new_iter.peek()) {
+                (None, None) => break,
+                (None, Some(new)) => {
+                    diff.added.push(&new.binding);
+                    new_iter.next();
+                },
+                (Some(old), None) => {
+                    diff.removed.push(old.binding.name.to_string());
+                    old_iter.next();
+                },
+                (Some(old), Some(new)) => {
+                    if old.binding.name == new.binding.name {
+                        if old.changed(new) {
+                            diff.changed.push(&new.binding);
+                        }
+                        old_iter.next();
+                        new_iter.next();
+                    } else if old.binding.name < new.binding.name {
+                        diff.removed.push(old.binding.name.to_string());
+                        old_iter.next();
+                    } else {
+                        diff.added.push(&new.binding);
+                        new_iter.next();
+                    }
+                },
+            }
+        }
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use harp::environment::Binding;
+    use harp::environment::Environment;
+    use harp::environment::R_ENVS;
+    use harp::eval::r_parse_eval0;
+    use harp::object::RObject;
+
+    use crate::test::r_test;
+    use crate::variables::diff::SnapshotBinding;
+    use crate::variables::diff::VariablesDiff;
+
+    fn snapshot(env: &RObject) -> Vec<SnapshotBinding> {
+        let mut bindings: Vec<SnapshotBinding> = Environment::new(env.clone())
+            .iter()
+            .filter_map(|b| b.ok())
+            .map(SnapshotBinding::new)
+            .collect();
+        bindings.sort_by(|a, b| a.binding.name.cmp(&b.binding.name));
+        bindings
+    }
+
+    fn assert_diff(diff: &VariablesDiff, added: &[&str], changed: &[&str], removed: &[&str]) {
+        let names = |bindings: &Vec<&Binding>| -> Vec<String> {
+            bindings.iter().map(|b| b.name.to_string()).collect()
+        };
+        assert_eq!(names(&diff.added), added);
+        assert_eq!(names(&diff.changed), changed);
+        assert_eq!(diff.removed, removed);
+    }
+
+    #[test]
+    fn test_variables_diff() {
+        r_test(|| {
+            let env = r_parse_eval0(
+                "local({
+                    x <- c(1, 2, 3)
+                    y <- 'a'
+                    z <- list(1)
+                    environment()
+                })",
+                R_ENVS.global,
+            )
+            .unwrap();
+            let eval = |code: &str| {
+                r_parse_eval0(code, env.clone()).unwrap();
+            };
+
+            let old = snapshot(&env);
+            let new = snapshot(&env);
+            assert!(VariablesDiff::new(&old, &new).is_empty());
+
+            // Add a variable, mutate another, and remove a third one
+            eval("w <- 1L; x[1] <- 10; rm(y)");
+            let new = snapshot(&env);
+            assert_diff(&VariablesDiff::new(&old, &new), &["w"], &["x"], &["y"]);
+
+            // Changes of class and of list elements are detected too
+            let old = new;
+            eval("class(z) <- 'foo'");
+            let new = snapshot(&env);
+            assert_diff(&VariablesDiff::new(&old, &new), &[], &["z"], &[]);
+
+            let old = new;
+            eval("z[[1]] <- 2");
+            let new = snapshot(&env);
+            assert_diff(&VariablesDiff::new(&old, &new), &[], &["z"], &[]);
+        })
+    }
+}
@@ -5,5 +5,7 @@
 //
 //
 
+pub mod diff;
 pub mod r_variables;
 pub mod variable;
+pub mod watch;
@@ -5,15 +5,19 @@
 //
 //
 
+use std::sync::Arc;
+
 use amalthea::comm::comm_channel::CommMsg;
 use amalthea::comm::event::CommManagerEvent;
 use amalthea::comm::variables_comm::ClipboardFormatFormat;
+use amalthea::comm::variables_comm::FormatVariableFormat;
 use amalthea::comm::variables_comm::FormattedVariable;
 use amalthea::comm::variables_comm::InspectedVariable;
 use amalthea::comm::variables_comm::RefreshParams;
 use amalthea::comm::variables_comm::UpdateParams;
 use amalthea::comm::variables_comm::Variable;
 use amalthea::comm::variables_comm::VariableList;
+use amalthea::comm::variables_comm::VariableSearch;
 use amalthea::comm::variables_comm::VariablesBackendReply;
 use amalthea::comm::variables_comm::VariablesBackendRequest;
 use amalthea::comm::variables_comm::VariablesFrontendEvent;
@@ -22,17 +26,22 @@ use crossbeam::channel::select;
 use crossbeam::channel::unbounded;
 use crossbeam::channel::Sender;
 use harp::environment::Binding;
+use harp::environment::BindingValue;
 use harp::environment::Environment;
 use harp::environment::EnvironmentFilter;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
 use harp::object::RObject;
 use harp::utils::r_assert_type;
+use harp::utils::r_inherits;
+use harp::utils::r_is_unbound;
 use harp::vector::CharacterVector;
 use harp::vector::Vector;
 use libr::R_GlobalEnv;
 use libr::Rf_ScalarLogical;
 use libr::ENVSXP;
+use libr::PRVALUE;
+use libr::SEXP;
 use log::debug;
 use log::error;
 use log::warn;
@@ -43,7 +52,11 @@ use crate::data_explorer::r_data_explorer::RDataExplorer;
 use crate::lsp::events::EVENTS;
 use crate::r_task;
 use crate::thread::RThreadSafe;
+use crate::variables::diff::SnapshotBinding;
+use crate::variables::diff::VariablesDiff;
 use crate::variables::variable::PositronVariable;
+use crate::variables::watch::schedule_watches;
+use crate::variables::watch::VariableWatches;
 
 /**
  * The R Variables handler provides the server side of Positron's Variables panel, and is
@@ -53,6 +66,9 @@ pub struct RVariables {
     comm: CommSocket,
     comm_manager_tx: Sender<CommManagerEvent>,
     pub env: RThreadSafe<RObject>,
+    /// The last snapshot of the bindings sent to the frontend, which updates
+    /// are diffed against.
+    ///
     /// `Binding` does not currently protect anything, and therefore doesn't
     /// implement `Drop`, which might use the R API. It assumes that R SYMSXPs
     /// protect themselves, and that the binding value is protected by the
@@ -67,8 +83,15 @@ pub struct RVariables {
     /// that the thread is then holding onto dangling pointers. For safety we
     /// should probably store the bindings in a list owned by the environment
     /// thread. Tracked in https://github.com/posit-dev/positron/issues/1812
-    current_bindings: RThreadSafe<Vec<Binding>>,
+    current_bindings: RThreadSafe<Vec<SnapshotBinding>>,
     version: u64,
+
+    /// Filter set by the last `search` request, and cleared by `list`
+    /// requests. Updates only include the matching variables.
+    search: Option<VariableSearch>,
+
+    /// Expressions watched by the frontend, evaluated when R is idle
+    watches: Arc<VariableWatches>,
 }
 
 impl RVariables {
@@ -90,6 +113,7 @@ impl RVariables {
         // To be able to `Send` the `env` to the thread, it needs to be made
         // thread safe. To create `current_bindings`, we need to be on the main
         // R thread.
+        let watches = Arc::new(VariableWatches::new(env.clone()));
         let env = RThreadSafe::new(env);
         let current_bindings = RThreadSafe::new(vec![]);
 
@@ -103,6 +127,8 @@ impl RVariables {
                 env,
                 current_bindings,
                 version: 0,
+                search: None,
+                watches,
             };
             environment.execution_thread();
         });
@@ -180,6 +206,9 @@ impl RVariables {
 
         EVENTS.console_prompt.remove(listen_id);
 
+        // Stop evaluating the watches at idle time
+        self.watches.clear();
+
         if !user_initiated_close {
             // Send a close message to the frontend if the frontend didn't
             // initiate the close
@@ -187,7 +216,7 @@ impl RVariables {
         }
     }
 
-    fn update_bindings(&mut self, new_bindings: RThreadSafe<Vec<Binding>>) -> u64 {
+    fn update_bindings(&mut self, new_bindings: RThreadSafe<Vec<SnapshotBinding>>) -> u64 {
         // Updating will `drop()` the old `current_bindings` on the main R thread
         self.current_bindings = new_bindings;
         self.version = self.version + 1;
@@ -201,8 +230,8 @@ impl RVariables {
         r_task(|| {
             self.update_bindings(self.bindings());
 
-            for binding in self.current_bindings.get() {
-                variables.push(PositronVariable::new(binding).var());
+            for snapshot in self.current_bindings.get() {
+                variables.push(PositronVariable::new(&snapshot.binding).var());
             }
         });
 
@@ -215,6 +244,7 @@ impl RVariables {
     ) -> anyhow::Result<VariablesBackendReply> {
         match req {
             VariablesBackendRequest::List => {
+                self.search = None;
                 let list = self.list_variables();
                 let count = list.len() as i64;
                 Ok(VariablesBackendReply::ListReply(VariableList {
@@ -223,6 +253,16 @@ impl RVariables {
                     version: Some(self.version as i64),
                 }))
             },
+            VariablesBackendRequest::Search(params) => {
+                self.search = Some(params.filter);
+                let list = self.list_variables();
+                let count = list.len() as i64;
+                Ok(VariablesBackendReply::SearchReply(VariableList {
+                    variables: list,
+                    length: count,
+                    version: Some(self.version as i64),
+                }))
+            },
             VariablesBackendRequest::Clear(params) => {
                 self.clear(params.include_hidden_objects)?;
                 self.update(None);
@@ -243,13 +283,29 @@ impl RVariables {
             VariablesBackendRequest::ClipboardFormat(params) => {
                 let content = self.clipboard_format(&params.path, params.format.clone())?;
                 Ok(VariablesBackendReply::ClipboardFormatReply(
-                    FormattedVariable { content },
+                    FormattedVariable {
+                        content,
+                        is_truncated: None,
+                    },
                 ))
             },
+            VariablesBackendRequest::FormatVariable(params) => {
+                let formatted = self.format_variable(&params.path, params.format.clone())?;
+                Ok(VariablesBackendReply::FormatVariableReply(formatted))
+            },
             VariablesBackendRequest::View(params) => {
                 let viewer_id = self.view(&params.path)?;
                 Ok(VariablesBackendReply::ViewReply(viewer_id))
             },
+            VariablesBackendRequest::WatchExpression(params) => {
+                let watch = r_task(|| self.watches.add(params.expression))?;
+                schedule_watches(self.watches.clone(), self.comm.outgoing_tx.clone());
+                Ok(VariablesBackendReply::WatchExpressionReply(watch))
+            },
+            VariablesBackendRequest::UnwatchExpression(params) => {
+                self.watches.remove(&params.id)?;
+                Ok(VariablesBackendReply::UnwatchExpressionReply())
+            },
         }
     }
 
@@ -313,6 +369,17 @@ impl RVariables {
         })
     }
 
+    fn format_variable(
+        &mut self,
+        path: &Vec<String>,
+        format: FormatVariableFormat,
+    ) -> Result<FormattedVariable, harp::error::Error> {
+        r_task(|| {
+            let env = self.env.get().clone();
+            PositronVariable::format(env, &path, &format)
+        })
+    }
+
     fn inspect(&mut self, path: &Vec<String>) -> Result<Vec<Variable>, harp::error::Error> {
         r_task(|| {
             let env = self.env.get().clone();
@@ -370,70 +437,20 @@ impl RVariables {
         r_task(|| {
             let new_bindings = self.bindings();
 
-            let mut old_iter = self.current_bindings.get().iter();
-            let mut old_next = old_iter.next();
-
-            let mut new_iter = new_bindings.get().iter();
-            let mut new_next = new_iter.next();
-
-            loop {
-                match (old_next, new_next) {
-                    // nothing more to do
-                    (None, None) => break,
-
-                    // No more old, collect last new into added
-                    (None, Some(mut new)) => {
-                        loop {
-                            assigned.push(PositronVariable::new(&new).var());
-
-                            match new_iter.next() {
-                                Some(x) => {
-                                    new = x;
-                                },
-                                None => break,
-                            };
-                        }
-                        break;
-                    },
-
-                    // No more new, collect the last old into removed
-                    (Some(mut old), None) => {
-                        loop {
-                            removed.push(old.name.to_string());
-
-                            match old_iter.next() {
-                                Some(x) => {
-                                    old = x;
-                                },
-                                None => break,
-                            };
-                        }
-
-                        break;
-                    },
+            // Only send the variables that were added or changed since the
+            // last snapshot, and the names of the removed ones
+            let diff = VariablesDiff::new(self.current_bindings.get(), new_bindings.get());
+            if diff.is_empty() {
+                return;
+            }
 
-                    (Some(old), Some(new)) => {
-                        if old.name == new.name {
-                            if old.value != new.value {
-                                assigned.push(PositronVariable::new(&new).var());
-                            }
-                            old_next = old_iter.next();
-                            new_next = new_iter.next();
-                        } else if old.name < new.name {
-                            removed.push(old.name.to_string());
-                            old_next = old_iter.next();
-                        } else {
-                            assigned.push(PositronVariable::new(&new).var());
-                            new_next = new_iter.next();
-                        }
-                    },
-                }
+            for binding in diff.added.iter().chain(diff.changed.iter()) {
+                assigned.push(PositronVariable::new(binding).var());
             }
+            removed = diff.removed;
 
             // Only update the bindings (and the version) if anything changed
-            if assigned.len() > 0 || removed.len() > 0 {
-                self.update_bindings(new_bindings);
-            }
+            self.update_bindings(new_bindings);
         });
 
         if assigned.len() > 0 || removed.len() > 0 || request_id.is_some() {
@@ -450,14 +467,181 @@ impl RVariables {
 
     // SAFETY: The following methods must be called in an `r_task()`
 
-    fn bindings(&self) -> RThreadSafe<Vec<Binding>> {
+    fn bindings(&self) -> RThreadSafe<Vec<SnapshotBinding>> {
         let env = self.env.get().clone();
         let env = Environment::new_filtered(env, EnvironmentFilter::ExcludeHidden);
 
-        let mut bindings: Vec<Binding> = env.iter().filter_map(|b| b.ok()).collect();
+        let mut bindings: Vec<Binding> = env
+            .iter()
+            .filter_map(|b| b.ok())
+            .filter(|b| match &self.search {
+                Some(search) => binding_matches(b, search),
+                None => true,
+            })
+            .collect();
 
         bindings.sort_by(|a, b| a.name.cmp(&b.name));
 
+        let bindings = bindings.into_iter().map(SnapshotBinding::new).collect();
         RThreadSafe::new(bindings)
     }
 }
+
+/// Does `binding` match the name and class criteria of `search`? Promises
+/// and active bindings are never evaluated, so an unforced promise or an
+/// active binding doesn't match a class criterion.
+fn binding_matches(binding: &Binding, search: &VariableSearch) -> bool {
+    if let Some(name) = &search.name {
+        let binding_name = binding.name.to_string();
+        let matches = if search.case_sensitive.unwrap_or(false) {
+            binding_name.contains(name.as_str())
+        } else {
+            binding_name
+                .to_lowercase()
+                .contains(name.to_lowercase().as_str())
+        };
+        if !matches {
+            return false;
+        }
+    }
+
+    if let Some(class) = &search.class {
+        let value = match &binding.value {
+            BindingValue::Standard { object, .. } | BindingValue::Altrep { object, .. } => {
+                object.sexp
+            },
+            BindingValue::Promise { promise } => {
+                let value = unsafe { PRVALUE(promise.sexp) };
+                if r_is_unbound(value) {
+                    return false;
+                }
+                value
+            },
+            BindingValue::Active { .. } => return false,
+        };
+        if !value_inherits(value, class) {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Like `inherits()`, including the implicit class of objects without a
+/// class attribute, e.g. `"numeric"`, `"function"`, or `"matrix"`
+fn value_inherits(value: SEXP, class: &str) -> bool {
+    // Fast path for objects with a class attribute
+    if r_inherits(value, class) {
+        return true;
+    }
+
+    let inherits = RFunction::new("base", "inherits")
+        .add(RObject::view(value))
+        .add(class)
+        .call()
+        .and_then(bool::try_from);
+
+    match inherits {
+        Ok(inherits) => inherits,
+        Err(err) => {
+            warn!("Can't check the class of a variable: {err:?}");
+            false
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amalthea::comm::variables_comm::VariableSearch;
+    use harp::environment::Environment;
+    use harp::environment::R_ENVS;
+    use harp::eval::r_parse_eval0;
+
+    use crate::test::r_test;
+    use crate::variables::r_variables::binding_matches;
+
+    fn matching_names(search: VariableSearch) -> Vec<String> {
+        let env = r_parse_eval0(
+            "local({
+                df_one <- data.frame(x = 1)
+                DF_two <- data.frame(y = 2)
+                dfn <- function() NULL
+                num <- 1.5
+                mat <- matrix(1:4, 2)
+                delayedAssign('df_lazy', stop('must not be forced'))
+                makeActiveBinding('df_active', function() stop('must not be called'), environment())
+                environment()
+            })",
+            R_ENVS.global,
+        )
+        .unwrap();
+
+        let mut names: Vec<String> = Environment::new(env)
+            .iter()
+            .filter_map(|b| b.ok())
+            .filter(|b| binding_matches(b, &search))
+            .map(|b| b.name.to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_binding_matches_name() {
+        r_test(|| {
+            let search = VariableSearch {
+                name: Some(String::from("df_")),
+                class: None,
+                case_sensitive: None,
+            };
+            assert_eq!(
+                matching_names(search),
+                vec!["DF_two", "df_active", "df_lazy", "df_one"]
+            );
+
+            let search = VariableSearch {
+                name: Some(String::from("df_")),
+                class: None,
+                case_sensitive: Some(true),
+            };
+            assert_eq!(
+                matching_names(search),
+                vec!["df_active", "df_lazy", "df_one"]
+            );
+        })
+    }
+
+    #[test]
+    fn test_binding_matches_class() {
+        r_test(|| {
+            // Promises and active bindings are left alone
+            let search = VariableSearch {
+                name: None,
+                class: Some(String::from("data.frame")),
+                case_sensitive: None,
+            };
+            assert_eq!(matching_names(search), vec!["DF_two", "df_one"]);
+
+            let search = VariableSearch {
+                name: Some(String::from("two")),
+                class: Some(String::from("data.frame")),
+                case_sensitive: None,
+            };
+            assert_eq!(matching_names(search), vec!["DF_two"]);
+        })
+    }
+
+    #[test]
+    fn test_binding_matches_implicit_class() {
+        r_test(|| {
+            let search = |class: &str| VariableSearch {
+                name: None,
+                class: Some(String::from(class)),
+                case_sensitive: None,
+            };
+            assert_eq!(matching_names(search("numeric")), vec!["num"]);
+            assert_eq!(matching_names(search("function")), vec!["dfn"]);
+            assert_eq!(matching_names(search("matrix")), vec!["mat"]);
+        })
+    }
+}
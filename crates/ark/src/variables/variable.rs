@@ -9,6 +9,8 @@ use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
 
 use amalthea::comm::variables_comm::ClipboardFormatFormat;
+use amalthea::comm::variables_comm::FormatVariableFormat;
+use amalthea::comm::variables_comm::FormattedVariable;
 use amalthea::comm::variables_comm::Variable;
 use amalthea::comm::variables_comm::VariableKind;
 use anyhow::anyhow;
@@ -55,6 +57,12 @@ use stdext::unwrap;
 const MAX_DISPLAY_VALUE_ENTRIES: usize = 1_000;
 const MAX_DISPLAY_VALUE_LENGTH: usize = 100;
 
+// Limits for `format_variable`. Data frames and matrices are cut to a number
+// of rows, and vectors to a number of elements, before being formatted. The
+// formatted output is then cut to a number of bytes, at a line boundary.
+const MAX_FORMAT_ENTRIES: i32 = 1_000;
+const MAX_FORMAT_LENGTH: usize = 100_000;
+
 pub struct WorkspaceVariableDisplayValue {
     pub display_value: String,
     pub is_truncated: bool,
@@ -821,6 +829,56 @@ impl PositronVariable {
         }
     }
 
+    /// Format the variable at `path` as JSON, CSV, or R code. Large objects
+    /// are truncated, which is reported with `is_truncated` so that the
+    /// content itself is not altered by a note.
+    pub fn format(
+        env: RObject,
+        path: &Vec<String>,
+        format: &FormatVariableFormat,
+    ) -> Result<FormattedVariable, harp::error::Error> {
+        let object = Self::resolve_data_object(env, path)?;
+
+        // The same object is returned if it was small enough
+        let head = RFunction::from(".ps.environment.formatHead")
+            .add(object.clone())
+            .add(MAX_FORMAT_ENTRIES)
+            .call()?;
+        let mut truncated = head.sexp != object.sexp;
+
+        let mut content = match format {
+            FormatVariableFormat::Json => {
+                let value = serde_json::Value::try_from(head)?;
+                serde_json::to_string_pretty(&value)
+                    .map_err(|err| Error::Anyhow(anyhow!("Can't serialize to JSON: {err}")))?
+            },
+            FormatVariableFormat::Csv => {
+                let lines: Vec<String> = RFunction::from(".ps.environment.formatCsv")
+                    .add(head)
+                    .call()?
+                    .try_into()?;
+                lines.join("\n")
+            },
+            FormatVariableFormat::R => {
+                let lines: Vec<String> = RFunction::new("base", "deparse")
+                    .add(head)
+                    .call()?
+                    .try_into()?;
+                lines.join("\n")
+            },
+        };
+
+        if content.len() > MAX_FORMAT_LENGTH {
+            content.truncate(truncation_boundary(&content, MAX_FORMAT_LENGTH));
+            truncated = true;
+        }
+
+        Ok(FormattedVariable {
+            content,
+            is_truncated: Some(truncated),
+        })
+    }
+
     pub fn resolve_data_object(
         env: RObject,
         path: &Vec<String>,
@@ -1271,3 +1329,106 @@ pub fn plain_binding_force_with_rollback(binding: &Binding) -> anyhow::Result<RO
         _ => Err(anyhow!("Unexpected binding type")),
     }
 }
+
+// Returns the end of the last complete line within `max` bytes of
+// `content`, or the last character boundary if the first line is longer
+fn truncation_boundary(content: &str, max: usize) -> usize {
+    let mut end = max;
+    while !content.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    match content[..end].rfind('\n') {
+        Some(newline) => newline,
+        None => end,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amalthea::comm::variables_comm::FormatVariableFormat;
+    use harp::environment::R_ENVS;
+    use harp::eval::r_parse_eval0;
+
+    use crate::test::r_test;
+    use crate::variables::variable::truncation_boundary;
+    use crate::variables::variable::PositronVariable;
+
+    fn format(code: &str, path: &[&str], format: FormatVariableFormat) -> (String, bool) {
+        let env = r_parse_eval0(
+            &format!("local({{ {code}; environment() }})"),
+            R_ENVS.global,
+        )
+        .unwrap();
+        let path: Vec<String> = path.iter().map(|x| x.to_string()).collect();
+        let formatted = PositronVariable::format(env, &path, &format).unwrap();
+        (formatted.content, formatted.is_truncated.unwrap())
+    }
+
+    #[test]
+    fn test_format_variable() {
+        r_test(|| {
+            let (out, truncated) = format(
+                "x <- list(a = 1L, b = 'b')",
+                &["x"],
+                FormatVariableFormat::Json,
+            );
+            let value: serde_json::Value = serde_json::from_str(&out).unwrap();
+            assert_eq!(value, serde_json::json!({ "a": 1, "b": "b" }));
+            assert!(!truncated);
+
+            // Nested paths are resolved like for inspection
+            let (out, _) = format("x <- list(a = 1:3)", &["x", "0"], FormatVariableFormat::R);
+            assert_eq!(out, "1:3");
+
+            let (out, _) = format(
+                "x <- data.frame(a = 1:2, b = c('x', 'y'))",
+                &["x"],
+                FormatVariableFormat::Csv,
+            );
+            assert_eq!(out, "\"a\",\"b\"\n1,\"x\"\n2,\"y\"");
+        })
+    }
+
+    #[test]
+    fn test_format_variable_errors() {
+        r_test(|| {
+            let env = r_parse_eval0("local({ x <- 1; environment() })", R_ENVS.global).unwrap();
+            let path = vec![String::from("x")];
+            assert!(PositronVariable::format(env, &path, &FormatVariableFormat::Csv).is_err());
+        })
+    }
+
+    #[test]
+    fn test_format_variable_truncated() {
+        r_test(|| {
+            // The head of the vector is still valid JSON
+            let (out, truncated) = format("x <- seq_len(1e5)", &["x"], FormatVariableFormat::Json);
+            let value: serde_json::Value = serde_json::from_str(&out).unwrap();
+            assert_eq!(value.as_array().unwrap().len(), 1_000);
+            assert!(truncated);
+
+            let (out, truncated) = format(
+                "x <- data.frame(a = seq_len(1e5))",
+                &["x"],
+                FormatVariableFormat::Csv,
+            );
+            assert_eq!(out.lines().count(), 1_000 + 1);
+            assert!(truncated);
+
+            let (out, truncated) = format("x <- strrep('a', 1e6)", &["x"], FormatVariableFormat::R);
+            assert!(out.len() <= 100_000);
+            assert!(truncated);
+
+            let (_, truncated) = format("x <- 1:10", &["x"], FormatVariableFormat::R);
+            assert!(!truncated);
+        })
+    }
+
+    #[test]
+    fn test_truncation_boundary() {
+        assert_eq!(truncation_boundary("ab\ncd\nef", 7), 5);
+        assert_eq!(truncation_boundary("abcdef", 4), 4);
+        assert_eq!(truncation_boundary("aé", 2), 1);
+    }
+}
@@ -0,0 +1,246 @@
+//
+// watch.rs
+//
+// Copyright (C) 2024 by Posit Software, PBC
+//
+//
+
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use amalthea::comm::comm_channel::CommMsg;
+use amalthea::comm::variables_comm::VariablesFrontendEvent;
+use amalthea::comm::variables_comm::WatchUpdateParams;
+use amalthea::comm::variables_comm::WatchedExpression;
+use anyhow::bail;
+use crossbeam::channel::Sender;
+use harp::eval::r_parse_eval0;
+use harp::object::RObject;
+use uuid::Uuid;
+
+use crate::interface::on_idle;
+use crate::thread::RThreadSafe;
+use crate::variables::variable::WorkspaceVariableDisplayType;
+use crate::variables::variable::WorkspaceVariableDisplayValue;
+
+/// The maximum number of expressions that can be watched at once.
+pub const MAX_WATCHES: usize = 20;
+
+/// Expressions watched by the Variables pane, e.g. `nrow(df)`.
+///
+/// Watches are evaluated each time R becomes idle at top level, and the
+/// frontend is only notified of the values that changed since the last
+/// evaluation. Shared between the variables thread, which adds and removes
+/// watches, and the idle callback, which evaluates them.
+pub struct VariableWatches {
+    /// The environment the expressions are evaluated in
+    env: RThreadSafe<RObject>,
+
+    state: Mutex<WatchState>,
+}
+
+#[derive(Default)]
+struct WatchState {
+    /// The active watches, with the last value sent to the frontend
+    watches: Vec<WatchedExpression>,
+
+    /// Whether an idle callback is currently registered to evaluate the
+    /// watches
+    scheduled: bool,
+}
+
+impl VariableWatches {
+    /// Must be called on the R thread.
+    pub fn new(env: RObject) -> Self {
+        Self {
+            env: RThreadSafe::new(env),
+            state: Mutex::new(WatchState::default()),
+        }
+    }
+
+    /// Adds a watch for `expression` and returns its current value.
+    ///
+    /// Must be called on the R thread.
+    pub fn add(&self, expression: String) -> anyhow::Result<WatchedExpression> {
+        if self.len() >= MAX_WATCHES {
+            bail!("Can't watch more than {MAX_WATCHES} expressions at once");
+        }
+
+        let id = Uuid::new_v4().to_string();
+        let watch = self.evaluate_expression(id, expression);
+
+        self.state.lock().unwrap().watches.push(watch.clone());
+        Ok(watch)
+    }
+
+    /// Removes the watch with identifier `id`.
+    pub fn remove(&self, id: &str) -> anyhow::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        let Some(index) = state.watches.iter().position(|watch| watch.id == id) else {
+            bail!("No such watch: {id}");
+        };
+        state.watches.remove(index);
+
+        Ok(())
+    }
+
+    /// Removes all watches. Stops the idle evaluations.
+    pub fn clear(&self) {
+        self.state.lock().unwrap().watches.clear();
+    }
+
+    fn len(&self) -> usize {
+        self.state.lock().unwrap().watches.len()
+    }
+
+    /// Evaluates all watches and returns the ones whose value changed.
+    ///
+    /// Must be called on the R thread. The lock isn't held while evaluating,
+    /// since the expressions may run R code that in turn runs R tasks.
+    pub fn evaluate(&self) -> Vec<WatchedExpression> {
+        let watches = self.state.lock().unwrap().watches.clone();
+
+        let mut changed = vec![];
+
+        for old in watches {
+            let new = self.evaluate_expression(old.id.clone(), old.expression.clone());
+            if new == old {
+                continue;
+            }
+
+            // The watch may have been removed in the meantime
+            let mut state = self.state.lock().unwrap();
+            if let Some(watch) = state.watches.iter_mut().find(|watch| watch.id == new.id) {
+                *watch = new.clone();
+                changed.push(new);
+            }
+        }
+
+        changed
+    }
+
+    fn evaluate_expression(&self, id: String, expression: String) -> WatchedExpression {
+        let env = self.env.get().clone();
+
+        match r_parse_eval0(&expression, env) {
+            Ok(value) => WatchedExpression {
+                id,
+                expression,
+                display_value: WorkspaceVariableDisplayValue::from(value.sexp).display_value,
+                display_type: WorkspaceVariableDisplayType::from(value.sexp, true).display_type,
+                error: None,
+            },
+            Err(err) => WatchedExpression {
+                id,
+                expression,
+                display_value: String::new(),
+                display_type: String::new(),
+                error: Some(format!("{err}")),
+            },
+        }
+    }
+}
+
+/// Evaluates the watches the next time R is idle at top level and sends the
+/// changed values to the frontend through `outgoing_tx`. Reschedules itself
+/// as long as there are watches left. Since `on_idle()` defers callbacks
+/// registered from callbacks to the next prompt, watches are evaluated once
+/// per prompt rather than in a loop while R is idle.
+pub fn schedule_watches(watches: Arc<VariableWatches>, outgoing_tx: Sender<CommMsg>) {
+    {
+        let mut state = watches.state.lock().unwrap();
+        if state.scheduled || state.watches.is_empty() {
+            return;
+        }
+        state.scheduled = true;
+    }
+
+    on_idle(move || {
+        watches.state.lock().unwrap().scheduled = false;
+
+        for watch in watches.evaluate() {
+            let event = VariablesFrontendEvent::WatchUpdate(WatchUpdateParams { watch });
+            let data = match serde_json::to_value(event) {
+                Ok(data) => data,
+                Err(err) => {
+                    log::error!("Variables: Failed to serialize watch update: {err}");
+                    continue;
+                },
+            };
+            if let Err(err) = outgoing_tx.send(CommMsg::Data(data)) {
+                log::error!("Variables: Failed to send watch update: {err}");
+                return;
+            }
+        }
+
+        schedule_watches(watches, outgoing_tx);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use harp::environment::R_ENVS;
+    use harp::eval::r_parse_eval0;
+    use harp::object::RObject;
+
+    use crate::test::r_test;
+    use crate::variables::watch::VariableWatches;
+    use crate::variables::watch::MAX_WATCHES;
+
+    #[test]
+    fn test_watch_updates_on_change() {
+        r_test(|| {
+            let env = r_parse_eval0("new.env(parent = baseenv())", R_ENVS.global).unwrap();
+            r_parse_eval0("x <- 1:3", env.clone()).unwrap();
+
+            let watches = VariableWatches::new(env.clone());
+
+            let length = watches.add(String::from("length(x)")).unwrap();
+            assert_eq!(length.display_value, "3");
+            assert_eq!(length.error, None);
+
+            let missing = watches.add(String::from("y")).unwrap();
+            assert!(missing.error.is_some());
+
+            // Nothing changed
+            assert!(watches.evaluate().is_empty());
+
+            // Only the changed values are reported
+            r_parse_eval0("x <- 1:10", env.clone()).unwrap();
+            let changed = watches.evaluate();
+            assert_eq!(changed.len(), 1);
+            assert_eq!(changed[0].id, length.id);
+            assert_eq!(changed[0].display_value, "10");
+            assert!(watches.evaluate().is_empty());
+
+            // Errors are resolved once the expression can be evaluated
+            r_parse_eval0("y <- 'y'", env.clone()).unwrap();
+            let changed = watches.evaluate();
+            assert_eq!(changed.len(), 1);
+            assert_eq!(changed[0].id, missing.id);
+            assert_eq!(changed[0].error, None);
+
+            // Removed watches are no longer evaluated
+            watches.remove(&length.id).unwrap();
+            assert!(watches.remove(&length.id).is_err());
+            r_parse_eval0("x <- 1", env.clone()).unwrap();
+            assert!(watches.evaluate().is_empty());
+        })
+    }
+
+    #[test]
+    fn test_watch_limit() {
+        r_test(|| {
+            let watches = VariableWatches::new(RObject::view(R_ENVS.global));
+
+            for _ in 0..MAX_WATCHES {
+                watches.add(String::from("1")).unwrap();
+            }
+            assert!(watches.add(String::from("1")).is_err());
+
+            watches.clear();
+            assert!(watches.add(String::from("1")).is_ok());
+        })
+    }
+}
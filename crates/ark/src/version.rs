@@ -7,14 +7,24 @@
 
 use std::collections::HashMap;
 use std::env;
+use std::env::consts::DLL_PREFIX;
+use std::env::consts::DLL_SUFFIX;
+use std::path::Path;
 use std::path::PathBuf;
 use std::process::Command;
 
 use anyhow::Context;
 use harp::object::RObject;
-use itertools::Itertools;
 use libr::SEXP;
+use once_cell::sync::Lazy;
+use regex::Regex;
 
+// Matches the first `major.minor.patch` (or `major.minor`) version number in a
+// string, e.g. in `R version 4.3.1 (2023-06-16)`.
+static RE_R_VERSION: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\b(\d+)\.(\d+)(?:\.(\d+))?\b").unwrap());
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct RVersion {
     // Major version of the R installation
     pub major: u32,
@@ -27,7 +37,58 @@ pub struct RVersion {
 
     // The full path on disk to the R installation -- that is, the value R_HOME
     // would have inside an R session: > R.home()
-    pub r_home: String,
+    pub r_home: PathBuf,
+
+    // The folder containing the R shared library, e.g. `R_HOME/lib` on Unix
+    pub lib_dir: PathBuf,
+}
+
+impl RVersion {
+    /// Creates an `RVersion` from a version string such as `4.3.1` or
+    /// `R version 4.3.1 (2023-06-16)`.
+    pub fn new(version: &str, r_home: PathBuf) -> anyhow::Result<Self> {
+        let (major, minor, patch) = parse_r_version(version)?;
+        let lib_dir = harp::sys::library::find_r_shared_library_folder(&r_home);
+
+        Ok(Self {
+            major,
+            minor,
+            patch,
+            r_home,
+            lib_dir,
+        })
+    }
+
+    /// Is this version of R at least `major.minor.patch`?
+    pub fn at_least(&self, major: u32, minor: u32, patch: u32) -> bool {
+        (self.major, self.minor, self.patch) >= (major, minor, patch)
+    }
+}
+
+impl std::fmt::Display for RVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Extracts `(major, minor, patch)` from an R version string. A missing
+/// patch component is taken to be 0.
+pub fn parse_r_version(version: &str) -> anyhow::Result<(u32, u32, u32)> {
+    let Some(captures) = RE_R_VERSION.captures(version) else {
+        anyhow::bail!("Failed to extract R version from '{version}'");
+    };
+
+    let component = |i: usize| -> anyhow::Result<u32> {
+        match captures.get(i) {
+            Some(x) => x
+                .as_str()
+                .parse::<u32>()
+                .with_context(|| format!("Invalid R version '{version}'")),
+            None => Ok(0),
+        }
+    };
+
+    Ok((component(1)?, component(2)?, component(3)?))
 }
 
 pub fn detect_r() -> anyhow::Result<RVersion> {
@@ -51,22 +112,80 @@ pub fn detect_r() -> anyhow::Result<RVersion> {
         .context("Failed to execute R to determine version number")?;
 
     let version = String::from_utf8(output.stdout)
-        .context("Failed to convert R version number to a string")?
-        .trim()
-        .to_string();
+        .context("Failed to convert R version number to a string")?;
 
-    let version = version.split(".").map(|x| x.parse::<u32>());
+    RVersion::new(version.trim(), PathBuf::from(r_home))
+}
 
-    if let Some((Ok(major), Ok(minor), Ok(patch))) = version.collect_tuple() {
-        Ok(RVersion {
-            major,
-            minor,
-            patch,
-            r_home,
-        })
-    } else {
-        anyhow::bail!("Failed to extract R version");
+/// Detects the version of the R installation at `r_home` without starting an
+/// R session. The version is read from the `Rversion.h` header, falling back
+/// to `R --version`. Fails if the R shared library can't be found, since we
+/// wouldn't be able to start R from this installation anyway.
+pub fn detect_r_at(r_home: &Path) -> anyhow::Result<RVersion> {
+    if !r_home.is_dir() {
+        anyhow::bail!("R home '{}' is not a directory", r_home.display());
+    }
+
+    let version = match read_rversion_header(r_home) {
+        Ok(version) => version,
+        Err(err) => {
+            log::trace!("Can't read the R version header, calling `R --version`: {err:?}");
+            read_r_version_output(r_home)?
+        },
+    };
+
+    let version = RVersion::new(&version, r_home.to_path_buf())?;
+
+    let lib = version.lib_dir.join(format!("{DLL_PREFIX}R{DLL_SUFFIX}"));
+    if !lib.exists() {
+        anyhow::bail!(
+            "Can't find the R shared library at '{}'. If this is a custom build of R, ensure it is compiled with `--enable-R-shlib`.",
+            lib.display()
+        );
     }
+
+    Ok(version)
+}
+
+fn read_rversion_header(r_home: &Path) -> anyhow::Result<String> {
+    let path = r_home.join("include").join("Rversion.h");
+    let header = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read '{}'", path.display()))?;
+    parse_rversion_header(&header)
+}
+
+/// Extracts the version from the contents of `Rversion.h`, which defines
+/// e.g. `R_MAJOR` as `"4"` and `R_MINOR` as `"3.1"`.
+fn parse_rversion_header(header: &str) -> anyhow::Result<String> {
+    let define = |name: &str| -> Option<String> {
+        header.lines().find_map(|line| {
+            let value = line.trim().strip_prefix("#define")?.trim_start();
+            let value = value.strip_prefix(name)?;
+            if !value.starts_with(char::is_whitespace) {
+                return None;
+            }
+            Some(value.trim().trim_matches('"').to_string())
+        })
+    };
+
+    let (Some(major), Some(minor)) = (define("R_MAJOR"), define("R_MINOR")) else {
+        anyhow::bail!("Failed to find `R_MAJOR` and `R_MINOR` in R version header");
+    };
+
+    Ok(format!("{major}.{minor}"))
+}
+
+fn read_r_version_output(r_home: &Path) -> anyhow::Result<String> {
+    let output = Command::new(r_home.join("bin").join("R"))
+        .arg("--version")
+        .output()
+        .context("Failed to execute R to determine version number")?;
+
+    // The version is on the first line, e.g. `R version 4.3.1 (2023-06-16)`
+    let output = String::from_utf8(output.stdout)
+        .context("Failed to convert R version output to a string")?;
+
+    Ok(output.lines().next().unwrap_or_default().to_string())
 }
 
 #[harp::register]
@@ -101,3 +220,86 @@ pub unsafe extern "C" fn ps_ark_version() -> anyhow::Result<SEXP> {
     let result = RObject::from(info);
     Ok(result.sexp)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::env::consts::DLL_PREFIX;
+    use std::env::consts::DLL_SUFFIX;
+    use std::path::PathBuf;
+
+    use crate::version::detect_r_at;
+    use crate::version::parse_r_version;
+    use crate::version::parse_rversion_header;
+    use crate::version::RVersion;
+
+    #[test]
+    fn test_parse_r_version() {
+        assert_eq!(parse_r_version("4.3.1").unwrap(), (4, 3, 1));
+        assert_eq!(parse_r_version("4.3.1\n").unwrap(), (4, 3, 1));
+        assert_eq!(
+            parse_r_version("R version 4.3.1 (2023-06-16)").unwrap(),
+            (4, 3, 1)
+        );
+        assert_eq!(
+            parse_r_version("R version 4.4.0 Patched (2024-05-20 r86569)").unwrap(),
+            (4, 4, 0)
+        );
+        assert_eq!(parse_r_version("R 3.6").unwrap(), (3, 6, 0));
+
+        assert!(parse_r_version("").is_err());
+        assert!(parse_r_version("R version unknown").is_err());
+    }
+
+    #[test]
+    fn test_r_version_at_least() {
+        let version = RVersion::new("4.2.1", PathBuf::from("/opt/R/4.2.1")).unwrap();
+
+        assert!(version.at_least(4, 2, 0));
+        assert!(version.at_least(4, 2, 1));
+        assert!(version.at_least(3, 6, 3));
+        assert!(!version.at_least(4, 2, 2));
+        assert!(!version.at_least(4, 3, 0));
+        assert!(!version.at_least(5, 0, 0));
+
+        assert_eq!(version.to_string(), "4.2.1");
+    }
+
+    #[test]
+    fn test_parse_rversion_header() {
+        let header = r#"
+#define R_VERSION 262913
+#define R_NICE_VERSION "4.3.1"
+#define R_Version(v,p,s) (((v) * 65536) + ((p) * 256) + (s))
+#define R_MAJOR  "4"
+#define R_MINOR  "3.1"
+#define R_STATUS ""
+"#;
+        assert_eq!(parse_rversion_header(header).unwrap(), "4.3.1");
+        assert!(parse_rversion_header("#define R_MAJORITY \"4\"").is_err());
+    }
+
+    #[test]
+    fn test_detect_r_at() {
+        let r_home = std::env::temp_dir().join(format!("ark-r-home-{}", std::process::id()));
+        std::fs::create_dir_all(r_home.join("include")).unwrap();
+        std::fs::write(
+            r_home.join("include").join("Rversion.h"),
+            "#define R_MAJOR  \"4\"\n#define R_MINOR  \"2.3\"\n",
+        )
+        .unwrap();
+
+        // Fails without a shared library
+        assert!(detect_r_at(&r_home).is_err());
+
+        let version = RVersion::new("0.0.0", r_home.clone()).unwrap();
+        let lib = format!("{DLL_PREFIX}R{DLL_SUFFIX}");
+        std::fs::create_dir_all(&version.lib_dir).unwrap();
+        std::fs::write(version.lib_dir.join(lib), "").unwrap();
+
+        let version = detect_r_at(&r_home).unwrap();
+        assert_eq!((version.major, version.minor, version.patch), (4, 2, 3));
+        assert_eq!(version.r_home, r_home);
+
+        std::fs::remove_dir_all(r_home).unwrap();
+    }
+}
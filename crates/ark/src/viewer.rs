@@ -5,23 +5,70 @@
 //
 //
 
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use amalthea::comm::ui_comm::ShowUrlParams;
+use amalthea::comm::ui_comm::UiFrontendEvent;
 use amalthea::socket::iopub::IOPubMessage;
 use amalthea::wire::display_data::DisplayData;
 use anyhow::Result;
+use base64::engine::general_purpose;
+use base64::Engine;
 use crossbeam::channel::Sender;
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
 use harp::object::RObject;
+use harp::utils::r_null_or_try_into;
 use libr::R_NilValue;
 use libr::SEXP;
+use once_cell::sync::Lazy;
+use regex::Captures;
+use regex::Regex;
+use uuid::Uuid;
+use walkdir::WalkDir;
 
+use crate::html_widget;
 use crate::interface::RMain;
 
+/// Local resources larger than this are served by the widget asset server
+/// rather than inlined as data URIs, to keep the page we send over IOPub small.
+const MAX_INLINE_RESOURCE_SIZE: u64 = 512 * 1024;
+
+// Matches the tags that load resources: scripts, stylesheets, and images.
+static RE_RESOURCE_TAG: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?is)<(?:script|link|img)\b[^>]*>").unwrap());
+
+// Matches a quoted `src` or `href` attribute within a tag.
+static RE_RESOURCE_ATTR: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?i)(?P<attr>\b(?:src|href)\s*=\s*)(?:"(?P<dq>[^"]*)"|'(?P<sq>[^']*)')"#).unwrap()
+});
+
+// URL of the Shiny app hosted in the viewer, if any. `shiny::runApp()` runs
+// a single app at a time and keeps the console busy until the app stops.
+static SHINY_APP_URL: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+
 /// Emit HTML output on IOPub for delivery to the client
 ///
 /// - `iopub_tx` - The IOPub channel to send the output on
 /// - `path` - The path to the HTML file to display
 fn emit_html_output(iopub_tx: Sender<IOPubMessage>, path: String) -> Result<()> {
     // Read the contents of the file
-    let contents = std::fs::read_to_string(path)?;
+    let contents = std::fs::read_to_string(&path)?;
+
+    // The frontend can't resolve paths relative to the file, so make them
+    // point to something it can fetch
+    let path = Path::new(&path);
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut assets = PageAssets::new(path);
+    let contents = rewrite_local_resources(
+        &contents,
+        dir,
+        max_inline_resource_size(),
+        |resource, file| assets.serve(resource, file),
+    );
 
     // Create the output object
     let output = serde_json::json!({
@@ -62,3 +109,359 @@ pub unsafe extern "C" fn ps_html_viewer(url: SEXP) -> anyhow::Result<SEXP> {
     // No return value
     Ok(R_NilValue)
 }
+
+/// Whether a Shiny app is currently hosted in the viewer
+pub fn is_shiny_app_running() -> bool {
+    SHINY_APP_URL.lock().unwrap().is_some()
+}
+
+/// Stops the Shiny app hosted in the viewer, if any, so that `runApp()`
+/// returns and frees the console. Must be called on the R thread.
+pub fn stop_shiny_app() -> harp::Result<()> {
+    // Take the URL first so reentrant calls, e.g. from polled events while
+    // `stopApp()` runs, are no-ops
+    let Some(url) = SHINY_APP_URL.lock().unwrap().take() else {
+        return Ok(());
+    };
+
+    log::info!("Stopping Shiny app at {url}");
+    RFunction::new("shiny", "stopApp").call()?;
+    Ok(())
+}
+
+/// Called by `runApp()` through the `shiny.launch.browser` option once the
+/// app is listening on `url`
+#[harp::register]
+pub unsafe extern "C" fn ps_viewer_shiny_started(url: SEXP) -> anyhow::Result<SEXP> {
+    let url: String = RObject::view(url).try_into()?;
+    log::info!("Hosting Shiny app at {url}");
+
+    *SHINY_APP_URL.lock().unwrap() = Some(url.clone());
+
+    let event = UiFrontendEvent::ShowUrl(ShowUrlParams { url });
+    RMain::with(|main| main.send_frontend_event(event));
+
+    Ok(R_NilValue)
+}
+
+/// Called when the app stops, whether it was stopped by us, by the app
+/// itself, or by an interrupt
+#[harp::register]
+pub unsafe extern "C" fn ps_viewer_shiny_stopped(url: SEXP) -> anyhow::Result<SEXP> {
+    let url: String = RObject::view(url).try_into()?;
+
+    let mut app = SHINY_APP_URL.lock().unwrap();
+    if app.as_deref() == Some(url.as_str()) {
+        *app = None;
+    }
+
+    Ok(R_NilValue)
+}
+
+/// Called when the frontend closes the viewer pane showing `url`
+#[harp::register]
+pub unsafe extern "C" fn ps_viewer_closed(url: SEXP) -> anyhow::Result<SEXP> {
+    let url: String = RObject::view(url).try_into()?;
+
+    let hosted = SHINY_APP_URL.lock().unwrap().as_deref() == Some(url.as_str());
+    if hosted {
+        stop_shiny_app()?;
+    }
+
+    Ok(R_NilValue)
+}
+
+/// Whether to make HTML pages self-contained by inlining their local
+/// resources. When disabled, all local resources are served by the widget
+/// asset server instead.
+fn max_inline_resource_size() -> u64 {
+    let opt: Option<bool> = r_null_or_try_into(harp::get_option("ark.viewer.self_contained"))
+        .ok()
+        .flatten();
+
+    match opt.unwrap_or(true) {
+        true => MAX_INLINE_RESOURCE_SIZE,
+        false => 0,
+    }
+}
+
+/// Rewrites `src` and `href` attributes of scripts, stylesheets, and images
+/// that point to local files relative to `dir`. Files up to `max_inline_size`
+/// bytes are inlined as data URIs, larger ones are pointed to the URL
+/// returned by `serve` for the relative reference and the resolved file.
+/// References we can't resolve are left alone.
+fn rewrite_local_resources(
+    html: &str,
+    dir: &Path,
+    max_inline_size: u64,
+    mut serve: impl FnMut(&str, &Path) -> Option<String>,
+) -> String {
+    RE_RESOURCE_TAG
+        .replace_all(html, |tag: &Captures| {
+            RE_RESOURCE_ATTR
+                .replace_all(&tag[0], |attr: &Captures| {
+                    let (value, quote) = match attr.name("dq") {
+                        Some(value) => (value.as_str(), '"'),
+                        None => (attr.name("sq").map_or("", |m| m.as_str()), '\''),
+                    };
+
+                    let Some(file) = local_resource_path(value, dir) else {
+                        return attr[0].to_string();
+                    };
+                    let Ok(metadata) = std::fs::metadata(&file) else {
+                        return attr[0].to_string();
+                    };
+
+                    let url = if metadata.len() <= max_inline_size {
+                        data_uri(&file)
+                    } else {
+                        serve(value, &file)
+                    };
+
+                    match url {
+                        Some(url) => format!("{}{quote}{url}{quote}", &attr["attr"]),
+                        None => attr[0].to_string(),
+                    }
+                })
+                .into_owned()
+        })
+        .into_owned()
+}
+
+/// Returns the path of the file `url` refers to if it's a relative path to a
+/// local file within `dir`, once resolved.
+fn local_resource_path(url: &str, dir: &Path) -> Option<PathBuf> {
+    // Skip URLs with a scheme (`https:`, `data:`, ...), protocol-relative and
+    // absolute URLs, and fragments
+    let is_scheme = url
+        .split_once(':')
+        .is_some_and(|(scheme, _)| !scheme.is_empty() && !scheme.contains('/'));
+    if url.is_empty() || is_scheme || url.starts_with('/') || url.starts_with('#') {
+        return None;
+    }
+
+    // Resources are never allowed to escape the page's directory
+    let path = Path::new(url);
+    let is_local = path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    if !is_local {
+        return None;
+    }
+
+    // Nor through symbolic links
+    let dir = dir.canonicalize().ok()?;
+    let file = dir.join(path).canonicalize().ok()?;
+    file.starts_with(&dir).then_some(file)
+}
+
+/// Local resources of an HTML page that are served by the widget asset
+/// server. The page's directory is often the home or temporary directory, so
+/// it isn't served as a whole. Instead the resources the page references are
+/// copied to a staging directory owned by the asset server, along with the
+/// `<page>_files` directory where htmlwidgets and R Markdown store
+/// dependencies, since these may load further files.
+struct PageAssets {
+    dir: PathBuf,
+    deps: Option<String>,
+    staging: Option<(PathBuf, String)>,
+}
+
+impl PageAssets {
+    fn new(page: &Path) -> Self {
+        let dir = page.parent().unwrap_or(Path::new("")).to_path_buf();
+        let deps = page
+            .file_stem()
+            .map(|stem| format!("{}_files", stem.to_string_lossy()));
+
+        Self {
+            dir,
+            deps,
+            staging: None,
+        }
+    }
+
+    /// Stages `file`, referenced as `resource` by the page, and returns the
+    /// URL it is served at
+    fn serve(&mut self, resource: &str, file: &Path) -> Option<String> {
+        let (staging, url) = self.staging()?;
+
+        let resource_path = Path::new(resource);
+        let in_deps = self.deps.as_deref().is_some_and(|deps| {
+            resource_path
+                .components()
+                .find(|component| !matches!(component, Component::CurDir))
+                .is_some_and(|component| component.as_os_str() == deps)
+        });
+
+        let result = match (&self.deps, in_deps) {
+            (Some(deps), true) => stage_dir(&self.dir.join(deps), &staging.join(deps)),
+            _ => stage_file(file, &staging.join(resource_path)),
+        };
+        if let Err(err) = result {
+            log::warn!("Can't stage '{}' for the viewer: {err}", file.display());
+            return None;
+        }
+
+        Some(format!("{url}{resource}"))
+    }
+
+    fn staging(&mut self) -> Option<(PathBuf, String)> {
+        if self.staging.is_none() {
+            let dir = std::env::temp_dir().join(format!("ark-viewer-{}", Uuid::new_v4()));
+            std::fs::create_dir_all(&dir).ok()?;
+
+            // The asset server deletes the directory once released
+            let Some(url) = html_widget::serve_widget_assets(dir.clone()) else {
+                let _ = std::fs::remove_dir_all(&dir);
+                return None;
+            };
+            self.staging = Some((dir, url));
+        }
+
+        self.staging.clone()
+    }
+}
+
+fn stage_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if to.exists() {
+        return Ok(());
+    }
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::copy(from, to)?;
+    Ok(())
+}
+
+/// Copies the files of `from` to `to`, skipping symbolic links so that
+/// nothing outside of `from` is staged
+fn stage_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    if to.exists() {
+        return Ok(());
+    }
+
+    for entry in WalkDir::new(from) {
+        let entry = entry?;
+        let Ok(relative) = entry.path().strip_prefix(from) else {
+            continue;
+        };
+        let target = to.join(relative);
+
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&target)?;
+        } else if entry.file_type().is_file() {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn data_uri(file: &Path) -> Option<String> {
+    let contents = std::fs::read(file).ok()?;
+    let mime_type = mime_guess::from_path(file).first_or_octet_stream();
+    let contents = general_purpose::STANDARD.encode(contents);
+    Some(format!("data:{mime_type};base64,{contents}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use harp::object::RObject;
+
+    use crate::test::r_test;
+    use crate::viewer::is_shiny_app_running;
+    use crate::viewer::ps_viewer_shiny_stopped;
+    use crate::viewer::rewrite_local_resources;
+    use crate::viewer::stage_dir;
+    use crate::viewer::stop_shiny_app;
+    use crate::viewer::SHINY_APP_URL;
+
+    #[test]
+    fn test_rewrite_local_resources() {
+        let dir = std::env::temp_dir().join(format!("ark-viewer-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        std::fs::write(dir.join("lib/small.css"), "body {}").unwrap();
+        std::fs::write(dir.join("lib/large.js"), "x".repeat(100)).unwrap();
+
+        let html =
+            r#"<link href="lib/small.css" rel="stylesheet"><script src='lib/large.js'></script>"#;
+        let out = rewrite_local_resources(html, &dir, 50, |resource, file| {
+            assert!(file.ends_with("lib/large.js"));
+            Some(format!("http://127.0.0.1:1234/widget-assets/id/{resource}"))
+        });
+        assert_eq!(
+            out,
+            r#"<link href="data:text/css;base64,Ym9keSB7fQ==" rel="stylesheet"><script src='http://127.0.0.1:1234/widget-assets/id/lib/large.js'></script>"#
+        );
+
+        // Without an asset server, large resources are left alone
+        let out = rewrite_local_resources(html, &dir, 50, |_, _| None);
+        assert!(out.ends_with(r#"<script src='lib/large.js'></script>"#));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_rewrite_local_resources_leaves_other_urls_alone() {
+        let dir = std::env::temp_dir();
+        let html = concat!(
+            r#"<script src="https://cdn.example.com/x.js"></script>"#,
+            r#"<img src="data:image/png;base64,AAAA">"#,
+            r#"<link href="//cdn.example.com/x.css">"#,
+            r#"<img src="../secret.png">"#,
+            r#"<a href="missing.html">link</a>"#,
+        );
+        assert_eq!(rewrite_local_resources(html, &dir, 1024, |_, _| None), html);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_rewrite_local_resources_rejects_links_outside_dir() {
+        let root = std::env::temp_dir().join(format!("ark-viewer-links-{}", std::process::id()));
+        let dir = root.join("page");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(root.join("secret.css"), "body {}").unwrap();
+        std::os::unix::fs::symlink(root.join("secret.css"), dir.join("link.css")).unwrap();
+
+        let html = r#"<link href="link.css" rel="stylesheet">"#;
+        assert_eq!(rewrite_local_resources(html, &dir, 1024, |_, _| None), html);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_stage_dir() {
+        let root = std::env::temp_dir().join(format!("ark-viewer-stage-{}", std::process::id()));
+        let from = root.join("page_files");
+        std::fs::create_dir_all(from.join("lib")).unwrap();
+        std::fs::write(from.join("lib/widget.js"), "x").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&root, from.join("escape")).unwrap();
+
+        let to = root.join("staging/page_files");
+        stage_dir(&from, &to).unwrap();
+        assert!(to.join("lib/widget.js").is_file());
+        assert!(!to.join("escape").exists());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_shiny_app_stopped() {
+        r_test(|| unsafe {
+            // Nothing to stop
+            assert!(stop_shiny_app().is_ok());
+
+            let url = "http://127.0.0.1:1234";
+            *SHINY_APP_URL.lock().unwrap() = Some(String::from(url));
+
+            // Stop notifications from other apps are ignored
+            ps_viewer_shiny_stopped(RObject::from("http://127.0.0.1:5678").sexp);
+            assert!(is_shiny_app_running());
+
+            ps_viewer_shiny_stopped(RObject::from(url).sexp);
+            assert!(!is_shiny_app_running());
+        })
+    }
+}
@@ -18,6 +18,8 @@ use ark::test::r_test;
 use ark::test::socket_rpc_request;
 use crossbeam::channel::bounded;
 use harp::assert_match;
+use harp::environment::R_ENVS;
+use harp::eval::r_parse_eval0;
 use harp::exec::RFunction;
 use harp::object::RObject;
 
@@ -203,6 +205,31 @@ fn test_connection_list_fields() {
     })
 }
 
+#[test]
+fn test_connections_metadata_is_cached() {
+    r_test(|| {
+        let socket = open_dummy_connection();
+
+        for _ in 0..2 {
+            let path = vec![obj("main", "schema")];
+            socket_rpc(
+                &socket,
+                ConnectionsBackendRequest::ListObjects(ListObjectsParams { path }),
+            );
+        }
+
+        let code = format!(
+            "length(ls(getOption('connectionObserver')$.connections[['{}']]$cache))",
+            socket.comm_id
+        );
+        let n_cached = r_task(|| {
+            let n = r_parse_eval0(code.as_str(), R_ENVS.global).unwrap();
+            RObject::to::<i32>(n).unwrap()
+        });
+        assert_eq!(n_cached, 1);
+    })
+}
+
 #[test]
 fn test_send_frontend_event() {
     r_test(|| {
@@ -6,6 +6,10 @@
 //
 
 use amalthea::comm::comm_channel::CommMsg;
+use amalthea::comm::data_explorer_comm::BetweenFilterParams;
+use amalthea::comm::data_explorer_comm::ColumnDisplayType;
+use amalthea::comm::data_explorer_comm::ColumnHistogramParams;
+use amalthea::comm::data_explorer_comm::ColumnLayout;
 use amalthea::comm::data_explorer_comm::ColumnProfileRequest;
 use amalthea::comm::data_explorer_comm::ColumnProfileType;
 use amalthea::comm::data_explorer_comm::ColumnSortKey;
@@ -16,6 +20,7 @@ use amalthea::comm::data_explorer_comm::DataExplorerBackendReply;
 use amalthea::comm::data_explorer_comm::DataExplorerBackendRequest;
 use amalthea::comm::data_explorer_comm::DataExplorerFrontendEvent;
 use amalthea::comm::data_explorer_comm::DataSelection;
+use amalthea::comm::data_explorer_comm::DataSelectionCellRange;
 use amalthea::comm::data_explorer_comm::DataSelectionKind;
 use amalthea::comm::data_explorer_comm::DataSelectionSingleCell;
 use amalthea::comm::data_explorer_comm::ExportDataSelectionParams;
@@ -29,9 +34,12 @@ use amalthea::comm::data_explorer_comm::GetSchemaParams;
 use amalthea::comm::data_explorer_comm::RowFilter;
 use amalthea::comm::data_explorer_comm::RowFilterCondition;
 use amalthea::comm::data_explorer_comm::RowFilterType;
+use amalthea::comm::data_explorer_comm::SearchDataParams;
 use amalthea::comm::data_explorer_comm::SearchFilterParams;
 use amalthea::comm::data_explorer_comm::SearchFilterType;
 use amalthea::comm::data_explorer_comm::Selection;
+use amalthea::comm::data_explorer_comm::SetColumnLayoutParams;
+use amalthea::comm::data_explorer_comm::SetMembershipFilterParams;
 use amalthea::comm::data_explorer_comm::SetRowFiltersParams;
 use amalthea::comm::data_explorer_comm::SetSortColumnsParams;
 use amalthea::comm::data_explorer_comm::SummaryStatsBoolean;
@@ -554,6 +562,7 @@ fn test_null_counts() {
             profiles: vec![ColumnProfileRequest {
                 column_index: 0,
                 profile_type: ColumnProfileType::NullCount,
+                histogram_params: None,
             }],
             format_options: default_format_options(),
         });
@@ -597,6 +606,7 @@ fn test_null_counts() {
             profiles: vec![ColumnProfileRequest {
                 column_index: 0,
                 profile_type: ColumnProfileType::NullCount,
+                histogram_params: None,
             }],
             format_options: default_format_options(),
         });
@@ -659,6 +669,7 @@ fn test_summary_stats() {
                 .map(|i| ColumnProfileRequest {
                     column_index: i,
                     profile_type: ColumnProfileType::SummaryStats,
+                    histogram_params: None,
                 })
                 .collect(),
             format_options: default_format_options(),
@@ -707,6 +718,186 @@ fn test_summary_stats() {
     })
 }
 
+#[test]
+fn test_histogram() {
+    r_test(|| {
+        let socket = open_data_explorer_from_expression(
+            "data.frame(x = c(1, 2, 3, 4, NA, 10), y = letters[1:6])",
+            None,
+        )
+        .unwrap();
+
+        let histogram_request = |column_index: i64| {
+            DataExplorerBackendRequest::GetColumnProfiles(GetColumnProfilesParams {
+                profiles: vec![ColumnProfileRequest {
+                    column_index,
+                    profile_type: ColumnProfileType::Histogram,
+                    histogram_params: Some(ColumnHistogramParams { num_bins: 3 }),
+                }],
+                format_options: default_format_options(),
+            })
+        };
+
+        assert_match!(socket_rpc(&socket, histogram_request(0)),
+           DataExplorerBackendReply::GetColumnProfilesReply(data) => {
+                let histogram = data[0].histogram.clone().unwrap();
+                assert_eq!(histogram.bin_edges, Some(vec![1.0, 4.0, 7.0, 10.0]));
+                assert_eq!(histogram.bin_counts, Some(vec![3, 1, 1]));
+                assert_eq!(histogram.bin_sizes, vec![3, 1, 1]);
+                assert_eq!(histogram.bin_width, 3.0);
+                assert_eq!(histogram.sampled, Some(false));
+           }
+        );
+
+        // Character columns don't have a histogram
+        assert_match!(socket_rpc(&socket, histogram_request(1)),
+           DataExplorerBackendReply::GetColumnProfilesReply(data) => {
+                assert!(data[0].histogram.is_none());
+           }
+        );
+
+        // The histogram only covers the filtered rows
+        let schema = match socket_rpc(
+            &socket,
+            DataExplorerBackendRequest::GetSchema(GetSchemaParams {
+                num_columns: 1,
+                start_index: 0,
+            }),
+        ) {
+            DataExplorerBackendReply::GetSchemaReply(schema) => schema,
+            reply => panic!("Unexpected reply: {:?}", reply),
+        };
+
+        let req = DataExplorerBackendRequest::SetRowFilters(SetRowFiltersParams {
+            filters: vec![RowFilter {
+                column_schema: schema.columns[0].clone(),
+                filter_type: RowFilterType::Compare,
+                compare_params: Some(CompareFilterParams {
+                    op: CompareFilterParamsOp::Lt,
+                    value: "4".to_string(),
+                }),
+                filter_id: "2B4C8B8E-5A53-4C52-9D0B-2F3E7C1A6D11".to_string(),
+                condition: RowFilterCondition::And,
+                error_message: None,
+                is_valid: None,
+                between_params: None,
+                search_params: None,
+                set_membership_params: None,
+            }],
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::SetRowFiltersReply(
+                FilterResult { selected_num_rows: num_rows, had_errors: Some(false) }
+            ) => {
+                assert_eq!(num_rows, 3);
+            }
+        );
+
+        assert_match!(socket_rpc(&socket, histogram_request(0)),
+           DataExplorerBackendReply::GetColumnProfilesReply(data) => {
+                let histogram = data[0].histogram.clone().unwrap();
+                let bin_edges = histogram.bin_edges.unwrap();
+                assert_eq!(bin_edges.first(), Some(&1.0));
+                assert_eq!(bin_edges.last(), Some(&3.0));
+                assert_eq!(histogram.bin_sizes, vec![1, 1, 1]);
+           }
+        );
+    })
+}
+
+#[test]
+fn test_search_data() {
+    r_test(|| {
+        let socket = open_data_explorer_from_expression(
+            "data.frame(fruit = c('apple', 'Banana', 'cherry', 'banana'), n = c(1, 22, 3, 42))",
+            None,
+        )
+        .unwrap();
+
+        let search = |term: &str, start_index: i64, case_sensitive: bool, wrap_around: bool| {
+            let req = DataExplorerBackendRequest::SearchData(SearchDataParams {
+                search_term: term.to_string(),
+                column_index: None,
+                start_index,
+                case_sensitive,
+                wrap_around,
+            });
+            match socket_rpc(&socket, req) {
+                DataExplorerBackendReply::SearchDataReply(result) => result.row_index,
+                reply => panic!("Unexpected reply: {:?}", reply),
+            }
+        };
+
+        // Case insensitive search finds the next match from the start row
+        assert_eq!(search("banana", 0, false, false), Some(1));
+        assert_eq!(search("banana", 2, false, false), Some(3));
+
+        // Case sensitive search skips `Banana`
+        assert_eq!(search("banana", 0, true, false), Some(3));
+
+        // Wrapping around restarts from the first row
+        assert_eq!(search("apple", 1, false, false), None);
+        assert_eq!(search("apple", 1, false, true), Some(0));
+
+        // All columns are searched, and terms are not regular expressions
+        assert_eq!(search("2", 0, false, false), Some(1));
+        assert_eq!(search(".", 0, false, false), None);
+
+        // Search can be restricted to a single column
+        let req = DataExplorerBackendRequest::SearchData(SearchDataParams {
+            search_term: "a".to_string(),
+            column_index: Some(1),
+            start_index: 0,
+            case_sensitive: false,
+            wrap_around: false,
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::SearchDataReply(result) => {
+                assert_eq!(result.row_index, None);
+            }
+        );
+
+        // Rows are searched in the current sort order
+        let req = DataExplorerBackendRequest::SetSortColumns(SetSortColumnsParams {
+            sort_keys: vec![ColumnSortKey {
+                column_index: 1,
+                ascending: false,
+            }],
+        });
+        assert_match!(
+            socket_rpc(&socket, req),
+            DataExplorerBackendReply::SetSortColumnsReply()
+        );
+        assert_eq!(search("banana", 0, false, false), Some(0));
+        assert_eq!(search("apple", 0, false, false), Some(3));
+    })
+}
+
+#[test]
+fn test_search_data_large() {
+    r_test(|| {
+        // Spans several search chunks
+        let socket = open_data_explorer_from_expression(
+            "data.frame(x = c(rep('a', 25000), 'needle', rep('b', 10)))",
+            None,
+        )
+        .unwrap();
+
+        let req = DataExplorerBackendRequest::SearchData(SearchDataParams {
+            search_term: "needle".to_string(),
+            column_index: Some(0),
+            start_index: 100,
+            case_sensitive: true,
+            wrap_around: false,
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::SearchDataReply(result) => {
+                assert_eq!(result.row_index, Some(25000));
+            }
+        );
+    })
+}
+
 #[test]
 fn test_search_filters() {
     r_test(|| {
@@ -1402,6 +1593,191 @@ fn test_invalid_filters_preserved() {
     });
 }
 
+#[test]
+fn test_factor_and_date_columns() {
+    r_test(|| {
+        let socket = open_data_explorer_from_expression(
+            r#"data.frame(
+                fct = factor(c("b", "a", "c", "a"), levels = c("c", "b", "a")),
+                date = as.Date(c("2024-03-01", "2024-01-15", "2024-02-10", NA)),
+                time = as.POSIXct(c(
+                    "2024-01-01 12:00:30",
+                    "2024-01-02 12:00:30",
+                    "2024-01-03 12:00:30",
+                    "2024-01-04 12:00:30"
+                ), tz = "UTC"),
+                diff = as.difftime(c(1, 2, 3, 4), units = "hours")
+            )"#,
+            None,
+        )
+        .unwrap();
+
+        let req = DataExplorerBackendRequest::GetSchema(GetSchemaParams {
+            num_columns: 4,
+            start_index: 0,
+        });
+        let schema = match socket_rpc(&socket, req) {
+            DataExplorerBackendReply::GetSchemaReply(schema) => schema,
+            reply => panic!("Unexpected reply: {:?}", reply),
+        };
+        assert_eq!(schema.columns[0].type_display, ColumnDisplayType::String);
+        assert_eq!(schema.columns[1].type_display, ColumnDisplayType::Date);
+        assert_eq!(schema.columns[2].type_display, ColumnDisplayType::Datetime);
+        assert_eq!(schema.columns[2].timezone, Some(String::from("UTC")));
+        assert_eq!(schema.columns[3].type_display, ColumnDisplayType::Number);
+
+        // Factors are shown by their labels, dates and datetimes with
+        // `format()`, in the timezone of the column
+        let req = DataExplorerBackendRequest::GetDataValues(GetDataValuesParams {
+            row_start_index: 0,
+            num_rows: 4,
+            column_indices: vec![0, 1, 2, 3],
+            format_options: default_format_options(),
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetDataValuesReply(data) => {
+                let value = |x: &str| ColumnValue::FormattedValue(x.to_string());
+                assert_eq!(data.columns[0][0], value("b"));
+                assert_eq!(data.columns[1][0], value("2024-03-01"));
+                assert_eq!(data.columns[1][3], ColumnValue::SpecialValueCode(1));
+                assert_eq!(data.columns[2][0], value("2024-01-01 12:00:30"));
+                assert_eq!(data.columns[3][0], value("1 hours"));
+            }
+        );
+
+        // Factors are sorted by the order of their levels, as in R
+        let req = DataExplorerBackendRequest::SetSortColumns(SetSortColumnsParams {
+            sort_keys: vec![ColumnSortKey {
+                column_index: 0,
+                ascending: true,
+            }],
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::SetSortColumnsReply() => {}
+        );
+
+        let req = DataExplorerBackendRequest::GetDataValues(GetDataValuesParams {
+            row_start_index: 0,
+            num_rows: 4,
+            column_indices: vec![0],
+            format_options: default_format_options(),
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetDataValuesReply(data) => {
+                let values: Vec<ColumnValue> = ["c", "b", "a", "a"]
+                    .iter()
+                    .map(|x| ColumnValue::FormattedValue(x.to_string()))
+                    .collect();
+                assert_eq!(data.columns[0], values);
+            }
+        );
+
+        let row_filter = |column: usize, filter_type: RowFilterType| RowFilter {
+            column_schema: schema.columns[column].clone(),
+            filter_type,
+            filter_id: format!("filter-{column}"),
+            condition: RowFilterCondition::And,
+            is_valid: None,
+            compare_params: None,
+            between_params: None,
+            search_params: None,
+            set_membership_params: None,
+            error_message: None,
+        };
+
+        let check_filter = |filter: RowFilter, expected: i64| {
+            let req = DataExplorerBackendRequest::SetRowFilters(SetRowFiltersParams {
+                filters: vec![filter],
+            });
+            assert_match!(socket_rpc(&socket, req),
+                DataExplorerBackendReply::SetRowFiltersReply(
+                    FilterResult { selected_num_rows: num_rows, had_errors: Some(false)}
+                ) => {
+                    assert_eq!(num_rows, expected);
+                }
+            );
+        };
+
+        // Factors are filtered by their levels
+        check_filter(
+            RowFilter {
+                set_membership_params: Some(SetMembershipFilterParams {
+                    values: vec![String::from("a"), String::from("c")],
+                    inclusive: true,
+                }),
+                ..row_filter(0, RowFilterType::SetMembership)
+            },
+            3,
+        );
+        check_filter(
+            RowFilter {
+                set_membership_params: Some(SetMembershipFilterParams {
+                    values: vec![String::from("a")],
+                    inclusive: false,
+                }),
+                ..row_filter(0, RowFilterType::SetMembership)
+            },
+            2,
+        );
+        check_filter(
+            RowFilter {
+                compare_params: Some(CompareFilterParams {
+                    op: CompareFilterParamsOp::Eq,
+                    value: String::from("b"),
+                }),
+                ..row_filter(0, RowFilterType::Compare)
+            },
+            1,
+        );
+        check_filter(
+            RowFilter {
+                search_params: Some(SearchFilterParams {
+                    search_type: SearchFilterType::StartsWith,
+                    term: String::from("A"),
+                    case_sensitive: false,
+                }),
+                ..row_filter(0, RowFilterType::Search)
+            },
+            2,
+        );
+
+        // Bounds are parsed as dates, and as datetimes in the timezone of
+        // the column
+        check_filter(
+            RowFilter {
+                between_params: Some(BetweenFilterParams {
+                    left_value: String::from("2024-01-01"),
+                    right_value: String::from("2024-02-15"),
+                }),
+                ..row_filter(1, RowFilterType::Between)
+            },
+            2,
+        );
+        check_filter(
+            RowFilter {
+                compare_params: Some(CompareFilterParams {
+                    op: CompareFilterParamsOp::Gt,
+                    value: String::from("2024-01-02 12:00:30"),
+                }),
+                ..row_filter(2, RowFilterType::Compare)
+            },
+            2,
+        );
+
+        // Durations are compared in the units of the column
+        check_filter(
+            RowFilter {
+                compare_params: Some(CompareFilterParams {
+                    op: CompareFilterParamsOp::GtEq,
+                    value: String::from("3"),
+                }),
+                ..row_filter(3, RowFilterType::Compare)
+            },
+            2,
+        );
+    })
+}
+
 #[test]
 fn test_data_explorer_special_values() {
     r_test(|| {
@@ -1536,3 +1912,159 @@ fn test_export_data() {
         );
     })
 }
+
+#[test]
+fn test_column_layout() {
+    r_test(|| {
+        let socket = open_data_explorer_from_expression(
+            "x <- data.frame(a = 1:2, b = c('x', 'y'), c = c(TRUE, FALSE), d = c(0.5, 1.5))",
+            Some("x"),
+        )
+        .unwrap();
+
+        let column_names = |socket: &CommSocket| {
+            let req = DataExplorerBackendRequest::GetSchema(GetSchemaParams {
+                num_columns: 10,
+                start_index: 0,
+            });
+            match socket_rpc(socket, req) {
+                DataExplorerBackendReply::GetSchemaReply(schema) => (
+                    schema
+                        .columns
+                        .iter()
+                        .map(|column| column.column_name.clone())
+                        .collect::<Vec<String>>(),
+                    schema.column_layout,
+                ),
+                reply => panic!("Unexpected reply: {reply:?}"),
+            }
+        };
+
+        // Reverse the columns and pin `b` to the left
+        let req = DataExplorerBackendRequest::SetColumnLayout(SetColumnLayoutParams {
+            column_order: vec![3, 2, 1, 0],
+            pinned_columns: vec![1],
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::SetColumnLayoutReply() => {}
+        );
+
+        // The frontend is told to fetch the schema again
+        assert_match!(socket.outgoing_rx.recv_timeout(std::time::Duration::from_secs(1)).unwrap(),
+            CommMsg::Data(value) => {
+                assert_match!(serde_json::from_value::<DataExplorerFrontendEvent>(value).unwrap(),
+                    DataExplorerFrontendEvent::SchemaUpdate
+                );
+        });
+
+        let layout = Some(ColumnLayout {
+            column_order: vec![1, 3, 2, 0],
+            pinned_columns: vec![1],
+        });
+        assert_eq!(
+            column_names(&socket),
+            (
+                vec![
+                    String::from("b"),
+                    String::from("d"),
+                    String::from("c"),
+                    String::from("a"),
+                ],
+                layout.clone()
+            )
+        );
+
+        // Column indices in the schema still refer to the table, so sorting
+        // by the first displayed column sorts by `b`
+        let req = DataExplorerBackendRequest::SetSortColumns(SetSortColumnsParams {
+            sort_keys: vec![ColumnSortKey {
+                column_index: 1,
+                ascending: false,
+            }],
+        });
+        socket_rpc(&socket, req);
+
+        // Exported column ranges are over display positions
+        let req = DataExplorerBackendRequest::ExportDataSelection(ExportDataSelectionParams {
+            format: ExportFormat::Csv,
+            selection: DataSelection {
+                kind: DataSelectionKind::CellRange,
+                selection: Selection::CellRange(DataSelectionCellRange {
+                    first_row_index: 0,
+                    last_row_index: 0,
+                    first_column_index: 0,
+                    last_column_index: 1,
+                }),
+            },
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::ExportDataSelectionReply(ExportedData { data, .. }) => {
+                assert_eq!(data, "b,d\ny,1.5".to_string());
+            }
+        );
+
+        // While column indices refer to the table, like in other requests
+        let req = DataExplorerBackendRequest::ExportDataSelection(ExportDataSelectionParams {
+            format: ExportFormat::Csv,
+            selection: DataSelection {
+                kind: DataSelectionKind::SingleCell,
+                selection: Selection::SingleCell(DataSelectionSingleCell {
+                    row_index: 0,
+                    column_index: 0,
+                }),
+            },
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::ExportDataSelectionReply(ExportedData { data, .. }) => {
+                assert_eq!(data, "2".to_string());
+            }
+        );
+
+        // The layout survives a data update
+        r_parse_eval0("x[1, 'a'] <- 10L", R_ENVS.global).unwrap();
+        EVENTS.console_prompt.emit(());
+        assert_match!(socket.outgoing_rx.recv_timeout(std::time::Duration::from_secs(1)).unwrap(),
+            CommMsg::Data(value) => {
+                assert_match!(serde_json::from_value::<DataExplorerFrontendEvent>(value).unwrap(),
+                    DataExplorerFrontendEvent::DataUpdate
+                );
+        });
+        assert_eq!(column_names(&socket).1, layout);
+
+        // And a change of column type, as long as the columns are the same
+        r_parse_eval0("x$a <- as.character(x$a)", R_ENVS.global).unwrap();
+        EVENTS.console_prompt.emit(());
+        assert_match!(socket.outgoing_rx.recv_timeout(std::time::Duration::from_secs(1)).unwrap(),
+            CommMsg::Data(value) => {
+                assert_match!(serde_json::from_value::<DataExplorerFrontendEvent>(value).unwrap(),
+                    DataExplorerFrontendEvent::SchemaUpdate
+                );
+        });
+        assert_eq!(column_names(&socket).1, layout);
+
+        // Adding a column resets the layout
+        r_parse_eval0("x$e <- 1:2", R_ENVS.global).unwrap();
+        EVENTS.console_prompt.emit(());
+        assert_match!(socket.outgoing_rx.recv_timeout(std::time::Duration::from_secs(1)).unwrap(),
+            CommMsg::Data(value) => {
+                assert_match!(serde_json::from_value::<DataExplorerFrontendEvent>(value).unwrap(),
+                    DataExplorerFrontendEvent::SchemaUpdate
+                );
+        });
+        assert_eq!(
+            column_names(&socket),
+            (
+                vec![
+                    String::from("a"),
+                    String::from("b"),
+                    String::from("c"),
+                    String::from("d"),
+                    String::from("e"),
+                ],
+                None
+            )
+        );
+
+        r_parse_eval0("rm(x)", R_ENVS.global).unwrap();
+    })
+}
@@ -6,13 +6,17 @@
 //
 
 use core::panic;
+use std::time::Duration;
 
 use amalthea::comm::comm_channel::CommMsg;
 use amalthea::comm::help_comm::HelpBackendReply;
 use amalthea::comm::help_comm::HelpBackendRequest;
+use amalthea::comm::help_comm::HelpFrontendEvent;
 use amalthea::comm::help_comm::ShowHelpTopicParams;
 use amalthea::socket::comm::CommInitiator;
 use amalthea::socket::comm::CommSocket;
+use ark::help::message::HelpEvent;
+use ark::help::message::ShowHelpUrlParams;
 use ark::help::r_help::RHelp;
 use ark::help_proxy;
 use ark::r_task::r_task;
@@ -33,15 +37,14 @@ fn test_help_comm() {
             String::from("positron.help"),
         );
 
+        let help_comm = comm.clone();
         let incoming_tx = comm.incoming_tx.clone();
         let outgoing_rx = comm.outgoing_rx.clone();
 
-        // Start the help comm. It's important to save the help event sender so
-        // that the help comm doesn't exit before we're done with it; allowing the
-        // sender to be dropped signals the help comm to exit.
+        // Start the help comm
         let r_port = RHelp::r_start_or_reconnect_to_help_server().unwrap();
         let proxy_port = help_proxy::start(r_port).unwrap();
-        let _help_event_tx = RHelp::start(comm, r_port, proxy_port).unwrap();
+        RHelp::start(comm, r_port, proxy_port).unwrap();
 
         // Utility function for testing `ShowHelpTopic` requests
         let test_topic = |topic: &str, id: &str| {
@@ -99,5 +102,32 @@ fn test_help_comm() {
             r_help_port
         );
         assert!(RHelp::is_help_url(url.as_str(), r_help_port));
+
+        // Help URLs sent by other threads are forwarded to the frontend
+        // through the proxy, without a reply to the frontend
+        let event = HelpEvent::ShowHelpUrl(ShowHelpUrlParams {
+            url: format!("http://127.0.0.1:{r_port}/library/base/html/plot.html"),
+        });
+        help_comm.notify(event).unwrap();
+
+        let response = outgoing_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        let CommMsg::Data(data) = response else {
+            panic!("Unexpected response from help comm: {:?}", response);
+        };
+        let HelpFrontendEvent::ShowHelp(params) = serde_json::from_value(data).unwrap();
+        assert_eq!(
+            params.content,
+            format!("http://127.0.0.1:{proxy_port}/library/base/html/plot.html")
+        );
+
+        // URLs that aren't help URLs are rejected with an error, for callers
+        // that wait for a reply
+        let event = HelpEvent::ShowHelpUrl(ShowHelpUrlParams {
+            url: String::from("https://www.example.com"),
+        });
+        assert!(help_comm
+            .rpc::<_, ()>(event, Duration::from_secs(1))
+            .is_err());
+        assert!(outgoing_rx.try_recv().is_err());
     })
 }
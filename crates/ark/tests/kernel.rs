@@ -0,0 +1,295 @@
+//
+// kernel.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use amalthea::comm::comm_channel::CommMsg;
+use amalthea::comm::variables_comm::VariablesFrontendEvent;
+use amalthea::wire::jupyter_message::Message;
+use amalthea::wire::jupyter_message::Status;
+use amalthea::wire::status::ExecutionState;
+use amalthea::wire::stream::Stream;
+use ark::r_task::r_task;
+use ark::test::TestExecution;
+use ark::test::TestKernel;
+use ark::variables::watch::schedule_watches;
+use ark::variables::watch::VariableWatches;
+use harp::environment::R_ENVS;
+use harp::object::RObject;
+use serde_json::json;
+
+// All checks live in a single test because R can only be started once per
+// process, and `shutdown()` makes it exit
+#[test]
+fn test_kernel_execute_round_trip() {
+    let kernel = TestKernel::start();
+
+    let execution = kernel.execute("1 + 1");
+    match execution.reply {
+        Message::ExecuteReply(reply) => assert_eq!(reply.content.status, Status::Ok),
+        msg => panic!("Unexpected reply: {msg:?}"),
+    }
+    let result = execution.iopub.iter().find_map(|msg| match msg {
+        Message::ExecuteResult(result) => Some(&result.content.data),
+        _ => None,
+    });
+    assert_eq!(result.unwrap()["text/plain"], "[1] 2");
+
+    let execution = kernel.execute("cat('hello\\n')");
+    assert!(execution.iopub.iter().any(|msg| match msg {
+        Message::StreamOutput(output) => output.content.text == "hello\n",
+        _ => false,
+    }));
+
+    // `stdout` and `stderr` outputs are tagged with their stream
+    let execution = kernel.execute("cat('out\\n'); message('err'); print(1L); warning('warn')");
+    let output = |stream: Stream| {
+        let mut text = String::new();
+        for msg in execution.iopub.iter() {
+            if let Message::StreamOutput(output) = msg {
+                if output.content.name == stream {
+                    text.push_str(&output.content.text);
+                }
+            }
+        }
+        text
+    };
+    let stdout = output(Stream::Stdout);
+    let stderr = output(Stream::Stderr);
+    assert!(stdout.contains("out\n"));
+    assert!(stdout.contains("[1] 1"));
+    assert!(!stdout.contains("err"));
+    assert!(stderr.contains("err\n"));
+    assert!(stderr.contains("warn"));
+    assert!(!stderr.contains("out"));
+
+    let execution = kernel.execute("stop('boom')");
+    match execution.reply {
+        Message::ExecuteReplyException(reply) => {
+            assert_eq!(reply.content.status, Status::Error);
+            assert!(reply.content.exception.evalue.contains("boom"));
+        },
+        msg => panic!("Unexpected reply: {msg:?}"),
+    }
+    assert!(execution
+        .iopub
+        .iter()
+        .any(|msg| matches!(msg, Message::ExecuteError(_))));
+
+    // `user_expressions` are evaluated after the code and returned in the reply
+    let mut req = TestKernel::execute_request("x <- 41");
+    req.user_expressions = json!({ "x": "x + 1", "bad": "stop('nope')" });
+    let execution = kernel.execute_with(req);
+    match execution.reply {
+        Message::ExecuteReply(reply) => {
+            let exprs = &reply.content.user_expressions;
+            assert_eq!(exprs["x"]["status"], "ok");
+            assert_eq!(exprs["x"]["data"]["text/plain"], "[1] 42");
+            assert_eq!(exprs["bad"]["status"], "error");
+            assert!(exprs["bad"]["evalue"].as_str().unwrap().contains("nope"));
+        },
+        msg => panic!("Unexpected reply: {msg:?}"),
+    }
+
+    // Input requests fail instead of hanging when `allow_stdin` is false
+    for code in ["readline('? ')", "readLines(stdin(), n = 1)"] {
+        let execution = kernel.execute(code);
+        assert!(matches!(execution.reply, Message::ExecuteReplyException(_)));
+    }
+
+    // Silent executions don't bump the execution counter nor broadcast
+    // their input and result
+    let count = |execution: &TestExecution| match &execution.reply {
+        Message::ExecuteReply(reply) => reply.content.execution_count,
+        msg => panic!("Unexpected reply: {msg:?}"),
+    };
+    let before = count(&kernel.execute("1"));
+
+    let mut req = TestKernel::execute_request("2");
+    req.silent = true;
+    req.store_history = false;
+    let execution = kernel.execute_with(req);
+    assert_eq!(count(&execution), before);
+    assert!(!execution
+        .iopub
+        .iter()
+        .any(|msg| matches!(msg, Message::ExecuteInput(_) | Message::ExecuteResult(_))));
+
+    let execution = kernel.execute("3");
+    assert_eq!(count(&execution), before + 1);
+    assert!(execution.iopub.iter().any(|msg| match msg {
+        Message::ExecuteInput(input) => input.content.execution_count == before + 1,
+        _ => false,
+    }));
+
+    // The session mode is exposed to R code
+    let execution = kernel.execute(".ps.session_mode()");
+    let result = execution.iopub.iter().find_map(|msg| match msg {
+        Message::ExecuteResult(result) => Some(&result.content.data),
+        _ => None,
+    });
+    assert_eq!(result.unwrap()["text/plain"], "[1] \"console\"");
+
+    // Idle callbacks run once, right away since R is waiting at the
+    // top-level prompt
+    let (tx, rx) = std::sync::mpsc::channel();
+    ark::interface::on_idle(move || tx.send(()).unwrap());
+    assert!(rx.recv_timeout(Duration::from_secs(5)).is_ok());
+
+    kernel.execute("1");
+    assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+
+    // A callback registered from a callback runs at the next prompt rather
+    // than right away, so callbacks rescheduling themselves run once per
+    // prompt
+    let (tx, rx) = std::sync::mpsc::channel();
+    fn reschedule(tx: std::sync::mpsc::Sender<()>) {
+        ark::interface::on_idle(move || {
+            if tx.send(()).is_ok() {
+                reschedule(tx);
+            }
+        });
+    }
+    reschedule(tx);
+    assert!(rx.recv_timeout(Duration::from_secs(5)).is_ok());
+    assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+
+    kernel.execute("1");
+    assert!(rx.recv_timeout(Duration::from_secs(5)).is_ok());
+    assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+
+    // Watches are evaluated exactly once per prompt
+    kernel.execute(".watch_count <- 0");
+    let watches = r_task(|| Arc::new(VariableWatches::new(RObject::view(R_ENVS.global))));
+    let watch = r_task(|| watches.add(String::from(".watch_count <- .watch_count + 1")));
+    assert_eq!(watch.unwrap().display_value, "1");
+
+    let (outgoing_tx, outgoing_rx) = crossbeam::channel::unbounded();
+    schedule_watches(watches.clone(), outgoing_tx);
+    let watch_update = || match outgoing_rx.recv_timeout(Duration::from_secs(5)) {
+        Ok(CommMsg::Data(data)) => match serde_json::from_value(data).unwrap() {
+            VariablesFrontendEvent::WatchUpdate(params) => params.watch.display_value,
+            event => panic!("Unexpected event: {event:?}"),
+        },
+        msg => panic!("Unexpected message: {msg:?}"),
+    };
+    assert_eq!(watch_update(), "2");
+    assert!(outgoing_rx
+        .recv_timeout(Duration::from_millis(200))
+        .is_err());
+
+    kernel.execute("1");
+    assert_eq!(watch_update(), "3");
+    assert!(outgoing_rx
+        .recv_timeout(Duration::from_millis(200))
+        .is_err());
+
+    watches.clear();
+    kernel.execute("1");
+    assert!(outgoing_rx
+        .recv_timeout(Duration::from_millis(200))
+        .is_err());
+
+    // Each page drawn within a single execution is recorded as its own plot
+    let execution = kernel.execute("for (i in 1:3) plot(i)");
+    assert_eq!(count_plots(&execution), 3);
+
+    // Auto-printed ggplots are captured, including when they are created in
+    // one execution and printed in a later one
+    let execution = kernel.execute("requireNamespace('ggplot2', quietly = TRUE)");
+    let has_ggplot2 = execution.iopub.iter().any(|msg| match msg {
+        Message::ExecuteResult(result) => result.content.data["text/plain"] == "[1] TRUE",
+        _ => false,
+    });
+    if has_ggplot2 {
+        let execution =
+            kernel.execute("library(ggplot2); p <- ggplot(mtcars, aes(mpg, wt)) + geom_point()");
+        assert_eq!(count_plots(&execution), 0);
+
+        let execution = kernel.execute("p");
+        assert_eq!(count_plots(&execution), 1);
+
+        let execution = kernel.execute("ggplot(mtcars, aes(mpg, wt)) + geom_point()");
+        assert_eq!(count_plots(&execution), 1);
+    }
+
+    // A burst of requests is executed in submission order, each wrapped in
+    // its own busy/idle pair
+    let before = count(&kernel.execute("1"));
+    let ids: Vec<String> = ["x <- 1", "Sys.sleep(0.2); x <- x * 10", "x + 1"]
+        .into_iter()
+        .map(|code| kernel.send_execute(code))
+        .collect();
+    for (i, id) in ids.iter().enumerate() {
+        let execution = kernel.receive_execution(id);
+        match &execution.reply {
+            Message::ExecuteReply(reply) => {
+                assert_eq!(reply.parent_header.as_ref().unwrap().msg_id, *id);
+                assert_eq!(reply.content.execution_count, before + 1 + i as u32);
+            },
+            msg => panic!("Unexpected reply: {msg:?}"),
+        }
+        assert!(matches!(
+            execution.iopub.first(),
+            Some(Message::Status(status)) if status.content.execution_state == ExecutionState::Busy
+        ));
+        if i == 2 {
+            let result = execution.iopub.iter().find_map(|msg| match msg {
+                Message::ExecuteResult(result) => Some(&result.content.data),
+                _ => None,
+            });
+            assert_eq!(result.unwrap()["text/plain"], "[1] 11");
+        }
+    }
+
+    // Custom continuation prompts are recognised when R asks for more input,
+    // including when they are identical to the input prompt
+    for options in [
+        "options(continue = '... ')",
+        "options(prompt = 'R> ', continue = 'R> ')",
+    ] {
+        kernel.execute(options);
+
+        let execution = kernel.execute("f <- function(x) {\n  x + 1\n}\nf(1)");
+        assert!(matches!(execution.reply, Message::ExecuteReply(_)));
+        assert_eq!(result_text(&execution).unwrap(), "[1] 2");
+
+        let execution = kernel.execute("1 +");
+        match execution.reply {
+            Message::ExecuteReplyException(reply) => {
+                assert_eq!(reply.content.exception.ename, "IncompleteInput")
+            },
+            msg => panic!("Unexpected reply: {msg:?}"),
+        }
+
+        // The next input completes the expression
+        let execution = kernel.execute("1");
+        assert!(matches!(execution.reply, Message::ExecuteReply(_)));
+        assert_eq!(result_text(&execution).unwrap(), "[1] 2");
+    }
+    kernel.execute("options(prompt = '> ', continue = '+ ')");
+
+    kernel.shutdown();
+}
+
+fn result_text(execution: &TestExecution) -> Option<String> {
+    execution.iopub.iter().find_map(|msg| match msg {
+        Message::ExecuteResult(result) => {
+            result.content.data["text/plain"].as_str().map(String::from)
+        },
+        _ => None,
+    })
+}
+
+fn count_plots(execution: &TestExecution) -> usize {
+    execution
+        .iopub
+        .iter()
+        .filter(|msg| matches!(msg, Message::DisplayData(_)))
+        .count()
+}
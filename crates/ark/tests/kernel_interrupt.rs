@@ -0,0 +1,43 @@
+//
+// kernel_interrupt.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::time::Duration;
+use std::time::Instant;
+
+use amalthea::wire::jupyter_message::Message;
+use ark::test::TestKernel;
+
+// Lives in its own file because R can only be started once per process
+#[test]
+fn test_kernel_interrupt() {
+    let kernel = TestKernel::start();
+
+    // Interrupting an idle kernel is acknowledged and has no effect
+    kernel.interrupt();
+    let execution = kernel.execute("1");
+    assert!(matches!(execution.reply, Message::ExecuteReply(_)));
+
+    let start = Instant::now();
+    let id = kernel.send_execute("Sys.sleep(30)");
+
+    // Give R some time to start sleeping
+    std::thread::sleep(Duration::from_millis(500));
+    kernel.interrupt();
+
+    let execution = kernel.receive_execution(&id);
+    assert!(start.elapsed() < Duration::from_secs(20));
+    assert!(matches!(
+        execution.reply,
+        Message::ExecuteReply(_) | Message::ExecuteReplyException(_)
+    ));
+
+    // The kernel is still responsive afterwards
+    let execution = kernel.execute("1 + 1");
+    assert!(matches!(execution.reply, Message::ExecuteReply(_)));
+
+    kernel.shutdown();
+}
@@ -0,0 +1,63 @@
+//
+// r_task.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::time::Duration;
+
+use ark::r_task::r_task;
+use ark::r_task::r_task_async;
+use ark::r_task::r_task_timeout;
+use ark::r_task::RTaskError;
+use ark::test::TestKernel;
+
+// Lives in its own file because R can only be started once per process.
+// Unlike `r_test()`, the kernel runs tasks on the actual R thread.
+#[test]
+fn test_r_task_reentrant() {
+    let kernel = TestKernel::start();
+
+    // A task requested from within a task runs inline instead of deadlocking
+    let value = r_task(|| r_task(|| 42));
+    assert_eq!(value, 42);
+
+    // A task with a timeout can't be honoured from the R thread and is
+    // reported as reentrant
+    let result = r_task(|| r_task_timeout(|| 42, Duration::from_secs(1)));
+    assert!(matches!(result, Err(RTaskError::Reentrant)));
+
+    // Outside of the R thread, the task is queued as usual
+    let result = r_task_timeout(|| 42, Duration::from_secs(5));
+    assert!(matches!(result, Ok(42)));
+
+    // An async task can be awaited from another thread
+    let value = futures::executor::block_on(r_task_async(|| 42));
+    assert_eq!(value, Ok(42));
+
+    // Dropping the receiver, whether the task is still queued or already
+    // running, doesn't block the R thread. The result is discarded.
+    drop(r_task_async(|| {
+        std::thread::sleep(Duration::from_millis(200))
+    }));
+    drop(r_task_async(|| 42));
+    let result = r_task_timeout(|| 42, Duration::from_secs(5));
+    assert!(matches!(result, Ok(42)));
+
+    // Same when a future awaiting the task is cancelled before completion
+    let receiver = r_task_async(|| {
+        std::thread::sleep(Duration::from_millis(200));
+        42
+    });
+    let cancelled = futures::executor::block_on(futures::future::select(
+        receiver,
+        futures::future::ready(()),
+    ));
+    assert!(matches!(cancelled, futures::future::Either::Right(_)));
+    drop(cancelled);
+    let result = r_task_timeout(|| 42, Duration::from_secs(5));
+    assert!(matches!(result, Ok(42)));
+
+    kernel.shutdown();
+}
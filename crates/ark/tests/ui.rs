@@ -9,6 +9,9 @@ use amalthea::comm::base_comm::JsonRpcError;
 use amalthea::comm::comm_channel::CommMsg;
 use amalthea::comm::ui_comm::BusyParams;
 use amalthea::comm::ui_comm::CallMethodParams;
+use amalthea::comm::ui_comm::DidChangeThemeParams;
+use amalthea::comm::ui_comm::DidChangeThemeTheme;
+use amalthea::comm::ui_comm::SetWorkingDirectoryParams;
 use amalthea::comm::ui_comm::UiBackendReply;
 use amalthea::comm::ui_comm::UiBackendRequest;
 use amalthea::comm::ui_comm::UiFrontendEvent;
@@ -142,3 +145,104 @@ fn test_ui_comm() {
             .unwrap();
     });
 }
+
+#[test]
+fn test_ui_comm_set_working_directory() {
+    r_test(|| {
+        let comm_socket = CommSocket::new(
+            CommInitiator::FrontEnd,
+            String::from("test-ui-comm-wd-id"),
+            String::from("positron.UI"),
+        );
+        let (stdin_request_tx, _stdin_request_rx) = bounded::<StdInRequest>(1);
+        let _ui_comm = UiComm::start(comm_socket.clone(), stdin_request_tx);
+
+        let old_dir = std::env::current_dir().unwrap();
+        let new_dir = std::env::temp_dir().canonicalize().unwrap();
+
+        let request = UiBackendRequest::SetWorkingDirectory(SetWorkingDirectoryParams {
+            directory: new_dir.to_string_lossy().to_string(),
+        });
+        comm_socket
+            .incoming_tx
+            .send(CommMsg::Rpc(
+                String::from("test-id-wd"),
+                serde_json::to_value(request).unwrap(),
+            ))
+            .unwrap();
+
+        let response = comm_socket
+            .outgoing_rx
+            .recv_timeout(std::time::Duration::from_secs(1))
+            .unwrap();
+
+        let result = std::env::current_dir().unwrap();
+        std::env::set_current_dir(old_dir).unwrap();
+
+        let CommMsg::Rpc(id, reply) = response else {
+            panic!("Unexpected response: {:?}", response);
+        };
+        assert_eq!(id, "test-id-wd");
+        assert_eq!(result, new_dir);
+
+        let reply = serde_json::from_value::<UiBackendReply>(reply).unwrap();
+        let UiBackendReply::SetWorkingDirectoryReply(directory) = reply else {
+            panic!("Unexpected reply: {:?}", reply);
+        };
+        assert_eq!(std::path::PathBuf::from(directory), new_dir);
+    });
+}
+
+#[test]
+fn test_ui_comm_did_change_theme() {
+    r_test(|| {
+        let comm_socket = CommSocket::new(
+            CommInitiator::FrontEnd,
+            String::from("test-ui-comm-theme-id"),
+            String::from("positron.UI"),
+        );
+        let (stdin_request_tx, _stdin_request_rx) = bounded::<StdInRequest>(1);
+        let _ui_comm = UiComm::start(comm_socket.clone(), stdin_request_tx);
+
+        let theme = || {
+            r_task(|| {
+                let option = RFunction::new("base", "getOption")
+                    .add("ark.theme")
+                    .call()
+                    .unwrap();
+                let accessor = RFunction::new("", ".ps.ui.theme").call().unwrap();
+                (
+                    Option::<String>::try_from(option).unwrap(),
+                    Option::<String>::try_from(accessor).unwrap(),
+                )
+            })
+        };
+
+        for (value, name) in [
+            (DidChangeThemeTheme::Dark, "dark"),
+            (DidChangeThemeTheme::Light, "light"),
+        ] {
+            let request = UiBackendRequest::DidChangeTheme(DidChangeThemeParams { theme: value });
+            comm_socket
+                .incoming_tx
+                .send(CommMsg::Rpc(
+                    String::from("test-id-theme"),
+                    serde_json::to_value(request).unwrap(),
+                ))
+                .unwrap();
+
+            let response = comm_socket
+                .outgoing_rx
+                .recv_timeout(std::time::Duration::from_secs(1))
+                .unwrap();
+            let CommMsg::Rpc(_, reply) = response else {
+                panic!("Unexpected response: {:?}", response);
+            };
+            let reply = serde_json::from_value::<UiBackendReply>(reply).unwrap();
+            assert_eq!(reply, UiBackendReply::DidChangeThemeReply());
+
+            let name = Some(String::from(name));
+            assert_eq!(theme(), (name.clone(), name));
+        }
+    });
+}
@@ -95,8 +95,15 @@ pub fn vector(_attr: TokenStream, item: TokenStream) -> TokenStream {
                 self.index = self.index + 1;
                 Some(item)
             }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                let remaining = (self.size - self.index) as usize;
+                (remaining, Some(remaining))
+            }
         }
 
+        impl<'a> std::iter::ExactSizeIterator for VectorIter<'a> {}
+
         impl #ident {
             pub fn iter(&self) -> VectorIter<'_> {
                 let size = unsafe { self.len() as isize };
@@ -7,6 +7,7 @@
 
 use std::cmp::min;
 
+use libr::R_IsNA;
 use libr::R_NamesSymbol;
 use libr::Rf_allocVector;
 use libr::Rf_setAttrib;
@@ -53,6 +54,11 @@ use crate::object::RObject;
 ///           {"a": 1, "b": true, "c": "applesauce"}
 /// - Named lists with duplicate keys have the values combined into an array
 ///   - e.g.: list(a = 1L, a = 2L, a = 3L) -> {"a": [1, 2, 3]}
+/// - Missing values of any type become JSON null values
+///   - e.g.: c(1L, NA) -> [1, null], NA_character_ -> null
+/// - Non-finite doubles, which JSON can't represent, become sentinel strings
+///   (see `JSON_NAN`, `JSON_INF`, and `JSON_NEG_INF`)
+///   - e.g.: c(1, NaN, Inf, -Inf) -> [1, "NaN", "Inf", "-Inf"]
 impl TryFrom<RObject> for Value {
     type Error = crate::error::Error;
     fn try_from(obj: RObject) -> Result<Self, Self::Error> {
@@ -70,9 +76,9 @@ impl TryFrom<RObject> for Value {
                 0 => Ok(Value::Null),
 
                 // A single integer becomes a JSON number
-                1 => {
-                    let value = unsafe { obj.to::<i32>()? };
-                    Ok(Value::Number(value.into()))
+                1 => match obj.get_i32(0)? {
+                    Some(value) => Ok(Value::Number(value.into())),
+                    None => Ok(Value::Null),
                 },
 
                 // Multiple integers become integer vectors
@@ -95,22 +101,14 @@ impl TryFrom<RObject> for Value {
                 0 => Ok(Value::Null),
 
                 // A single value becomes a JSON number
-                1 => {
-                    let value = unsafe { obj.to::<f64>()? };
-                    // There's no try/into implicit conversion from f64 to a
-                    // JSON number, but json! handles it.
-                    Ok(json!(value))
-                },
+                1 => Ok(f64_to_json(obj.get_f64(0)?)),
 
                 // Multiple values become a vector
                 _ => {
                     let mut arr = Vec::<Value>::with_capacity(obj.length().try_into().unwrap());
                     let n = obj.length();
                     for i in 0..n {
-                        arr.push(match obj.get_f64(i)? {
-                            Some(value) => value.into(),
-                            None => Value::Null,
-                        });
+                        arr.push(f64_to_json(obj.get_f64(i)?));
                     }
                     Ok(serde_json::Value::Array(arr))
                 },
@@ -122,9 +120,9 @@ impl TryFrom<RObject> for Value {
                 0 => Ok(Value::Null),
 
                 // A single value becomes a JSON true/false value
-                1 => {
-                    let value = unsafe { obj.to::<bool>()? };
-                    Ok(Value::Bool(value))
+                1 => match obj.get_bool(0)? {
+                    Some(value) => Ok(Value::Bool(value)),
+                    None => Ok(Value::Null),
                 },
 
                 // Multiple values become a vector
@@ -158,9 +156,9 @@ impl TryFrom<RObject> for Value {
                 0 => Ok(Value::Null),
 
                 // With exactly one value, convert to a string
-                1 => {
-                    let str = unsafe { obj.to::<String>()? };
-                    Ok(Value::String(str))
+                1 => match obj.get_string(0)? {
+                    Some(str) => Ok(Value::String(str)),
+                    None => Ok(Value::Null),
                 },
 
                 // With multiple values, convert to a string array
@@ -291,6 +289,44 @@ impl TryFrom<RObject> for Value {
     }
 }
 
+/// Sentinel strings for the doubles that JSON numbers can't represent.
+pub const JSON_NAN: &str = "NaN";
+pub const JSON_INF: &str = "Inf";
+pub const JSON_NEG_INF: &str = "-Inf";
+
+/// Converts an element of a double vector to JSON. `NA` becomes `null` and
+/// other non-finite values become sentinel strings.
+fn f64_to_json(value: Option<f64>) -> Value {
+    let Some(value) = value else {
+        return Value::Null;
+    };
+
+    if value.is_nan() {
+        // `get_f64()` only catches the canonical NA payload
+        if unsafe { R_IsNA(value) } != 0 {
+            return Value::Null;
+        }
+        return Value::String(JSON_NAN.to_string());
+    }
+
+    if value.is_infinite() {
+        let sentinel = if value > 0.0 { JSON_INF } else { JSON_NEG_INF };
+        return Value::String(sentinel.to_string());
+    }
+
+    json!(value)
+}
+
+/// The inverse of the sentinel strings of `f64_to_json()`.
+fn json_sentinel_to_f64(value: &str) -> Option<f64> {
+    match value {
+        JSON_NAN => Some(f64::NAN),
+        JSON_INF => Some(f64::INFINITY),
+        JSON_NEG_INF => Some(f64::NEG_INFINITY),
+        _ => None,
+    }
+}
+
 /**
  * Convert a JSON number value to an R object.
  */
@@ -318,10 +354,23 @@ impl TryFrom<Vec<Value>> for RObject {
         // Consider: currently, this creates an unnamed list. It would be
         // better, presuming that the values are all the same type, to create an
         // atomic vector of that type.
+
+        // Sentinel strings for non-finite doubles are only ambiguous with
+        // regular strings on their own. Alongside numbers, we take them to
+        // come from a double vector and convert them back.
+        let has_numbers = vals.iter().any(|val| val.is_number());
+
         unsafe {
             let list = RObject::from(Rf_allocVector(VECSXP, vals.len() as isize));
             for (i, val) in vals.iter().enumerate() {
-                let val = RObject::try_from(val.clone())?;
+                let sentinel = match val {
+                    Value::String(str) if has_numbers => json_sentinel_to_f64(str),
+                    _ => None,
+                };
+                let val = match sentinel {
+                    Some(value) => RObject::from(value),
+                    None => RObject::try_from(val.clone())?,
+                };
                 SET_VECTOR_ELT(list.sexp, i as isize, val.sexp);
             }
             return Ok(list);
@@ -464,6 +513,32 @@ mod tests {
         }
     }
 
+    #[test]
+    #[allow(non_snake_case)]
+    fn test_json_na_scalars() {
+        // Missing values of length one serialize to null rather than failing
+        r_test! {
+            assert_r_matches_json("NA", "null");
+            assert_r_matches_json("NA_integer_", "null");
+            assert_r_matches_json("NA_real_", "null");
+            assert_r_matches_json("NA_character_", "null");
+        }
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn test_json_non_finite_doubles() {
+        // NA is null, the other non-finite values are sentinel strings
+        r_test! {
+            assert_r_matches_json(
+                "c(1.5, NA, NaN, Inf, -Inf)",
+                "[1.5, null, \"NaN\", \"Inf\", \"-Inf\"]"
+            );
+            assert_r_matches_json("NaN", "\"NaN\"");
+            assert_r_matches_json("-Inf", "\"-Inf\"");
+        }
+    }
+
     #[test]
     #[allow(non_snake_case)]
     fn test_json_lists_unnamed() {
@@ -586,6 +661,23 @@ mod tests {
         }
     }
 
+    #[test]
+    #[allow(non_snake_case)]
+    fn test_r_to_json_non_finite_doubles() {
+        r_test! {
+            // Sentinels alongside numbers are converted back to doubles
+            assert_json_matches_r(
+                "[1.5, null, \"NaN\", \"Inf\", \"-Inf\"]",
+                "list(1.5, NULL, NaN, Inf, -Inf)");
+
+            // Otherwise they are regular strings
+            assert_json_matches_r(
+                "[\"NaN\", \"Inf\"]",
+                "list(\"NaN\", \"Inf\")");
+            assert_json_matches_r("\"Inf\"", "\"Inf\"");
+        }
+    }
+
     #[test]
     #[allow(non_snake_case)]
     fn test_r_to_json_objects() {
@@ -284,6 +284,13 @@ pub fn r_node_cdr(x: SEXP) -> SEXP {
 }
 
 impl RObject {
+    /// Takes ownership of `data`, protecting it until the `RObject` is
+    /// dropped.
+    ///
+    /// Protection goes through the precious list rather than the protect
+    /// stack, so `RObject`s can be dropped in any order. Allocate and wrap
+    /// in one step (`RObject::new(Rf_allocVector(..))`), and never hold a
+    /// bare SEXP across another allocation.
     pub unsafe fn new(data: SEXP) -> Self {
         RObject {
             sexp: data,
@@ -424,14 +431,35 @@ impl RObject {
     /// Gets a vector containing names for the object's values (from the `names`
     /// attribute). Returns `None` if the object's value(s) don't have names.
     pub fn names(&self) -> Option<Vec<Option<String>>> {
-        let names = unsafe { Rf_getAttrib(self.sexp, R_NamesSymbol) };
-        let names = RObject::view(names);
+        // Protected since the names of pairlists are allocated on the fly
+        let names = unsafe { RObject::new(Rf_getAttrib(self.sexp, R_NamesSymbol)) };
         match names.kind() {
             STRSXP => Vec::<Option<String>>::try_from(names).ok(),
             _ => None,
         }
     }
 
+    /// Gets the classes of the object (from the `class` attribute). Returns an
+    /// empty vector if the object doesn't have a `class` attribute, e.g. for
+    /// bare vectors and matrices.
+    pub fn class(&self) -> Vec<String> {
+        let class = unsafe { RObject::new(Rf_getAttrib(self.sexp, R_ClassSymbol)) };
+        match class.kind() {
+            STRSXP => Vec::<String>::try_from(class).unwrap_or_default(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Gets the dimensions of the object (from the `dim` attribute). Returns
+    /// `None` if the object doesn't have dimensions, e.g. for vectors.
+    pub fn dim(&self) -> Option<Vec<i32>> {
+        let dim = unsafe { RObject::new(Rf_getAttrib(self.sexp, R_DimSymbol)) };
+        match dim.kind() {
+            INTSXP => Vec::<i32>::try_from(dim).ok(),
+            _ => None,
+        }
+    }
+
     /// Gets a named attribute from the object. Returns `None` if the attribute
     /// doesn't exist.
     pub fn attr(&self, name: &str) -> Option<RObject> {
@@ -877,6 +905,30 @@ impl TryFrom<RObject> for Vec<i32> {
     }
 }
 
+impl TryFrom<RObject> for Vec<f64> {
+    type Error = crate::error::Error;
+    fn try_from(value: RObject) -> Result<Self, Self::Error> {
+        unsafe {
+            r_assert_type(value.sexp, &[REALSXP, NILSXP])?;
+            if r_is_null(value.sexp) {
+                return Ok(Vec::new());
+            }
+
+            let n = Rf_xlength(value.sexp);
+            let mut result: Vec<f64> = Vec::with_capacity(n as usize);
+            for i in 0..n {
+                let res = REAL_ELT(value.sexp, i);
+                if R_IsNA(res) != 0 {
+                    return Err(Error::MissingValueError);
+                }
+                result.push(res);
+            }
+
+            return Ok(result);
+        }
+    }
+}
+
 impl TryFrom<RObject> for Vec<RObject> {
     type Error = crate::error::Error;
     fn try_from(value: RObject) -> Result<Self, Self::Error> {
@@ -1575,6 +1627,58 @@ mod tests {
                 Vec::<String>::try_from(s),
                 Err(Error::MissingValueError) => {}
             );
+
+            let i = RObject::from(Rf_allocVector(INTSXP, 1));
+            assert_match!(
+                Vec::<String>::try_from(i),
+                Err(Error::UnexpectedType(actual, expected)) => {
+                    assert_eq!(actual, INTSXP);
+                    assert_eq!(expected, vec![STRSXP, NILSXP]);
+                }
+            );
+        }
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn test_tryfrom_RObject_Vec_f64() {
+        r_test! {
+            let x = RObject::from(Rf_allocVector(REALSXP, 3));
+            SET_REAL_ELT(*x, 0, 1.5);
+            SET_REAL_ELT(*x, 1, f64::INFINITY);
+            SET_REAL_ELT(*x, 2, -2.0);
+
+            assert_match!(
+                Vec::<f64>::try_from(x),
+                Ok(x) => {
+                    assert_eq!(x, vec![1.5, f64::INFINITY, -2.0]);
+                }
+            );
+
+            let y = RObject::from(Rf_allocVector(REALSXP, 2));
+            SET_REAL_ELT(*y, 0, 1.0);
+            SET_REAL_ELT(*y, 1, R_NaReal);
+            assert_match!(
+                Vec::<f64>::try_from(y),
+                Err(Error::MissingValueError) => {}
+            );
+
+            assert_match!(
+                RObject::null().to::<Vec<f64>>(),
+                Ok(x) => {
+                    assert!(x.is_empty());
+                }
+            );
+
+            // Integer vectors are not silently coerced
+            let i = RObject::from(Rf_allocVector(INTSXP, 1));
+            assert_match!(
+                i.to::<Vec<f64>>(),
+                Err(Error::UnexpectedType(actual, expected)) => {
+                    assert_eq!(actual, INTSXP);
+                    assert_eq!(expected, vec![REALSXP, NILSXP]);
+                }
+            );
         }
     }
 
@@ -1602,6 +1706,14 @@ mod tests {
                     assert_eq!(x, vec![1i32, 2, 3]);
                 }
             );
+
+            let d = RObject::from(Rf_allocVector(REALSXP, 1));
+            assert_match!(
+                Vec::<i32>::try_from(d),
+                Err(Error::UnexpectedType(actual, _)) => {
+                    assert_eq!(actual, REALSXP);
+                }
+            );
         }
     }
 
@@ -1644,4 +1756,58 @@ mod tests {
             assert_eq!(items_in, items_out);
         }
     }
+
+    #[test]
+    fn test_attributes_matrix() {
+        r_test! {
+            let x = r_parse_eval0("matrix(1:6, nrow = 2)", R_ENVS.global).unwrap();
+            assert_eq!(x.dim(), Some(vec![2, 3]));
+            assert_eq!(x.names(), None);
+            assert!(x.class().is_empty());
+            assert!(x.attr("dim").is_some());
+            assert!(x.attr("foo").is_none());
+
+            let x = r_parse_eval0("1:3", R_ENVS.global).unwrap();
+            assert_eq!(x.dim(), None);
+        }
+    }
+
+    #[test]
+    fn test_attributes_named_list() {
+        r_test! {
+            let x = r_parse_eval0("list(a = 1, 2, c = 3)", R_ENVS.global).unwrap();
+            assert_eq!(
+                x.names(),
+                Some(vec![Some(String::from("a")), Some(String::new()), Some(String::from("c"))])
+            );
+            assert_eq!(x.dim(), None);
+
+            let x = r_parse_eval0("stats::setNames(1:2, c('a', NA))", R_ENVS.global).unwrap();
+            assert_eq!(x.names(), Some(vec![Some(String::from("a")), None]));
+
+            // The names of pairlists are computed on access
+            let x = r_parse_eval0("as.pairlist(list(a = 1, b = 2))", R_ENVS.global).unwrap();
+            assert_eq!(x.names(), Some(vec![Some(String::from("a")), Some(String::from("b"))]));
+        }
+    }
+
+    #[test]
+    fn test_attributes_classed_object() {
+        r_test! {
+            let x = r_parse_eval0(
+                "structure(list(), class = c('foo', 'bar'), extra = 'value')",
+                R_ENVS.global,
+            )
+            .unwrap();
+            assert_eq!(x.class(), vec![String::from("foo"), String::from("bar")]);
+            assert_eq!(x.names(), None);
+
+            let extra = x.attr("extra").unwrap();
+            assert_eq!(String::try_from(extra).unwrap(), "value");
+            assert!(x.attr("missing").is_none());
+
+            let x = r_parse_eval0("factor(c('a', 'b'))", R_ENVS.global).unwrap();
+            assert_eq!(x.class(), vec![String::from("factor")]);
+        }
+    }
 }
@@ -13,6 +13,11 @@ use libr::SEXP;
 // only appropriate for R objects with 'automatic' lifetime. In general, this
 // should only be used when interfacing with native R APIs; general usages
 // should use the RObject struct instead.
+//
+// The guard keeps a count of the objects it protected and pops exactly that
+// many on drop, so the protect stack stays balanced as long as guards are
+// dropped in reverse order of creation (which Rust scoping guarantees for
+// local bindings). Don't move a guard out of the scope that created it.
 pub struct RProtect {
     count: i32,
 }
@@ -36,3 +41,93 @@ impl Drop for RProtect {
         unsafe { Rf_unprotect(self.count) }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use libr::R_gc;
+    use libr::Rf_allocVector;
+    use libr::SET_STRING_ELT;
+    use libr::SET_VECTOR_ELT;
+    use libr::STRING_ELT;
+    use libr::STRSXP;
+    use libr::VECSXP;
+    use libr::VECTOR_ELT;
+
+    use super::RProtect;
+    use crate::object::RObject;
+    use crate::r_char;
+    use crate::r_test;
+    use crate::utils::r_str_to_owned_utf8_unchecked;
+    use crate::vector::CharacterVector;
+    use crate::vector::IntegerVector;
+    use crate::vector::NumericVector;
+    use crate::vector::Vector;
+
+    #[test]
+    fn test_protect_survives_gc() {
+        r_test! {
+            let mut objects: Vec<(NumericVector, IntegerVector, CharacterVector)> = Vec::new();
+
+            for i in 0..500 {
+                let x = i as f64;
+                let n = i as i32;
+                let s = format!("item-{i}");
+
+                objects.push((
+                    NumericVector::create([x, x + 0.5]),
+                    IntegerVector::create([n, n + 1]),
+                    CharacterVector::create([s.as_str(), "tail"]),
+                ));
+
+                if i % 25 == 0 {
+                    R_gc();
+                }
+            }
+
+            R_gc();
+
+            for (i, (dbl, int, chr)) in objects.iter().enumerate() {
+                assert_eq!(dbl.get_value(1).unwrap(), i as f64 + 0.5);
+                assert_eq!(int.get_value(1).unwrap(), i as i32 + 1);
+                assert_eq!(chr.get_value(0).unwrap(), format!("item-{i}"));
+            }
+
+            // Dropping in an arbitrary order must not unbalance anything.
+            let mut objects = objects;
+            while !objects.is_empty() {
+                objects.swap_remove(objects.len() / 2);
+                if objects.len() % 50 == 0 {
+                    R_gc();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_rprotect_survives_gc() {
+        r_test! {
+            let mut protect = RProtect::new();
+
+            let list = protect.add(Rf_allocVector(VECSXP, 100));
+            for i in 0..100 {
+                // Each allocation may trigger a collection of unprotected objects
+                let chr = protect.add(Rf_allocVector(STRSXP, 1));
+                SET_STRING_ELT(chr, 0, r_char!(format!("{i}")));
+                SET_VECTOR_ELT(list, i, chr);
+                R_gc();
+            }
+
+            for i in 0..100 {
+                let chr = VECTOR_ELT(list, i);
+                let value = r_str_to_owned_utf8_unchecked(STRING_ELT(chr, 0));
+                assert_eq!(value, format!("{i}"));
+            }
+
+            // RObject protection is independent from the protect stack
+            let object = RObject::new(Rf_allocVector(VECSXP, 1));
+            drop(protect);
+            R_gc();
+            assert_eq!(object.length(), 1);
+        }
+    }
+}
@@ -10,8 +10,16 @@ use libr::R_getEmbeddingDllInfo;
 use libr::R_registerRoutines;
 use log::error;
 
+// All the native routines of the process, collected from every crate
+// linked into the binary. This is the single `.Call` table installed by
+// `r_register_routines()`, so a new `ps_*` function only needs the
+// `#[harp::register]` attribute to be callable from R.
 static mut R_ROUTINES: Vec<R_CallMethodDef> = vec![];
 
+// The table handed over to R, terminated by an "empty" routine. R keeps
+// a pointer to it so it must outlive the session.
+static mut R_ROUTINES_TABLE: Vec<R_CallMethodDef> = vec![];
+
 // NOTE: This function is used via the #[harp::register] macro,
 // which ensures that routines are initialized and executed on
 // application startup.
@@ -19,6 +27,8 @@ pub unsafe fn add(def: R_CallMethodDef) {
     R_ROUTINES.push(def);
 }
 
+/// Install all routines collected by `#[harp::register]` in the embedding
+/// DLL. Safe to call more than once: the table is rebuilt from scratch.
 pub unsafe fn r_register_routines() {
     let info = R_getEmbeddingDllInfo();
     if info.is_null() {
@@ -26,17 +36,21 @@ pub unsafe fn r_register_routines() {
         return;
     }
 
+    let mut table = R_ROUTINES.clone();
+
     // Make sure we have an "empty" routine at the end.
-    R_ROUTINES.push(R_CallMethodDef {
+    table.push(R_CallMethodDef {
         name: std::ptr::null(),
         fun: None,
         numArgs: 0,
     });
 
+    R_ROUTINES_TABLE = table;
+
     R_registerRoutines(
         info,
         std::ptr::null(),
-        R_ROUTINES.as_ptr(),
+        R_ROUTINES_TABLE.as_ptr(),
         std::ptr::null(),
         std::ptr::null(),
     );
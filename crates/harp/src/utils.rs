@@ -230,9 +230,16 @@ pub fn r_str_to_owned_utf8(x: SEXP) -> Result<String> {
 ///
 /// Unchecked here only refers to checking for `NA`. Otherwise it still attempts
 /// a UTF-8 translation and will replace any remaining invalid UTF-8 characters
-/// with the UTF-8 replacement character.
+/// with the UTF-8 replacement character. Strings marked as `"bytes"`, which R
+/// refuses to translate, have their non-ASCII bytes escaped as `\xNN` like R
+/// prints them.
 pub fn r_str_to_owned_utf8_unchecked(x: SEXP) -> String {
     unsafe {
+        if Rf_getCharCE(x) == cetype_t_CE_BYTES {
+            let bytes = std::slice::from_raw_parts(R_CHAR(x) as *const u8, Rf_xlength(x) as usize);
+            return escape_bytes(bytes);
+        }
+
         // Attempt to translate it to a UTF-8 C string (note that this allocates
         // with `R_alloc()` so we need to save and reset the protection stack).
         // Sadly this can still result in invalid UTF-8 bytes, so we are forced
@@ -252,6 +259,18 @@ pub fn r_str_to_owned_utf8_unchecked(x: SEXP) -> String {
     }
 }
 
+fn escape_bytes(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len());
+    for byte in bytes {
+        if byte.is_ascii() {
+            out.push(*byte as char);
+        } else {
+            out.push_str(&format!("\\x{byte:02x}"));
+        }
+    }
+    out
+}
+
 pub fn pairlist_size(mut pairlist: SEXP) -> Result<isize> {
     let mut n = 0;
     unsafe {
@@ -798,4 +817,23 @@ mod tests {
             assert_eq!(x, String::from(std::char::REPLACEMENT_CHARACTER));
         })
     }
+
+    #[test]
+    fn test_r_str_to_utf8_honours_encoding() {
+        r_test(|| {
+            let to_utf8 = |code: &str| {
+                let x = r_parse_eval0(code, R_ENVS.base).unwrap();
+                let x = unsafe { STRING_ELT(x.sexp, 0) };
+                r_str_to_owned_utf8_unchecked(x)
+            };
+
+            // Latin-1 strings are translated
+            let code = "iconv('caf\\u00e9', from = 'UTF-8', to = 'latin1')";
+            assert_eq!(to_utf8(code), "café");
+
+            // Bytes can't be translated so are escaped instead of failing
+            let code = "local({ x <- 'caf\\xe9'; Encoding(x) <- 'bytes'; x })";
+            assert_eq!(to_utf8(code), "caf\\xe9");
+        })
+    }
 }
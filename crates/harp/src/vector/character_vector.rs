@@ -18,6 +18,7 @@ use libr::STRSXP;
 
 use crate::object::RObject;
 use crate::utils::r_str_to_owned_utf8_unchecked;
+use crate::vector::AsVectorItem;
 use crate::vector::Vector;
 
 #[harp_macros::vector]
@@ -46,7 +47,7 @@ impl Vector for CharacterVector {
     where
         T: IntoIterator,
         <T as IntoIterator>::IntoIter: ExactSizeIterator,
-        <T as IntoIterator>::Item: AsRef<Self::Item>,
+        <T as IntoIterator>::Item: AsVectorItem<Self::Item>,
     {
         // convert into iterator
         let mut data = data.into_iter();
@@ -56,7 +57,7 @@ impl Vector for CharacterVector {
         let vector = CharacterVector::with_length(n);
         for i in 0..data.len() {
             let value = data.next().unwrap_unchecked();
-            let value = value.as_ref();
+            let value = value.as_vector_item();
             let charsexp = Rf_mkCharLenCE(
                 value.as_ptr() as *const c_char,
                 value.len() as i32,
@@ -80,15 +81,24 @@ impl Vector for CharacterVector {
         r_str_to_owned_utf8_unchecked(*x)
     }
 
+    /// Strings are quoted like `print()` does, so that they can be told apart
+    /// from the unquoted `NA`.
     fn format_one(&self, x: Self::Type) -> String {
-        x
+        quote_string(&x)
     }
 }
 
+/// Quotes a string with double quotes, escaping backslashes and quotes.
+pub fn quote_string(x: &str) -> String {
+    format!("\"{}\"", x.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
 #[cfg(test)]
 mod test {
     use libr::STRSXP;
 
+    use crate::environment::R_ENVS;
+    use crate::eval::r_parse_eval0;
     use crate::r_test;
     use crate::utils::r_typeof;
     use crate::vector::*;
@@ -149,4 +159,24 @@ mod test {
 
         }
     }
+
+    #[test]
+    fn test_character_vector_na() {
+        r_test! {
+            let x = r_parse_eval0(r#"c("a", NA, "NA", 'say "hi"')"#, R_ENVS.global).unwrap();
+            let vector = CharacterVector::new(x).unwrap();
+
+            assert_eq!(vector.get(1).unwrap(), None);
+            assert_eq!(vector.get(2).unwrap(), Some(String::from("NA")));
+
+            // Strings are quoted, `NA` isn't
+            let formatted: Vec<String> = (0..4).map(|i| vector.format_elt_unchecked(i)).collect();
+            assert_eq!(formatted, vec![
+                r#""a""#,
+                "NA",
+                r#""NA""#,
+                r#""say \"hi\"""#
+            ]);
+        }
+    }
 }
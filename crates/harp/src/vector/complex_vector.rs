@@ -8,13 +8,13 @@
 use libr::R_IsNA;
 use libr::R_xlen_t;
 use libr::Rcomplex;
-use libr::Rf_allocVector;
 use libr::COMPLEX_ELT;
 use libr::CPLXSXP;
 use libr::DATAPTR;
 use libr::SEXP;
 
 use crate::object::RObject;
+use crate::vector::AsVectorItem;
 use crate::vector::Vector;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -51,18 +51,18 @@ impl Vector for ComplexVector {
     where
         T: IntoIterator,
         <T as IntoIterator>::IntoIter: ExactSizeIterator,
-        <T as IntoIterator>::Item: AsRef<Self::Item>,
+        <T as IntoIterator>::Item: AsVectorItem<Self::Item>,
     {
         let it = data.into_iter();
         let count = it.len();
 
-        let vector = Rf_allocVector(Self::SEXPTYPE, count as R_xlen_t);
-        let dataptr = DATAPTR(vector) as *mut Self::Type;
+        let vector = Self::with_length(count);
+        let dataptr = DATAPTR(vector.data()) as *mut Self::Type;
         it.enumerate().for_each(|(index, value)| {
-            *(dataptr.offset(index as isize)) = *value.as_ref();
+            *(dataptr.offset(index as isize)) = *value.as_vector_item();
         });
 
-        Self::new_unchecked(vector)
+        vector
     }
 
     fn data(&self) -> SEXP {
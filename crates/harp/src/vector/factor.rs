@@ -7,7 +7,6 @@
 
 use libr::R_NaInt;
 use libr::R_xlen_t;
-use libr::Rf_allocVector;
 use libr::Rf_getAttrib;
 use libr::DATAPTR;
 use libr::INTEGER_ELT;
@@ -16,6 +15,7 @@ use libr::SEXP;
 
 use crate::object::RObject;
 use crate::r_symbol;
+use crate::vector::AsVectorItem;
 use crate::vector::CharacterVector;
 use crate::vector::Vector;
 
@@ -43,18 +43,18 @@ impl Vector for Factor {
     where
         T: IntoIterator,
         <T as IntoIterator>::IntoIter: ExactSizeIterator,
-        <T as IntoIterator>::Item: AsRef<Self::Item>,
+        <T as IntoIterator>::Item: AsVectorItem<Self::Item>,
     {
         let it = data.into_iter();
         let count = it.len();
 
-        let vector = Rf_allocVector(Self::SEXPTYPE, count as R_xlen_t);
-        let dataptr = DATAPTR(vector) as *mut Self::Type;
+        let vector = Self::with_length(count);
+        let dataptr = DATAPTR(vector.data()) as *mut Self::Type;
         it.enumerate().for_each(|(index, value)| {
-            *(dataptr.offset(index as isize)) = *value.as_ref();
+            *(dataptr.offset(index as isize)) = *value.as_vector_item();
         });
 
-        Self::new_unchecked(vector)
+        vector
     }
 
     fn data(&self) -> SEXP {
@@ -144,27 +144,28 @@ impl FormattedVector {
             FormattedVector::Integer { vector } => vector.format_elt_unchecked(index),
             FormattedVector::Numeric { vector } => vector.format_elt_unchecked(index),
             FormattedVector::Character { vector, options } => {
-                let value = vector.format_elt_unchecked(index);
-                self.format_with_string_options(value, options)
+                Self::format_character(vector, index, options)
             },
             FormattedVector::Complex { vector } => vector.format_elt_unchecked(index),
             FormattedVector::Factor { vector } => vector.format_elt_unchecked(index),
             FormattedVector::FormattedVector { vector, options } => {
-                let value = vector.format_elt_unchecked(index);
-                self.format_with_string_options(value, options)
+                Self::format_character(vector, index, options)
             },
         }
     }
 
-    fn format_with_string_options(
-        &self,
-        value: String,
+    // `NA` is never quoted, so that it can be told apart from `"NA"`
+    fn format_character(
+        vector: &CharacterVector,
+        index: isize,
         options: &FormattedVectorCharacterOptions,
     ) -> String {
         if options.quote {
-            format!("\"{}\"", value.replace("\"", "\\\""))
+            vector.format_elt_unchecked(index)
         } else {
-            value
+            vector
+                .get_unchecked(index)
+                .unwrap_or_else(|| String::from("NA"))
         }
     }
 
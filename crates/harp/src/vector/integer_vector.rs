@@ -7,13 +7,13 @@
 
 use libr::R_NaInt;
 use libr::R_xlen_t;
-use libr::Rf_allocVector;
 use libr::DATAPTR;
 use libr::INTEGER_ELT;
 use libr::INTSXP;
 use libr::SEXP;
 
 use crate::object::RObject;
+use crate::vector::AsVectorItem;
 use crate::vector::Vector;
 
 #[harp_macros::vector]
@@ -38,18 +38,18 @@ impl Vector for IntegerVector {
     where
         T: IntoIterator,
         <T as IntoIterator>::IntoIter: ExactSizeIterator,
-        <T as IntoIterator>::Item: AsRef<Self::Item>,
+        <T as IntoIterator>::Item: AsVectorItem<Self::Item>,
     {
         let it = data.into_iter();
         let count = it.len();
 
-        let vector = Rf_allocVector(Self::SEXPTYPE, count as R_xlen_t);
-        let dataptr = DATAPTR(vector) as *mut Self::Type;
+        let vector = Self::with_length(count);
+        let dataptr = DATAPTR(vector.data()) as *mut Self::Type;
         it.enumerate().for_each(|(index, value)| {
-            *(dataptr.offset(index as isize)) = *value.as_ref();
+            *(dataptr.offset(index as isize)) = *value.as_vector_item();
         });
 
-        Self::new_unchecked(vector)
+        vector
     }
 
     fn data(&self) -> SEXP {
@@ -72,3 +72,45 @@ impl Vector for IntegerVector {
         x.to_string()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use libr::INTSXP;
+
+    use crate::environment::R_ENVS;
+    use crate::eval::r_parse_eval0;
+    use crate::r_test;
+    use crate::utils::r_typeof;
+    use crate::vector::*;
+
+    #[test]
+    fn test_integer_vector() {
+        r_test! {
+            let vector = IntegerVector::create([1, 2, 3]);
+            assert_eq!(r_typeof(*vector), INTSXP);
+            assert_eq!(vector.len(), 3);
+            assert!(vector == [1, 2, 3]);
+
+            assert_eq!(vector.get(0).unwrap(), Some(1));
+            assert_eq!(vector.get(2).unwrap(), Some(3));
+            assert!(vector.get(3).is_err());
+
+            // From an iterator
+            let vector = IntegerVector::create((10..13).collect::<Vec<i32>>().iter());
+            assert_eq!(vector.iter().collect::<Vec<_>>(), vec![Some(10), Some(11), Some(12)]);
+        }
+    }
+
+    #[test]
+    fn test_integer_vector_na() {
+        r_test! {
+            let x = r_parse_eval0("c(1L, NA, -3L)", R_ENVS.global).unwrap();
+            let vector = IntegerVector::new(x).unwrap();
+            assert_eq!(vector.get(1).unwrap(), None);
+            assert!(vector.get_value(1).is_err());
+
+            let formatted: Vec<String> = (0..3).map(|i| vector.format_elt_unchecked(i)).collect();
+            assert_eq!(formatted, vec!["1", "NA", "-3"]);
+        }
+    }
+}
@@ -7,13 +7,13 @@
 
 use libr::R_NaInt;
 use libr::R_xlen_t;
-use libr::Rf_allocVector;
 use libr::DATAPTR;
 use libr::LGLSXP;
 use libr::LOGICAL_ELT;
 use libr::SEXP;
 
 use crate::object::RObject;
+use crate::vector::AsVectorItem;
 use crate::vector::Vector;
 
 #[harp_macros::vector]
@@ -38,18 +38,19 @@ impl Vector for LogicalVector {
     where
         T: IntoIterator,
         <T as IntoIterator>::IntoIter: ExactSizeIterator,
-        <T as IntoIterator>::Item: AsRef<Self::Item>,
+        <T as IntoIterator>::Item: AsVectorItem<Self::Item>,
     {
         let it = data.into_iter();
         let count = it.len();
 
-        let vector = Rf_allocVector(Self::SEXPTYPE, count as R_xlen_t);
-        let dataptr = DATAPTR(vector) as *mut Self::Type;
+        // Logicals are stored as `int`, not as Rust's one-byte `bool`
+        let vector = Self::with_length(count);
+        let dataptr = DATAPTR(vector.data()) as *mut Self::UnderlyingType;
         it.enumerate().for_each(|(index, value)| {
-            *(dataptr.offset(index as isize)) = *value.as_ref();
+            *(dataptr.offset(index as isize)) = *value.as_vector_item() as Self::UnderlyingType;
         });
 
-        Self::new_unchecked(vector)
+        vector
     }
 
     fn data(&self) -> SEXP {
@@ -76,3 +77,45 @@ impl Vector for LogicalVector {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use libr::LGLSXP;
+
+    use crate::environment::R_ENVS;
+    use crate::eval::r_parse_eval0;
+    use crate::r_test;
+    use crate::utils::r_typeof;
+    use crate::vector::*;
+
+    #[test]
+    fn test_logical_vector() {
+        r_test! {
+            let vector = LogicalVector::create([true, false, true]);
+            assert_eq!(r_typeof(*vector), LGLSXP);
+            assert_eq!(vector.len(), 3);
+            assert!(vector == [true, false, true]);
+
+            assert_eq!(vector.get(0).unwrap(), Some(true));
+            assert_eq!(vector.get(1).unwrap(), Some(false));
+            assert_eq!(vector.get(2).unwrap(), Some(true));
+
+            // From an iterator of references
+            let values = vec![false, true];
+            let vector = LogicalVector::create(values.iter());
+            assert_eq!(vector.iter().collect::<Vec<_>>(), vec![Some(false), Some(true)]);
+        }
+    }
+
+    #[test]
+    fn test_logical_vector_na() {
+        r_test! {
+            let x = r_parse_eval0("c(TRUE, NA, FALSE)", R_ENVS.global).unwrap();
+            let vector = LogicalVector::new(x).unwrap();
+            assert_eq!(vector.get(1).unwrap(), None);
+
+            let formatted: Vec<String> = (0..3).map(|i| vector.format_elt_unchecked(i)).collect();
+            assert_eq!(formatted, vec!["TRUE", "NA", "FALSE"]);
+        }
+    }
+}
@@ -37,6 +37,39 @@ pub use raw_vector::RawVector;
 pub mod formatted_vector;
 pub mod names;
 
+/// Values that can be written to a vector with elements of type `T` by
+/// `Vector::create()`. This is `AsRef<T>` for strings, which the primitive
+/// types don't implement, so they get their own implementations below.
+pub trait AsVectorItem<T: ?Sized> {
+    fn as_vector_item(&self) -> &T;
+}
+
+impl<U: AsRef<str> + ?Sized> AsVectorItem<str> for U {
+    fn as_vector_item(&self) -> &str {
+        self.as_ref()
+    }
+}
+
+macro_rules! impl_as_vector_item {
+    ($($ty:ty),*) => {
+        $(
+            impl AsVectorItem<$ty> for $ty {
+                fn as_vector_item(&self) -> &$ty {
+                    self
+                }
+            }
+
+            impl<U: AsVectorItem<$ty> + ?Sized> AsVectorItem<$ty> for &U {
+                fn as_vector_item(&self) -> &$ty {
+                    (**self).as_vector_item()
+                }
+            }
+        )*
+    };
+}
+
+impl_as_vector_item!(bool, i32, f64, u8, complex_vector::Complex);
+
 pub trait Vector {
     type Type;
     type Item: ?Sized;
@@ -44,6 +77,11 @@ pub trait Vector {
     type UnderlyingType;
     type CompareType;
 
+    /// Wraps `object` without checking its type.
+    ///
+    /// The object is held by an `RObject`, which keeps it protected until
+    /// the vector is dropped. Freshly allocated SEXPs must be wrapped before
+    /// any further R allocation happens, otherwise the GC may collect them.
     unsafe fn new_unchecked(object: impl Into<SEXP>) -> Self;
     fn data(&self) -> SEXP;
     fn is_na(x: &Self::UnderlyingType) -> bool;
@@ -94,7 +132,7 @@ pub trait Vector {
     where
         T: IntoIterator,
         <T as IntoIterator>::IntoIter: ExactSizeIterator,
-        <T as IntoIterator>::Item: AsRef<Self::Item>;
+        <T as IntoIterator>::Item: AsVectorItem<Self::Item>;
 
     unsafe fn len(&self) -> usize {
         Rf_xlength(self.data()) as usize
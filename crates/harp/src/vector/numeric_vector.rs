@@ -7,13 +7,13 @@
 
 use libr::R_IsNA;
 use libr::R_xlen_t;
-use libr::Rf_allocVector;
 use libr::DATAPTR;
 use libr::REALSXP;
 use libr::REAL_ELT;
 use libr::SEXP;
 
 use crate::object::RObject;
+use crate::vector::AsVectorItem;
 use crate::vector::Vector;
 
 #[harp_macros::vector]
@@ -38,18 +38,18 @@ impl Vector for NumericVector {
     where
         T: IntoIterator,
         <T as IntoIterator>::IntoIter: ExactSizeIterator,
-        <T as IntoIterator>::Item: AsRef<Self::Item>,
+        <T as IntoIterator>::Item: AsVectorItem<Self::Item>,
     {
         let it = data.into_iter();
         let count = it.len();
 
-        let vector = Rf_allocVector(Self::SEXPTYPE, count as R_xlen_t);
-        let dataptr = DATAPTR(vector) as *mut Self::Type;
+        let vector = Self::with_length(count);
+        let dataptr = DATAPTR(vector.data()) as *mut Self::Type;
         it.enumerate().for_each(|(index, value)| {
-            *(dataptr.offset(index as isize)) = *value.as_ref();
+            *(dataptr.offset(index as isize)) = *value.as_vector_item();
         });
 
-        Self::new_unchecked(vector)
+        vector
     }
 
     fn data(&self) -> SEXP {
@@ -72,3 +72,33 @@ impl Vector for NumericVector {
         x.to_string()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::environment::R_ENVS;
+    use crate::eval::r_parse_eval0;
+    use crate::r_test;
+    use crate::vector::*;
+
+    #[test]
+    fn test_numeric_vector_iter() {
+        r_test! {
+            let x = r_parse_eval0("c(1.5, NA, 3)", R_ENVS.global).unwrap();
+            let vector = NumericVector::new(x).unwrap();
+
+            let mut it = vector.iter();
+            assert_eq!(it.len(), 3);
+
+            assert_eq!(it.next(), Some(Some(1.5)));
+            assert_eq!(it.len(), 2);
+            assert_eq!(it.next(), Some(None));
+            assert_eq!(it.next(), Some(Some(3.0)));
+            assert_eq!(it.len(), 0);
+            assert_eq!(it.next(), None);
+
+            // Elements are bounds-checked
+            assert_eq!(vector.get(1).unwrap(), None);
+            assert!(vector.get(3).is_err());
+        }
+    }
+}
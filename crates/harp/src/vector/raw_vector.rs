@@ -6,13 +6,13 @@
 //
 
 use libr::R_xlen_t;
-use libr::Rf_allocVector;
 use libr::DATAPTR;
 use libr::RAWSXP;
 use libr::RAW_ELT;
 use libr::SEXP;
 
 use crate::object::RObject;
+use crate::vector::AsVectorItem;
 use crate::vector::Vector;
 
 #[harp_macros::vector]
@@ -37,18 +37,18 @@ impl Vector for RawVector {
     where
         T: IntoIterator,
         <T as IntoIterator>::IntoIter: ExactSizeIterator,
-        <T as IntoIterator>::Item: AsRef<Self::Item>,
+        <T as IntoIterator>::Item: AsVectorItem<Self::Item>,
     {
         let it = data.into_iter();
         let count = it.len();
 
-        let vector = Rf_allocVector(Self::SEXPTYPE, count as R_xlen_t);
-        let dataptr = DATAPTR(vector) as *mut Self::Type;
+        let vector = Self::with_length(count);
+        let dataptr = DATAPTR(vector.data()) as *mut Self::Type;
         it.enumerate().for_each(|(index, value)| {
-            *(dataptr.offset(index as isize)) = *value.as_ref();
+            *(dataptr.offset(index as isize)) = *value.as_vector_item();
         });
 
-        Self::new_unchecked(vector)
+        vector
     }
 
     fn data(&self) -> SEXP {
@@ -57,6 +57,8 @@ functions::generate! {
 
     pub fn R_MakeExternalPtr(p: *mut std::ffi::c_void, tag: SEXP, prot: SEXP) -> SEXP;
 
+    pub fn R_gc();
+
     pub fn R_IsNA(arg1: f64) -> std::ffi::c_int;
 
     pub fn R_IsNaN(arg1: f64) -> std::ffi::c_int;
@@ -134,6 +136,8 @@ functions::generate! {
 
     pub fn Rf_getAttrib(arg1: SEXP, arg2: SEXP) -> SEXP;
 
+    pub fn Rf_getCharCE(arg1: SEXP) -> cetype_t;
+
     pub fn Rf_duplicate(arg: SEXP) -> SEXP;
 
     pub fn Rf_shallow_duplicate(arg: SEXP) -> SEXP;